@@ -0,0 +1,101 @@
+//! Parsing a large manifest file through a memory map compared with
+//! reading it first.
+//!
+//! ```sh
+//! cargo bench --bench mmap --features mmap
+//! ```
+
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use mtree3::Spec;
+use mtree3::mmap::MappedSpec;
+
+const DIRS: usize = 200;
+const FILES: usize = 1000;
+const RUNS: usize = 5;
+
+fn write_manifest(path: &Path) {
+    let mut out = BufWriter::new(File::create(path).unwrap());
+    writeln!(out, "#mtree\n/set type=file uid=0 gid=0 mode=0644 nlink=1").unwrap();
+    writeln!(out, ". type=dir mode=0755").unwrap();
+
+    for dir in 0..DIRS {
+        writeln!(out, "    dir{dir} type=dir mode=0755 time=1769640177.0").unwrap();
+
+        for file in 0..FILES {
+            let n = dir * FILES + file;
+            writeln!(
+                out,
+                "        file{file}.txt size={n} time=1769640177.{:09} sha256digest={:064x}",
+                n % 1_000_000_000,
+                n * 2_654_435_761
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "    ..").unwrap();
+    }
+
+    writeln!(out, "..").unwrap();
+    out.flush().unwrap();
+}
+
+fn best(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("mtree3-bench-{}", std::process::id()));
+    write_manifest(&path);
+    let size = fs::metadata(&path).unwrap().len();
+
+    let results = [
+        (
+            "read_to_string + parse",
+            best(|| {
+                let input = fs::read_to_string(black_box(&path)).unwrap();
+                black_box(Spec::parse(&input).unwrap());
+            }),
+        ),
+        (
+            "from_reader",
+            best(|| {
+                black_box(Spec::from_reader(File::open(black_box(&path)).unwrap()).unwrap());
+            }),
+        ),
+        (
+            "parse_mmap",
+            best(|| {
+                black_box(Spec::parse_mmap(black_box(&path)).unwrap());
+            }),
+        ),
+        (
+            "MappedSpec::lines",
+            best(|| {
+                let mapped = MappedSpec::open(black_box(&path)).unwrap();
+                black_box(mapped.lines().unwrap());
+            }),
+        ),
+    ];
+
+    fs::remove_file(&path).unwrap();
+
+    for (name, time) in results {
+        println!(
+            "{name:<24} {} MB in {time:.2?}, {:.0} MB/s",
+            size / 1_000_000,
+            size as f64 / 1e6 / time.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Parsing speed on a large generated manifest.
+//!
+//! Compare the combinator parser with the hand-written one by running
+//!
+//! ```sh
+//! cargo bench --bench parse
+//! cargo bench --bench parse --features fast
+//! ```
+
+use std::fmt::Write;
+use std::hint::black_box;
+use std::time::Instant;
+
+use mtree3::Spec;
+
+const DIRS: usize = 200;
+const FILES: usize = 1000;
+const RUNS: usize = 5;
+
+fn manifest() -> String {
+    let mut out = String::from("#mtree\n/set type=file uid=0 gid=0 mode=0644 nlink=1\n");
+    out.push_str(". type=dir mode=0755\n");
+
+    for dir in 0..DIRS {
+        writeln!(out, "    dir{dir} type=dir mode=0755 time=1769640177.0").unwrap();
+
+        for file in 0..FILES {
+            let n = dir * FILES + file;
+            writeln!(
+                out,
+                "        file{file}.txt size={n} time=1769640177.{:09} sha256digest={:064x}",
+                n % 1_000_000_000,
+                n * 2_654_435_761
+            )
+            .unwrap();
+        }
+
+        out.push_str("    ..\n");
+    }
+
+    out.push_str("..\n");
+    out
+}
+
+fn main() {
+    let input = manifest();
+    let lines = input.lines().count();
+
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(Spec::parse(black_box(&input)).unwrap());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    println!(
+        "parsed {lines} lines ({} MB) in {best:.2?}, {:.0} lines/s",
+        input.len() / 1_000_000,
+        lines as f64 / best.as_secs_f64()
+    );
+}
@@ -0,0 +1,119 @@
+/* Generated by cbindgen from capi/src/lib.rs. Do not edit. */
+
+#ifndef MTREE3_H
+#define MTREE3_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * An entry of a [`Spec`], with the `/set` state in effect applied.
+ */
+typedef struct mtree3_entry mtree3_entry;
+
+/**
+ * A parsed spec, with its entries resolved by [`mtree3::Spec::resolve`],
+ * the same resolution [`mtree3_verify_path`] checks against.
+ */
+typedef struct mtree3_spec mtree3_spec;
+
+/**
+ * Called with each finding of [`mtree3_verify_path`], formatted like
+ * `./etc/passwd: size expected 10, found 12`.
+ */
+typedef void (*mtree3_finding_callback)(const char *message, void *data);
+
+/**
+ * Parses the `len` bytes of spec text at `input`.
+ *
+ * Returns NULL if the text isn't UTF-8 or a line can't be parsed, and
+ * stores the 1-based number of the offending line in `error_line`, if it's
+ * not NULL, or 0 if the text isn't UTF-8. The handle must be freed with
+ * [`mtree3_spec_free`].
+ *
+ * # Safety
+ *
+ * `input` must point to `len` readable bytes, and `error_line` must be
+ * NULL or writable.
+ */
+mtree3_spec *mtree3_parse(const char *input, uintptr_t len, uintptr_t *error_line);
+
+/**
+ * Frees a handle returned by [`mtree3_parse`]. Does nothing if `spec` is
+ * NULL.
+ *
+ * # Safety
+ *
+ * `spec` must be NULL or a handle that hasn't been freed yet.
+ */
+void mtree3_spec_free(mtree3_spec *spec);
+
+/**
+ * The number of entries in `spec`.
+ *
+ * # Safety
+ *
+ * `spec` must be a live handle.
+ */
+uintptr_t mtree3_spec_entry_count(const mtree3_spec *spec);
+
+/**
+ * The entry at `index`, in the order [`mtree3::Spec::resolve`] gives, or
+ * NULL if `index` is out of range.
+ *
+ * # Safety
+ *
+ * `spec` must be a live handle. The entry is freed along with it.
+ */
+const mtree3_entry *mtree3_spec_entry(const mtree3_spec *spec, uintptr_t index);
+
+/**
+ * The entry's full path, vis-encoded.
+ *
+ * # Safety
+ *
+ * `entry` must come from [`mtree3_spec_entry`] on a live handle.
+ */
+const char *mtree3_entry_path(const mtree3_entry *entry);
+
+/**
+ * The entry's keywords, separated by spaces, as they'd be written in a
+ * spec.
+ *
+ * # Safety
+ *
+ * `entry` must come from [`mtree3_spec_entry`] on a live handle.
+ */
+const char *mtree3_entry_keywords(const mtree3_entry *entry);
+
+/**
+ * The 1-based line the entry starts on.
+ *
+ * # Safety
+ *
+ * `entry` must come from [`mtree3_spec_entry`] on a live handle.
+ */
+uintptr_t mtree3_entry_line(const mtree3_entry *entry);
+
+/**
+ * Checks `spec` against the directory `root`, passing each finding to
+ * `on_finding`, if it's not NULL, along with `data`.
+ *
+ * The entries checked are those [`mtree3_spec_entry`] lists. `root` is
+ * taken as raw bytes on Unix; elsewhere it has to be UTF-8.
+ *
+ * Returns the number of findings, so 0 means the tree matches, or -1 if
+ * `root` can't be read as a path.
+ *
+ * # Safety
+ *
+ * `spec` must be a live handle and `root` a NUL-terminated string.
+ */
+intptr_t mtree3_verify_path(const mtree3_spec *spec,
+                            const char *root,
+                            mtree3_finding_callback on_finding,
+                            void *data);
+
+#endif  /* MTREE3_H */
@@ -0,0 +1,285 @@
+//! C bindings for mtree3.
+//!
+//! A spec is parsed into an opaque [`Spec`] handle, whose entries can be
+//! listed and checked against a directory. Strings handed out are
+//! NUL-terminated and live as long as the handle. Paths are vis-encoded as
+//! in a spec, so they are always plain ASCII.
+//!
+//! `include/mtree3.h` is generated with
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/mtree3.h
+//! ```
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use mtree3::escape;
+use mtree3::verify::verify;
+
+/// A parsed spec, with its entries resolved by [`mtree3::Spec::resolve`],
+/// the same resolution [`mtree3_verify_path`] checks against.
+pub struct Spec {
+    spec: mtree3::Spec,
+    entries: Vec<Entry>,
+}
+
+/// An entry of a [`Spec`], with the `/set` state in effect applied.
+pub struct Entry {
+    path: CString,
+    keywords: CString,
+    line: usize,
+}
+
+/// Called with each finding of [`mtree3_verify_path`], formatted like
+/// `./etc/passwd: size expected 10, found 12`.
+pub type FindingCallback = Option<unsafe extern "C" fn(message: *const c_char, data: *mut c_void)>;
+
+fn c_string(text: String) -> CString {
+    // Encoded paths and keywords escape NUL, so there are none to strip.
+    CString::new(text).unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn root_path(root: &CStr) -> Option<&Path> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(Path::new(std::ffi::OsStr::from_bytes(root.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn root_path(root: &CStr) -> Option<&Path> {
+    root.to_str().ok().map(Path::new)
+}
+
+/// Parses the `len` bytes of spec text at `input`.
+///
+/// Returns NULL if the text isn't UTF-8 or a line can't be parsed, and
+/// stores the 1-based number of the offending line in `error_line`, if it's
+/// not NULL, or 0 if the text isn't UTF-8. The handle must be freed with
+/// [`mtree3_spec_free`].
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes, and `error_line` must be
+/// NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_parse(
+    input: *const c_char,
+    len: usize,
+    error_line: *mut usize,
+) -> *mut Spec {
+    let bytes = match input.is_null() {
+        true => &[][..],
+        false => unsafe { slice::from_raw_parts(input.cast::<u8>(), len) },
+    };
+
+    let parsed = std::str::from_utf8(bytes)
+        .map_err(|_| 0)
+        .and_then(|text| mtree3::Spec::parse(text).map_err(|err| err.span.line));
+
+    let spec = match parsed {
+        Ok(spec) => spec,
+        Err(line) => {
+            if !error_line.is_null() {
+                unsafe { *error_line = line };
+            }
+            return ptr::null_mut();
+        }
+    };
+
+    let entries = spec
+        .resolve()
+        .into_iter()
+        .map(|entry| Entry {
+            path: c_string(escape::encode_path(&entry.path)),
+            keywords: c_string(
+                entry
+                    .keywords
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            line: entry.span.line,
+        })
+        .collect();
+
+    Box::into_raw(Box::new(Spec { spec, entries }))
+}
+
+/// Frees a handle returned by [`mtree3_parse`]. Does nothing if `spec` is
+/// NULL.
+///
+/// # Safety
+///
+/// `spec` must be NULL or a handle that hasn't been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_spec_free(spec: *mut Spec) {
+    if !spec.is_null() {
+        drop(unsafe { Box::from_raw(spec) });
+    }
+}
+
+/// The number of entries in `spec`.
+///
+/// # Safety
+///
+/// `spec` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_spec_entry_count(spec: *const Spec) -> usize {
+    unsafe { &*spec }.entries.len()
+}
+
+/// The entry at `index`, in the order [`mtree3::Spec::resolve`] gives, or
+/// NULL if `index` is out of range.
+///
+/// # Safety
+///
+/// `spec` must be a live handle. The entry is freed along with it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_spec_entry(spec: *const Spec, index: usize) -> *const Entry {
+    unsafe { &*spec }
+        .entries
+        .get(index)
+        .map_or(ptr::null(), |entry| entry)
+}
+
+/// The entry's full path, vis-encoded.
+///
+/// # Safety
+///
+/// `entry` must come from [`mtree3_spec_entry`] on a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_entry_path(entry: *const Entry) -> *const c_char {
+    unsafe { &*entry }.path.as_ptr()
+}
+
+/// The entry's keywords, separated by spaces, as they'd be written in a
+/// spec.
+///
+/// # Safety
+///
+/// `entry` must come from [`mtree3_spec_entry`] on a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_entry_keywords(entry: *const Entry) -> *const c_char {
+    unsafe { &*entry }.keywords.as_ptr()
+}
+
+/// The 1-based line the entry starts on.
+///
+/// # Safety
+///
+/// `entry` must come from [`mtree3_spec_entry`] on a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_entry_line(entry: *const Entry) -> usize {
+    unsafe { &*entry }.line
+}
+
+/// Checks `spec` against the directory `root`, passing each finding to
+/// `on_finding`, if it's not NULL, along with `data`.
+///
+/// The entries checked are those [`mtree3_spec_entry`] lists. `root` is
+/// taken as raw bytes on Unix; elsewhere it has to be UTF-8.
+///
+/// Returns the number of findings, so 0 means the tree matches, or -1 if
+/// `root` can't be read as a path.
+///
+/// # Safety
+///
+/// `spec` must be a live handle and `root` a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mtree3_verify_path(
+    spec: *const Spec,
+    root: *const c_char,
+    on_finding: FindingCallback,
+    data: *mut c_void,
+) -> isize {
+    let spec = unsafe { &*spec };
+    let Some(root) = root_path(unsafe { CStr::from_ptr(root) }) else {
+        return -1;
+    };
+
+    let report = verify(&spec.spec, root);
+
+    if let Some(on_finding) = on_finding {
+        for finding in &report.findings {
+            let message = c_string(finding.to_string());
+            unsafe { on_finding(message.as_ptr(), data) };
+        }
+    }
+
+    report.findings.len() as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_verify() {
+        let input = "/set type=file\n. type=dir\n    missing\\040file size=1\n..\n";
+
+        unsafe {
+            let spec = mtree3_parse(input.as_ptr().cast(), input.len(), ptr::null_mut());
+            assert!(!spec.is_null());
+            assert_eq!(mtree3_spec_entry_count(spec), 2);
+            assert!(mtree3_spec_entry(spec, 2).is_null());
+
+            let entry = mtree3_spec_entry(spec, 1);
+            assert_eq!(
+                CStr::from_ptr(mtree3_entry_path(entry)),
+                c"./missing\\040file"
+            );
+            assert_eq!(
+                CStr::from_ptr(mtree3_entry_keywords(entry)),
+                c"type=file size=1"
+            );
+            assert_eq!(mtree3_entry_line(entry), 3);
+
+            unsafe extern "C" fn collect(message: *const c_char, data: *mut c_void) {
+                let messages = unsafe { &mut *data.cast::<Vec<String>>() };
+                messages.push(
+                    unsafe { CStr::from_ptr(message) }
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+            }
+
+            let root = CString::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+            let mut messages: Vec<String> = Vec::new();
+            let count = mtree3_verify_path(
+                spec,
+                root.as_ptr(),
+                Some(collect),
+                (&raw mut messages).cast(),
+            );
+            assert_eq!(count, 1);
+            assert_eq!(messages, vec!["./missing file: missing"]);
+
+            mtree3_spec_free(spec);
+        }
+
+        let mut line = 0;
+        let bad = "foo size=x\n";
+        let spec = unsafe { mtree3_parse(bad.as_ptr().cast(), bad.len(), &mut line) };
+        assert!(spec.is_null());
+        assert_eq!(line, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verify_non_utf8_root() {
+        let input = "./file type=file\n";
+        let root = CString::new(b"/nonexistent/\xff".to_vec()).unwrap();
+
+        unsafe {
+            let spec = mtree3_parse(input.as_ptr().cast(), input.len(), ptr::null_mut());
+            let count = mtree3_verify_path(spec, root.as_ptr(), None, ptr::null_mut());
+            assert!(count > 0);
+            mtree3_spec_free(spec);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Checking a manifest uploaded in the browser.
+//!
+//! Build with
+//!
+//! ```sh
+//! cargo build --release --example wasm_validate \
+//!     --target wasm32-unknown-unknown --no-default-features
+//! ```
+//!
+//! and load `target/wasm32-unknown-unknown/release/examples/wasm_validate.wasm`
+//! without any bindings:
+//!
+//! ```js
+//! const { instance } = await WebAssembly.instantiateStreaming(fetch("wasm_validate.wasm"));
+//! const { memory, alloc, check, report } = instance.exports;
+//!
+//! const input = new TextEncoder().encode(await file.text());
+//! const ptr = alloc(input.length);
+//! new Uint8Array(memory.buffer, ptr, input.length).set(input);
+//!
+//! const len = check(ptr, input.length);
+//! const text = new TextDecoder().decode(new Uint8Array(memory.buffer, report(), len));
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::Write;
+
+use mtree3::{ParseOptions, Spec};
+
+thread_local! {
+    static REPORT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Allocates `len` bytes for the caller to copy a manifest into.
+#[unsafe(no_mangle)]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()).cast()
+}
+
+/// Parses and validates the manifest at `ptr`, freeing it, and returns the
+/// length of the report, which is empty if nothing is wrong.
+///
+/// # Safety
+///
+/// `ptr` and `len` must come from a single call to [`alloc`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn check(ptr: *mut u8, len: usize) -> usize {
+    let bytes = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) };
+    let input = String::from_utf8_lossy(&bytes);
+
+    let options = ParseOptions::default();
+    let (spec, errors) = Spec::parse_lenient_with(&input, &options);
+    let mut out = String::new();
+
+    for err in &errors {
+        let _ = writeln!(out, "{}", err.diagnose(&input, &options));
+    }
+    for issue in spec.validate() {
+        let _ = writeln!(out, "{issue}");
+    }
+
+    REPORT.with_borrow_mut(|report| {
+        *report = out;
+        report.len()
+    })
+}
+
+/// The report written by the last call to [`check`].
+#[unsafe(no_mangle)]
+pub extern "C" fn report() -> *const u8 {
+    REPORT.with_borrow(|report| report.as_ptr())
+}
@@ -0,0 +1,157 @@
+//! A machine-readable record of changes made to a filesystem.
+//!
+//! Each action is written as one JSON object per line, so logs can be
+//! appended to, rotated and processed line by line:
+//!
+//! ```text
+//! {"time":"1769640380.000000000","path":"./etc/shadow","operation":"chmod","old":"0644","new":"0600","result":"ok"}
+//! ```
+//!
+//! Paths are written vis-encoded, as in a spec, so the log is plain ASCII.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::{Timestamp, escape};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionRecord {
+    pub time: Timestamp,
+    pub path: PathBuf,
+    /// What was done, e.g. `chmod` or `mkdir`.
+    pub operation: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    /// `Err` holds a description of why the action failed.
+    pub result: Result<(), String>,
+}
+
+impl ActionRecord {
+    /// Creates a successful record timestamped with the current time.
+    ///
+    /// This reads the system clock, which panics on targets without one,
+    /// such as `wasm32-unknown-unknown`; use [`ActionRecord::at`] there.
+    pub fn new(path: impl Into<PathBuf>, operation: impl Into<String>) -> Self {
+        ActionRecord::at(SystemTime::now().into(), path, operation)
+    }
+
+    /// Creates a successful record with the given timestamp.
+    pub fn at(time: Timestamp, path: impl Into<PathBuf>, operation: impl Into<String>) -> Self {
+        ActionRecord {
+            time,
+            path: path.into(),
+            operation: operation.into(),
+            old: None,
+            new: None,
+            result: Ok(()),
+        }
+    }
+
+    pub fn old(mut self, old: impl Into<String>) -> Self {
+        self.old = Some(old.into());
+        self
+    }
+
+    pub fn new_value(mut self, new: impl Into<String>) -> Self {
+        self.new = Some(new.into());
+        self
+    }
+
+    pub fn failed(mut self, error: impl ToString) -> Self {
+        self.result = Err(error.to_string());
+        self
+    }
+
+    /// Writes the record as a single line of JSON.
+    pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+        write!(out, "{{\"time\":")?;
+        write_json_string(&mut out, &self.time.to_string())?;
+        write!(out, ",\"path\":")?;
+        write_json_string(&mut out, &escape::encode_path(&self.path))?;
+        write!(out, ",\"operation\":")?;
+        write_json_string(&mut out, &self.operation)?;
+
+        for (key, value) in [("old", &self.old), ("new", &self.new)] {
+            if let Some(value) = value {
+                write!(out, ",\"{key}\":")?;
+                write_json_string(&mut out, value)?;
+            }
+        }
+
+        match &self.result {
+            Ok(()) => write!(out, ",\"result\":\"ok\"")?,
+            Err(error) => {
+                write!(out, ",\"result\":\"error\",\"error\":")?;
+                write_json_string(&mut out, error)?;
+            }
+        }
+
+        writeln!(out, "}}")
+    }
+}
+
+fn write_json_string(out: &mut impl Write, value: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+
+    for c in value.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\t' => out.write_all(b"\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+
+    out.write_all(b"\"")
+}
+
+/// Appends [`ActionRecord`]s to a writer as JSON lines.
+#[derive(Debug)]
+pub struct ActionLog<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ActionLog<W> {
+    pub fn new(writer: W) -> Self {
+        ActionLog { writer }
+    }
+
+    pub fn record(&mut self, record: &ActionRecord) -> io::Result<()> {
+        record.write_json(&mut self.writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str) -> ActionRecord {
+        ActionRecord::at(Timestamp::new(1769640380, 0), path, "chmod")
+    }
+
+    #[test]
+    fn test_action_log() {
+        let mut log = ActionLog::new(Vec::new());
+
+        log.record(&record("./etc/shadow").old("0644").new_value("0600"))
+            .unwrap();
+        log.record(&record("./etc/my file").failed("permission \"denied\"\n"))
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(log.into_inner()).unwrap(),
+            "{\"time\":\"1769640380.000000000\",\"path\":\"./etc/shadow\",\"operation\":\"chmod\",\
+             \"old\":\"0644\",\"new\":\"0600\",\"result\":\"ok\"}\n\
+             {\"time\":\"1769640380.000000000\",\"path\":\"./etc/my\\\\040file\",\"operation\":\"chmod\",\
+             \"result\":\"error\",\"error\":\"permission \\\"denied\\\"\\n\"}\n"
+        );
+    }
+}
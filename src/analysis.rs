@@ -0,0 +1,317 @@
+//! Statistical oddities in a spec, as a starting point for auditing it.
+//!
+//! Nothing reported here is necessarily wrong: a file owned by someone other
+//! than its neighbours or an executable under `share` is just worth a look.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::sanitize;
+use crate::{Keyword, KeywordKind, ResolvedEntry, Span, Spec, Timestamp, Type};
+
+/// Thresholds for [`Spec::analyze_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnalysisOptions {
+    /// How many siblings must agree on an owner before a differing one is
+    /// reported. Three quarters of them must share it as well.
+    pub min_siblings: usize,
+    /// Directory names whose files shouldn't be executable.
+    pub data_dirs: Vec<String>,
+    /// How many seconds after the median `time` a file must have changed to
+    /// count as recent.
+    pub recent_secs: i64,
+    /// Recent files are only reported while they make up at most this
+    /// percentage of the files with a `time`, so a spec of a freshly
+    /// updated tree doesn't report everything.
+    pub max_recent_percent: usize,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            min_siblings: 4,
+            data_dirs: ["doc", "include", "locale", "man", "share"]
+                .map(String::from)
+                .to_vec(),
+            recent_secs: 30 * 24 * 60 * 60,
+            max_recent_percent: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// The entry's `uid` or `uname` differs from the one most of its
+    /// siblings share.
+    UnusualOwner {
+        path: PathBuf,
+        span: Span,
+        owner: Keyword,
+        usual: Keyword,
+    },
+    /// A file with an execute bit set below one of
+    /// [`AnalysisOptions::data_dirs`].
+    ExecutableInDataDir {
+        path: PathBuf,
+        span: Span,
+        mode: Keyword,
+    },
+    /// A file that changed long after most others did.
+    RecentlyChanged {
+        path: PathBuf,
+        span: Span,
+        time: Timestamp,
+        median: Timestamp,
+    },
+}
+
+impl Anomaly {
+    pub fn path(&self) -> &Path {
+        match self {
+            Anomaly::UnusualOwner { path, .. }
+            | Anomaly::ExecutableInDataDir { path, .. }
+            | Anomaly::RecentlyChanged { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::UnusualOwner {
+                path,
+                span,
+                owner,
+                usual,
+            } => write!(
+                f,
+                "line {}: {} has {} where its siblings have {}",
+                span.line,
+                sanitize::path(path),
+                sanitize::text(&owner.to_string()),
+                sanitize::text(&usual.to_string())
+            ),
+            Anomaly::ExecutableInDataDir { path, span, mode } => write!(
+                f,
+                "line {}: {} is executable ({mode}) in a data directory",
+                span.line,
+                sanitize::path(path)
+            ),
+            Anomaly::RecentlyChanged {
+                path,
+                span,
+                time,
+                median,
+            } => write!(
+                f,
+                "line {}: {} changed at {time}, long after most files ({median})",
+                span.line,
+                sanitize::path(path)
+            ),
+        }
+    }
+}
+
+impl Spec {
+    /// How often each value of `kind` occurs among the resolved entries,
+    /// most common first. Values that occur equally often are in the order
+    /// they first appear.
+    pub fn keyword_frequency(&self, kind: KeywordKind) -> Vec<(Keyword, usize)> {
+        let entries = self.resolve();
+        frequency(entries.iter().filter_map(|entry| entry.get(kind)))
+    }
+
+    pub fn analyze(&self) -> Vec<Anomaly> {
+        self.analyze_with(&AnalysisOptions::default())
+    }
+
+    /// Reports anomalies in the order of the entries they're about.
+    pub fn analyze_with(&self, options: &AnalysisOptions) -> Vec<Anomaly> {
+        let entries = self.resolve();
+
+        let mut siblings: HashMap<&Path, Vec<&ResolvedEntry>> = HashMap::new();
+        for entry in &entries {
+            if let Some(parent) = entry.path.parent() {
+                siblings.entry(parent).or_default().push(entry);
+            }
+        }
+
+        let usual_owners: HashMap<(&Path, KeywordKind), Keyword> = siblings
+            .iter()
+            .flat_map(|(parent, entries)| {
+                [KeywordKind::Uid, KeywordKind::Uname]
+                    .into_iter()
+                    .filter_map(|kind| {
+                        let usual = usual_value(entries, kind, options.min_siblings)?;
+                        Some(((*parent, kind), usual))
+                    })
+            })
+            .collect();
+
+        let median = median_time(&entries);
+        let recent = median.filter(|median| {
+            let timed = entries.iter().filter(|entry| time(entry).is_some()).count();
+            let recent = entries
+                .iter()
+                .filter(|entry| is_recent(entry, *median, options))
+                .count();
+            recent * 100 <= timed * options.max_recent_percent
+        });
+
+        let mut anomalies = Vec::new();
+
+        for entry in &entries {
+            for kind in [KeywordKind::Uid, KeywordKind::Uname] {
+                let usual = entry
+                    .path
+                    .parent()
+                    .and_then(|parent| usual_owners.get(&(parent, kind)));
+
+                if let (Some(owner), Some(usual)) = (entry.get(kind), usual)
+                    && owner != usual
+                {
+                    anomalies.push(Anomaly::UnusualOwner {
+                        path: entry.path.clone(),
+                        span: entry.span.clone(),
+                        owner: owner.clone(),
+                        usual: usual.clone(),
+                    });
+                }
+            }
+
+            if let Some(mode @ Keyword::Mode(bits)) = entry.get(KeywordKind::Mode)
+                && bits.bits() & 0o111 != 0
+                && entry.get(KeywordKind::Type) == Some(&Keyword::Type(Type::File))
+                && in_data_dir(&entry.path, options)
+            {
+                anomalies.push(Anomaly::ExecutableInDataDir {
+                    path: entry.path.clone(),
+                    span: entry.span.clone(),
+                    mode: mode.clone(),
+                });
+            }
+
+            if let Some(median) = recent
+                && is_recent(entry, median, options)
+            {
+                anomalies.push(Anomaly::RecentlyChanged {
+                    path: entry.path.clone(),
+                    span: entry.span.clone(),
+                    time: time(entry).expect("recent entries have a time"),
+                    median,
+                });
+            }
+        }
+
+        anomalies
+    }
+}
+
+fn frequency<'a>(keywords: impl Iterator<Item = &'a Keyword>) -> Vec<(Keyword, usize)> {
+    let mut counts: Vec<(Keyword, usize)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for keyword in keywords {
+        match index.get(&keyword.to_string()) {
+            Some(&idx) => counts[idx].1 += 1,
+            None => {
+                index.insert(keyword.to_string(), counts.len());
+                counts.push((keyword.clone(), 1));
+            }
+        }
+    }
+
+    // A stable sort keeps ties in order of appearance.
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+    counts
+}
+
+/// The value of `kind` shared by at least `min` of `entries` and three
+/// quarters of those that have one.
+fn usual_value(entries: &[&ResolvedEntry], kind: KeywordKind, min: usize) -> Option<Keyword> {
+    let values: Vec<&Keyword> = entries.iter().filter_map(|entry| entry.get(kind)).collect();
+    let (usual, count) = frequency(values.iter().copied()).into_iter().next()?;
+
+    (count >= min && count * 4 >= values.len() * 3).then_some(usual)
+}
+
+fn time(entry: &ResolvedEntry) -> Option<Timestamp> {
+    match entry.get(KeywordKind::Time) {
+        Some(Keyword::Time(time)) => Some(*time),
+        _ => None,
+    }
+}
+
+fn median_time(entries: &[ResolvedEntry]) -> Option<Timestamp> {
+    let mut times: Vec<Timestamp> = entries.iter().filter_map(time).collect();
+    times.sort();
+    times.get(times.len() / 2).copied()
+}
+
+fn is_recent(entry: &ResolvedEntry, median: Timestamp, options: &AnalysisOptions) -> bool {
+    time(entry).is_some_and(|time| time.secs.saturating_sub(median.secs) > options.recent_secs)
+}
+
+fn in_data_dir(path: &Path, options: &AnalysisOptions) -> bool {
+    path.parent().is_some_and(|parent| {
+        parent.iter().any(|component| {
+            options
+                .data_dirs
+                .iter()
+                .any(|dir| component == dir.as_str())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mode;
+
+    #[test]
+    fn test_analyze() {
+        let spec = Spec::parse(
+            "/set type=file uid=0 mode=0644 time=1600000000.0\n\
+             ./usr/share/doc/a\n\
+             ./usr/share/doc/b\n\
+             ./usr/share/doc/c uid=1000\n\
+             ./usr/share/doc/d\n\
+             ./usr/share/doc/e mode=0755\n\
+             ./usr/bin/f mode=0755\n\
+             ./usr/bin/g mode=0755\n\
+             ./usr/bin/h mode=0755\n\
+             ./usr/bin/i mode=0755\n\
+             ./usr/bin/j mode=0755 time=1769640177.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.analyze()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 4: ./usr/share/doc/c has uid=1000 where its siblings have uid=0",
+                "line 6: ./usr/share/doc/e is executable (mode=0755) in a data directory",
+                "line 11: ./usr/bin/j changed at 1769640177.000000000, long after most files \
+                 (1600000000.000000000)",
+            ]
+        );
+
+        assert_eq!(
+            spec.keyword_frequency(KeywordKind::Mode),
+            vec![
+                (Keyword::Mode(Mode::new(0o755).unwrap()), 6),
+                (Keyword::Mode(Mode::new(0o644).unwrap()), 4),
+            ]
+        );
+
+        let options = AnalysisOptions {
+            max_recent_percent: 5,
+            ..AnalysisOptions::default()
+        };
+        assert_eq!(spec.analyze_with(&options).len(), 2);
+    }
+}
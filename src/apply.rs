@@ -0,0 +1,670 @@
+//! Changing a filesystem tree to match a spec, like `mtree -U`.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File, Permissions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt, lchown, symlink};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os = "linux")]
+use crate::dirfd::Dir;
+use crate::sanitize;
+use crate::verify::Verifier;
+use crate::{
+    ActionRecord, IdMap, Keyword, KeywordKind, ResolvedEntry, RootPath, Spec, Type, VerifyFinding,
+};
+
+/// A single change to the filesystem. Paths are spec paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    CreateDir {
+        path: PathBuf,
+    },
+    CreateSymlink {
+        path: PathBuf,
+        target: PathBuf,
+    },
+    /// Sets a file's `mode`, `uid`, `gid` or `time`. `old` is the value found
+    /// on disk, if the file already existed.
+    Set {
+        path: PathBuf,
+        old: Option<Keyword>,
+        new: Keyword,
+    },
+    /// Removes a file that isn't in the spec, with everything beneath it.
+    Remove {
+        path: PathBuf,
+    },
+}
+
+impl Operation {
+    pub fn path(&self) -> &Path {
+        match self {
+            Operation::CreateDir { path }
+            | Operation::CreateSymlink { path, .. }
+            | Operation::Set { path, .. }
+            | Operation::Remove { path } => path,
+        }
+    }
+
+    /// A short name for the operation, as used in [`ActionRecord::operation`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::CreateDir { .. } => "mkdir",
+            Operation::CreateSymlink { .. } => "symlink",
+            Operation::Set { new, .. } => match new {
+                Keyword::Mode(_) => "chmod",
+                Keyword::Uid(_) => "chown",
+                Keyword::Gid(_) => "chgrp",
+                _ => "touch",
+            },
+            Operation::Remove { .. } => "remove",
+        }
+    }
+
+    /// A successful record of this operation, timestamped now.
+    pub fn record(&self) -> ActionRecord {
+        let record = ActionRecord::new(self.path(), self.name());
+
+        match self {
+            Operation::CreateSymlink { target, .. } => {
+                record.new_value(target.display().to_string())
+            }
+            Operation::Set { old, new, .. } => {
+                let record = record.new_value(value(new));
+                match old {
+                    Some(old) => record.old(value(old)),
+                    None => record,
+                }
+            }
+            _ => record,
+        }
+    }
+
+    /// The order operations are carried out in: ownership is changed before
+    /// the mode, since `chown` clears the setuid bits, and times come last
+    /// because creating and removing files changes their parent's.
+    fn phase(&self) -> u8 {
+        match self {
+            Operation::CreateDir { .. } | Operation::CreateSymlink { .. } => 0,
+            Operation::Remove { .. } => 1,
+            Operation::Set { new, .. } => match new {
+                Keyword::Uid(_) | Keyword::Gid(_) => 2,
+                Keyword::Mode(_) => 3,
+                _ => 4,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", sanitize::path(self.path()), self.name())?;
+
+        match self {
+            Operation::CreateSymlink { target, .. } => write!(f, " -> {}", sanitize::path(target)),
+            Operation::Set {
+                old: Some(old),
+                new,
+                ..
+            } => write!(
+                f,
+                " {} -> {}",
+                sanitize::text(&value(old)),
+                sanitize::text(&value(new))
+            ),
+            Operation::Set { old: None, new, .. } => write!(f, " {}", sanitize::text(&value(new))),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn value(keyword: &Keyword) -> String {
+    match keyword {
+        Keyword::Mode(mode) => mode.to_string(),
+        Keyword::Uid(id) | Keyword::Gid(id) => id.to_string(),
+        Keyword::Time(time) => time.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The changes needed to make a tree match a spec.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub operations: Vec<Operation>,
+    /// Differences that changing metadata can't fix, such as a missing
+    /// regular file or a wrong checksum.
+    pub unfixable: Vec<VerifyFinding>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+/// Operations found to be impossible by [`Applier::check_first`], before
+/// anything was changed.
+#[derive(Debug)]
+pub struct CheckError {
+    pub problems: Vec<(Operation, io::Error)>,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} operations can't be carried out", self.problems.len())?;
+
+        if let Some((operation, err)) = self.problems.first() {
+            write!(f, ", first: {operation}: {err}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+/// Fixes the metadata of the files under a root directory to match a spec:
+/// mode, ownership and modification time. Missing directories and symlinks
+/// are created, and files that aren't in the spec can be removed.
+///
+/// On Linux, changes are made relative to descriptors for the directories
+/// leading up to each file, and no symlink in the way is followed. With
+/// [`RootPath::jail`], which trees containing absolute symlinks need, and
+/// elsewhere, paths are resolved with [`RootPath::resolve`] instead.
+/// Symlinks themselves only get their ownership changed.
+#[derive(Clone, Debug)]
+pub struct Applier {
+    root: RootPath,
+    remove_extras: bool,
+    check_first: bool,
+    id_map: Option<IdMap>,
+}
+
+impl Applier {
+    pub fn new(root: RootPath) -> Self {
+        Applier {
+            root,
+            remove_extras: false,
+            check_first: false,
+            id_map: None,
+        }
+    }
+
+    /// Also removes files that aren't in the spec. Off by default.
+    pub fn remove_extras(mut self, remove_extras: bool) -> Self {
+        self.remove_extras = remove_extras;
+        self
+    }
+
+    /// Checks that every operation in a plan can be carried out before
+    /// running any of them, so a failure doesn't leave the tree half fixed.
+    ///
+    /// The check covers existence, write access to the directories entries
+    /// are created in or removed from, and ownership of the files to change.
+    /// It can't rule out everything, e.g. `chgrp` to a group the process
+    /// isn't a member of.
+    pub fn check_first(mut self, check_first: bool) -> Self {
+        self.check_first = check_first;
+        self
+    }
+
+    /// Treats the spec's `uid` and `gid` values as ids inside the user
+    /// namespace `map` describes, and gives files the host's ids for them.
+    pub fn id_map(mut self, map: IdMap) -> Self {
+        self.id_map = Some(map);
+        self
+    }
+
+    /// Works out what needs to change, without touching anything.
+    pub fn plan(&self, spec: &Spec) -> Plan {
+        let mut entries = spec.resolve();
+        if let Some(map) = &self.id_map {
+            for entry in &mut entries {
+                entry
+                    .keywords
+                    .iter_mut()
+                    .for_each(|keyword| map.keyword_outside(keyword));
+            }
+        }
+        let by_path: HashMap<&Path, &ResolvedEntry> = entries
+            .iter()
+            .map(|entry| (entry.path.as_path(), entry))
+            .collect();
+
+        let report = Verifier::new(self.root.clone())
+            .extras(self.remove_extras)
+            .verify_entries(&entries);
+
+        let mut plan = Plan::default();
+
+        for finding in report.findings {
+            let entry = by_path.get(finding.path()).copied();
+            let is_link = entry.is_some_and(|entry| {
+                entry.get(KeywordKind::Type) == Some(&Keyword::Type(Type::Link))
+            });
+
+            match (&finding, entry) {
+                (VerifyFinding::Missing { path }, Some(entry)) => {
+                    let create = match entry.get(KeywordKind::Type) {
+                        Some(Keyword::Type(Type::Dir)) => {
+                            Operation::CreateDir { path: path.clone() }
+                        }
+                        Some(Keyword::Type(Type::Link)) => match entry.get(KeywordKind::Link) {
+                            Some(Keyword::Link(target)) => Operation::CreateSymlink {
+                                path: path.clone(),
+                                target: target.clone(),
+                            },
+                            _ => {
+                                plan.unfixable.push(finding);
+                                continue;
+                            }
+                        },
+                        _ => {
+                            plan.unfixable.push(finding);
+                            continue;
+                        }
+                    };
+
+                    plan.operations.push(create);
+                    plan.operations.extend(
+                        entry
+                            .keywords
+                            .iter()
+                            .filter(|keyword| settable(keyword, is_link))
+                            .map(|keyword| Operation::Set {
+                                path: path.clone(),
+                                old: None,
+                                new: keyword.clone(),
+                            }),
+                    );
+                }
+                (
+                    VerifyFinding::Mismatch {
+                        path,
+                        expected,
+                        actual,
+                    },
+                    _,
+                ) if settable(expected, is_link) => plan.operations.push(Operation::Set {
+                    path: path.clone(),
+                    old: Some(actual.clone()),
+                    new: expected.clone(),
+                }),
+                (VerifyFinding::Extra { path }, _) => plan
+                    .operations
+                    .push(Operation::Remove { path: path.clone() }),
+                _ => plan.unfixable.push(finding),
+            }
+        }
+
+        plan.operations.sort_by_key(Operation::phase);
+        plan
+    }
+
+    /// Plans and executes the changes for `spec`.
+    pub fn apply(&self, spec: &Spec) -> Result<Vec<ActionRecord>, CheckError> {
+        self.execute(&self.plan(spec))
+    }
+
+    /// Carries out `plan`. Every operation is attempted even if an earlier
+    /// one failed, and the returned records say which ones did.
+    ///
+    /// With [`Applier::check_first`], nothing is changed if any operation
+    /// is found to be impossible beforehand.
+    pub fn execute(&self, plan: &Plan) -> Result<Vec<ActionRecord>, CheckError> {
+        if self.check_first {
+            self.check(plan)?;
+        }
+
+        Ok(plan
+            .operations
+            .iter()
+            .map(|operation| match self.run(operation) {
+                Ok(()) => operation.record(),
+                Err(err) => operation.record().failed(err),
+            })
+            .collect())
+    }
+
+    fn run(&self, operation: &Operation) -> io::Result<()> {
+        // Outside a jail, work relative to descriptors for the directories
+        // on the way, so that a directory swapped for a symlink can't
+        // redirect the change, as the verifier does.
+        #[cfg(target_os = "linux")]
+        if !self.root.is_jail() {
+            let relative = self.root.relative(operation.path())?;
+            let (dir, name) = Dir::open(self.root.path())?.open_parent(&relative)?;
+            return run_at(&dir, &name, operation);
+        }
+
+        let path = self.root.resolve(operation.path())?;
+
+        match operation {
+            Operation::CreateDir { .. } => fs::create_dir(&path),
+            Operation::CreateSymlink { target, .. } => symlink(target, &path),
+            Operation::Set { new, .. } => match new {
+                Keyword::Uid(uid) => lchown(&path, Some(*uid), None),
+                Keyword::Gid(gid) => lchown(&path, None, Some(*gid)),
+                Keyword::Mode(mode) => {
+                    refuse_symlink(&path)?;
+                    fs::set_permissions(&path, Permissions::from_mode(mode.bits()))
+                }
+                Keyword::Time(time) => {
+                    refuse_symlink(&path)?;
+                    let time = time.to_system_time().ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "time out of range")
+                    })?;
+                    File::open(&path)?.set_modified(time)
+                }
+                other => Err(unsettable(other)),
+            },
+            Operation::Remove { .. } => {
+                if fs::symlink_metadata(&path)?.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                }
+            }
+        }
+    }
+
+    fn check(&self, plan: &Plan) -> Result<(), CheckError> {
+        let euid = unsafe { libc::geteuid() };
+        let mut created = HashSet::new();
+        let mut problems = Vec::new();
+
+        for operation in &plan.operations {
+            if let Err(err) = self.check_one(operation, euid, &mut created) {
+                problems.push((operation.clone(), err));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(CheckError { problems })
+        }
+    }
+
+    fn check_one(
+        &self,
+        operation: &Operation,
+        euid: u32,
+        created: &mut HashSet<PathBuf>,
+    ) -> io::Result<()> {
+        let path = self.root.resolve(operation.path())?;
+
+        match operation {
+            Operation::CreateDir { .. } | Operation::CreateSymlink { .. } => {
+                if let Some(parent) = path.parent().filter(|parent| !created.contains(*parent)) {
+                    writable(parent)?;
+                }
+                created.insert(path);
+            }
+            Operation::Remove { .. } => {
+                if let Some(parent) = path.parent() {
+                    writable(parent)?;
+                }
+            }
+            Operation::Set { new, .. } => {
+                if matches!(new, Keyword::Uid(_)) && euid != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "changing the owner requires root",
+                    ));
+                }
+
+                if !created.contains(&path) {
+                    let metadata = fs::symlink_metadata(&path)?;
+                    if euid != 0 && metadata.uid() != euid {
+                        return Err(io::Error::new(
+                            io::ErrorKind::PermissionDenied,
+                            "not the owner of the file",
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `keyword` is one the applier sets. Symlinks only get their
+/// ownership changed.
+fn settable(keyword: &Keyword, is_link: bool) -> bool {
+    match keyword {
+        Keyword::Uid(_) | Keyword::Gid(_) => true,
+        Keyword::Mode(_) | Keyword::Time(_) => !is_link,
+        _ => false,
+    }
+}
+
+/// Carries out `operation` on `name` in `dir`, without following symlinks.
+#[cfg(target_os = "linux")]
+fn run_at(dir: &Dir, name: &OsStr, operation: &Operation) -> io::Result<()> {
+    match operation {
+        Operation::CreateDir { .. } => dir.create_dir(name),
+        Operation::CreateSymlink { target, .. } => dir.symlink(target, name),
+        Operation::Set { new, .. } => match new {
+            Keyword::Uid(uid) => dir.chown(name, Some(*uid), None),
+            Keyword::Gid(gid) => dir.chown(name, None, Some(*gid)),
+            Keyword::Mode(mode) => dir.set_mode(name, mode.bits()),
+            Keyword::Time(time) => dir.set_modified(name, *time),
+            other => Err(unsettable(other)),
+        },
+        Operation::Remove { .. } => dir.remove_all(name),
+    }
+}
+
+fn unsettable(keyword: &Keyword) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("can't set {}", keyword.kind().name()),
+    )
+}
+
+fn refuse_symlink(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_symlink() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "refusing to follow a symlink",
+        ));
+    }
+
+    Ok(())
+}
+
+fn writable(dir: &Path) -> io::Result<()> {
+    let dir = CString::new(dir.as_os_str().as_bytes())?;
+
+    if unsafe { libc::access(dir.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mode;
+    use crate::testutil::TempDir;
+
+    fn set_mode(path: &Path, mode: u32) {
+        fs::set_permissions(path, Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::symlink_metadata(path).unwrap().mode() & Mode::MASK
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = TempDir::new();
+        set_mode(&dir.file("etc/passwd", "root:x:0:0"), 0o600);
+        dir.file("stray", "");
+
+        let spec = Spec::parse(
+            ". type=dir\n\
+             etc type=dir\n\
+             \x20   passwd type=file mode=0644\n\
+             ..\n\
+             var type=dir mode=0750\n\
+             \x20   log type=dir\n\
+             \x20   ..\n\
+             ..\n\
+             link type=link link=etc/passwd\n",
+        )
+        .unwrap();
+
+        let applier = Applier::new(RootPath::new(dir.path())).remove_extras(true);
+        let plan = applier.plan(&spec);
+        let mode_0644 = Keyword::Mode(Mode::new(0o644).unwrap());
+
+        assert_eq!(plan.unfixable, vec![]);
+        assert_eq!(
+            plan.operations,
+            vec![
+                Operation::CreateDir {
+                    path: PathBuf::from("./var")
+                },
+                Operation::CreateDir {
+                    path: PathBuf::from("./var/log")
+                },
+                Operation::CreateSymlink {
+                    path: PathBuf::from("./link"),
+                    target: PathBuf::from("etc/passwd")
+                },
+                Operation::Remove {
+                    path: PathBuf::from("./stray")
+                },
+                Operation::Set {
+                    path: PathBuf::from("./etc/passwd"),
+                    old: Some(Keyword::Mode(Mode::new(0o600).unwrap())),
+                    new: mode_0644.clone(),
+                },
+                Operation::Set {
+                    path: PathBuf::from("./var"),
+                    old: None,
+                    new: Keyword::Mode(Mode::new(0o750).unwrap()),
+                },
+            ]
+        );
+        assert_eq!(
+            plan.operations[4].to_string(),
+            "./etc/passwd: chmod 0600 -> 0644"
+        );
+
+        // Planning is a dry run.
+        assert_eq!(mode(&dir.path().join("etc/passwd")), 0o600);
+        assert!(dir.path().join("stray").exists());
+
+        let records = applier.execute(&plan).unwrap();
+        assert!(records.iter().all(|record| record.result.is_ok()));
+        assert_eq!(records[4].old.as_deref(), Some("0600"));
+
+        assert_eq!(mode(&dir.path().join("etc/passwd")), 0o644);
+        assert_eq!(mode(&dir.path().join("var")), 0o750);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("link")).unwrap(),
+            "root:x:0:0"
+        );
+        assert!(applier.plan(&spec).is_empty());
+    }
+
+    #[test]
+    fn test_apply_check_first() {
+        let dir = TempDir::new();
+        let file = dir.file("file", "");
+        set_mode(&file, 0o644);
+
+        let spec = Spec::parse(
+            "missing type=file\n\
+             missing/dir type=dir\n\
+             file type=file mode=0600\n",
+        )
+        .unwrap();
+
+        let applier = Applier::new(RootPath::new(dir.path()));
+        let plan = applier.plan(&spec);
+
+        assert_eq!(
+            plan.unfixable,
+            vec![VerifyFinding::Missing {
+                path: PathBuf::from("./missing")
+            }]
+        );
+        assert_eq!(plan.operations.len(), 2);
+
+        let err = applier
+            .clone()
+            .check_first(true)
+            .execute(&plan)
+            .unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+        assert_eq!(err.problems[0].0.path(), Path::new("./missing/dir"));
+        assert_eq!(mode(&file), 0o644);
+
+        let records = applier.execute(&plan).unwrap();
+        assert!(records[0].result.is_err());
+        assert!(records[1].result.is_ok());
+        assert_eq!(mode(&file), 0o600);
+    }
+
+    #[test]
+    fn test_apply_id_map() {
+        let dir = TempDir::new();
+        let file = dir.file("file", "");
+        let uid = fs::metadata(&file).unwrap().uid();
+        let spec = Spec::parse("file type=file uid=0\n").unwrap();
+
+        let plain = Applier::new(RootPath::new(dir.path()));
+        let mapped = plain.clone().id_map(IdMap::new().map_uids(0, uid, 1));
+
+        assert_eq!(plain.plan(&spec).is_empty(), uid == 0);
+        assert!(mapped.plan(&spec).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_apply_swapped_parent() {
+        let dir = TempDir::new();
+        let outside = dir.file("outside/file", "");
+        set_mode(&outside, 0o600);
+        symlink("outside", dir.path().join("swapped")).unwrap();
+
+        let plan = Plan {
+            operations: vec![
+                Operation::Set {
+                    path: PathBuf::from("./swapped/file"),
+                    old: None,
+                    new: Keyword::Mode(Mode::new(0o644).unwrap()),
+                },
+                Operation::Remove {
+                    path: PathBuf::from("./swapped/file"),
+                },
+                Operation::Remove {
+                    path: PathBuf::from("./swapped"),
+                },
+            ],
+            unfixable: Vec::new(),
+        };
+
+        let records = Applier::new(RootPath::new(dir.path()))
+            .execute(&plan)
+            .unwrap();
+        assert!(records[0].result.is_err());
+        assert!(records[1].result.is_err());
+        assert!(records[2].result.is_ok());
+
+        assert_eq!(mode(&outside), 0o600);
+        assert!(!dir.path().join("swapped").exists());
+    }
+}
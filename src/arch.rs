@@ -0,0 +1,119 @@
+//! pacman's `.MTREE` files, which list the files in an Arch Linux package.
+//!
+//! libarchive writes them gzip-compressed, with `./`-prefixed full paths,
+//! `/set` lines for the common `uid`, `gid` and `mode`, `time` values with a
+//! `.0` fraction, and both `md5digest` and `sha256digest` on every file. They
+//! also list the package's own metadata files, such as `.PKGINFO`, which
+//! aren't installed.
+
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::{Line, ReadError, Spec};
+
+/// Files at the top of a package that describe it rather than being
+/// installed.
+pub const METADATA_FILES: &[&str] = &[".BUILDINFO", ".CHANGELOG", ".INSTALL", ".MTREE", ".PKGINFO"];
+
+/// Whether `path` is one of the [`METADATA_FILES`] at the top of a package.
+pub fn is_package_metadata(path: &Path) -> bool {
+    let mut components = path
+        .components()
+        .filter(|component| *component != Component::CurDir);
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => METADATA_FILES.iter().any(|file| name == *file),
+        _ => false,
+    }
+}
+
+impl Spec {
+    /// Reads a `.MTREE` file, compressed or not, leaving out the package's
+    /// metadata files. Compressed files need the `gzip` feature.
+    pub fn from_arch_mtree(reader: impl Read) -> Result<Spec, ReadError> {
+        let mut spec = Spec::from_reader(reader)?;
+        spec.lines.retain(
+            |line| !matches!(line, Line::Entry { entry, .. } if is_package_metadata(&entry.path)),
+        );
+
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{Digest, DigestKind, Keyword, KeywordKind, Mode, Timestamp, Type};
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/hello.mtree");
+
+    #[test]
+    fn test_arch_mtree() {
+        let spec = Spec::from_arch_mtree(FIXTURE).unwrap();
+        let entries = spec.resolve();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>(),
+            [
+                "./usr",
+                "./usr/bin",
+                "./usr/bin/hello",
+                "./usr/lib",
+                "./usr/lib/libhello.so",
+                "./usr/share",
+                "./usr/share/licenses",
+                "./usr/share/licenses/hello",
+                "./usr/share/licenses/hello/LICENSE file",
+            ]
+            .map(PathBuf::from)
+        );
+
+        let hello = &entries[2];
+        let mut expected = vec![
+            Keyword::Type(Type::File),
+            Keyword::Uid(0),
+            Keyword::Gid(0),
+            Keyword::Mode(Mode::new(0o755).unwrap()),
+            Keyword::Time(Timestamp::new(1700000000, 0)),
+            Keyword::Size(21),
+            Keyword::Digest(
+                Digest::from_hex(DigestKind::Md5, "d604a220708aa59433ba410986cd4ffa").unwrap(),
+            ),
+            Keyword::Digest(
+                Digest::from_hex(
+                    DigestKind::Sha256,
+                    "bfdeaeb08cffb6a36438bcd12dda25417e3cdd36f1e7e482a2849d539225288b",
+                )
+                .unwrap(),
+            ),
+        ];
+        let mut actual = hello.keywords.clone();
+        expected.sort_by_key(Keyword::kind);
+        actual.sort_by_key(Keyword::kind);
+        assert_eq!(actual, expected);
+
+        let link = &entries[4];
+        assert_eq!(
+            link.get(KeywordKind::Link),
+            Some(&Keyword::Link(PathBuf::from("libhello.so.1")))
+        );
+        assert_eq!(
+            link.get(KeywordKind::Mode),
+            Some(&Keyword::Mode(Mode::new(0o777).unwrap()))
+        );
+
+        assert!(is_package_metadata(Path::new(".PKGINFO")));
+        assert!(!is_package_metadata(Path::new("./usr/.PKGINFO")));
+
+        #[cfg(feature = "gzip")]
+        assert_eq!(
+            Spec::from_arch_mtree(&include_bytes!("../fixtures/hello.MTREE")[..]).unwrap(),
+            spec
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Integration with tar archives, behind the `tar` feature.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::cksum::Cksum;
+use crate::{
+    Keyword, KeywordKind, KeywordSet, Mode, ResolvedEntry, Spec, Timestamp, Tree, Type,
+    VerifyFinding, VerifyReport,
+};
+
+/// Normalizes a path for matching archive members against spec entries, so
+/// `./usr/bin/`, `usr/bin` and `/usr/bin` all compare equal.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+fn keywords_by_path(spec: &Spec) -> HashMap<PathBuf, Vec<Keyword>> {
+    Tree::from_spec(spec)
+        .iter()
+        .filter(|node| !node.keywords.is_empty())
+        .map(|node| (normalize(&node.path), node.keywords.clone()))
+        .collect()
+}
+
+/// Copies the tar archive in `input` to `output`, overriding each member's
+/// ownership, mode and modification time with the values the spec records
+/// for its path. Members without a matching entry are copied unchanged.
+///
+/// This allows a tree built by an unprivileged user to be packaged with the
+/// ownership described by a spec, without needing root.
+pub fn rewrite_archive<R: Read, W: Write>(spec: &Spec, input: R, output: W) -> io::Result<W> {
+    let keywords = keywords_by_path(spec);
+
+    let mut archive = tar::Archive::new(input);
+    let mut builder = tar::Builder::new(output);
+
+    for member in archive.entries()? {
+        let mut member = member?;
+        let path = member.path()?.into_owned();
+        let mut header = member.header().clone();
+
+        if let Some(keywords) = keywords.get(&normalize(&path)) {
+            apply_keywords(&mut header, keywords)?;
+        }
+
+        builder.append_data(&mut header, &path, &mut member)?;
+    }
+
+    builder.into_inner()
+}
+
+/// Checks the members of the tar archive in `input` against `spec` without
+/// extracting it: their type, size, ownership, mode, modification time, link
+/// target, `cksum` and, with the `hash` feature, digests.
+///
+/// Tar headers store whole seconds, so only those of `time` are compared.
+/// Members without an entry are reported as [`VerifyFinding::Extra`] and
+/// entries without a member as [`VerifyFinding::Missing`], except for the
+/// root, which archives often leave out.
+pub fn verify_archive(spec: &Spec, input: impl Read) -> io::Result<VerifyReport> {
+    let entries = spec.resolve();
+    let by_path: HashMap<PathBuf, &ResolvedEntry> = entries
+        .iter()
+        .map(|entry| (normalize(&entry.path), entry))
+        .collect();
+    let ignored: Vec<PathBuf> = entries
+        .iter()
+        .filter(|entry| entry.is_ignored())
+        .map(|entry| normalize(&entry.path))
+        .collect();
+    let is_ignored = |path: &Path| {
+        ignored
+            .iter()
+            .any(|dir| path != dir && path.starts_with(dir))
+    };
+
+    let mut report = VerifyReport::default();
+    let mut seen = HashSet::new();
+
+    let mut archive = tar::Archive::new(input);
+    for member in archive.entries()? {
+        let mut member = member?;
+        let path = normalize(&member.path()?);
+
+        // Headers such as pax global headers don't describe a file.
+        let Some(ty) = member_type(member.header().entry_type()) else {
+            continue;
+        };
+        if is_ignored(&path) {
+            continue;
+        }
+
+        let Some(entry) = by_path.get(&path) else {
+            report.findings.push(VerifyFinding::Extra {
+                path: Path::new(".").join(&path),
+            });
+            continue;
+        };
+        seen.insert(path);
+
+        if entry.get(KeywordKind::NoChange).is_some() {
+            continue;
+        }
+
+        let content = entry
+            .keywords
+            .iter()
+            .fold(KeywordSet::empty(), |set, keyword| {
+                set | KeywordSet::from_kind(keyword.kind())
+            });
+        let actual = member_keywords(&mut member, ty, content)?;
+        report.findings.extend(mismatches(entry, &actual));
+    }
+
+    for entry in &entries {
+        let path = normalize(&entry.path);
+        if !path.as_os_str().is_empty()
+            && !seen.contains(&path)
+            && !entry.is_optional()
+            && !is_ignored(&path)
+        {
+            report.findings.push(VerifyFinding::Missing {
+                path: entry.path.clone(),
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+pub(crate) fn member_type(ty: tar::EntryType) -> Option<Type> {
+    match ty {
+        // A hard link is another name for a regular file.
+        tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::Link => {
+            Some(Type::File)
+        }
+        tar::EntryType::Directory => Some(Type::Dir),
+        tar::EntryType::Symlink => Some(Type::Link),
+        tar::EntryType::Block => Some(Type::Block),
+        tar::EntryType::Char => Some(Type::Char),
+        tar::EntryType::Fifo => Some(Type::Fifo),
+        _ => None,
+    }
+}
+
+/// The keywords a tar header describes, plus those of `content` that need
+/// the member's data, which is read to compute them.
+pub(crate) fn member_keywords<R: Read>(
+    member: &mut tar::Entry<'_, R>,
+    ty: Type,
+    content: KeywordSet,
+) -> io::Result<Vec<Keyword>> {
+    let header = member.header();
+    let mut keywords = vec![
+        Keyword::Type(ty.clone()),
+        Keyword::Mode(Mode::from_st_mode(header.mode()?)),
+        Keyword::Time(Timestamp::new(header.mtime()? as i64, 0)),
+    ];
+
+    if let Ok(uid) = u32::try_from(header.uid()?) {
+        keywords.push(Keyword::Uid(uid));
+    }
+    if let Ok(gid) = u32::try_from(header.gid()?) {
+        keywords.push(Keyword::Gid(gid));
+    }
+    if let Ok(Some(name)) = header.username()
+        && !name.is_empty()
+    {
+        keywords.push(Keyword::Uname(name.into()));
+    }
+    if let Ok(Some(name)) = header.groupname()
+        && !name.is_empty()
+    {
+        keywords.push(Keyword::Gname(name.into()));
+    }
+
+    let is_hard_link = header.entry_type() == tar::EntryType::Link;
+    if ty == Type::Link
+        && let Some(target) = member.link_name()?
+    {
+        keywords.push(Keyword::Link(target.into_owned()));
+    }
+
+    if ty == Type::File && !is_hard_link {
+        keywords.push(Keyword::Size(member.header().size()?));
+        keywords.extend(content_keywords(member, content)?);
+    }
+
+    Ok(keywords)
+}
+
+/// The `cksum` and digests in `keywords`, computed in one pass.
+fn content_keywords(mut data: impl Read, keywords: KeywordSet) -> io::Result<Vec<Keyword>> {
+    let mut cksum = keywords.contains(KeywordSet::CKSUM).then(Cksum::new);
+
+    #[cfg(feature = "hash")]
+    let mut hashers: Vec<crate::hash::Hasher> =
+        keywords.digests().map(crate::hash::Hasher::new).collect();
+    #[cfg(not(feature = "hash"))]
+    let hashers: Vec<()> = Vec::new();
+
+    if cksum.is_none() && hashers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = data.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        if let Some(cksum) = &mut cksum {
+            cksum.update(&buf[..n]);
+        }
+        #[cfg(feature = "hash")]
+        hashers
+            .iter_mut()
+            .for_each(|hasher| hasher.update(&buf[..n]));
+    }
+
+    let mut keywords = Vec::new();
+    if let Some(cksum) = cksum {
+        keywords.push(Keyword::Cksum(cksum.finish()));
+    }
+    #[cfg(feature = "hash")]
+    keywords.extend(
+        hashers
+            .into_iter()
+            .map(|hasher| Keyword::Digest(hasher.finish())),
+    );
+
+    Ok(keywords)
+}
+
+fn mismatches(entry: &ResolvedEntry, actual: &[Keyword]) -> Vec<VerifyFinding> {
+    entry
+        .keywords
+        .iter()
+        .filter_map(|expected| {
+            let actual = actual
+                .iter()
+                .find(|actual| actual.kind() == expected.kind())?;
+
+            let matches = match (expected, actual) {
+                (Keyword::Time(expected), Keyword::Time(actual)) => expected.secs == actual.secs,
+                _ => expected == actual,
+            };
+
+            (!matches).then(|| VerifyFinding::Mismatch {
+                path: entry.path.clone(),
+                expected: expected.clone(),
+                actual: actual.clone(),
+            })
+        })
+        .collect()
+}
+
+fn apply_keywords(header: &mut tar::Header, keywords: &[Keyword]) -> io::Result<()> {
+    for keyword in keywords {
+        match keyword {
+            Keyword::Uid(uid) => header.set_uid(u64::from(*uid)),
+            Keyword::Gid(gid) => header.set_gid(u64::from(*gid)),
+            Keyword::Uname(name) => header.set_username(name)?,
+            Keyword::Gname(name) => header.set_groupname(name)?,
+            Keyword::Mode(mode) => header.set_mode(mode.bits()),
+            Keyword::Time(time) => header.set_mtime(time.secs.max(0) as u64),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_with(path: &str, data: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o600);
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_mtime(12345);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder.append_data(&mut header, path, data).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_rewrite_archive() {
+        let spec = Spec::parse(
+            "/set uid=0 gid=0 uname=root gname=wheel\n\
+             ./etc/passwd mode=0644 time=1700000000.0\n",
+        )
+        .unwrap();
+
+        let input = archive_with("etc/passwd", b"root:x:0:0");
+        let output = rewrite_archive(&spec, input.as_slice(), Vec::new()).unwrap();
+
+        let mut archive = tar::Archive::new(output.as_slice());
+        let mut member = archive.entries().unwrap().next().unwrap().unwrap();
+        let header = member.header();
+
+        assert_eq!(header.uid().unwrap(), 0);
+        assert_eq!(header.gid().unwrap(), 0);
+        assert_eq!(header.mode().unwrap(), 0o644);
+        assert_eq!(header.mtime().unwrap(), 1700000000);
+        assert_eq!(header.username().unwrap(), Some("root"));
+
+        let mut data = String::new();
+        member.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "root:x:0:0");
+    }
+
+    #[test]
+    fn test_verify_archive() {
+        let spec = Spec::parse(
+            "/set type=file uid=1000 gid=1000 mode=0600\n\
+             ./etc/passwd size=10 time=12345.678 cksum=1408427749\n\
+             ./etc/shadow optional\n\
+             ./etc/group\n",
+        )
+        .unwrap();
+
+        let archive = archive_with("etc/passwd", b"root:x:0:0");
+        let report = verify_archive(&spec, archive.as_slice()).unwrap();
+        assert_eq!(
+            report.findings,
+            [VerifyFinding::Missing {
+                path: PathBuf::from("./etc/group"),
+            }]
+        );
+
+        let archive = archive_with("etc/passwd", b"root:x:0:1");
+        let report = verify_archive(&spec, archive.as_slice()).unwrap();
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter_map(VerifyFinding::keyword)
+                .collect::<Vec<_>>(),
+            [KeywordKind::Cksum]
+        );
+
+        let archive = archive_with("etc/hosts", b"");
+        let report = verify_archive(&spec, archive.as_slice()).unwrap();
+        assert!(report.findings.contains(&VerifyFinding::Extra {
+            path: PathBuf::from("./etc/hosts"),
+        }));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("./usr/bin/")), PathBuf::from("usr/bin"));
+        assert_eq!(normalize(Path::new("/usr/bin")), PathBuf::from("usr/bin"));
+    }
+}
@@ -0,0 +1,73 @@
+//! Standard base64 with padding, as go-mtree uses for `xattr` values.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (idx, &byte)| {
+            n | u32::from(byte) << (16 - 8 * idx)
+        });
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+
+    for (chunk_idx, chunk) in text.chunks(4).enumerate() {
+        let is_last = chunk_idx == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+
+        let mut n = 0u32;
+        for (idx, &c) in chunk[..4 - padding].iter().enumerate() {
+            let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+            n |= value << (18 - 6 * idx);
+        }
+
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text).as_deref(), Some(bytes));
+        }
+
+        assert_eq!(decode("Zm9"), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Zm9*"), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Command-line interface to the library, loosely following BSD mtree(8).
+//!
+//! Exits with 0 on success, 2 when `check` or `diff` find differences or
+//! `lint` finds problems, and 1 on errors, as mtree does.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use mtree3::format::{FormatOptions, format_spec};
+use mtree3::reader::decompressed;
+use mtree3::verify::Verifier;
+use mtree3::{
+    KeywordSet, Linter, ParseOptions, RootPath, Severity, Spec, SpecBuilder, TerminalFormatter,
+    Timestamp, diff, sanitize, write_spec,
+};
+
+const USAGE: &str = "\
+usage: mtree3 create [-k keywords] <dir>
+       mtree3 check [-e] [-k keywords] <spec> <dir>
+       mtree3 diff <spec> <spec>
+       mtree3 fmt <spec>
+       mtree3 lint [-x rule] <spec>
+
+A spec of `-` is read from standard input. Specs may be gzip-compressed
+if built with the `gzip` feature. `-k all` selects every keyword. `create`
+records no time later than SOURCE_DATE_EPOCH, if it's set.";
+
+const MISMATCH: u8 = 2;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("mtree3: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    let Some((command, args)) = args.split_first() else {
+        return Ok(usage());
+    };
+
+    match command.as_str() {
+        "create" => create(args),
+        "check" => check(args),
+        "diff" => diff_specs(args),
+        "fmt" => fmt(args),
+        "lint" => lint(args),
+        "help" | "-h" | "--help" => {
+            println!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        other => {
+            eprintln!("mtree3: unknown command `{}`", sanitize::text(other));
+            Ok(usage())
+        }
+    }
+}
+
+fn create(args: &[String]) -> Result<ExitCode, String> {
+    let mut keywords = KeywordSet::default();
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-k" => {
+                let list = args.next().ok_or("-k needs a list of keywords")?;
+                keywords = list.parse().map_err(|err| format!("-k: {err}"))?;
+            }
+            arg => operands.push(option_or_operand(arg)?),
+        }
+    }
+
+    let [dir] = operands[..] else {
+        return Ok(usage());
+    };
+
+    let mut builder = SpecBuilder::from_dir(dir).keywords(keywords);
+    if env::var_os("SOURCE_DATE_EPOCH").is_some() {
+        let epoch = Timestamp::source_date_epoch().ok_or("invalid SOURCE_DATE_EPOCH")?;
+        builder = builder.clamp_times(epoch);
+    }
+
+    let spec = builder
+        .build()
+        .map_err(|err| format!("{}: {err}", sanitize::text(dir)))?;
+    write_spec(&spec, io::stdout().lock()).map_err(|err| err.to_string())?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check(args: &[String]) -> Result<ExitCode, String> {
+    let mut extras = false;
+    let mut keywords = None;
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => extras = true,
+            "-k" => {
+                let list = args.next().ok_or("-k needs a list of keywords")?;
+                keywords = Some(list.parse().map_err(|err| format!("-k: {err}"))?);
+            }
+            arg => operands.push(option_or_operand(arg)?),
+        }
+    }
+
+    let [spec, dir] = operands[..] else {
+        return Ok(usage());
+    };
+
+    let spec = read_spec(spec)?;
+    let mut verifier = Verifier::new(RootPath::new(dir)).extras(extras);
+    if let Some(keywords) = keywords {
+        verifier = verifier.keywords(keywords);
+    }
+    let report = verifier.verify(&spec);
+
+    let formatter = TerminalFormatter::for_stdout();
+    for finding in &report.findings {
+        println!("{}", formatter.format_finding(finding));
+    }
+
+    Ok(status(report.is_clean()))
+}
+
+fn diff_specs(args: &[String]) -> Result<ExitCode, String> {
+    let operands = args
+        .iter()
+        .map(|arg| option_or_operand(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [old, new] = operands[..] else {
+        return Ok(usage());
+    };
+
+    let diff = diff(&read_spec(old)?, &read_spec(new)?);
+    print!("{diff}");
+
+    Ok(status(diff.is_empty()))
+}
+
+fn fmt(args: &[String]) -> Result<ExitCode, String> {
+    let operands = args
+        .iter()
+        .map(|arg| option_or_operand(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [spec] = operands[..] else {
+        return Ok(usage());
+    };
+
+    print!(
+        "{}",
+        format_spec(&read_spec(spec)?, &FormatOptions::default())
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn lint(args: &[String]) -> Result<ExitCode, String> {
+    let mut linter = Linter::default();
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-x" => linter = linter.without(args.next().ok_or("-x needs a rule name")?),
+            arg => operands.push(option_or_operand(arg)?),
+        }
+    }
+
+    let [spec] = operands[..] else {
+        return Ok(usage());
+    };
+
+    let findings = linter.lint(&read_spec(spec)?);
+    for finding in &findings {
+        println!("{finding}");
+    }
+
+    Ok(status(
+        findings
+            .iter()
+            .all(|finding| finding.severity < Severity::Warning),
+    ))
+}
+
+/// Rejects unknown options; `-` on its own is an operand.
+fn option_or_operand(arg: &str) -> Result<&str, String> {
+    if arg.starts_with('-') && arg != "-" {
+        Err(format!("unknown option `{}`", sanitize::text(arg)))
+    } else {
+        Ok(arg)
+    }
+}
+
+fn read_spec(path: &str) -> Result<Spec, String> {
+    let name = sanitize::text(path);
+
+    let reader = if path == "-" {
+        decompressed(io::stdin().lock())
+    } else {
+        File::open(path).and_then(decompressed)
+    };
+
+    let mut input = String::new();
+    reader
+        .and_then(|mut reader| reader.read_to_string(&mut input))
+        .map_err(|err| format!("{name}: {err}"))?;
+
+    Spec::parse(&input).map_err(|err| {
+        let diagnostic = err.diagnose(&input, &ParseOptions::default());
+        format!("{name}: {diagnostic}")
+    })
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::FAILURE
+}
+
+fn status(clean: bool) -> ExitCode {
+    if clean {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(MISMATCH)
+    }
+}
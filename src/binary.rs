@@ -0,0 +1,110 @@
+//! Compact binary encoding of a [`Spec`], intended for services that reload
+//! the same large manifest repeatedly and want to skip text parsing.
+//!
+//! The encoding is a 4-byte magic (`MTR3`), a little-endian `u16` format
+//! version, and the postcard serialization of the spec. Digest kinds are
+//! written by name, so specs encoded with and without the `blake3` and
+//! `xxh3` features read the same; a kind the reader doesn't support is an
+//! error.
+
+use std::fmt;
+
+use crate::Spec;
+
+pub const MAGIC: [u8; 4] = *b"MTR3";
+pub const VERSION: u16 = 2;
+
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+#[derive(Debug)]
+pub enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Postcard(postcard::Error),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::BadMagic => write!(f, "not a binary mtree spec"),
+            BinaryError::UnsupportedVersion(version) => {
+                write!(f, "unsupported binary spec version {version}")
+            }
+            BinaryError::Postcard(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<postcard::Error> for BinaryError {
+    fn from(err: postcard::Error) -> Self {
+        BinaryError::Postcard(err)
+    }
+}
+
+pub fn encode(spec: &Spec) -> Result<Vec<u8>, BinaryError> {
+    let body = postcard::to_stdvec(spec)?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&body);
+
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Spec, BinaryError> {
+    if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+        return Err(BinaryError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+
+    Ok(postcard::from_bytes(&bytes[HEADER_LEN..])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let spec = Spec::parse(
+            "# comment\n\
+             /set type=file uid=0\n\
+             foo size=1 time=1769203027.452198079\n",
+        )
+        .unwrap();
+
+        let bytes = encode(&spec).unwrap();
+        assert_eq!(&bytes[..4], b"MTR3");
+        assert_eq!(decode(&bytes).unwrap(), spec);
+    }
+
+    #[test]
+    fn test_binary_digest_kind() {
+        let spec = Spec::parse(&format!("foo sha256digest={}\n", "ab".repeat(32))).unwrap();
+        let mut bytes = encode(&spec).unwrap();
+        assert_eq!(decode(&bytes).unwrap(), spec);
+
+        let name = bytes
+            .windows(12)
+            .position(|window| window == b"sha256digest")
+            .unwrap();
+        bytes[name..name + 12].copy_from_slice(b"sha999digest");
+        assert!(matches!(decode(&bytes), Err(BinaryError::Postcard(_))));
+    }
+
+    #[test]
+    fn test_binary_header() {
+        assert!(matches!(decode(b"nope"), Err(BinaryError::BadMagic)));
+        assert!(matches!(
+            decode(b"MTR3\x09\x00"),
+            Err(BinaryError::UnsupportedVersion(9))
+        ));
+    }
+}
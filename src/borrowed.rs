@@ -0,0 +1,415 @@
+//! Parsing without copying names and paths out of the source text.
+//!
+//! [`parse_lines_ref`] yields the same lines as [`parse_lines`](crate::parse_lines),
+//! but user and group names, tags, comments and paths borrow from the input
+//! instead of each getting their own `String`. Only paths with escapes and
+//! lines joined by `\` continuations are copied.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use chumsky::prelude::*;
+
+use crate::line::logical_lines;
+use crate::{
+    Command, Device, Digest, Entry, EntryId, Flags, Keyword, KeywordKind, Line, Mode, ParseError,
+    ParseOptions, Span, Timestamp, Type, escape, parse_command, parse_keyword, value,
+};
+
+/// A [`Keyword`] that borrows its text from the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeywordRef<'src> {
+    Type(Type),
+    Uid(u32),
+    Gid(u32),
+    Uname(Cow<'src, str>),
+    Gname(Cow<'src, str>),
+    Mode(Mode),
+    Flags(Flags),
+    Nlink(u64),
+    Inode(u64),
+    Device(Device),
+    ResDevice(Device),
+    Time(Timestamp),
+    TarTime(Timestamp),
+    Size(u64),
+    Cksum(u32),
+    Digest(Digest),
+    Link(Cow<'src, Path>),
+    Contents(Cow<'src, Path>),
+    HardLink(Cow<'src, Path>),
+    Optional,
+    Ignore,
+    NoChange,
+    Tags(Vec<Cow<'src, str>>),
+    EntryDigest(Digest),
+    Selinux(Cow<'src, str>),
+    Xattr {
+        name: Cow<'src, str>,
+        value: Vec<u8>,
+    },
+    Raw {
+        kind: KeywordKind,
+        value: Cow<'src, str>,
+    },
+}
+
+impl KeywordRef<'_> {
+    /// Copies any borrowed text, so the keyword no longer refers to the
+    /// source.
+    pub fn into_owned(self) -> KeywordRef<'static> {
+        match self {
+            KeywordRef::Uname(name) => KeywordRef::Uname(Cow::Owned(name.into_owned())),
+            KeywordRef::Gname(name) => KeywordRef::Gname(Cow::Owned(name.into_owned())),
+            KeywordRef::Link(path) => KeywordRef::Link(Cow::Owned(path.into_owned())),
+            KeywordRef::Contents(path) => KeywordRef::Contents(Cow::Owned(path.into_owned())),
+            KeywordRef::HardLink(path) => KeywordRef::HardLink(Cow::Owned(path.into_owned())),
+            KeywordRef::Tags(tags) => KeywordRef::Tags(
+                tags.into_iter()
+                    .map(|tag| Cow::Owned(tag.into_owned()))
+                    .collect(),
+            ),
+            KeywordRef::Selinux(label) => KeywordRef::Selinux(Cow::Owned(label.into_owned())),
+            KeywordRef::Xattr { name, value } => KeywordRef::Xattr {
+                name: Cow::Owned(name.into_owned()),
+                value,
+            },
+            KeywordRef::Raw { kind, value } => KeywordRef::Raw {
+                kind,
+                value: Cow::Owned(value.into_owned()),
+            },
+            other => Keyword::from(other).into(),
+        }
+    }
+}
+
+impl From<Keyword> for KeywordRef<'_> {
+    fn from(keyword: Keyword) -> Self {
+        match keyword {
+            Keyword::Type(ty) => KeywordRef::Type(ty),
+            Keyword::Uid(uid) => KeywordRef::Uid(uid),
+            Keyword::Gid(gid) => KeywordRef::Gid(gid),
+            Keyword::Uname(name) => KeywordRef::Uname(Cow::Owned(name.to_string())),
+            Keyword::Gname(name) => KeywordRef::Gname(Cow::Owned(name.to_string())),
+            Keyword::Mode(mode) => KeywordRef::Mode(mode),
+            Keyword::Flags(flags) => KeywordRef::Flags(flags),
+            Keyword::Nlink(nlink) => KeywordRef::Nlink(nlink),
+            Keyword::Inode(inode) => KeywordRef::Inode(inode),
+            Keyword::Device(device) => KeywordRef::Device(device),
+            Keyword::ResDevice(device) => KeywordRef::ResDevice(device),
+            Keyword::Time(time) => KeywordRef::Time(time),
+            Keyword::TarTime(time) => KeywordRef::TarTime(time),
+            Keyword::Size(size) => KeywordRef::Size(size),
+            Keyword::Cksum(cksum) => KeywordRef::Cksum(cksum),
+            Keyword::Digest(digest) => KeywordRef::Digest(digest),
+            Keyword::Link(path) => KeywordRef::Link(Cow::Owned(path)),
+            Keyword::Contents(path) => KeywordRef::Contents(Cow::Owned(path)),
+            Keyword::HardLink(path) => KeywordRef::HardLink(Cow::Owned(path)),
+            Keyword::Optional => KeywordRef::Optional,
+            Keyword::Ignore => KeywordRef::Ignore,
+            Keyword::NoChange => KeywordRef::NoChange,
+            Keyword::Tags(tags) => KeywordRef::Tags(tags.into_iter().map(Cow::Owned).collect()),
+            Keyword::EntryDigest(digest) => KeywordRef::EntryDigest(digest),
+            Keyword::Selinux(label) => KeywordRef::Selinux(Cow::Owned(label)),
+            Keyword::Xattr { name, value } => KeywordRef::Xattr {
+                name: Cow::Owned(name),
+                value,
+            },
+            Keyword::Raw { kind, value } => KeywordRef::Raw {
+                kind,
+                value: Cow::Owned(value),
+            },
+        }
+    }
+}
+
+impl From<KeywordRef<'_>> for Keyword {
+    fn from(keyword: KeywordRef<'_>) -> Self {
+        match keyword {
+            KeywordRef::Type(ty) => Keyword::Type(ty),
+            KeywordRef::Uid(uid) => Keyword::Uid(uid),
+            KeywordRef::Gid(gid) => Keyword::Gid(gid),
+            KeywordRef::Uname(name) => Keyword::Uname(name.into()),
+            KeywordRef::Gname(name) => Keyword::Gname(name.into()),
+            KeywordRef::Mode(mode) => Keyword::Mode(mode),
+            KeywordRef::Flags(flags) => Keyword::Flags(flags),
+            KeywordRef::Nlink(nlink) => Keyword::Nlink(nlink),
+            KeywordRef::Inode(inode) => Keyword::Inode(inode),
+            KeywordRef::Device(device) => Keyword::Device(device),
+            KeywordRef::ResDevice(device) => Keyword::ResDevice(device),
+            KeywordRef::Time(time) => Keyword::Time(time),
+            KeywordRef::TarTime(time) => Keyword::TarTime(time),
+            KeywordRef::Size(size) => Keyword::Size(size),
+            KeywordRef::Cksum(cksum) => Keyword::Cksum(cksum),
+            KeywordRef::Digest(digest) => Keyword::Digest(digest),
+            KeywordRef::Link(path) => Keyword::Link(path.into_owned()),
+            KeywordRef::Contents(path) => Keyword::Contents(path.into_owned()),
+            KeywordRef::HardLink(path) => Keyword::HardLink(path.into_owned()),
+            KeywordRef::Optional => Keyword::Optional,
+            KeywordRef::Ignore => Keyword::Ignore,
+            KeywordRef::NoChange => Keyword::NoChange,
+            KeywordRef::Tags(tags) => {
+                Keyword::Tags(tags.into_iter().map(Cow::into_owned).collect())
+            }
+            KeywordRef::EntryDigest(digest) => Keyword::EntryDigest(digest),
+            KeywordRef::Selinux(label) => Keyword::Selinux(label.into_owned()),
+            KeywordRef::Xattr { name, value } => Keyword::Xattr {
+                name: name.into_owned(),
+                value,
+            },
+            KeywordRef::Raw { kind, value } => Keyword::Raw {
+                kind,
+                value: value.into_owned(),
+            },
+        }
+    }
+}
+
+/// An [`Entry`] that borrows its text from the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryRef<'src> {
+    pub path: Cow<'src, Path>,
+    pub keywords: Vec<KeywordRef<'src>>,
+}
+
+impl EntryRef<'_> {
+    pub fn into_owned(self) -> EntryRef<'static> {
+        EntryRef {
+            path: Cow::Owned(self.path.into_owned()),
+            keywords: self
+                .keywords
+                .into_iter()
+                .map(KeywordRef::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl From<EntryRef<'_>> for Entry {
+    fn from(entry: EntryRef<'_>) -> Self {
+        Entry {
+            path: entry.path.into_owned(),
+            keywords: entry.keywords.into_iter().map(Keyword::from).collect(),
+        }
+    }
+}
+
+/// A [`Line`] that borrows its text from the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineRef<'src> {
+    Comment {
+        text: Cow<'src, str>,
+        span: Span,
+    },
+    Blank {
+        span: Span,
+    },
+    Command {
+        command: Command,
+        span: Span,
+    },
+    Entry {
+        id: EntryId,
+        entry: EntryRef<'src>,
+        span: Span,
+    },
+}
+
+impl LineRef<'_> {
+    pub fn span(&self) -> &Span {
+        match self {
+            LineRef::Comment { span, .. }
+            | LineRef::Blank { span }
+            | LineRef::Command { span, .. }
+            | LineRef::Entry { span, .. } => span,
+        }
+    }
+
+    pub fn into_owned(self) -> LineRef<'static> {
+        match self {
+            LineRef::Comment { text, span } => LineRef::Comment {
+                text: Cow::Owned(text.into_owned()),
+                span,
+            },
+            LineRef::Blank { span } => LineRef::Blank { span },
+            LineRef::Command { command, span } => LineRef::Command { command, span },
+            LineRef::Entry { id, entry, span } => LineRef::Entry {
+                id,
+                entry: entry.into_owned(),
+                span,
+            },
+        }
+    }
+}
+
+impl From<LineRef<'_>> for Line {
+    fn from(line: LineRef<'_>) -> Self {
+        match line {
+            LineRef::Comment { text, span } => Line::Comment {
+                text: text.into_owned(),
+                span,
+            },
+            LineRef::Blank { span } => Line::Blank { span },
+            LineRef::Command { command, span } => Line::Command { command, span },
+            LineRef::Entry { id, entry, span } => Line::Entry {
+                id,
+                entry: entry.into(),
+                span,
+            },
+        }
+    }
+}
+
+/// Decodes a path, borrowing it if it has no escapes.
+fn decode_path(input: &str) -> Cow<'_, Path> {
+    if input.contains('\\') {
+        Cow::Owned(escape::decode_path(input))
+    } else {
+        Cow::Borrowed(Path::new(input))
+    }
+}
+
+/// Decodes a name or other text, borrowing it if it has no escapes.
+fn decode_str(input: &str) -> Cow<'_, str> {
+    if input.contains('\\') {
+        Cow::Owned(escape::decode_str(input))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+pub fn parse_keyword_ref<'src>() -> impl Parser<'src, &'src str, KeywordRef<'src>> {
+    let tag = none_of(" \t,")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .map(decode_str);
+
+    // Keywords without text to borrow are left to `parse_keyword`.
+    choice((
+        just("uname=")
+            .ignore_then(value())
+            .map(|name| KeywordRef::Uname(decode_str(name))),
+        just("gname=")
+            .ignore_then(value())
+            .map(|name| KeywordRef::Gname(decode_str(name))),
+        just("link=")
+            .ignore_then(value())
+            .map(|path| KeywordRef::Link(decode_path(path))),
+        just("contents=")
+            .ignore_then(value())
+            .map(|path| KeywordRef::Contents(decode_path(path))),
+        just("tags=")
+            .ignore_then(tag.separated_by(just(',')).at_least(1).collect())
+            .map(KeywordRef::Tags),
+        parse_keyword().map(KeywordRef::from),
+    ))
+}
+
+pub fn parse_entry_ref<'src>() -> impl Parser<'src, &'src str, EntryRef<'src>> {
+    let path = value().map(decode_path);
+    let keywords = parse_keyword_ref()
+        .separated_by(text::whitespace())
+        .collect();
+
+    path.padded()
+        .then(keywords)
+        .map(|(path, keywords)| EntryRef { path, keywords })
+}
+
+/// Like [`parse_line`](crate::line::parse_line), but borrows from `text`.
+pub fn parse_line_ref(text: &str, span: Span) -> Result<LineRef<'_>, ParseError> {
+    let trimmed = text.trim();
+
+    if trimmed.is_empty() {
+        return Ok(LineRef::Blank { span });
+    }
+
+    if let Some(comment) = trimmed.strip_prefix('#') {
+        return Ok(LineRef::Comment {
+            text: Cow::Borrowed(comment),
+            span,
+        });
+    }
+
+    if trimmed.starts_with('/') {
+        return match parse_command().parse(trimmed).into_result() {
+            Ok(command) => Ok(LineRef::Command { command, span }),
+            Err(_) => Err(ParseError::new(text, span, &ParseOptions::default())),
+        };
+    }
+
+    match parse_entry_ref().parse(trimmed).into_result() {
+        Ok(entry) => Ok(LineRef::Entry {
+            id: EntryId::default(),
+            entry,
+            span,
+        }),
+        Err(_) => Err(ParseError::new(text, span, &ParseOptions::default())),
+    }
+}
+
+/// Like [`parse_lines`](crate::parse_lines), but borrows from `input`.
+pub fn parse_lines_ref(input: &str) -> Result<Vec<LineRef<'_>>, ParseError> {
+    let mut next_id = 0;
+
+    logical_lines(input)
+        .into_iter()
+        .map(|(text, span)| {
+            let mut line = match text {
+                Cow::Borrowed(text) => parse_line_ref(text, span)?,
+                Cow::Owned(text) => parse_line_ref(&text, span)?.into_owned(),
+            };
+
+            if let LineRef::Entry { id, .. } = &mut line {
+                *id = EntryId(next_id);
+                next_id += 1;
+            }
+
+            Ok(line)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::parse_lines;
+
+    #[test]
+    fn test_parse_lines_ref() {
+        let input = "# comment\n\
+                     /set uid=0 uname=root\n\
+                     ./usr/bin/su uname=root gname=wheel tags=a,b link=../b\\040c\n\
+                     ./joined \\\n    contents=./x\n";
+
+        let lines = parse_lines_ref(input).unwrap();
+        assert_eq!(
+            lines.iter().cloned().map(Line::from).collect::<Vec<_>>(),
+            parse_lines(input).unwrap()
+        );
+
+        let LineRef::Entry { entry, .. } = &lines[2] else {
+            panic!("expected an entry, got {:?}", lines[2]);
+        };
+        assert!(matches!(entry.path, Cow::Borrowed(_)));
+        assert!(matches!(
+            &entry.keywords[..],
+            [
+                KeywordRef::Uname(Cow::Borrowed("root")),
+                KeywordRef::Gname(Cow::Borrowed("wheel")),
+                KeywordRef::Tags(_),
+                KeywordRef::Link(Cow::Owned(_)),
+            ]
+        ));
+        assert_eq!(
+            entry.keywords[3],
+            KeywordRef::Link(Cow::Owned(PathBuf::from("../b c")))
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Exporting file contents into a content-addressed store.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Digest, DigestKind, Keyword, KeywordKind, RootPath, Spec, Type};
+
+/// The digests used to name objects, most preferred first.
+const PREFERENCE: [DigestKind; 6] = [
+    DigestKind::Sha256,
+    DigestKind::Sha512,
+    DigestKind::Sha384,
+    DigestKind::Rmd160,
+    DigestKind::Sha1,
+    DigestKind::Md5,
+];
+
+/// A directory of file contents named by their digest, laid out as
+/// `objects/ab/cdef…`, so identical files are only stored once.
+#[derive(Clone, Debug)]
+pub struct ContentStore {
+    path: PathBuf,
+}
+
+impl ContentStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ContentStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the object for `digest` is kept, relative to the store.
+    pub fn object_path(digest: &Digest) -> PathBuf {
+        let hex = digest.to_string();
+        let (prefix, rest) = hex.split_at(2);
+
+        Path::new("objects").join(prefix).join(rest)
+    }
+
+    /// Copies `source` into the store as the object for `digest`, unless it's
+    /// already there. Returns whether the object was added.
+    ///
+    /// The contents aren't hashed, so `digest` must be correct.
+    pub fn insert(&self, digest: &Digest, source: &Path) -> io::Result<bool> {
+        let object = self.path.join(Self::object_path(digest));
+        if object.exists() {
+            return Ok(false);
+        }
+
+        if let Some(parent) = object.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Copy under a temporary name first so an interrupted copy never
+        // leaves a truncated object behind.
+        let partial = object.with_extension("partial");
+        fs::copy(source, &partial)?;
+        fs::rename(&partial, &object)?;
+
+        Ok(true)
+    }
+
+    /// Copies every regular file in `spec` that has a digest from `source`
+    /// into the store, and returns a copy of `spec` where those entries have
+    /// a `contents` keyword pointing at their object, relative to the store.
+    pub fn export(&self, spec: &Spec, source: &RootPath) -> io::Result<Spec> {
+        let mut exported = spec.clone();
+
+        for entry in spec.resolve() {
+            if matches!(entry.get(KeywordKind::Type), Some(Keyword::Type(ty)) if *ty != Type::File)
+            {
+                continue;
+            }
+
+            let Some(digest) =
+                PREFERENCE
+                    .iter()
+                    .find_map(|kind| match entry.get(KeywordKind::Digest(*kind)) {
+                        Some(Keyword::Digest(digest)) => Some(digest),
+                        _ => None,
+                    })
+            else {
+                continue;
+            };
+
+            self.insert(digest, &source.resolve(&entry.path)?)?;
+
+            let Some(exported) = exported.entry_mut(entry.id) else {
+                continue;
+            };
+
+            let contents = Keyword::Contents(Self::object_path(digest));
+            match exported
+                .keywords
+                .iter_mut()
+                .find(|keyword| keyword.kind() == KeywordKind::Contents)
+            {
+                Some(existing) => *existing = contents,
+                None => exported.keywords.push(contents),
+            }
+        }
+
+        Ok(exported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    const HELLO: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    const OTHER: &str = "d9298a10d1b0735837dc4bd85dac641b0f3cef27a47e5d53a54f2f3f5b2fcffa";
+
+    #[test]
+    fn test_export() {
+        let source = TempDir::new();
+        source.file("a", "hello");
+        source.file("dir/b", "hello");
+        source.file("c", "other");
+
+        let spec = Spec::parse(&format!(
+            ". type=dir\n\
+             a type=file sha256digest={HELLO}\n\
+             c type=file sha256digest={OTHER}\n\
+             dir type=dir\n\
+             \x20   b sha256digest={HELLO}\n"
+        ))
+        .unwrap();
+
+        let store = TempDir::new();
+        let cas = ContentStore::new(store.path());
+        let exported = cas.export(&spec, &RootPath::new(source.path())).unwrap();
+
+        let hello_object = PathBuf::from(format!("objects/2c/{}", &HELLO[2..]));
+        assert_eq!(
+            ContentStore::object_path(&HELLO.parse().unwrap()),
+            hello_object
+        );
+        assert_eq!(
+            fs::read_to_string(store.path().join(&hello_object)).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            fs::read_dir(store.path().join("objects")).unwrap().count(),
+            2
+        );
+
+        let contents: Vec<_> = exported
+            .resolve()
+            .into_iter()
+            .map(|entry| entry.get(KeywordKind::Contents).cloned())
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                None,
+                Some(Keyword::Contents(hello_object.clone())),
+                Some(Keyword::Contents(
+                    format!("objects/d9/{}", &OTHER[2..]).into()
+                )),
+                None,
+                Some(Keyword::Contents(hello_object)),
+            ]
+        );
+
+        // Exporting again reuses the existing objects.
+        assert_eq!(
+            cas.export(&spec, &RootPath::new(source.path())).unwrap(),
+            exported
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Specs that vouch for other specs by digest, so one spec for a whole
+//! distribution can cover the specs of the packages in it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hash::hash_reader;
+use crate::sanitize;
+use crate::verify::Verifier;
+use crate::{
+    Digest, DigestKind, Entry, Keyword, Line, ResolvedEntry, RootPath, Span, Spec, Type,
+    VerifyReport,
+};
+
+impl Spec {
+    /// Records the size and digests of the child spec at `path`, whose
+    /// contents are `text`. An existing entry with that path keeps its other
+    /// keywords; otherwise a new entry is added at the end.
+    pub fn pin_child(
+        &mut self,
+        path: impl Into<PathBuf>,
+        text: &[u8],
+        kinds: impl IntoIterator<Item = DigestKind>,
+    ) {
+        let path = path.into();
+        let digests = hash_reader(text, kinds).expect("reading from a slice can't fail");
+
+        let mut pinned = vec![Keyword::Type(Type::File), Keyword::Size(text.len() as u64)];
+        pinned.extend(digests.into_iter().map(Keyword::Digest));
+
+        let entry = match self.find_id(&path) {
+            Some(id) => self.entry_mut(id).expect("found by id"),
+            None => {
+                let id = self.next_entry_id();
+                self.lines.push(Line::Entry {
+                    id,
+                    entry: Entry {
+                        path,
+                        keywords: Vec::new(),
+                    },
+                    span: Span::default(),
+                });
+                self.entry_mut(id).expect("just added")
+            }
+        };
+
+        entry
+            .keywords
+            .retain(|keyword| !pinned.iter().any(|new| new.kind() == keyword.kind()));
+        entry.keywords.extend(pinned);
+    }
+}
+
+/// A problem with a child spec, which keeps it from being checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainFinding {
+    /// The child spec's entry has no digest to pin its contents.
+    Unpinned { path: PathBuf },
+    /// The child spec's contents don't match the digest it's pinned by.
+    Tampered {
+        path: PathBuf,
+        expected: Digest,
+        actual: Digest,
+    },
+    /// The child spec couldn't be read or parsed.
+    Unreadable { path: PathBuf, message: String },
+}
+
+impl ChainFinding {
+    pub fn path(&self) -> &Path {
+        match self {
+            ChainFinding::Unpinned { path }
+            | ChainFinding::Tampered { path, .. }
+            | ChainFinding::Unreadable { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ChainFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainFinding::Unpinned { path } => write!(f, "{}: no digest", sanitize::path(path)),
+            ChainFinding::Tampered {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: {} expected {expected}, found {actual}",
+                sanitize::path(path),
+                expected.kind().name()
+            ),
+            ChainFinding::Unreadable { path, message } => {
+                write!(f, "{}: {message}", sanitize::path(path))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainReport {
+    /// The child specs that were trusted and checked, in the order they were
+    /// reached.
+    pub children: Vec<PathBuf>,
+    /// The findings of the top-level spec and every trusted child spec.
+    pub report: VerifyReport,
+    pub chain: Vec<ChainFinding>,
+}
+
+impl ChainReport {
+    pub fn is_clean(&self) -> bool {
+        self.report.is_clean() && self.chain.is_empty()
+    }
+}
+
+type ChildFilter = dyn Fn(&Path) -> bool + Send + Sync;
+
+/// Checks a spec and, through the digests it records for them, the child
+/// specs it lists, all against the same root.
+///
+/// A child spec is only read if its entry pins it with at least one digest,
+/// and only trusted if its contents match every one of them. Its entries
+/// are then checked too, along with any child specs of its own.
+#[derive(Clone)]
+pub struct ChainVerifier {
+    root: RootPath,
+    is_child: Arc<ChildFilter>,
+}
+
+impl fmt::Debug for ChainVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainVerifier")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChainVerifier {
+    /// Treats files with the `.mtree` extension as child specs.
+    pub fn new(root: RootPath) -> Self {
+        ChainVerifier {
+            root,
+            is_child: Arc::new(|path| path.extension().is_some_and(|ext| ext == "mtree")),
+        }
+    }
+
+    /// Sets which entries are child specs, by their spec path.
+    pub fn children(mut self, is_child: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        self.is_child = Arc::new(is_child);
+        self
+    }
+
+    pub fn verify(&self, spec: &Spec) -> ChainReport {
+        let mut report = ChainReport::default();
+        let mut seen = HashSet::new();
+        let mut pending = vec![spec.clone()];
+
+        while let Some(spec) = pending.pop() {
+            let entries = spec.resolve();
+            let findings = Verifier::new(self.root.clone()).verify_entries(&entries);
+            report.report.findings.extend(findings.findings);
+
+            let mut children = Vec::new();
+            for entry in &entries {
+                if !(self.is_child)(&entry.path) || !seen.insert(entry.path.clone()) {
+                    continue;
+                }
+
+                match self.load_child(entry) {
+                    Ok(child) => {
+                        report.children.push(entry.path.clone());
+                        children.push(child);
+                    }
+                    Err(finding) => report.chain.push(*finding),
+                }
+            }
+
+            // Pushed in reverse so they're checked in spec order.
+            pending.extend(children.into_iter().rev());
+        }
+
+        report
+    }
+
+    /// Reads the child spec `entry` describes and checks it against the
+    /// digests in the entry.
+    fn load_child(&self, entry: &ResolvedEntry) -> Result<Spec, Box<ChainFinding>> {
+        let path = &entry.path;
+        let unreadable = |message: String| {
+            Box::new(ChainFinding::Unreadable {
+                path: path.clone(),
+                message,
+            })
+        };
+
+        let pinned: Vec<&Digest> = entry
+            .keywords
+            .iter()
+            .filter_map(|keyword| match keyword {
+                Keyword::Digest(digest) => Some(digest),
+                _ => None,
+            })
+            .collect();
+        if pinned.is_empty() {
+            return Err(Box::new(ChainFinding::Unpinned { path: path.clone() }));
+        }
+
+        let file = self
+            .root
+            .resolve(path)
+            .map_err(|err| unreadable(err.to_string()))?;
+        let text = fs::read(file).map_err(|err| unreadable(err.to_string()))?;
+
+        let actual = hash_reader(&text[..], pinned.iter().map(|digest| digest.kind()))
+            .expect("reading from a slice can't fail");
+        for (expected, actual) in pinned.into_iter().zip(actual) {
+            if *expected != actual {
+                return Err(Box::new(ChainFinding::Tampered {
+                    path: path.clone(),
+                    expected: *expected,
+                    actual,
+                }));
+            }
+        }
+
+        let text = String::from_utf8(text).map_err(|err| unreadable(err.to_string()))?;
+        Spec::parse(&text).map_err(|err| unreadable(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_verify_chain() {
+        let dir = TempDir::new();
+        dir.file("pkg/a/file", "hello");
+        let child = "./pkg/a/file type=file size=5\n";
+        dir.file("pkg/a.mtree", child);
+
+        let mut spec = Spec::parse(". type=dir\n").unwrap();
+        spec.pin_child("./pkg/a.mtree", child.as_bytes(), [DigestKind::Sha256]);
+        assert_eq!(
+            spec.find(Path::new("./pkg/a.mtree")).unwrap().keywords[..2],
+            [Keyword::Type(Type::File), Keyword::Size(child.len() as u64)]
+        );
+
+        let verifier = ChainVerifier::new(RootPath::new(dir.path()));
+        let report = verifier.verify(&spec);
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(report.children, vec![PathBuf::from("./pkg/a.mtree")]);
+
+        dir.file("pkg/a/file", "hello!");
+        let report = verifier.verify(&spec);
+        assert_eq!(report.report.findings.len(), 1);
+        assert_eq!(report.report.findings[0].path(), Path::new("./pkg/a/file"));
+
+        dir.file("pkg/a.mtree", "./pkg/a/file type=file size=6\n");
+        let report = verifier.verify(&spec);
+        assert!(report.children.is_empty());
+        assert!(matches!(report.chain[..], [ChainFinding::Tampered { .. }]));
+
+        let mut unpinned = Spec::parse("./pkg/a.mtree type=file\n").unwrap();
+        assert_eq!(
+            verifier.verify(&unpinned).chain,
+            vec![ChainFinding::Unpinned {
+                path: PathBuf::from("./pkg/a.mtree")
+            }]
+        );
+
+        unpinned.pin_child(
+            "./pkg/a.mtree",
+            b"./pkg/a/file size=6\n",
+            [DigestKind::Sha256],
+        );
+        assert_eq!(unpinned.entries().count(), 1);
+    }
+}
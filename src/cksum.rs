@@ -0,0 +1,102 @@
+//! The POSIX 1003.2 `cksum` CRC, as recorded by the `cksum` keyword.
+
+use std::io::{self, Read};
+
+const POLYNOMIAL: u32 = 0x04c1_1db7;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ POLYNOMIAL
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+/// Incremental POSIX `cksum` state.
+#[derive(Clone, Debug, Default)]
+pub struct Cksum {
+    crc: u32,
+    len: u64,
+}
+
+impl Cksum {
+    pub fn new() -> Self {
+        Cksum::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.push(byte);
+        }
+
+        self.len += data.len() as u64;
+    }
+
+    /// Returns the checksum of all data passed to [`Cksum::update`].
+    pub fn finish(&self) -> u32 {
+        let mut state = self.clone();
+        let mut len = self.len;
+
+        while len != 0 {
+            state.push(len as u8);
+            len >>= 8;
+        }
+
+        !state.crc
+    }
+
+    fn push(&mut self, byte: u8) {
+        let idx = ((self.crc >> 24) as u8 ^ byte) as usize;
+        self.crc = (self.crc << 8) ^ TABLE[idx];
+    }
+}
+
+/// Computes the `cksum` value of everything read from `reader`.
+pub fn cksum(mut reader: impl Read) -> io::Result<u32> {
+    let mut state = Cksum::new();
+    let mut buf = [0u8; 8192];
+
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(state.finish()),
+            n => state.update(&buf[..n]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cksum() {
+        assert_eq!(cksum(&b""[..]).unwrap(), 4294967295);
+        assert_eq!(cksum(&b"123456789"[..]).unwrap(), 930766865);
+        assert_eq!(cksum(&b"hello world\n"[..]).unwrap(), 3733384285);
+    }
+
+    #[test]
+    fn test_cksum_incremental() {
+        let mut state = Cksum::new();
+        state.update(b"hello ");
+        state.update(b"world\n");
+
+        assert_eq!(state.finish(), 3733384285);
+    }
+}
@@ -0,0 +1,157 @@
+//! Comments attached to entries.
+//!
+//! A run of comment lines directly above an entry, with no blank line or
+//! command in between, is taken to be about that entry. Edits that move or
+//! remove the entry take the comments along.
+
+use std::ops::Range;
+
+use crate::{Entry, EntryId, Line, Span, Spec};
+
+impl Spec {
+    /// The comments attached to the entry `id`, without the leading `#` and
+    /// the space after it.
+    pub fn comments(&self, id: EntryId) -> Vec<&str> {
+        let Some(idx) = self.line_index(id) else {
+            return Vec::new();
+        };
+
+        self.lines[attached_comments(&self.lines, idx)]
+            .iter()
+            .filter_map(|line| match line {
+                Line::Comment { text, .. } => Some(text.strip_prefix(' ').unwrap_or(text)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces the comments attached to the entry `id`. Each one is written
+    /// as `# comment`. Returns whether the entry exists.
+    pub fn set_comments<S: Into<String>>(
+        &mut self,
+        id: EntryId,
+        comments: impl IntoIterator<Item = S>,
+    ) -> bool {
+        let Some(idx) = self.line_index(id) else {
+            return false;
+        };
+
+        let lines = comments.into_iter().map(|comment| Line::Comment {
+            text: format!(" {}", comment.into()),
+            span: Span::default(),
+        });
+
+        let range = attached_comments(&self.lines, idx);
+        self.lines.splice(range, lines);
+        true
+    }
+
+    /// Removes the entry `id` along with its comments.
+    pub fn remove_entry(&mut self, id: EntryId) -> Option<Entry> {
+        let idx = self.line_index(id)?;
+        let range = attached_comments(&self.lines, idx);
+
+        match self.lines.drain(range.start..=idx).next_back() {
+            Some(Line::Entry { entry, .. }) => Some(entry),
+            _ => unreachable!("the range ends at the entry"),
+        }
+    }
+
+    fn line_index(&self, id: EntryId) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| matches!(line, Line::Entry { id: other, .. } if *other == id))
+    }
+}
+
+/// The range of comment lines directly above `lines[idx]`.
+pub(crate) fn attached_comments(lines: &[Line], idx: usize) -> Range<usize> {
+    let count = lines[..idx]
+        .iter()
+        .rev()
+        .take_while(|line| matches!(line, Line::Comment { .. }))
+        .count();
+
+    idx - count..idx
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{KeywordEdits, KeywordKind};
+
+    const SPEC: &str = "#mtree\n\
+                        \n\
+                        /set uid=0\n\
+                        # keep this setuid\n\
+                        #see bug 123\n\
+                        ./usr/bin/su mode=4755\n\
+                        ./usr/bin/ls mode=0755\n";
+
+    #[test]
+    fn test_comments() {
+        let spec = Spec::parse(SPEC).unwrap();
+        let su = spec.find_id(Path::new("./usr/bin/su")).unwrap();
+        let ls = spec.find_id(Path::new("./usr/bin/ls")).unwrap();
+
+        assert_eq!(spec.comments(su), vec!["keep this setuid", "see bug 123"]);
+        assert!(spec.comments(ls).is_empty());
+    }
+
+    #[test]
+    fn test_set_comments() {
+        let mut spec = Spec::parse(SPEC).unwrap();
+        let su = spec.find_id(Path::new("./usr/bin/su")).unwrap();
+        let ls = spec.find_id(Path::new("./usr/bin/ls")).unwrap();
+
+        assert!(spec.set_comments(su, ["setuid on purpose"]));
+        assert!(spec.set_comments(ls, ["from coreutils"]));
+        assert!(!spec.set_comments(EntryId(99), ["nothing"]));
+
+        assert_eq!(
+            spec.to_string(),
+            "#mtree\n\
+             \n\
+             /set uid=0\n\
+             # setuid on purpose\n\
+             ./usr/bin/su mode=4755\n\
+             # from coreutils\n\
+             ./usr/bin/ls mode=0755\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let mut spec = Spec::parse(SPEC).unwrap();
+        let su = spec.find_id(Path::new("./usr/bin/su")).unwrap();
+
+        assert_eq!(
+            spec.remove_entry(su).map(|entry| entry.path),
+            Some("./usr/bin/su".into())
+        );
+        assert_eq!(
+            spec.to_string(),
+            "#mtree\n\n/set uid=0\n./usr/bin/ls mode=0755\n"
+        );
+        assert_eq!(spec.remove_entry(su), None);
+    }
+
+    #[test]
+    fn test_comments_survive_update() {
+        let mut spec = Spec::parse(SPEC).unwrap();
+        let su = spec.find_id(Path::new("./usr/bin/su")).unwrap();
+
+        spec.update_where(
+            |entry| entry.id == su,
+            &KeywordEdits::new().unset(KeywordKind::Uid),
+        );
+
+        let reparsed = Spec::parse(&spec.to_string()).unwrap();
+        assert_eq!(
+            reparsed.comments(reparsed.find_id(Path::new("./usr/bin/su")).unwrap()),
+            vec!["keep this setuid", "see bug 123"]
+        );
+    }
+}
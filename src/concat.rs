@@ -0,0 +1,145 @@
+//! Combining specs of parts of a tree, such as one per package or build
+//! stage, into a spec of the whole tree.
+
+use std::path::Path;
+
+use crate::resolve::SetState;
+use crate::{Command, EntryId, Line, Span, Spec, UnsetTarget};
+
+impl Spec {
+    /// Moves every entry below `prefix`, e.g. `./bin/ls` to
+    /// `./opt/pkg/bin/ls` for a prefix of `opt/pkg`, so the spec can be
+    /// mounted into a larger tree. `hardlink` keywords follow their
+    /// targets. The directories leading to `prefix` aren't added; they're
+    /// up to the spec it's mounted into.
+    ///
+    /// Specs in the relative form come out with full paths and their
+    /// defaults applied to each entry, since relative entries can only be
+    /// placed below directories of the same spec.
+    pub fn prefixed(&self, prefix: impl AsRef<Path>) -> Spec {
+        let mut spec = Spec::new(self.full_path_lines().into_owned());
+        spec.rewrite_paths(".", prefix);
+        spec
+    }
+
+    /// Joins `specs` into one, in order. Each part resolves as it would on
+    /// its own: where a part leaves `/set` state behind, an `/unset all`
+    /// goes before the next one. Parts in the relative form are written
+    /// with full paths, as by [`Spec::prefixed`], so that they don't depend
+    /// on the directory the part before them ended in.
+    ///
+    /// Entries are numbered afresh, in order, so that ids stay unique.
+    pub fn concat(specs: impl IntoIterator<Item = Spec>) -> Spec {
+        let mut lines = Vec::new();
+        let mut state = SetState::new();
+        let mut next_id = 0;
+
+        for spec in specs {
+            if !state.keywords().is_empty() {
+                lines.push(Line::Command {
+                    command: Command::Unset(UnsetTarget::All),
+                    span: Span::default(),
+                });
+                state = SetState::new();
+            }
+
+            for mut line in spec.full_path_lines().into_owned() {
+                match &mut line {
+                    Line::Command { command, .. } => state.apply(command),
+                    Line::Entry { id, .. } => {
+                        *id = EntryId(next_id);
+                        next_id += 1;
+                    }
+                    _ => {}
+                }
+
+                lines.push(line);
+            }
+        }
+
+        Spec::new(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Keyword, KeywordKind};
+
+    fn paths(spec: &Spec) -> Vec<String> {
+        spec.resolve()
+            .iter()
+            .map(|entry| entry.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_prefixed() {
+        let spec = Spec::parse(
+            "/set uid=0\n\
+             . type=dir\n\
+             ./bin/ls hardlink=./bin/dir\n\
+             etc/passwd\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.prefixed("opt/pkg").to_string(),
+            "/set uid=0\n\
+             ./opt/pkg type=dir\n\
+             ./opt/pkg/bin/ls hardlink=./opt/pkg/bin/dir\n\
+             opt/pkg/etc/passwd\n"
+        );
+
+        let relative = Spec::parse(
+            "/set type=file uid=0\n\
+             . type=dir\n\
+             bin type=dir\n\
+             \x20   ls size=1\n\
+             ..\n",
+        )
+        .unwrap();
+        let prefixed = relative.prefixed("./usr");
+
+        assert_eq!(paths(&prefixed), ["./usr", "./usr/bin", "./usr/bin/ls"]);
+        assert_eq!(
+            prefixed.resolve()[2].get(KeywordKind::Uid),
+            Some(&Keyword::Uid(0))
+        );
+    }
+
+    #[test]
+    fn test_concat() {
+        let base = Spec::parse("/set uid=0\n./usr type=dir\n./usr/lib type=dir\n").unwrap();
+        let pkg = Spec::parse(
+            "/set type=file\n\
+             . type=dir\n\
+             bin type=dir\n\
+             \x20   ls\n\
+             ..\n",
+        )
+        .unwrap();
+        let extra = Spec::parse("./share/doc\n").unwrap();
+
+        let spec = Spec::concat([base, pkg.prefixed("usr/lib/pkg"), extra.prefixed("usr")]);
+
+        assert_eq!(
+            paths(&spec),
+            [
+                "./usr",
+                "./usr/lib",
+                "./usr/lib/pkg",
+                "./usr/lib/pkg/bin",
+                "./usr/lib/pkg/bin/ls",
+                "./usr/share/doc",
+            ]
+        );
+
+        let resolved = spec.resolve();
+        assert_eq!(resolved[2].get(KeywordKind::Uid), None);
+        assert!(resolved[5].keywords.is_empty());
+
+        let ids: Vec<_> = spec.entry_ids().map(|EntryId(id)| id).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,1228 @@
+//! Generating specs from the filesystem, like `mtree -c`.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+#[cfg(feature = "ignore")]
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::cksum::cksum;
+#[cfg(feature = "ignore")]
+use crate::ignore::Ignores;
+use crate::matcher::{PathMatcher, Selection};
+use crate::only::normalize;
+use crate::progress::{ProgressReader, ProgressSink};
+use crate::xattr;
+use crate::{
+    Digest, Entry, EntryId, ExcludeList, IdMap, Keyword, KeywordKind, KeywordSet, Line, Mode,
+    OnlyList, Progress, Span, Spec, SpecWriter, Timestamp, Type,
+};
+
+/// Walks a directory tree and records every file in it as a spec entry.
+///
+/// Entries use full paths starting with `./`, in depth-first order with the
+/// children of each directory sorted by name, so the output is stable for an
+/// unchanged tree.
+#[derive(Clone, Debug)]
+pub struct SpecBuilder {
+    root: PathBuf,
+    keywords: KeywordSet,
+    digest_source: Option<DigestSource>,
+    previous: Option<Previous>,
+    exclude: ExcludeList,
+    only: Option<OnlyList>,
+    select: Option<Selection>,
+    device_limit: Option<u64>,
+    latest_time: Option<Timestamp>,
+    id_map: Option<IdMap>,
+    follow_symlinks: bool,
+    one_filesystem: bool,
+    #[cfg(feature = "ignore")]
+    ignore_files: Vec<OsString>,
+    hard_links: bool,
+    progress: ProgressSink,
+}
+
+type DigestFn = dyn Fn(&Path) -> Vec<Digest> + Send + Sync;
+
+#[derive(Clone)]
+struct DigestSource(Arc<DigestFn>);
+
+impl fmt::Debug for DigestSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DigestSource(..)")
+    }
+}
+
+/// The content keywords of a previous spec's files, by path.
+#[derive(Clone)]
+struct Previous(Arc<HashMap<PathBuf, PreviousFile>>);
+
+struct PreviousFile {
+    /// The size and modification time the keywords were recorded for.
+    stamp: (u64, Timestamp),
+    contents: Vec<Keyword>,
+}
+
+impl fmt::Debug for Previous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Previous({} files)", self.0.len())
+    }
+}
+
+impl SpecBuilder {
+    pub fn from_dir(path: impl Into<PathBuf>) -> Self {
+        SpecBuilder {
+            root: path.into(),
+            keywords: KeywordSet::DEFAULT,
+            digest_source: None,
+            previous: None,
+            exclude: ExcludeList::new(),
+            only: None,
+            select: None,
+            device_limit: None,
+            latest_time: None,
+            id_map: None,
+            follow_symlinks: false,
+            one_filesystem: false,
+            #[cfg(feature = "ignore")]
+            ignore_files: Vec::new(),
+            hard_links: false,
+            progress: ProgressSink::default(),
+        }
+    }
+
+    /// Sets which keywords are recorded. Keywords that don't apply to a file,
+    /// such as `size` for a directory, are left out regardless.
+    ///
+    /// Digest keywords are only computed with the `hash` feature enabled,
+    /// but can be supplied with [`SpecBuilder::digests_from`] without it.
+    pub fn keywords(mut self, keywords: KeywordSet) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    /// Uses digests the caller already has, e.g. from a build system's
+    /// content-addressed store, instead of hashing the files again.
+    ///
+    /// `source` is called with the spec path of each regular file, such as
+    /// `./usr/bin/ls`, and returns the digests it knows for it. Those of the
+    /// selected kinds are recorded as they are; any others that were selected
+    /// are still computed from the file.
+    pub fn digests_from(
+        mut self,
+        source: impl Fn(&Path) -> Vec<Digest> + Send + Sync + 'static,
+    ) -> Self {
+        self.digest_source = Some(DigestSource(Arc::new(source)));
+        self
+    }
+
+    /// Copies `cksum` and digests from `previous`, an earlier spec of the
+    /// same tree, for regular files whose size and modification time are
+    /// unchanged, instead of reading them again. Only entries that record
+    /// both `size` and `time` can be reused; see also [`Spec::refresh`].
+    pub fn reuse(mut self, previous: &Spec) -> Self {
+        let files = previous
+            .resolve()
+            .into_iter()
+            .filter_map(|entry| {
+                let size = entry.size()?;
+                let Some(Keyword::Time(time)) = entry.get(KeywordKind::Time).cloned() else {
+                    return None;
+                };
+                let contents = entry
+                    .keywords
+                    .into_iter()
+                    .filter(|keyword| matches!(keyword, Keyword::Cksum(_) | Keyword::Digest(_)))
+                    .collect();
+
+                Some((
+                    normalize(&entry.path),
+                    PreviousFile {
+                        stamp: (size, time),
+                        contents,
+                    },
+                ))
+            })
+            .collect();
+
+        self.previous = Some(Previous(Arc::new(files)));
+        self
+    }
+
+    /// Leaves out files matching `exclude`, and everything below excluded
+    /// directories.
+    pub fn exclude(mut self, exclude: ExcludeList) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Only records the files in `only` and the directories leading up to
+    /// them.
+    pub fn only(mut self, only: OnlyList) -> Self {
+        self.only = Some(only);
+        self
+    }
+
+    /// Only records files whose paths match `matcher`. Directories that
+    /// don't match aren't descended into.
+    pub fn select(mut self, matcher: impl PathMatcher + 'static) -> Self {
+        self.select = Some(Selection(Arc::new(matcher)));
+        self
+    }
+
+    /// Also reads block and character devices for `cksum` and digests, such
+    /// as the partitions in a firmware image manifest. A device with more
+    /// than `limit` bytes, such as `/dev/zero`, gets neither.
+    pub fn device_contents(mut self, limit: u64) -> Self {
+        self.device_limit = Some(limit);
+        self
+    }
+
+    /// Records `time` and `tar_time` values later than `latest` as
+    /// `latest`, so that a tree built from the same sources at different
+    /// times gives the same spec. Reproducible builds pass
+    /// [`Timestamp::source_date_epoch`].
+    pub fn clamp_times(mut self, latest: Timestamp) -> Self {
+        self.latest_time = Some(latest);
+        self
+    }
+
+    /// Records `uid` and `gid` as seen inside the user namespace `map`
+    /// describes, rather than the host's ids.
+    pub fn id_map(mut self, map: IdMap) -> Self {
+        self.id_map = Some(map);
+        self
+    }
+
+    /// Leaves out files matched by the ignore files called `names`, such as
+    /// `.gitignore` and `.mtreeignore`, in the directories walked. Each one
+    /// applies to the directory it's in and everything below, with those
+    /// further down taking precedence, following git's rules. If `names`
+    /// includes `.gitignore`, the root's `.git/info/exclude` and git's global
+    /// excludes file apply too. The ignore files themselves are still
+    /// recorded unless they ignore themselves. Needs the `ignore` feature.
+    #[cfg(feature = "ignore")]
+    pub fn ignore_files<S: Into<OsString>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.ignore_files = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Records a `hardlink` keyword on each file that is a hard link to one
+    /// seen earlier in the walk, naming that file, so that
+    /// [`Verifier`](crate::verify::Verifier) can check they are still the
+    /// same file. Only supported on Unix.
+    ///
+    /// `hardlink` is an extension of this crate that other mtree
+    /// implementations don't understand.
+    pub fn hard_links(mut self, hard_links: bool) -> Self {
+        self.hard_links = hard_links;
+        self
+    }
+
+    /// Records what symlinks point to instead of the links themselves, like
+    /// `mtree -L`. A link that points back to a directory being walked is
+    /// recorded but not descended into again, and one whose target doesn't
+    /// exist is recorded as a link.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Doesn't descend into directories on a different filesystem from the
+    /// root, like `mtree -x`. The mount points themselves are still
+    /// recorded. Has no effect on platforms other than Unix.
+    pub fn one_filesystem(mut self, one: bool) -> Self {
+        self.one_filesystem = one;
+        self
+    }
+
+    /// Reports the files processed and bytes read while building to
+    /// `progress`. The total is known once the tree has been walked, before
+    /// any file contents are read.
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = ProgressSink(Arc::new(progress));
+        self
+    }
+
+    /// Walks the tree and builds the spec.
+    ///
+    /// The tree is walked first, and file contents are read for `cksum` and
+    /// digests afterwards. With the `rayon` feature that second step runs on
+    /// rayon's thread pool; the entries come out in the same order either way.
+    pub fn build(&self) -> io::Result<Spec> {
+        let files = self.walk()?;
+
+        #[cfg(feature = "rayon")]
+        let contents = {
+            use rayon::prelude::*;
+
+            files
+                .par_iter()
+                .map(|file| self.content_keywords(file))
+                .collect::<io::Result<Vec<_>>>()?
+        };
+
+        #[cfg(not(feature = "rayon"))]
+        let contents = files
+            .iter()
+            .map(|file| self.content_keywords(file))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let lines = files
+            .into_iter()
+            .zip(contents)
+            .enumerate()
+            .map(|(idx, (file, contents))| {
+                let mut entry = file.entry;
+                entry.keywords.extend(contents);
+
+                Line::Entry {
+                    id: EntryId(idx as u64),
+                    entry,
+                    span: Span::default(),
+                }
+            })
+            .collect();
+
+        Ok(Spec::new(lines))
+    }
+
+    /// Walks the tree and writes each entry to `writer` as soon as its
+    /// contents have been read, so that the spec of a large tree is never
+    /// held in memory. Each directory's entries are written as it is
+    /// visited, and files are read one at a time, even with the `rayon`
+    /// feature. The number of files isn't known up front, so
+    /// [`Progress::start`] isn't called.
+    pub fn write_to<W: io::Write>(&self, writer: &mut SpecWriter<W>) -> io::Result<()> {
+        self.walk_with(&mut |file| {
+            let contents = self.content_keywords(&file)?;
+            let mut entry = file.entry;
+            entry.keywords.extend(contents);
+            writer.write_entry(&entry)
+        })
+    }
+
+    /// Records every file in the tree, without reading their contents.
+    fn walk(&self) -> io::Result<Vec<WalkedFile>> {
+        let mut files = Vec::new();
+        self.walk_with(&mut |file| {
+            files.push(file);
+            Ok(())
+        })?;
+        self.progress.0.start(files.len() as u64);
+
+        Ok(files)
+    }
+
+    /// Walks the tree, passing each file to `found` as it is visited.
+    fn walk_with(&self, found: &mut dyn FnMut(WalkedFile) -> io::Result<()>) -> io::Result<()> {
+        #[cfg(feature = "ignore")]
+        let mut walk = Walk {
+            ignores: (!self.ignore_files.is_empty())
+                .then(|| Ignores::new(&self.root, &self.ignore_files))
+                .transpose()?,
+            ..Walk::default()
+        };
+        #[cfg(not(feature = "ignore"))]
+        let mut walk = Walk::default();
+
+        self.visit(&self.root, PathBuf::from("."), &mut walk, found)
+    }
+
+    /// Computes the keywords that require reading the file's contents.
+    fn content_keywords(&self, file: &WalkedFile) -> io::Result<Vec<Keyword>> {
+        self.progress.0.file(&file.entry.path);
+
+        if file.is_file {
+            return self.read_contents(file, || File::open(&file.path));
+        }
+
+        let Some(limit) = self.device_limit.filter(|_| file.is_device) else {
+            return Ok(Vec::new());
+        };
+
+        let open = || Ok(DeviceReader::new(File::open(&file.path)?, limit));
+        match self.read_contents(file, open) {
+            Err(err) if err.kind() == io::ErrorKind::FileTooLarge => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Computes the `cksum` and digests of a file, opening it with `open`
+    /// for each pass over its contents.
+    fn read_contents<R: Read>(
+        &self,
+        file: &WalkedFile,
+        open: impl Fn() -> io::Result<R>,
+    ) -> io::Result<Vec<Keyword>> {
+        let progress = &*self.progress.0;
+        let open = || open().map(|reader| ProgressReader::new(reader, progress));
+        let mut keywords = Vec::new();
+        let reused = self.reused(file);
+
+        if self.keywords.contains(KeywordSet::CKSUM) {
+            match reused
+                .iter()
+                .find(|keyword| matches!(keyword, Keyword::Cksum(_)))
+            {
+                Some(keyword) => keywords.push(keyword.clone()),
+                None => keywords.push(Keyword::Cksum(cksum(open()?)?)),
+            }
+        }
+
+        let wanted: Vec<_> = self.keywords.digests().collect();
+        let mut digests: Vec<Digest> = match &self.digest_source {
+            Some(DigestSource(source)) if file.is_file => source(&file.entry.path),
+            _ => Vec::new(),
+        };
+        digests.extend(reused.iter().filter_map(|keyword| match keyword {
+            Keyword::Digest(digest) => Some(*digest),
+            _ => None,
+        }));
+        digests.retain(|digest| wanted.contains(&digest.kind()));
+
+        #[cfg(feature = "hash")]
+        {
+            let missing: Vec<_> = wanted
+                .iter()
+                .copied()
+                .filter(|kind| !digests.iter().any(|digest| digest.kind() == *kind))
+                .collect();
+
+            if !missing.is_empty() {
+                digests.extend(crate::hash::hash_reader(open()?, missing)?);
+            }
+        }
+
+        digests.sort_by_key(Digest::kind);
+        digests.dedup_by_key(|digest| digest.kind());
+        keywords.extend(digests.into_iter().map(Keyword::Digest));
+
+        Ok(keywords)
+    }
+
+    /// The content keywords recorded for `file` in the spec passed to
+    /// [`SpecBuilder::reuse`], if its size and modification time still match.
+    fn reused(&self, file: &WalkedFile) -> &[Keyword] {
+        let (Some(Previous(previous)), Some(stamp)) = (&self.previous, file.stamp) else {
+            return &[];
+        };
+
+        match previous.get(&file.entry.path) {
+            Some(previous) if previous.stamp == stamp => &previous.contents,
+            _ => &[],
+        }
+    }
+
+    /// Records the file at `path` and, for a directory, everything below it.
+    fn visit(
+        &self,
+        path: &Path,
+        spec_path: PathBuf,
+        walk: &mut Walk,
+        found: &mut dyn FnMut(WalkedFile) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let metadata = if self.follow_symlinks {
+            fs::metadata(path).or_else(|err| match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => Ok(metadata),
+                _ => Err(err),
+            })?
+        } else {
+            fs::symlink_metadata(path)?
+        };
+
+        #[cfg(feature = "ignore")]
+        if walk
+            .ignores
+            .as_ref()
+            .is_some_and(|ignores| ignores.is_ignored(path, metadata.is_dir()))
+        {
+            return Ok(());
+        }
+
+        let mut keywords = metadata_keywords(&metadata, || fs::read_link(path))?;
+        keywords.retain(|keyword| self.keywords.contains_kind(keyword.kind()));
+
+        if let Some(map) = &self.id_map {
+            keywords
+                .iter_mut()
+                .for_each(|keyword| map.keyword_inside(keyword));
+        }
+
+        if let Some(latest) = self.latest_time {
+            for keyword in &mut keywords {
+                match keyword {
+                    Keyword::Time(time) if *time > latest => *time = latest,
+                    Keyword::TarTime(time) if time.secs > latest.secs => {
+                        *time = Timestamp::new(latest.secs, 0)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !(self.keywords & (KeywordSet::XATTR | KeywordSet::SECURITY | KeywordSet::SELINUX))
+            .is_empty()
+        {
+            keywords.extend(xattr::select(xattr::read(path)?, self.keywords));
+        }
+
+        if self.hard_links
+            && !metadata.is_dir()
+            && nlink(&metadata) > 1
+            && let Some(id) = dev_ino(&metadata)
+        {
+            match walk.links.get(&id) {
+                Some(first) => keywords.push(Keyword::HardLink(first.clone())),
+                None => {
+                    walk.links.insert(id, spec_path.clone());
+                }
+            }
+        }
+
+        found(WalkedFile {
+            path: path.to_path_buf(),
+            entry: Entry {
+                path: spec_path.clone(),
+                keywords,
+            },
+            is_file: metadata.is_file(),
+            is_device: matches!(file_type(&metadata), Some(Type::Block | Type::Char)),
+            stamp: modified(&metadata)
+                .filter(|_| metadata.is_file())
+                .map(|time| (metadata.len(), time)),
+        })?;
+
+        if metadata.is_dir() && self.descends(&metadata, &walk.ancestors) {
+            walk.ancestors.extend(dev_ino(&metadata));
+
+            #[cfg(feature = "ignore")]
+            let depth = match &mut walk.ignores {
+                Some(ignores) => ignores.enter(path)?,
+                None => 0,
+            };
+
+            let mut names = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect::<io::Result<Vec<_>>>()?;
+            names.sort();
+
+            for name in names {
+                let child = spec_child(&spec_path, &name);
+                if self.exclude.matches(&child)
+                    || self.only.as_ref().is_some_and(|only| !only.matches(&child))
+                    || self
+                        .select
+                        .as_ref()
+                        .is_some_and(|select| !select.0.matches(&child))
+                {
+                    continue;
+                }
+
+                self.visit(&path.join(&name), child, walk, found)?;
+            }
+
+            walk.ancestors.pop();
+            #[cfg(feature = "ignore")]
+            if let Some(ignores) = &mut walk.ignores {
+                ignores.leave(depth);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether to walk the directory described by `metadata`, given the
+    /// directories above it.
+    fn descends(&self, metadata: &Metadata, ancestors: &[(u64, u64)]) -> bool {
+        let Some((dev, ino)) = dev_ino(metadata) else {
+            return true;
+        };
+
+        if self.one_filesystem
+            && ancestors
+                .first()
+                .is_some_and(|&(root_dev, _)| root_dev != dev)
+        {
+            return false;
+        }
+
+        !ancestors.contains(&(dev, ino))
+    }
+}
+
+/// The device and inode numbers that identify a file, where there are any.
+#[cfg(unix)]
+pub(crate) fn dev_ino(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn dev_ino(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn nlink(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn nlink(_metadata: &Metadata) -> u64 {
+    1
+}
+
+/// What the walk has seen on the way down to the current directory.
+#[derive(Default)]
+struct Walk {
+    /// The device and inode numbers of the directories above, the root's
+    /// first.
+    ancestors: Vec<(u64, u64)>,
+    /// The ignore files found above, if [`SpecBuilder::ignore_files`] was
+    /// given any names.
+    #[cfg(feature = "ignore")]
+    ignores: Option<Ignores>,
+    /// The spec path of the first file seen with each device and inode
+    /// number that has more than one link.
+    links: HashMap<(u64, u64), PathBuf>,
+}
+
+/// A file found while walking, with the keywords read from its metadata.
+struct WalkedFile {
+    path: PathBuf,
+    entry: Entry,
+    is_file: bool,
+    is_device: bool,
+    /// The size and modification time of a regular file, for
+    /// [`SpecBuilder::reuse`].
+    stamp: Option<(u64, Timestamp)>,
+}
+
+/// Reads a device, failing with [`io::ErrorKind::FileTooLarge`] past `limit`
+/// bytes so that endless devices aren't read forever.
+pub(crate) struct DeviceReader<R> {
+    inner: R,
+    limit: u64,
+    left: u64,
+}
+
+impl<R> DeviceReader<R> {
+    pub(crate) fn new(inner: R, limit: u64) -> Self {
+        DeviceReader {
+            inner,
+            limit,
+            left: limit,
+        }
+    }
+}
+
+impl<R: Read> Read for DeviceReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if n as u64 > self.left {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("device is larger than {} bytes", self.limit),
+            ));
+        }
+
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Describes a file with the keywords that can be read from its metadata,
+/// without reading its contents. `read_link` is called for symlinks.
+pub(crate) fn metadata_keywords(
+    metadata: &Metadata,
+    read_link: impl FnOnce() -> io::Result<PathBuf>,
+) -> io::Result<Vec<Keyword>> {
+    let mut keywords = Vec::new();
+
+    if let Some(ty) = file_type(metadata) {
+        keywords.push(Keyword::Type(ty));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        keywords.push(Keyword::Mode(Mode::from_st_mode(metadata.mode())));
+        keywords.push(Keyword::Uid(metadata.uid()));
+        keywords.push(Keyword::Gid(metadata.gid()));
+        keywords.push(Keyword::Nlink(metadata.nlink()));
+        keywords.push(Keyword::Inode(metadata.ino()));
+
+        if matches!(file_type(metadata), Some(Type::Block | Type::Char)) {
+            keywords.push(Keyword::Device(crate::Device::Number(metadata.rdev())));
+        }
+    }
+
+    // Only the read-only attribute is known, so make up the permissions a
+    // Unix system would typically give the file.
+    #[cfg(not(unix))]
+    {
+        let mut mode = if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o644
+        };
+        if metadata.is_dir() {
+            mode |= 0o111;
+        }
+        keywords.push(Keyword::Mode(Mode::from_st_mode(mode)));
+    }
+
+    if metadata.is_file() {
+        keywords.push(Keyword::Size(metadata.len()));
+    }
+
+    if let Some(time) = modified(metadata) {
+        keywords.push(Keyword::Time(time));
+        keywords.push(Keyword::TarTime(Timestamp::new(time.secs, 0)));
+    }
+
+    if metadata.is_symlink() {
+        keywords.push(Keyword::Link(read_link()?));
+    }
+
+    Ok(keywords)
+}
+
+/// The spec path of the file `name` in the directory at spec path `parent`,
+/// always separated with `/` so that specs made on Windows read the same.
+/// An empty `parent` gives `name` alone.
+pub(crate) fn spec_child(parent: &Path, name: &OsStr) -> PathBuf {
+    if parent.as_os_str().is_empty() {
+        return PathBuf::from(name);
+    }
+
+    let mut path = parent.as_os_str().to_os_string();
+    path.push("/");
+    path.push(name);
+    PathBuf::from(path)
+}
+
+pub(crate) fn file_type(metadata: &Metadata) -> Option<Type> {
+    let ty = metadata.file_type();
+
+    if ty.is_dir() {
+        return Some(Type::Dir);
+    } else if ty.is_file() {
+        return Some(Type::File);
+    } else if ty.is_symlink() {
+        return Some(Type::Link);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if ty.is_block_device() {
+            return Some(Type::Block);
+        } else if ty.is_char_device() {
+            return Some(Type::Char);
+        } else if ty.is_fifo() {
+            return Some(Type::Fifo);
+        } else if ty.is_socket() {
+            return Some(Type::Socket);
+        }
+    }
+
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn modified(metadata: &Metadata) -> Option<Timestamp> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(Timestamp::new(
+        metadata.mtime(),
+        metadata.mtime_nsec() as u32,
+    ))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn modified(metadata: &Metadata) -> Option<Timestamp> {
+    metadata.modified().ok().map(Timestamp::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_build() {
+        let dir = TempDir::new();
+        dir.file("b/file", "hello");
+        dir.file("a", "");
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("b/file", dir.path().join("c")).unwrap();
+
+        let spec = SpecBuilder::from_dir(dir.path()).build().unwrap();
+        let resolved = spec.resolve();
+
+        let paths: Vec<_> = resolved.iter().map(|entry| entry.path.clone()).collect();
+        let mut expected = vec![
+            PathBuf::from("."),
+            PathBuf::from("./a"),
+            PathBuf::from("./b"),
+            PathBuf::from("./b/file"),
+        ];
+        if cfg!(unix) {
+            expected.push(PathBuf::from("./c"));
+        }
+        assert_eq!(paths, expected);
+
+        assert_eq!(
+            resolved[0].get(KeywordKind::Type),
+            Some(&Keyword::Type(Type::Dir))
+        );
+        assert_eq!(resolved[0].get(KeywordKind::Size), None);
+        assert_eq!(resolved[3].get(KeywordKind::Size), Some(&Keyword::Size(5)));
+        assert!(resolved[3].get(KeywordKind::Time).is_some());
+
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                resolved[4].get(KeywordKind::Type),
+                Some(&Keyword::Type(Type::Link))
+            );
+            assert_eq!(
+                resolved[4].get(KeywordKind::Link),
+                Some(&Keyword::Link(PathBuf::from("b/file")))
+            );
+            assert!(resolved[4].get(KeywordKind::Uid).is_some());
+        }
+
+        let reparsed = Spec::parse(&spec.to_string()).unwrap();
+        assert_eq!(reparsed.resolve().len(), resolved.len());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        dir.file("sub/b", "world");
+
+        let builder = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::CKSUM);
+        let mut writer = SpecWriter::new(Vec::new());
+        builder.write_to(&mut writer).unwrap();
+
+        let written = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(written, builder.build().unwrap().to_string());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_build_windows() {
+        let dir = TempDir::new();
+        dir.file("b/file", "hello");
+        let mut permissions = fs::metadata(dir.path().join("b/file"))
+            .unwrap()
+            .permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(dir.path().join("b/file"), permissions).unwrap();
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::DEFAULT)
+            .build()
+            .unwrap();
+        let text = spec.to_string();
+
+        assert!(text.contains("./b/file "), "{text}");
+        assert!(!text.contains("uid="), "{text}");
+        assert_eq!(
+            spec.resolve()[2].get(KeywordKind::Mode),
+            Some(&Keyword::Mode(Mode::new(0o444).unwrap()))
+        );
+        assert!(crate::verify::verify(&spec, dir.path()).is_clean());
+    }
+
+    #[test]
+    fn test_spec_child() {
+        assert_eq!(
+            spec_child(Path::new("./a"), OsStr::new("b")).as_os_str(),
+            "./a/b"
+        );
+        assert_eq!(spec_child(Path::new(""), OsStr::new("b")).as_os_str(), "b");
+    }
+
+    #[test]
+    fn test_build_keywords() {
+        let dir = TempDir::new();
+        dir.file("file", "123456789");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::CKSUM)
+            .build()
+            .unwrap();
+        let resolved = spec.resolve();
+
+        assert_eq!(resolved[0].keywords, vec![Keyword::Type(Type::Dir)]);
+        assert_eq!(
+            resolved[1].keywords,
+            vec![
+                Keyword::Type(Type::File),
+                Keyword::Size(9),
+                Keyword::Cksum(930766865)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_clamp_times() {
+        let dir = TempDir::new();
+        dir.file("file", "");
+
+        let keywords = KeywordSet::TIME | KeywordSet::TAR_TIME;
+        let latest = Timestamp::new(1000, 0);
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(keywords)
+            .clamp_times(latest)
+            .build()
+            .unwrap();
+
+        for entry in spec.resolve() {
+            assert_eq!(
+                entry.keywords,
+                vec![Keyword::Time(latest), Keyword::TarTime(latest)]
+            );
+        }
+
+        let builder = SpecBuilder::from_dir(dir.path()).keywords(keywords);
+        assert_eq!(
+            builder.clone().clamp_times(Timestamp::MAX).build().unwrap(),
+            builder.build().unwrap()
+        );
+    }
+
+    #[cfg(feature = "ignore")]
+    #[test]
+    fn test_build_ignore_files() {
+        let dir = TempDir::new();
+        dir.file(".gitignore", "target/\n*.log\n");
+        dir.file("src/.mtreeignore", "!debug.log\n");
+        dir.file("src/debug.log", "");
+        dir.file("src/main.rs", "");
+        dir.file("target/main", "");
+        dir.file("trace.log", "");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .ignore_files([".gitignore", ".mtreeignore"])
+            .build()
+            .unwrap();
+
+        let paths: Vec<_> = spec.entries().map(|entry| entry.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                ".",
+                "./.gitignore",
+                "./src",
+                "./src/.mtreeignore",
+                "./src/debug.log",
+                "./src/main.rs",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_follow_symlinks() {
+        let dir = TempDir::new();
+        dir.file("real/file", "hello");
+        std::os::unix::fs::symlink("real", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(".", dir.path().join("real/loop")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("real/dangling")).unwrap();
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE)
+            .follow_symlinks(true)
+            .build()
+            .unwrap();
+
+        let entries: Vec<_> = spec
+            .entries()
+            .map(|entry| (entry.path.to_str().unwrap(), entry.keywords[0].clone()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (".", Keyword::Type(Type::Dir)),
+                ("./link", Keyword::Type(Type::Dir)),
+                ("./link/dangling", Keyword::Type(Type::Link)),
+                ("./link/file", Keyword::Type(Type::File)),
+                ("./link/loop", Keyword::Type(Type::Dir)),
+                ("./real", Keyword::Type(Type::Dir)),
+                ("./real/dangling", Keyword::Type(Type::Link)),
+                ("./real/file", Keyword::Type(Type::File)),
+                ("./real/loop", Keyword::Type(Type::Dir)),
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_build_one_filesystem() {
+        let builder = SpecBuilder::from_dir("/")
+            .keywords(KeywordSet::TYPE)
+            .only(["./proc/version"].into_iter().collect());
+
+        let paths = |spec: Spec| -> Vec<PathBuf> {
+            spec.entries().map(|entry| entry.path.clone()).collect()
+        };
+        assert_eq!(
+            paths(builder.clone().build().unwrap()),
+            [".", "./proc", "./proc/version"].map(PathBuf::from)
+        );
+        assert_eq!(
+            paths(builder.one_filesystem(true).build().unwrap()),
+            [".", "./proc"].map(PathBuf::from)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_hard_links() {
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        dir.file("c", "hello");
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::NLINK)
+            .hard_links(true)
+            .build()
+            .unwrap();
+        let keywords: Vec<_> = spec
+            .entries()
+            .skip(1)
+            .map(|entry| entry.keywords.clone())
+            .collect();
+
+        assert_eq!(
+            keywords,
+            vec![
+                vec![Keyword::Nlink(2)],
+                vec![Keyword::Nlink(2), Keyword::HardLink(PathBuf::from("./a"))],
+                vec![Keyword::Nlink(1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_progress() {
+        use crate::ProgressCounter;
+
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        dir.file("b/c", "abc");
+
+        let counter = Arc::new(ProgressCounter::new());
+        SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::CKSUM)
+            .progress(counter.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(counter.total(), 4);
+        assert_eq!(counter.files(), 4);
+        assert_eq!(counter.bytes_read(), 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_id_map() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new();
+        let metadata = fs::metadata(dir.path()).unwrap();
+        let map = IdMap::new()
+            .map_uids(0, metadata.uid(), 1)
+            .map_gids(0, metadata.gid(), 1);
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::UID | KeywordSet::GID)
+            .id_map(map)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            spec.resolve()[0].keywords,
+            vec![Keyword::Uid(0), Keyword::Gid(0)]
+        );
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn test_build_digests() {
+        let dir = TempDir::new();
+        dir.file("file", "abc");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::SHA256)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            spec.resolve()[1].keywords,
+            vec![Keyword::Digest(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+                    .parse()
+                    .unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_build_digests_from() {
+        let dir = TempDir::new();
+        dir.file("a", "abc");
+        dir.file("b", "abc");
+
+        let known: Digest = "00112233445566778899aabbccddeeff".parse().unwrap();
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::MD5)
+            .digests_from(move |path| {
+                if path == Path::new("./a") {
+                    vec![
+                        known,
+                        "da39a3ee5e6b4b0d3255bfef95601890afd80709".parse().unwrap(),
+                    ]
+                } else {
+                    Vec::new()
+                }
+            })
+            .build()
+            .unwrap();
+        let resolved = spec.resolve();
+
+        assert_eq!(
+            resolved[1].keywords,
+            vec![Keyword::Type(Type::File), Keyword::Digest(known)]
+        );
+
+        #[cfg(feature = "hash")]
+        assert_eq!(
+            resolved[2].keywords,
+            vec![
+                Keyword::Type(Type::File),
+                Keyword::Digest("900150983cd24fb0d6963f7d28e17f72".parse().unwrap())
+            ]
+        );
+        #[cfg(not(feature = "hash"))]
+        assert_eq!(resolved[2].keywords, vec![Keyword::Type(Type::File)]);
+    }
+
+    #[test]
+    fn test_build_exclude() {
+        let dir = TempDir::new();
+        dir.file("src/main.py", "");
+        dir.file("src/main.pyc", "");
+        dir.file("var/log/messages", "");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .exclude(ExcludeList::new().pattern("*.pyc").pattern("./var/log"))
+            .build()
+            .unwrap();
+
+        let paths: Vec<_> = spec.entries().map(|entry| entry.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("."),
+                PathBuf::from("./src"),
+                PathBuf::from("./src/main.py"),
+                PathBuf::from("./var"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_only() {
+        let dir = TempDir::new();
+        dir.file("usr/bin/ls", "");
+        dir.file("usr/bin/cat", "");
+        dir.file("etc/passwd", "");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .only(["./usr/bin/ls"].into_iter().collect())
+            .build()
+            .unwrap();
+
+        let paths: Vec<_> = spec.entries().map(|entry| entry.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("."),
+                PathBuf::from("./usr"),
+                PathBuf::from("./usr/bin"),
+                PathBuf::from("./usr/bin/ls"),
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_build_device_contents() {
+        use crate::verify::{Verifier, VerifyFinding};
+        use crate::{KeywordKind, RootPath};
+
+        let builder = SpecBuilder::from_dir("/dev")
+            .keywords(KeywordSet::TYPE | KeywordSet::CKSUM)
+            .only(["./null", "./zero"].into_iter().collect());
+
+        let cksums = |spec: &Spec| -> Vec<Option<Keyword>> {
+            spec.entries()
+                .skip(1)
+                .map(|entry| {
+                    entry
+                        .keywords
+                        .iter()
+                        .find(|keyword| keyword.kind() == KeywordKind::Cksum)
+                        .cloned()
+                })
+                .collect()
+        };
+        assert_eq!(cksums(&builder.build().unwrap()), [None, None]);
+
+        let spec = builder.device_contents(4096).build().unwrap();
+        assert_eq!(cksums(&spec), [Some(Keyword::Cksum(4294967295)), None]);
+
+        let spec =
+            Spec::parse("./null type=char cksum=4294967295\n./zero type=char cksum=1\n").unwrap();
+        let report = Verifier::new(RootPath::new("/dev"))
+            .device_contents(4096)
+            .verify(&spec);
+        assert!(matches!(
+            &report.findings[..],
+            [VerifyFinding::Error { path, .. }] if path == Path::new("./zero")
+        ));
+    }
+
+    #[test]
+    fn test_build_missing_root() {
+        let dir = TempDir::new();
+
+        assert!(
+            SpecBuilder::from_dir(dir.path().join("missing"))
+                .build()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The value of the `device` and `resdevice` keywords.
+///
+/// Devices are written either as a raw device number or as
+/// `format,major,minor[,subunit]`, where `format` names the scheme used to
+/// pack the numbers (e.g. `native`, `freebsd`, `linux`).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Device {
+    Number(u64),
+    Parts {
+        format: String,
+        major: u64,
+        minor: u64,
+        subunit: Option<u64>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDevice;
+
+impl fmt::Display for InvalidDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid device")
+    }
+}
+
+impl std::error::Error for InvalidDevice {}
+
+fn parse_number(s: &str) -> Result<u64, InvalidDevice> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| InvalidDevice)
+}
+
+impl FromStr for Device {
+    type Err = InvalidDevice;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+
+        match parts.as_slice() {
+            [number] => Ok(Device::Number(parse_number(number)?)),
+            [format, major, minor] | [format, major, minor, _] if !format.is_empty() => {
+                Ok(Device::Parts {
+                    format: format.to_string(),
+                    major: parse_number(major)?,
+                    minor: parse_number(minor)?,
+                    subunit: parts.get(3).map(|s| parse_number(s)).transpose()?,
+                })
+            }
+            _ => Err(InvalidDevice),
+        }
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Device::Number(number) => write!(f, "{number}"),
+            Device::Parts {
+                format,
+                major,
+                minor,
+                subunit,
+            } => {
+                write!(f, "{format},{major},{minor}")?;
+
+                if let Some(subunit) = subunit {
+                    write!(f, ",{subunit}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_device() {
+        assert_eq!("66306".parse::<Device>(), Ok(Device::Number(66306)));
+        assert_eq!("0x10302".parse::<Device>(), Ok(Device::Number(0x10302)));
+        assert_eq!(
+            "native,8,0".parse::<Device>(),
+            Ok(Device::Parts {
+                format: "native".to_string(),
+                major: 8,
+                minor: 0,
+                subunit: None
+            })
+        );
+        assert_eq!(
+            "bsdos,4,1,2".parse::<Device>(),
+            Ok(Device::Parts {
+                format: "bsdos".to_string(),
+                major: 4,
+                minor: 1,
+                subunit: Some(2)
+            })
+        );
+        assert_eq!("native,8".parse::<Device>(), Err(InvalidDevice));
+        assert_eq!(",8,0".parse::<Device>(), Err(InvalidDevice));
+        assert_eq!("native,x,0".parse::<Device>(), Err(InvalidDevice));
+    }
+
+    #[test]
+    fn test_display_device() {
+        for text in ["66306", "native,8,0", "bsdos,4,1,2"] {
+            assert_eq!(text.parse::<Device>().unwrap().to_string(), text);
+        }
+    }
+}
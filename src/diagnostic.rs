@@ -0,0 +1,513 @@
+//! Pointing at the word of a line that made it fail to parse.
+//!
+//! A [`ParseError`] records which line was rejected and why, but not where
+//! in the line. Working that out takes another, slower look at the line, so
+//! it's done on request by [`ParseError::diagnose`] rather than for every
+//! error.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use chumsky::Parser;
+
+use crate::keyword_info::ValueType;
+use crate::{
+    Keyword, KeywordKind, ParseError, ParseOptions, is_unknown_keyword, parse_keyword_with,
+    sanitize,
+};
+
+/// A parse error narrowed down to the word that caused it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte offsets of the offending text in the input.
+    pub range: Range<usize>,
+    /// The 1-based line number of the offending text.
+    pub line: usize,
+    /// The 1-based column, in characters, where the offending text starts.
+    pub column: usize,
+    pub cause: Cause,
+    pub message: String,
+    /// What would have been accepted instead, if anything specific.
+    pub expected: Option<&'static str>,
+}
+
+/// What was wrong with a line, without any of its paths or values.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Cause {
+    /// Nothing more specific could be found.
+    Unknown,
+    UnknownCommand(String),
+    /// An `/unset` without any keyword names.
+    NothingToUnset,
+    UnknownKeyword(String),
+    MissingValue(KeywordKind),
+    UnexpectedValue(KeywordKind),
+    InvalidValue(KeywordKind),
+    /// See [`ParseOptions::reject_duplicates`].
+    DuplicateKeyword(KeywordKind),
+    /// See [`ParseOptions::reject_trailing_whitespace`].
+    TrailingWhitespace,
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::Unknown => f.write_str("could not parse line"),
+            Cause::UnknownCommand(name) => {
+                write!(f, "unknown command `/{}`", sanitize::text(name))
+            }
+            Cause::NothingToUnset => f.write_str("nothing to unset"),
+            Cause::UnknownKeyword(name) => write!(f, "unknown keyword `{}`", sanitize::text(name)),
+            Cause::MissingValue(kind) => write!(f, "`{}` needs a value", kind.info().name),
+            Cause::UnexpectedValue(kind) => write!(f, "`{}` takes no value", kind.info().name),
+            Cause::InvalidValue(kind) => write!(f, "invalid {}", kind.info().name),
+            Cause::DuplicateKeyword(kind) => write!(f, "duplicate `{}`", kind.info().name),
+            Cause::TrailingWhitespace => f.write_str("trailing whitespace"),
+        }
+    }
+}
+
+impl Diagnostic {
+    fn new(
+        input: &str,
+        range: Range<usize>,
+        cause: Cause,
+        message: String,
+        expected: Option<&'static str>,
+    ) -> Self {
+        let before = &input[..range.start];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+        Diagnostic {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            range,
+            cause,
+            message,
+            expected,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )?;
+
+        if let Some(expected) = self.expected {
+            write!(f, ", expected {expected}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ParseError {
+    /// Finds the part of the line that the error is about, given the
+    /// `input` and `options` it was parsed with.
+    pub fn diagnose(&self, input: &str, options: &ParseOptions) -> Diagnostic {
+        let range = self.span.range();
+
+        if options.reject_trailing_whitespace {
+            let content = input[range.clone()].trim_end_matches([' ', '\t']);
+            let start = range.start + content.len();
+
+            if start < range.end {
+                return Diagnostic::new(
+                    input,
+                    start..range.end,
+                    Cause::TrailingWhitespace,
+                    "trailing whitespace".to_string(),
+                    None,
+                );
+            }
+        }
+
+        let words = words(input, range.clone());
+
+        let Some((first, rest)) = words.split_first() else {
+            return unknown(input, range);
+        };
+
+        if let Some(command) = input[first.clone()].strip_prefix('/') {
+            match command {
+                "set" => {}
+                "unset" if rest.is_empty() => {
+                    return Diagnostic::new(
+                        input,
+                        first.clone(),
+                        Cause::NothingToUnset,
+                        "nothing to unset".to_string(),
+                        Some("keyword names or `all`"),
+                    );
+                }
+                "unset" => return unknown(input, range),
+                _ => {
+                    return Diagnostic::new(
+                        input,
+                        first.clone(),
+                        Cause::UnknownCommand(command.to_string()),
+                        format!("unknown command `/{}`", sanitize::text(command)),
+                        Some("`/set` or `/unset`"),
+                    );
+                }
+            }
+        }
+
+        rest.iter()
+            .find_map(|word| diagnose_keyword(input, word.clone(), options))
+            .or_else(|| {
+                options
+                    .reject_duplicates
+                    .then(|| diagnose_duplicate(input, rest, options))?
+            })
+            .unwrap_or_else(|| unknown(input, range))
+    }
+}
+
+fn unknown(input: &str, range: Range<usize>) -> Diagnostic {
+    Diagnostic::new(
+        input,
+        range,
+        Cause::Unknown,
+        "could not parse line".to_string(),
+        None,
+    )
+}
+
+/// Checks a single `name=value` word.
+fn diagnose_keyword(
+    input: &str,
+    range: Range<usize>,
+    options: &ParseOptions,
+) -> Option<Diagnostic> {
+    let word = &input[range.clone()];
+
+    if options.ignore_unknown_keywords && is_unknown_keyword(word) {
+        return None;
+    }
+
+    if parse_keyword_with(options)
+        .parse(word)
+        .into_result()
+        .is_ok()
+    {
+        return None;
+    }
+
+    let (name, value) = match word.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (word, None),
+    };
+
+    let Some(kind) = KeywordKind::from_name(name) else {
+        return Some(Diagnostic::new(
+            input,
+            range.start..range.start + name.len(),
+            Cause::UnknownKeyword(name.to_string()),
+            format!("unknown keyword `{}`", sanitize::text(name)),
+            None,
+        ));
+    };
+
+    let expected = expected(kind.info().value);
+    let diagnostic = match value {
+        None => Diagnostic::new(
+            input,
+            range,
+            Cause::MissingValue(kind),
+            format!("`{name}` needs a value"),
+            expected,
+        ),
+        Some(_) if expected.is_none() => Diagnostic::new(
+            input,
+            range,
+            Cause::UnexpectedValue(kind),
+            format!("`{name}` takes no value"),
+            None,
+        ),
+        Some(value) => Diagnostic::new(
+            input,
+            range.start + name.len() + 1..range.end,
+            Cause::InvalidValue(kind),
+            format!("invalid {name} `{}`", sanitize::text(value)),
+            expected,
+        ),
+    };
+
+    Some(diagnostic)
+}
+
+/// Finds a keyword that repeats an earlier one, once every word is known to
+/// parse on its own.
+fn diagnose_duplicate(
+    input: &str,
+    words: &[Range<usize>],
+    options: &ParseOptions,
+) -> Option<Diagnostic> {
+    let parser = parse_keyword_with(options);
+    let mut seen: Vec<Keyword> = Vec::new();
+
+    for word in words {
+        let text = &input[word.clone()];
+        let Ok(keyword) = parser.parse(text).into_result() else {
+            continue;
+        };
+
+        if seen.iter().any(|earlier| earlier.same_property(&keyword)) {
+            let name = text.split_once('=').map_or(text, |(name, _)| name);
+            return Some(Diagnostic::new(
+                input,
+                word.clone(),
+                Cause::DuplicateKeyword(keyword.kind()),
+                format!("duplicate `{}`", sanitize::text(name)),
+                None,
+            ));
+        }
+
+        seen.push(keyword);
+    }
+
+    None
+}
+
+fn expected(value: ValueType) -> Option<&'static str> {
+    match value {
+        ValueType::None => None,
+        ValueType::FileType => Some("a file type such as `file` or `dir`"),
+        ValueType::Number => Some("an unsigned decimal number"),
+        ValueType::Name => Some("a name"),
+        ValueType::Mode => Some("an octal or symbolic mode"),
+        ValueType::Flags => Some("a comma-separated list of file flags"),
+        ValueType::Device => Some("a device number or `format,major,minor`"),
+        ValueType::Timestamp => Some("`seconds.nanoseconds`"),
+        ValueType::Hex => Some("a hex digest of the right length"),
+        ValueType::Base64 => Some("base64-encoded bytes"),
+        ValueType::Path => Some("a path"),
+        ValueType::List => Some("a comma-separated list"),
+        ValueType::Label => Some("a security label"),
+    }
+}
+
+/// The whitespace-separated words in `input[range]`, skipping `\`
+/// continuations, as byte ranges into `input`.
+fn words(input: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut offset = range.start;
+
+    for raw in input[range].split_inclusive('\n') {
+        let content = raw.trim_end();
+        let content = content.strip_suffix('\\').unwrap_or(content);
+        let mut start = None;
+
+        for (idx, c) in content.char_indices().chain([(content.len(), ' ')]) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(idx),
+                (true, Some(word)) => {
+                    words.push(offset + word..offset + idx);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        offset += raw.len();
+    }
+
+    words
+}
+
+/// Diagnostics with the same [`Cause`], as grouped by [`summarize`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiagnosticGroup {
+    pub cause: Cause,
+    pub count: usize,
+    /// The first few diagnostics with this cause, in input order.
+    pub examples: Vec<Diagnostic>,
+}
+
+const EXAMPLES: usize = 3;
+
+impl fmt::Display for DiagnosticGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self
+            .examples
+            .iter()
+            .map(|example| example.line.to_string())
+            .collect();
+
+        match self.count {
+            1 => write!(f, "{} on line {}", self.cause, lines.join(", ")),
+            count if count > lines.len() => {
+                write!(
+                    f,
+                    "{} on {count} lines ({}, ...)",
+                    self.cause,
+                    lines.join(", ")
+                )
+            }
+            count => write!(f, "{} on {count} lines ({})", self.cause, lines.join(", ")),
+        }
+    }
+}
+
+/// Groups the errors from a lenient parse of `input` by cause, most common
+/// first, so that a mistake repeated on thousands of lines is reported once.
+pub fn summarize(
+    errors: &[ParseError],
+    input: &str,
+    options: &ParseOptions,
+) -> Vec<DiagnosticGroup> {
+    let mut groups: Vec<DiagnosticGroup> = Vec::new();
+    let mut index: HashMap<Cause, usize> = HashMap::new();
+
+    for err in errors {
+        let diagnostic = err.diagnose(input, options);
+        let idx = *index.entry(diagnostic.cause.clone()).or_insert_with(|| {
+            groups.push(DiagnosticGroup {
+                cause: diagnostic.cause.clone(),
+                count: 0,
+                examples: Vec::new(),
+            });
+            groups.len() - 1
+        });
+
+        let group = &mut groups[idx];
+        group.count += 1;
+        if group.examples.len() < EXAMPLES {
+            group.examples.push(diagnostic);
+        }
+    }
+
+    groups.sort_by_key(|group| Reverse(group.count));
+    groups
+}
+
+#[cfg(feature = "ariadne")]
+impl Diagnostic {
+    /// Renders the diagnostic with the offending text underlined in its
+    /// line, naming the input `name`.
+    pub fn render(&self, name: &str, input: &str, color: crate::ColorChoice) -> String {
+        use ariadne::{Config, IndexType, Label, Report, ReportKind, Source};
+
+        let mut label = Label::new((name, self.range.clone()));
+        if let Some(expected) = self.expected {
+            label = label.with_message(format!("expected {expected}"));
+        }
+
+        let mut out = Vec::new();
+        Report::build(ReportKind::Error, (name, self.range.clone()))
+            .with_config(
+                Config::default()
+                    .with_index_type(IndexType::Byte)
+                    .with_color(color.enabled()),
+            )
+            .with_message(&self.message)
+            .with_label(label)
+            .finish()
+            .write((name, Source::from(input)), &mut out)
+            .expect("writing to a Vec can't fail");
+
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Spec;
+
+    #[test]
+    fn test_diagnose() {
+        let input = "foo size=1\n\
+                     bar size=2 uid=x\n\
+                     baz \\\n    mode=0644 bogus=1\n\
+                     /frob\n\
+                     qux optional=1\n";
+        let options = ParseOptions::default();
+
+        let (_, errors) = Spec::parse_lenient(input);
+        let diagnostics: Vec<String> = errors
+            .iter()
+            .map(|err| err.diagnose(input, &options))
+            .map(|diagnostic| format!("{} {}", &input[diagnostic.range.clone()], diagnostic))
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "x line 2, column 16: invalid uid `x`, expected an unsigned decimal number",
+                "bogus line 4, column 15: unknown keyword `bogus`",
+                "/frob line 5, column 1: unknown command `/frob`, expected `/set` or `/unset`",
+                "optional=1 line 6, column 5: `optional` takes no value",
+            ]
+        );
+
+        let options = ParseOptions {
+            raw_values: true,
+            ..ParseOptions::default()
+        };
+        let input = "bar uid=x nope\n";
+        let err = Spec::parse_with(input, &options).unwrap_err();
+        assert_eq!(err.diagnose(input, &options).range, 10..14);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut input = String::from("/set type=file\n");
+        for idx in 0..5 {
+            input.push_str(&format!("f{idx} bogus=1\n"));
+        }
+        input.push_str("g uid=x\nh size=1\ni bogus=2\n");
+
+        let options = ParseOptions::default();
+        let (_, errors) = Spec::parse_lenient(&input);
+        let groups = summarize(&errors, &input, &options);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].cause, Cause::UnknownKeyword("bogus".to_string()));
+        assert_eq!(groups[0].count, 6);
+        assert_eq!(groups[0].examples.len(), 3);
+        assert_eq!(
+            groups[0].to_string(),
+            "unknown keyword `bogus` on 6 lines (2, 3, 4, ...)"
+        );
+        assert_eq!(groups[1].to_string(), "invalid uid on line 7");
+    }
+
+    #[test]
+    fn test_diagnose_strict() {
+        let input = "foo size=1 \\\n    uid=0 size=2\n\
+                     bar size=1 \n\
+                     baz md5=D41D8CD98F00B204E9800998ECF8427E\n";
+        let options = ParseOptions::strict();
+
+        let (_, errors) = Spec::parse_lenient_with(input, &options);
+        let diagnostics: Vec<String> = errors
+            .iter()
+            .map(|err| err.diagnose(input, &options))
+            .map(|diagnostic| format!("{:?} {}", &input[diagnostic.range.clone()], diagnostic))
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                "\"size=2\" line 2, column 11: duplicate `size`",
+                "\" \" line 3, column 11: trailing whitespace",
+                "\"D41D8CD98F00B204E9800998ECF8427E\" line 4, column 9: invalid md5 \
+                 `D41D8CD98F00B204E9800998ECF8427E`, expected a hex digest of the right length",
+            ]
+        );
+        assert_eq!(
+            Spec::parse_lenient_with(input, &ParseOptions::default())
+                .1
+                .len(),
+            0
+        );
+    }
+}
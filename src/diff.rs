@@ -0,0 +1,579 @@
+//! Comparing two specs without touching the filesystem, like
+//! `mtree -f old -f new`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::matcher::PathMatcher;
+use crate::sanitize;
+use crate::{Keyword, KeywordKind, ResolvedEntry, Spec};
+
+/// A keyword whose value differs between two versions of an entry. `None`
+/// means the keyword isn't present on that side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeywordChange {
+    pub kind: KeywordKind,
+    pub old: Option<Keyword>,
+    pub new: Option<Keyword>,
+}
+
+/// An entry present in both specs with different keywords.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryChange {
+    pub path: PathBuf,
+    pub changes: Vec<KeywordChange>,
+}
+
+/// How sure [`diff`] is that a removed and an added entry are one file that
+/// moved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RenameConfidence {
+    /// Only the type and size match, and no other file has that size.
+    Size,
+    /// A `cksum` or digest matches, and none differs.
+    Content,
+}
+
+/// A file that moved from `old.path` to `new.path`, possibly changing other
+/// keywords on the way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    pub old: ResolvedEntry,
+    pub new: ResolvedEntry,
+    pub confidence: RenameConfidence,
+    pub changes: Vec<KeywordChange>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpecDiff {
+    /// Entries only in the new spec.
+    pub added: Vec<ResolvedEntry>,
+    /// Entries only in the old spec.
+    pub removed: Vec<ResolvedEntry>,
+    /// Removed and added files paired up as probable moves, which are left
+    /// out of `added` and `removed`.
+    pub renamed: Vec<Rename>,
+    pub changed: Vec<EntryChange>,
+}
+
+impl SpecDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// The part of the diff whose paths match `matcher`. A rename is kept
+    /// if either side matches.
+    pub fn select(&self, matcher: &dyn PathMatcher) -> SpecDiff {
+        SpecDiff {
+            added: self
+                .added
+                .iter()
+                .filter(|entry| matcher.matches(&entry.path))
+                .cloned()
+                .collect(),
+            removed: self
+                .removed
+                .iter()
+                .filter(|entry| matcher.matches(&entry.path))
+                .cloned()
+                .collect(),
+            renamed: self
+                .renamed
+                .iter()
+                .filter(|rename| {
+                    matcher.matches(&rename.old.path) || matcher.matches(&rename.new.path)
+                })
+                .cloned()
+                .collect(),
+            changed: self
+                .changed
+                .iter()
+                .filter(|change| matcher.matches(&change.path))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for SpecDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.removed {
+            writeln!(f, "- {}", sanitize::path(&entry.path))?;
+        }
+
+        for entry in &self.added {
+            writeln!(f, "+ {}", sanitize::path(&entry.path))?;
+        }
+
+        for rename in &self.renamed {
+            let certainty = match rename.confidence {
+                RenameConfidence::Content => "",
+                RenameConfidence::Size => "?",
+            };
+            writeln!(
+                f,
+                "> {} ->{certainty} {}",
+                sanitize::path(&rename.old.path),
+                sanitize::path(&rename.new.path)
+            )?;
+        }
+
+        for entry in &self.changed {
+            write!(f, "~ {}:", sanitize::path(&entry.path))?;
+
+            for change in &entry.changes {
+                let old = change.old.as_ref().map(Keyword::to_string);
+                let new = change.new.as_ref().map(Keyword::to_string);
+
+                match (old.as_deref(), new.as_deref()) {
+                    (Some(old), Some(new)) => {
+                        write!(f, " {} -> {}", sanitize::text(old), sanitize::text(new))?
+                    }
+                    (Some(old), None) => write!(f, " -{}", sanitize::text(old))?,
+                    (None, Some(new)) => write!(f, " +{}", sanitize::text(new))?,
+                    (None, None) => {}
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the entries of two specs by path, with `/set` defaults applied.
+/// Relative and full-path entries for the same file compare equal.
+///
+/// A removed and an added file with the same type and size are reported as
+/// a rename if their contents match, or if nothing else has that size (see
+/// [`RenameConfidence`]). Empty files aren't paired by size alone.
+///
+/// Added, renamed and changed entries are in the order of `new`, removed
+/// ones in the order of `old`.
+pub fn diff(old: &Spec, new: &Spec) -> SpecDiff {
+    let old = old.resolve();
+    let new = new.resolve();
+
+    let old_by_path: HashMap<&Path, &ResolvedEntry> = old
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry))
+        .collect();
+    let new_by_path: HashMap<&Path, &ResolvedEntry> = new
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry))
+        .collect();
+
+    let mut diff = SpecDiff::default();
+
+    for entry in &new {
+        let Some(previous) = old_by_path.get(entry.path.as_path()) else {
+            diff.added.push(entry.clone());
+            continue;
+        };
+
+        let changes = keyword_changes(previous, entry);
+        if !changes.is_empty() {
+            diff.changed.push(EntryChange {
+                path: entry.path.clone(),
+                changes,
+            });
+        }
+    }
+
+    diff.removed = old
+        .iter()
+        .filter(|entry| !new_by_path.contains_key(entry.path.as_path()))
+        .cloned()
+        .collect();
+
+    pair_renames(&mut diff);
+    diff
+}
+
+fn pair_renames(diff: &mut SpecDiff) {
+    let count_sizes = |entries: &[ResolvedEntry]| -> HashMap<u64, usize> {
+        let mut by_size = HashMap::new();
+        for entry in entries {
+            if let Some(Keyword::Size(size)) = entry.get(KeywordKind::Size) {
+                *by_size.entry(*size).or_default() += 1;
+            }
+        }
+        by_size
+    };
+    let (removed_sizes, added_sizes) = (count_sizes(&diff.removed), count_sizes(&diff.added));
+
+    let mut paired = vec![false; diff.removed.len()];
+    let mut added = Vec::new();
+
+    for entry in std::mem::take(&mut diff.added) {
+        let confidence = diff.removed.iter().enumerate().find_map(|(idx, old)| {
+            if paired[idx] {
+                return None;
+            }
+            rename_confidence(old, &entry, &removed_sizes, &added_sizes).map(|c| (idx, c))
+        });
+
+        let Some((idx, confidence)) = confidence else {
+            added.push(entry);
+            continue;
+        };
+
+        paired[idx] = true;
+        let old = diff.removed[idx].clone();
+        diff.renamed.push(Rename {
+            // An `entrydigest` covers the path, so it always changes.
+            changes: keyword_changes(&old, &entry)
+                .into_iter()
+                .filter(|change| change.kind != KeywordKind::EntryDigest)
+                .collect(),
+            old,
+            new: entry,
+            confidence,
+        });
+    }
+
+    diff.added = added;
+    let mut paired = paired.into_iter();
+    diff.removed.retain(|_| !paired.next().unwrap());
+}
+
+fn rename_confidence(
+    old: &ResolvedEntry,
+    new: &ResolvedEntry,
+    removed_sizes: &HashMap<u64, usize>,
+    added_sizes: &HashMap<u64, usize>,
+) -> Option<RenameConfidence> {
+    let size = match (old.get(KeywordKind::Size), new.get(KeywordKind::Size)) {
+        (Some(Keyword::Size(old)), Some(Keyword::Size(new))) if old == new => *old,
+        _ => return None,
+    };
+    if old.get(KeywordKind::Type) != new.get(KeywordKind::Type) {
+        return None;
+    }
+
+    let mut content_matches = false;
+    for keyword in &old.keywords {
+        if !matches!(keyword, Keyword::Cksum(_) | Keyword::Digest(_)) {
+            continue;
+        }
+        match new.get(keyword.kind()) {
+            Some(other) if other == keyword => content_matches = true,
+            Some(_) => return None,
+            None => {}
+        }
+    }
+
+    if content_matches {
+        Some(RenameConfidence::Content)
+    } else if size > 0 && removed_sizes[&size] == 1 && added_sizes[&size] == 1 {
+        Some(RenameConfidence::Size)
+    } else {
+        None
+    }
+}
+
+/// What happened to an entry between two generations of a tree, for
+/// release notes and similar summaries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// The entry moved, and may have changed in other ways too.
+    Renamed,
+    /// The `type` changed.
+    Retyped,
+    /// The `link` target changed.
+    Relinked,
+    /// The size, `cksum` or a digest changed.
+    ContentModified,
+    /// Only other keywords, such as ownership, mode or time, changed.
+    MetadataModified,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeRecord {
+    pub path: PathBuf,
+    /// The old path of a renamed entry.
+    pub renamed_from: Option<PathBuf>,
+    pub kind: ChangeKind,
+    /// Every keyword that differs. For added and removed entries, all of
+    /// their keywords with the other side `None`.
+    pub changes: Vec<KeywordChange>,
+}
+
+/// Sorts the differences between `old` and `new` into one record per entry.
+/// A record takes the first kind that applies, in the order of
+/// [`ChangeKind`], so a file that became a symlink is `Retyped` even though
+/// its size changed as well.
+///
+/// Records are ordered like the lines of [`SpecDiff`]'s `Display`: removed,
+/// added, renamed, then changed entries.
+pub fn classify(old: &Spec, new: &Spec) -> Vec<ChangeRecord> {
+    let diff = diff(old, new);
+
+    let whole = |entry: ResolvedEntry, kind: ChangeKind| {
+        let changes = entry
+            .keywords
+            .into_iter()
+            .map(|keyword| {
+                let is_removed = kind == ChangeKind::Removed;
+                KeywordChange {
+                    kind: keyword.kind(),
+                    old: is_removed.then(|| keyword.clone()),
+                    new: (!is_removed).then_some(keyword),
+                }
+            })
+            .collect();
+
+        ChangeRecord {
+            path: entry.path,
+            renamed_from: None,
+            kind,
+            changes,
+        }
+    };
+
+    let mut records: Vec<ChangeRecord> = Vec::new();
+    records.extend(
+        diff.removed
+            .into_iter()
+            .map(|entry| whole(entry, ChangeKind::Removed)),
+    );
+    records.extend(
+        diff.added
+            .into_iter()
+            .map(|entry| whole(entry, ChangeKind::Added)),
+    );
+    records.extend(diff.renamed.into_iter().map(|rename| ChangeRecord {
+        path: rename.new.path,
+        renamed_from: Some(rename.old.path),
+        kind: ChangeKind::Renamed,
+        changes: rename.changes,
+    }));
+    records.extend(diff.changed.into_iter().map(|change| ChangeRecord {
+        kind: change_kind(&change.changes),
+        path: change.path,
+        renamed_from: None,
+        changes: change.changes,
+    }));
+
+    records
+}
+
+fn change_kind(changes: &[KeywordChange]) -> ChangeKind {
+    let has = |matches: fn(KeywordKind) -> bool| changes.iter().any(|change| matches(change.kind));
+
+    if has(|kind| kind == KeywordKind::Type) {
+        ChangeKind::Retyped
+    } else if has(|kind| kind == KeywordKind::Link) {
+        ChangeKind::Relinked
+    } else if has(|kind| {
+        matches!(
+            kind,
+            KeywordKind::Size | KeywordKind::Cksum | KeywordKind::Digest(_)
+        )
+    }) {
+        ChangeKind::ContentModified
+    } else {
+        ChangeKind::MetadataModified
+    }
+}
+
+fn keyword_changes(old: &ResolvedEntry, new: &ResolvedEntry) -> Vec<KeywordChange> {
+    let mut kinds: Vec<KeywordKind> = old.keywords.iter().map(Keyword::kind).collect();
+    for keyword in &new.keywords {
+        if !kinds.contains(&keyword.kind()) {
+            kinds.push(keyword.kind());
+        }
+    }
+
+    kinds
+        .into_iter()
+        .filter_map(|kind| {
+            let (old, new) = (old.get(kind), new.get(kind));
+
+            (old != new).then(|| KeywordChange {
+                kind,
+                old: old.cloned(),
+                new: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Glob;
+
+    #[test]
+    fn test_diff() {
+        let old = Spec::parse(
+            "/set uid=0 gid=0\n\
+             ./usr type=dir\n\
+             ./usr/bin/ls type=file size=100\n\
+             ./usr/bin/dir type=file size=10\n",
+        )
+        .unwrap();
+        let new = Spec::parse(
+            "/set uid=0 gid=0\n\
+             . type=dir\n\
+             usr type=dir\n\
+             \x20   bin type=dir\n\
+             \x20       ls type=file size=120 optional\n\
+             \x20       cat type=file size=50\n",
+        )
+        .unwrap();
+
+        let diff = diff(&old, &new);
+
+        let paths = |entries: &[ResolvedEntry]| -> Vec<PathBuf> {
+            entries.iter().map(|entry| entry.path.clone()).collect()
+        };
+
+        assert_eq!(
+            paths(&diff.added),
+            vec![
+                PathBuf::from("."),
+                PathBuf::from("./usr/bin"),
+                PathBuf::from("./usr/bin/cat")
+            ]
+        );
+        assert_eq!(paths(&diff.removed), vec![PathBuf::from("./usr/bin/dir")]);
+        assert_eq!(
+            diff.changed,
+            vec![EntryChange {
+                path: PathBuf::from("./usr/bin/ls"),
+                changes: vec![
+                    KeywordChange {
+                        kind: KeywordKind::Size,
+                        old: Some(Keyword::Size(100)),
+                        new: Some(Keyword::Size(120)),
+                    },
+                    KeywordChange {
+                        kind: KeywordKind::Optional,
+                        old: None,
+                        new: Some(Keyword::Optional),
+                    },
+                ],
+            }]
+        );
+        assert!(
+            diff.to_string()
+                .contains("~ ./usr/bin/ls: size=100 -> size=120 +optional\n")
+        );
+
+        let selected = diff.select(&Glob::new("usr/bin/{ls,cat}"));
+        assert_eq!(paths(&selected.added), vec![PathBuf::from("./usr/bin/cat")]);
+        assert!(selected.removed.is_empty());
+        assert_eq!(selected.changed, diff.changed);
+    }
+
+    #[test]
+    fn test_diff_renames() {
+        let old = Spec::parse(
+            "/set type=file\n\
+             ./old/one size=10 cksum=1\n\
+             ./old/two size=20\n\
+             ./old/three size=30 cksum=5\n\
+             ./old/empty size=0\n",
+        )
+        .unwrap();
+        let new = Spec::parse(
+            "/set type=file\n\
+             ./new/one size=10 cksum=1 mode=0644\n\
+             ./new/two size=20\n\
+             ./new/three size=30 cksum=6\n\
+             ./new/empty size=0\n",
+        )
+        .unwrap();
+
+        let diff = diff(&old, &new);
+        let renames: Vec<_> = diff
+            .renamed
+            .iter()
+            .map(|rename| {
+                (
+                    rename.old.path.to_str().unwrap(),
+                    rename.new.path.to_str().unwrap(),
+                    rename.confidence,
+                )
+            })
+            .collect();
+        assert_eq!(
+            renames,
+            [
+                ("./old/one", "./new/one", RenameConfidence::Content),
+                ("./old/two", "./new/two", RenameConfidence::Size),
+            ]
+        );
+        assert_eq!(
+            diff.renamed[0].changes,
+            [KeywordChange {
+                kind: KeywordKind::Mode,
+                old: None,
+                new: Some(Keyword::Mode("0644".parse().unwrap())),
+            }]
+        );
+        assert_eq!(diff.added.len(), 2);
+        assert_eq!(diff.removed.len(), 2);
+        assert!(diff.to_string().contains("> ./old/two ->? ./new/two\n"));
+    }
+
+    #[test]
+    fn test_classify() {
+        let old = Spec::parse(
+            "./a type=file size=1\n\
+             ./b type=file size=1 mode=0644\n\
+             ./c type=link link=x\n\
+             ./d type=file size=1\n\
+             ./e type=file\n",
+        )
+        .unwrap();
+        let new = Spec::parse(
+            "./a type=file size=2 mode=0644\n\
+             ./b type=file size=1 mode=0600\n\
+             ./c type=link link=y\n\
+             ./d type=link link=x\n\
+             ./f type=dir\n",
+        )
+        .unwrap();
+
+        let records = classify(&old, &new);
+        let kinds: Vec<_> = records
+            .iter()
+            .map(|record| (record.path.to_str().unwrap(), record.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("./e", ChangeKind::Removed),
+                ("./f", ChangeKind::Added),
+                ("./a", ChangeKind::ContentModified),
+                ("./b", ChangeKind::MetadataModified),
+                ("./c", ChangeKind::Relinked),
+                ("./d", ChangeKind::Retyped),
+            ]
+        );
+        assert_eq!(
+            records[1].changes,
+            [KeywordChange {
+                kind: KeywordKind::Type,
+                old: None,
+                new: Some(Keyword::Type(crate::Type::Dir)),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let spec = Spec::parse("/set uid=0\nfoo size=1\n").unwrap();
+
+        assert!(diff(&spec, &spec).is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A message digest algorithm supported by mtree(5).
+///
+/// BLAKE3 and XXH3 aren't part of mtree(5), so BSD mtree won't read specs
+/// that use them; they're only available with the `blake3` and `xxh3`
+/// features.
+///
+/// With the `serde` feature, kinds are serialized by keyword name rather
+/// than by index, so that the feature-gated kinds don't shift the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DigestKind {
+    Md5,
+    Sha1,
+    Rmd160,
+    Sha256,
+    Sha384,
+    Sha512,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    #[cfg(feature = "xxh3")]
+    Xxh3,
+}
+
+impl DigestKind {
+    pub const ALL: &'static [DigestKind] = &[
+        DigestKind::Md5,
+        DigestKind::Sha1,
+        DigestKind::Rmd160,
+        DigestKind::Sha256,
+        DigestKind::Sha384,
+        DigestKind::Sha512,
+        #[cfg(feature = "blake3")]
+        DigestKind::Blake3,
+        #[cfg(feature = "xxh3")]
+        DigestKind::Xxh3,
+    ];
+
+    /// The keyword name written for this digest, e.g. `sha256digest`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            DigestKind::Md5 => "md5digest",
+            DigestKind::Sha1 => "sha1digest",
+            DigestKind::Rmd160 => "rmd160digest",
+            DigestKind::Sha256 => "sha256digest",
+            DigestKind::Sha384 => "sha384digest",
+            DigestKind::Sha512 => "sha512digest",
+            #[cfg(feature = "blake3")]
+            DigestKind::Blake3 => "blake3digest",
+            #[cfg(feature = "xxh3")]
+            DigestKind::Xxh3 => "xxh3digest",
+        }
+    }
+
+    /// Looks up a digest by keyword name, accepting the short aliases
+    /// (`md5`, `sha256`, ...) as well.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "md5" | "md5digest" => Some(DigestKind::Md5),
+            "sha1" | "sha1digest" => Some(DigestKind::Sha1),
+            "rmd160" | "rmd160digest" | "ripemd160digest" => Some(DigestKind::Rmd160),
+            "sha256" | "sha256digest" => Some(DigestKind::Sha256),
+            "sha384" | "sha384digest" => Some(DigestKind::Sha384),
+            "sha512" | "sha512digest" => Some(DigestKind::Sha512),
+            #[cfg(feature = "blake3")]
+            "blake3" | "blake3digest" => Some(DigestKind::Blake3),
+            #[cfg(feature = "xxh3")]
+            "xxh3" | "xxh3digest" => Some(DigestKind::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Length of the digest output, in bytes.
+    pub fn output_len(&self) -> usize {
+        match self {
+            DigestKind::Md5 => 16,
+            DigestKind::Sha1 | DigestKind::Rmd160 => 20,
+            DigestKind::Sha256 => 32,
+            DigestKind::Sha384 => 48,
+            DigestKind::Sha512 => 64,
+            #[cfg(feature = "blake3")]
+            DigestKind::Blake3 => 32,
+            #[cfg(feature = "xxh3")]
+            DigestKind::Xxh3 => 8,
+        }
+    }
+}
+
+impl fmt::Display for DigestKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A digest value, stored as raw bytes of the length its algorithm produces.
+/// XXH3 hashes are stored big-endian, as `xxhsum` prints them.
+///
+/// Digests order by kind, then bytewise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Digest {
+    Md5([u8; 16]),
+    Sha1([u8; 20]),
+    Rmd160([u8; 20]),
+    Sha256([u8; 32]),
+    Sha384([u8; 48]),
+    Sha512([u8; 64]),
+    #[cfg(feature = "blake3")]
+    Blake3([u8; 32]),
+    #[cfg(feature = "xxh3")]
+    Xxh3([u8; 8]),
+}
+
+impl Digest {
+    pub fn kind(&self) -> DigestKind {
+        match self {
+            Digest::Md5(_) => DigestKind::Md5,
+            Digest::Sha1(_) => DigestKind::Sha1,
+            Digest::Rmd160(_) => DigestKind::Rmd160,
+            Digest::Sha256(_) => DigestKind::Sha256,
+            Digest::Sha384(_) => DigestKind::Sha384,
+            Digest::Sha512(_) => DigestKind::Sha512,
+            #[cfg(feature = "blake3")]
+            Digest::Blake3(_) => DigestKind::Blake3,
+            #[cfg(feature = "xxh3")]
+            Digest::Xxh3(_) => DigestKind::Xxh3,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Digest::Md5(bytes) => bytes,
+            Digest::Sha1(bytes) | Digest::Rmd160(bytes) => bytes,
+            Digest::Sha256(bytes) => bytes,
+            Digest::Sha384(bytes) => bytes,
+            Digest::Sha512(bytes) => bytes,
+            #[cfg(feature = "blake3")]
+            Digest::Blake3(bytes) => bytes,
+            #[cfg(feature = "xxh3")]
+            Digest::Xxh3(bytes) => bytes,
+        }
+    }
+
+    pub fn from_bytes(kind: DigestKind, bytes: &[u8]) -> Result<Self, InvalidDigest> {
+        let invalid = |_| InvalidDigest;
+
+        Ok(match kind {
+            DigestKind::Md5 => Digest::Md5(bytes.try_into().map_err(invalid)?),
+            DigestKind::Sha1 => Digest::Sha1(bytes.try_into().map_err(invalid)?),
+            DigestKind::Rmd160 => Digest::Rmd160(bytes.try_into().map_err(invalid)?),
+            DigestKind::Sha256 => Digest::Sha256(bytes.try_into().map_err(invalid)?),
+            DigestKind::Sha384 => Digest::Sha384(bytes.try_into().map_err(invalid)?),
+            DigestKind::Sha512 => Digest::Sha512(bytes.try_into().map_err(invalid)?),
+            #[cfg(feature = "blake3")]
+            DigestKind::Blake3 => Digest::Blake3(bytes.try_into().map_err(invalid)?),
+            #[cfg(feature = "xxh3")]
+            DigestKind::Xxh3 => Digest::Xxh3(bytes.try_into().map_err(invalid)?),
+        })
+    }
+
+    /// Parses a hex digest, in either case, which must be exactly as long as
+    /// `kind`'s output.
+    pub fn from_hex(kind: DigestKind, hex: &str) -> Result<Self, InvalidDigest> {
+        if hex.len() != kind.output_len() * 2 {
+            return Err(InvalidDigest);
+        }
+
+        // Large manifests have a digest on every line, so avoid allocating.
+        let mut bytes = [0u8; 64];
+        let bytes = &mut bytes[..kind.output_len()];
+
+        for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = (hex_value(pair[0])? << 4) | hex_value(pair[1])?;
+        }
+
+        Digest::from_bytes(kind, bytes)
+    }
+}
+
+fn hex_value(c: u8) -> Result<u8, InvalidDigest> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(InvalidDigest),
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.as_bytes() {
+            write!(f, "{byte:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a hex digest, picking the algorithm from its length. `rmd160`
+/// digests are the same length as `sha1` ones, and `blake3` ones as `sha256`
+/// ones, so they have to be parsed with [`Digest::from_hex`] instead.
+impl FromStr for Digest {
+    type Err = InvalidDigest;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kind = DigestKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.output_len() * 2 == s.len())
+            .ok_or(InvalidDigest)?;
+
+        Digest::from_hex(kind, s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DigestKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DigestKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name: String = serde::Deserialize::deserialize(deserializer)?;
+        DigestKind::from_name(&name).ok_or_else(|| {
+            serde::de::Error::custom(format!("unknown digest `{}`", crate::sanitize::text(&name)))
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Digest {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&(self.kind(), self.as_bytes()), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Digest {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (kind, bytes): (DigestKind, Vec<u8>) = serde::Deserialize::deserialize(deserializer)?;
+        Digest::from_bytes(kind, &bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidDigest;
+
+impl fmt::Display for InvalidDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid digest")
+    }
+}
+
+impl std::error::Error for InvalidDigest {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_names() {
+        for &kind in DigestKind::ALL {
+            assert_eq!(DigestKind::from_name(kind.name()), Some(kind));
+        }
+
+        assert_eq!(DigestKind::from_name("sha1"), Some(DigestKind::Sha1));
+        assert_eq!(DigestKind::from_name("sha2"), None);
+    }
+
+    #[test]
+    fn test_digest_hex() {
+        let hex = "d41d8cd98f00b204e9800998ecf8427e";
+        let digest: Digest = hex.parse().unwrap();
+
+        assert_eq!(digest.kind(), DigestKind::Md5);
+        assert_eq!(digest.as_bytes()[..2], [0xd4, 0x1d]);
+        assert_eq!(digest.to_string(), hex);
+        assert_eq!(
+            Digest::from_hex(DigestKind::Md5, &hex.to_uppercase()),
+            Ok(digest)
+        );
+    }
+
+    #[test]
+    fn test_digest_invalid() {
+        assert_eq!("abc".parse::<Digest>(), Err(InvalidDigest));
+        assert_eq!(
+            Digest::from_hex(DigestKind::Sha256, "d41d8cd98f00b204e9800998ecf8427e"),
+            Err(InvalidDigest)
+        );
+        assert_eq!(
+            Digest::from_hex(DigestKind::Md5, "z41d8cd98f00b204e9800998ecf8427e"),
+            Err(InvalidDigest)
+        );
+        assert_eq!(
+            Digest::from_bytes(DigestKind::Sha1, &[0; 16]),
+            Err(InvalidDigest)
+        );
+    }
+}
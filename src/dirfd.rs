@@ -0,0 +1,352 @@
+//! Directory file descriptor based file access, on Linux.
+//!
+//! Looking files up by full path means every call walks the path again, so a
+//! directory swapped for a symlink halfway through a check redirects the
+//! remaining calls somewhere else. [`Dir`] instead holds a descriptor for a
+//! directory and opens everything relative to it with `O_NOFOLLOW`, so once a
+//! directory has been opened, the files checked are the ones inside it.
+
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::{File, Metadata};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Keyword, Timestamp, xattr};
+
+#[derive(Debug)]
+pub struct Dir {
+    fd: OwnedFd,
+}
+
+impl Dir {
+    /// Opens the directory at `path`. Symlinks in `path` itself are followed.
+    pub fn open(path: &Path) -> io::Result<Dir> {
+        let path = c_string(path.as_os_str())?;
+        let fd = cvt(unsafe {
+            libc::open(
+                path.as_ptr(),
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            )
+        })?;
+
+        Ok(Dir {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Opens the subdirectory `name`, failing if it is a symlink.
+    pub fn open_dir(&self, name: &OsStr) -> io::Result<Dir> {
+        let fd = self.openat(
+            name,
+            libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+        )?;
+
+        Ok(Dir { fd })
+    }
+
+    /// Opens the file `name` for reading, failing if it is a symlink.
+    pub fn open_file(&self, name: &OsStr) -> io::Result<File> {
+        let fd = self.openat(
+            name,
+            libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_NONBLOCK | libc::O_CLOEXEC,
+        )?;
+
+        Ok(File::from(fd))
+    }
+
+    /// Returns the metadata of `name` without following it if it is a
+    /// symlink, like [`std::fs::symlink_metadata`].
+    pub fn metadata(&self, name: &OsStr) -> io::Result<Metadata> {
+        let fd = self.openat(name, libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC)?;
+        File::from(fd).metadata()
+    }
+
+    /// The extended attributes of `name`, see [`xattr::read`].
+    pub fn xattrs(&self, name: &OsStr) -> io::Result<Vec<Keyword>> {
+        // There are no `*at` variants of the xattr calls, but going through
+        // the descriptor's entry in /proc keeps the lookup in this directory.
+        let path = PathBuf::from(format!("/proc/self/fd/{}", self.fd.as_raw_fd()));
+        xattr::read(&path.join(name))
+    }
+
+    pub fn read_link(&self, name: &OsStr) -> io::Result<PathBuf> {
+        let name = c_string(name)?;
+        let mut buf = vec![0u8; libc::PATH_MAX as usize];
+
+        let len = unsafe {
+            libc::readlinkat(
+                self.fd.as_raw_fd(),
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        buf.truncate(len as usize);
+        Ok(PathBuf::from(OsString::from_vec(buf)))
+    }
+
+    /// Lists the names in the directory, excluding `.` and `..`.
+    pub fn entries(&self) -> io::Result<Vec<OsString>> {
+        let fd = cvt(unsafe { libc::dup(self.fd.as_raw_fd()) })?;
+        let stream = unsafe { libc::fdopendir(fd) };
+        if stream.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        // The duplicated descriptor shares its offset with ours, so start
+        // from the beginning in case the directory was listed before.
+        unsafe { libc::rewinddir(stream) };
+
+        let mut names = Vec::new();
+        let result = loop {
+            unsafe { *libc::__errno_location() = 0 };
+            let entry = unsafe { libc::readdir(stream) };
+
+            if entry.is_null() {
+                let err = io::Error::last_os_error();
+                break match err.raw_os_error() {
+                    Some(0) => Ok(()),
+                    _ => Err(err),
+                };
+            }
+
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            if !matches!(name.to_bytes(), b"." | b"..") {
+                names.push(OsStr::from_bytes(name.to_bytes()).to_os_string());
+            }
+        };
+
+        unsafe { libc::closedir(stream) };
+        result.map(|()| names)
+    }
+
+    /// Opens the directory containing `path`, relative to this one, without
+    /// following any symlinks on the way, and returns it together with the
+    /// final component of `path`. A path with no components refers to this
+    /// directory itself and yields `.`.
+    pub fn open_parent(&self, path: &Path) -> io::Result<(Dir, OsString)> {
+        let mut names = Vec::new();
+
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::CurDir => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "path must be relative and must not contain `..`",
+                    ));
+                }
+            }
+        }
+
+        let Some(last) = names.pop() else {
+            return Ok((self.try_clone()?, OsString::from(".")));
+        };
+
+        let mut dir = self.try_clone()?;
+        for name in names {
+            dir = dir.open_dir(name)?;
+        }
+
+        Ok((dir, last.to_os_string()))
+    }
+
+    /// Changes the owner and group of `name`, or of the symlink itself if it
+    /// is one. `None` leaves that id as it is.
+    pub fn chown(&self, name: &OsStr, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        let name = c_string(name)?;
+        cvt(unsafe {
+            libc::fchownat(
+                self.fd.as_raw_fd(),
+                name.as_ptr(),
+                uid.unwrap_or(u32::MAX),
+                gid.unwrap_or(u32::MAX),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Sets the permission bits of `name`, failing if it is a symlink.
+    pub fn set_mode(&self, name: &OsStr, mode: u32) -> io::Result<()> {
+        let name = c_string(name)?;
+        cvt(unsafe {
+            libc::fchmodat(
+                self.fd.as_raw_fd(),
+                name.as_ptr(),
+                mode as libc::mode_t,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Sets the modification time of `name`, or of the symlink itself if it
+    /// is one. The access time is left alone.
+    pub fn set_modified(&self, name: &OsStr, time: Timestamp) -> io::Result<()> {
+        let name = c_string(name)?;
+        let times = [
+            libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_OMIT,
+            },
+            libc::timespec {
+                tv_sec: time.secs as libc::time_t,
+                tv_nsec: time.nanos as libc::c_long,
+            },
+        ];
+        cvt(unsafe {
+            libc::utimensat(
+                self.fd.as_raw_fd(),
+                name.as_ptr(),
+                times.as_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    pub fn create_dir(&self, name: &OsStr) -> io::Result<()> {
+        let name = c_string(name)?;
+        cvt(unsafe { libc::mkdirat(self.fd.as_raw_fd(), name.as_ptr(), 0o777) })?;
+
+        Ok(())
+    }
+
+    /// Creates a symlink `name` pointing at `target`.
+    pub fn symlink(&self, target: &Path, name: &OsStr) -> io::Result<()> {
+        let target = c_string(target.as_os_str())?;
+        let name = c_string(name)?;
+        cvt(unsafe { libc::symlinkat(target.as_ptr(), self.fd.as_raw_fd(), name.as_ptr()) })?;
+
+        Ok(())
+    }
+
+    /// Removes `name` and, if it is a directory, everything beneath it.
+    /// Symlinks are removed, never followed.
+    pub fn remove_all(&self, name: &OsStr) -> io::Result<()> {
+        if self.metadata(name)?.is_dir() {
+            let dir = self.open_dir(name)?;
+            for child in dir.entries()? {
+                dir.remove_all(&child)?;
+            }
+
+            self.unlink(name, libc::AT_REMOVEDIR)
+        } else {
+            self.unlink(name, 0)
+        }
+    }
+
+    fn unlink(&self, name: &OsStr, flags: libc::c_int) -> io::Result<()> {
+        let name = c_string(name)?;
+        cvt(unsafe { libc::unlinkat(self.fd.as_raw_fd(), name.as_ptr(), flags) })?;
+
+        Ok(())
+    }
+
+    pub fn try_clone(&self) -> io::Result<Dir> {
+        Ok(Dir {
+            fd: self.fd.try_clone()?,
+        })
+    }
+
+    fn openat(&self, name: &OsStr, flags: libc::c_int) -> io::Result<OwnedFd> {
+        let name = c_string(name)?;
+        let fd = cvt(unsafe { libc::openat(self.fd.as_raw_fd(), name.as_ptr(), flags) })?;
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+fn c_string(name: &OsStr) -> io::Result<CString> {
+    CString::new(name.as_bytes()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+fn cvt(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_dir() {
+        let dir = TempDir::new();
+        dir.file("a/b/file", "hello");
+        symlink("file", dir.path().join("a/b/link")).unwrap();
+        symlink("b", dir.path().join("a/swapped")).unwrap();
+
+        let root = Dir::open(dir.path()).unwrap();
+
+        let (parent, name) = root.open_parent(Path::new("./a/b/file")).unwrap();
+        let mut contents = String::new();
+        parent
+            .open_file(&name)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+
+        let mut entries = parent.entries().unwrap();
+        entries.sort();
+        assert_eq!(entries, ["file", "link"]);
+
+        let link = OsStr::new("link");
+        assert!(parent.metadata(link).unwrap().is_symlink());
+        assert_eq!(parent.read_link(link).unwrap(), PathBuf::from("file"));
+        assert!(parent.open_file(link).is_err());
+
+        assert!(root.open_parent(Path::new("a/swapped/file")).is_err());
+        assert!(root.open_parent(Path::new("../etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_dir_changes() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new();
+        dir.file("outside/file", "hello");
+        dir.file("tree/a/file", "");
+        symlink("../../outside", dir.path().join("tree/a/link")).unwrap();
+
+        let root = Dir::open(dir.path()).unwrap();
+        let name = OsStr::new("new");
+
+        root.create_dir(name).unwrap();
+        root.set_mode(name, 0o750).unwrap();
+        root.set_modified(name, Timestamp::new(1769640177, 5))
+            .unwrap();
+        let metadata = root.metadata(name).unwrap();
+        assert_eq!(metadata.mode() & 0o7777, 0o750);
+        assert_eq!((metadata.mtime(), metadata.mtime_nsec()), (1769640177, 5));
+
+        root.symlink(Path::new("new"), OsStr::new("link")).unwrap();
+        assert!(root.set_mode(OsStr::new("link"), 0o700).is_err());
+        assert_eq!(root.metadata(name).unwrap().mode() & 0o7777, 0o750);
+
+        root.remove_all(OsStr::new("tree")).unwrap();
+        assert!(!dir.path().join("tree").exists());
+        assert!(dir.path().join("outside/file").exists());
+    }
+}
@@ -0,0 +1,275 @@
+//! Editing a spec in place, so that the lines an edit doesn't touch are
+//! written back exactly as they were read.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::comments::attached_comments;
+use crate::{Entry, EntryId, KeywordEdits, Line, ParseError, ParseOptions, Span, Spec};
+
+/// A spec parsed from `source` that can be edited and written back with
+/// every untouched line, comment and blank line byte for byte as it was,
+/// so that updating a spec gives a minimal diff.
+///
+/// Changed and new lines are written as [`Spec`]'s `Display` writes them,
+/// with the line terminator of the source. An entry that is changed and
+/// then changed back is written as it was.
+#[derive(Clone, Debug)]
+pub struct SpecEditor<'a> {
+    source: &'a str,
+    spec: Spec,
+    /// The entries as parsed, for those that have been handed out for
+    /// editing.
+    original: HashMap<EntryId, Entry>,
+    next_id: EntryId,
+    newline: &'a str,
+}
+
+impl<'a> SpecEditor<'a> {
+    pub fn new(source: &'a str) -> Result<Self, ParseError> {
+        Self::with_options(source, &ParseOptions::default())
+    }
+
+    pub fn with_options(source: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        let spec = Spec::parse_with(source, options)?;
+        let newline = match source.find('\n') {
+            Some(end) if source[..end].ends_with('\r') => "\r\n",
+            _ => "\n",
+        };
+
+        Ok(SpecEditor {
+            source,
+            next_id: spec.next_entry_id(),
+            spec,
+            original: HashMap::new(),
+            newline,
+        })
+    }
+
+    pub fn spec(&self) -> &Spec {
+        &self.spec
+    }
+
+    pub fn into_spec(self) -> Spec {
+        self.spec
+    }
+
+    pub fn entry_mut(&mut self, id: EntryId) -> Option<&mut Entry> {
+        let entry = self.spec.entry_mut(id)?;
+        self.original.entry(id).or_insert_with(|| entry.clone());
+        Some(entry)
+    }
+
+    /// Applies `edits` to the entry `id` as [`Spec::update_where`] does,
+    /// adding `/unset` and `/set` lines around it if it has to stop
+    /// inheriting a keyword. Returns whether the entry exists.
+    pub fn update(&mut self, id: EntryId, edits: &KeywordEdits) -> bool {
+        if self.entry_mut(id).is_none() {
+            return false;
+        }
+
+        self.spec.update_where(|entry| entry.id == id, edits) > 0
+    }
+
+    /// Inserts `entry` above the entry `before` and its comments, returning
+    /// the new entry's id, or `None` if `before` doesn't exist.
+    pub fn insert_before(&mut self, before: EntryId, entry: Entry) -> Option<EntryId> {
+        let idx = self.line_index(before)?;
+        let idx = attached_comments(&self.spec.lines, idx).start;
+        Some(self.insert(idx, entry))
+    }
+
+    /// Inserts `entry` right below the entry `after`, returning the new
+    /// entry's id, or `None` if `after` doesn't exist.
+    pub fn insert_after(&mut self, after: EntryId, entry: Entry) -> Option<EntryId> {
+        let idx = self.line_index(after)?;
+        Some(self.insert(idx + 1, entry))
+    }
+
+    /// Adds `entry` at the end of the spec, returning its id.
+    pub fn push(&mut self, entry: Entry) -> EntryId {
+        self.insert(self.spec.lines.len(), entry)
+    }
+
+    /// Removes the entry `id` along with its comments.
+    pub fn remove(&mut self, id: EntryId) -> Option<Entry> {
+        self.original.remove(&id);
+        self.spec.remove_entry(id)
+    }
+
+    fn insert(&mut self, idx: usize, entry: Entry) -> EntryId {
+        let id = self.next_id;
+        self.next_id = EntryId(id.0 + 1);
+
+        self.spec.lines.insert(
+            idx,
+            Line::Entry {
+                id,
+                entry,
+                span: Span::default(),
+            },
+        );
+        id
+    }
+
+    fn line_index(&self, id: EntryId) -> Option<usize> {
+        self.spec
+            .lines
+            .iter()
+            .position(|line| matches!(line, Line::Entry { id: other, .. } if *other == id))
+    }
+
+    /// Whether `line` is still as it was read from the source. Lines added
+    /// by an edit have the default span, whose line number is 0.
+    fn untouched(&self, line: &Line) -> bool {
+        if line.span().line == 0 {
+            return false;
+        }
+
+        match line {
+            Line::Entry { id, entry, .. } => self
+                .original
+                .get(id)
+                .is_none_or(|original| original == entry),
+            _ => true,
+        }
+    }
+
+    /// The line terminator after `span` in the source, empty if the source
+    /// ends there without one.
+    fn terminator(&self, span: &Span) -> &'a str {
+        let rest = &self.source[span.end..];
+        rest.find('\n').map_or(rest, |end| &rest[..=end])
+    }
+}
+
+impl fmt::Display for SpecEditor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Whether the last line written was left without a terminator, as
+        // the last line of the source may be.
+        let mut unterminated = false;
+
+        for line in &self.spec.lines {
+            if unterminated {
+                f.write_str(self.newline)?;
+            }
+
+            let span = line.span();
+            let terminator = if span.line == 0 {
+                self.newline
+            } else {
+                self.terminator(span)
+            };
+
+            if self.untouched(line) {
+                f.write_str(&self.source[span.start..span.end])?;
+            } else {
+                write!(f, "{line}")?;
+            }
+            f.write_str(terminator)?;
+            unterminated = terminator.is_empty();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::{Keyword, KeywordKind};
+
+    const SPEC: &str = "#\t   mtree  \r\n\
+                        \r\n\
+                        /set  uid=0 \tgid=0\r\n\
+                        # the shell\r\n\
+                        ./bin/sh   size=10 \\\r\n\
+                        \x20   mode=0755\r\n\
+                        ./bin/ls\tsize=20";
+
+    fn entry(path: &str, keywords: Vec<Keyword>) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            keywords,
+        }
+    }
+
+    fn id(editor: &SpecEditor, path: &str) -> EntryId {
+        editor.spec().find_id(Path::new(path)).unwrap()
+    }
+
+    #[test]
+    fn test_untouched() {
+        let mut editor = SpecEditor::new(SPEC).unwrap();
+        assert_eq!(editor.to_string(), SPEC);
+
+        let sh = id(&editor, "./bin/sh");
+        editor.entry_mut(sh).unwrap().keywords[0] = Keyword::Size(11);
+        editor.entry_mut(sh).unwrap().keywords[0] = Keyword::Size(10);
+        assert_eq!(editor.to_string(), SPEC);
+    }
+
+    #[test]
+    fn test_edit_entry() {
+        let mut editor = SpecEditor::new(SPEC).unwrap();
+        let sh = id(&editor, "./bin/sh");
+        let ls = id(&editor, "./bin/ls");
+
+        assert!(editor.update(sh, &KeywordEdits::new().set(Keyword::Size(11))));
+        assert!(editor.update(ls, &KeywordEdits::new().unset(KeywordKind::Gid)));
+        assert!(!editor.update(EntryId(99), &KeywordEdits::new()));
+
+        assert_eq!(
+            editor.to_string(),
+            "#\t   mtree  \r\n\
+             \r\n\
+             /set  uid=0 \tgid=0\r\n\
+             # the shell\r\n\
+             ./bin/sh size=11 mode=0755\r\n\
+             /unset gid\r\n\
+             ./bin/ls\tsize=20\r\n\
+             /set gid=0\r\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut editor = SpecEditor::new(SPEC).unwrap();
+        let sh = id(&editor, "./bin/sh");
+        let ls = id(&editor, "./bin/ls");
+
+        let bash = editor
+            .insert_before(sh, entry("./bin/bash", vec![Keyword::Size(30)]))
+            .unwrap();
+        editor.insert_after(bash, entry("./bin/cat", Vec::new()));
+        editor.push(entry("./bin/zsh", Vec::new()));
+        assert_eq!(
+            editor.remove(ls).map(|entry| entry.path),
+            Some("./bin/ls".into())
+        );
+        assert_eq!(editor.insert_after(ls, entry("./bin/cp", Vec::new())), None);
+
+        assert_eq!(
+            editor.to_string(),
+            "#\t   mtree  \r\n\
+             \r\n\
+             /set  uid=0 \tgid=0\r\n\
+             ./bin/bash size=30\r\n\
+             ./bin/cat\r\n\
+             # the shell\r\n\
+             ./bin/sh   size=10 \\\r\n\
+             \x20   mode=0755\r\n\
+             ./bin/zsh\r\n"
+        );
+        assert_ne!(editor.push(entry("./bin/ls", Vec::new())), ls);
+    }
+
+    #[test]
+    fn test_unterminated() {
+        let mut editor = SpecEditor::new("./a\n./b").unwrap();
+        editor.push(entry("./c", Vec::new()));
+        assert_eq!(editor.to_string(), "./a\n./b\n./c\n");
+    }
+}
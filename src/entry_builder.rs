@@ -0,0 +1,186 @@
+//! Building entries in code without assembling their keywords by hand.
+
+use std::fs::Metadata;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::create::{file_type, modified};
+use crate::{Digest, Entry, Keyword, Mode, Timestamp, Type};
+
+/// Builds an [`Entry`] one keyword at a time. Setting a keyword again
+/// replaces its earlier value, and the path is `.` unless set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryBuilder {
+    entry: Entry,
+}
+
+impl EntryBuilder {
+    pub fn new() -> Self {
+        EntryBuilder {
+            entry: Entry {
+                path: PathBuf::from("."),
+                keywords: Vec::new(),
+            },
+        }
+    }
+
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.entry.path = path.into();
+        self
+    }
+
+    /// Sets any keyword, in place of an earlier one of the same kind.
+    pub fn keyword(mut self, keyword: Keyword) -> Self {
+        match self
+            .entry
+            .keywords
+            .iter_mut()
+            .find(|existing| existing.same_property(&keyword))
+        {
+            Some(existing) => *existing = keyword,
+            None => self.entry.keywords.push(keyword),
+        }
+        self
+    }
+
+    pub fn typ(self, ty: Type) -> Self {
+        self.keyword(Keyword::Type(ty))
+    }
+
+    pub fn size(self, size: u64) -> Self {
+        self.keyword(Keyword::Size(size))
+    }
+
+    pub fn mode(self, mode: Mode) -> Self {
+        self.keyword(Keyword::Mode(mode))
+    }
+
+    pub fn uid(self, uid: u32) -> Self {
+        self.keyword(Keyword::Uid(uid))
+    }
+
+    pub fn gid(self, gid: u32) -> Self {
+        self.keyword(Keyword::Gid(gid))
+    }
+
+    pub fn uname(self, uname: impl Into<Arc<str>>) -> Self {
+        self.keyword(Keyword::Uname(uname.into()))
+    }
+
+    pub fn gname(self, gname: impl Into<Arc<str>>) -> Self {
+        self.keyword(Keyword::Gname(gname.into()))
+    }
+
+    pub fn time(self, time: Timestamp) -> Self {
+        self.keyword(Keyword::Time(time))
+    }
+
+    pub fn link(self, target: impl Into<PathBuf>) -> Self {
+        self.keyword(Keyword::Link(target.into()))
+    }
+
+    pub fn cksum(self, cksum: u32) -> Self {
+        self.keyword(Keyword::Cksum(cksum))
+    }
+
+    pub fn digest(self, digest: Digest) -> Self {
+        self.keyword(Keyword::Digest(digest))
+    }
+
+    pub fn sha256(self, bytes: [u8; 32]) -> Self {
+        self.digest(Digest::Sha256(bytes))
+    }
+
+    pub fn optional(self) -> Self {
+        self.keyword(Keyword::Optional)
+    }
+
+    pub fn build(self) -> Entry {
+        self.entry
+    }
+}
+
+impl Default for EntryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Takes the type, size of a regular file, modification time and, on Unix,
+/// the mode and owner from `metadata`. Symlink targets aren't part of the
+/// metadata, so set them with [`EntryBuilder::link`].
+impl From<&Metadata> for EntryBuilder {
+    fn from(metadata: &Metadata) -> Self {
+        let mut builder = EntryBuilder::new();
+
+        if let Some(ty) = file_type(metadata) {
+            builder = builder.typ(ty);
+        }
+        if metadata.is_file() {
+            builder = builder.size(metadata.len());
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            builder = builder
+                .mode(Mode::from_st_mode(metadata.mode()))
+                .uid(metadata.uid())
+                .gid(metadata.gid());
+        }
+
+        if let Some(time) = modified(metadata) {
+            builder = builder.time(time);
+        }
+
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_entry_builder() {
+        let entry = EntryBuilder::new()
+            .path("./etc/passwd")
+            .typ(Type::File)
+            .size(10)
+            .size(12)
+            .sha256([0; 32])
+            .build();
+
+        assert_eq!(entry.path, PathBuf::from("./etc/passwd"));
+        assert_eq!(
+            entry.keywords,
+            [
+                Keyword::Type(Type::File),
+                Keyword::Size(12),
+                Keyword::Digest(Digest::Sha256([0; 32])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let dir = TempDir::new();
+        dir.file("a", "abc");
+
+        let metadata = std::fs::metadata(dir.path().join("a")).unwrap();
+        let entry = EntryBuilder::from(&metadata).path("./a").build();
+
+        assert_eq!(
+            entry.keywords[..2],
+            [Keyword::Type(Type::File), Keyword::Size(3)]
+        );
+        assert!(
+            entry
+                .keywords
+                .iter()
+                .any(|keyword| matches!(keyword, Keyword::Time(_)))
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! The `entrydigest` extension keyword, which holds a digest of the rest of
+//! its entry so that edits to single lines of a spec can be detected without
+//! signing the whole file.
+//!
+//! Other implementations don't know the keyword, so only add it to specs read
+//! by this crate.
+
+use crate::{Entry, Keyword, KeywordKind, escape};
+
+impl Entry {
+    /// The text `entrydigest` is computed over: the encoded path followed by
+    /// every other keyword, sorted by kind and separated by single spaces.
+    /// Keywords inherited from `/set` aren't included.
+    pub fn canonical_form(&self) -> String {
+        let mut keywords: Vec<&Keyword> = self
+            .keywords
+            .iter()
+            .filter(|keyword| keyword.kind() != KeywordKind::EntryDigest)
+            .collect();
+        keywords.sort_by_key(|keyword| keyword.kind());
+
+        let mut out = escape::encode_path(&self.path);
+        for keyword in keywords {
+            out.push(' ');
+            out.push_str(&keyword.to_string());
+        }
+
+        out
+    }
+}
+
+#[cfg(feature = "hash")]
+mod sealing {
+    use crate::hash::Hasher;
+    use crate::{
+        Digest, DigestKind, Entry, Keyword, KeywordKind, Spec, Tree, VerifyFinding, VerifyReport,
+    };
+
+    impl Entry {
+        pub fn entry_digest(&self) -> Digest {
+            let mut hasher = Hasher::new(DigestKind::Sha256);
+            hasher.update(self.canonical_form().as_bytes());
+            hasher.finish()
+        }
+
+        /// Adds an `entrydigest` keyword, replacing any existing one.
+        pub fn seal(&mut self) {
+            let digest = self.entry_digest();
+            self.keywords
+                .retain(|keyword| keyword.kind() != KeywordKind::EntryDigest);
+            self.keywords.push(Keyword::EntryDigest(digest));
+        }
+    }
+
+    impl Spec {
+        /// Seals every entry, see [`Entry::seal`].
+        pub fn seal_entries(&mut self) {
+            for entry in self.entries_mut() {
+                entry.seal();
+            }
+        }
+
+        /// Reports a mismatch for each entry whose `entrydigest` doesn't
+        /// match its other keywords. Entries without one are skipped.
+        pub fn verify_entry_digests(&self) -> VerifyReport {
+            let tree = Tree::from_spec(self);
+            let mut report = VerifyReport::default();
+
+            for node in &tree {
+                let Some(entry) = node.id.and_then(|id| self.entry(id)) else {
+                    continue;
+                };
+
+                for keyword in &entry.keywords {
+                    if let Keyword::EntryDigest(expected) = keyword {
+                        let actual = entry.entry_digest();
+
+                        if actual != *expected {
+                            report.findings.push(VerifyFinding::Mismatch {
+                                path: node.path.clone(),
+                                expected: keyword.clone(),
+                                actual: Keyword::EntryDigest(actual),
+                            });
+                        }
+                    }
+                }
+            }
+
+            report
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Spec;
+
+    #[test]
+    fn test_canonical_form() {
+        let spec = Spec::parse(
+            "/set uid=0\n\
+             my\\040file mode=0644 type=file entrydigest=e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 size=3\n",
+        )
+        .unwrap();
+        let entry = spec.entries().next().unwrap();
+
+        assert_eq!(
+            entry.canonical_form(),
+            "my\\040file type=file mode=0644 size=3"
+        );
+
+        #[cfg(feature = "hash")]
+        {
+            let mut spec = spec.clone();
+            spec.seal_entries();
+            assert!(spec.verify_entry_digests().is_clean());
+
+            let reparsed = Spec::parse(&spec.to_string()).unwrap();
+            assert!(reparsed.verify_entry_digests().is_clean());
+
+            let tampered = Spec::parse(&spec.to_string().replace("0644", "4755")).unwrap();
+            let report = tampered.verify_entry_digests();
+            assert_eq!(report.findings.len(), 1);
+            assert_eq!(report.findings[0].path(), std::path::Path::new("./my file"));
+        }
+    }
+}
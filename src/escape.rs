@@ -0,0 +1,183 @@
+//! vis(3)-style escaping of file names.
+//!
+//! mtree encodes whitespace, control characters, backslashes and non-ASCII
+//! bytes in file names as three-digit octal escapes, so `foo bar` is written
+//! as `foo\040bar`. C-style escapes such as `\n` and `\t` are also accepted
+//! when decoding.
+
+use std::path::{Path, PathBuf};
+
+/// Decodes escapes in `input` into raw bytes. Backslashes that don't start a
+/// recognised escape are kept literally.
+pub fn unescape(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let octal = &bytes[i + 1..bytes.len().min(i + 4)];
+        if octal.len() == 3 && octal.iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let value = octal
+                .iter()
+                .fold(0u32, |acc, digit| acc * 8 + u32::from(digit - b'0'));
+
+            if let Ok(byte) = u8::try_from(value) {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+
+        let decoded = match bytes[i + 1] {
+            b'\\' => Some(b'\\'),
+            b'n' => Some(b'\n'),
+            b't' => Some(b'\t'),
+            b'r' => Some(b'\r'),
+            b's' => Some(b' '),
+            b'a' => Some(0x07),
+            b'b' => Some(0x08),
+            b'f' => Some(0x0c),
+            b'v' => Some(0x0b),
+            b'0' => Some(0),
+            b'#' => Some(b'#'),
+            _ => None,
+        };
+
+        match decoded {
+            Some(byte) => {
+                out.push(byte);
+                i += 2;
+            }
+            None => {
+                out.push(b'\\');
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+/// Encodes `bytes` so the result contains no whitespace and can be read back
+/// with [`unescape`].
+pub fn escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+
+    for &byte in bytes {
+        if needs_escape(byte) {
+            out.push_str(&format!("\\{byte:03o}"));
+        } else {
+            out.push(char::from(byte));
+        }
+    }
+
+    out
+}
+
+fn needs_escape(byte: u8) -> bool {
+    byte <= b' ' || byte >= 0x7f || byte == b'\\' || byte == b'#'
+}
+
+/// Decodes escapes in a value that isn't a path, such as a user name.
+/// Bytes that aren't UTF-8 once decoded are replaced.
+pub fn decode_str(input: &str) -> String {
+    String::from_utf8_lossy(&unescape(input)).into_owned()
+}
+
+/// Like [`escape`], but keeps non-ASCII characters as they are, since a
+/// value that isn't a path is always UTF-8.
+pub fn encode_str(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_ascii() && needs_escape(c as u8) {
+            out.push_str(&format!("\\{:03o}", c as u8));
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+pub fn decode_path(input: &str) -> PathBuf {
+    bytes_to_path(unescape(input))
+}
+
+pub fn encode_path(path: &Path) -> String {
+    escape(&path_to_bytes(path))
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Windows paths are written with `/`, which it accepts as a separator too.
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    let text = path.to_string_lossy();
+
+    if cfg!(windows) {
+        text.replace('\\', "/").into_bytes()
+    } else {
+        text.into_owned().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("foo\\040bar"), b"foo bar");
+        assert_eq!(unescape("a\\011b\\012c"), b"a\tb\nc");
+        assert_eq!(unescape("back\\\\slash"), b"back\\slash");
+        assert_eq!(unescape("tab\\tend"), b"tab\tend");
+        assert_eq!(unescape("\\303\\251"), "é".as_bytes());
+        assert_eq!(unescape("trailing\\"), b"trailing\\");
+        assert_eq!(unescape("odd\\q"), b"odd\\q");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(b"foo bar"), "foo\\040bar");
+        assert_eq!(escape(b"a\tb"), "a\\011b");
+        assert_eq!(escape(b"back\\slash"), "back\\134slash");
+        assert_eq!(escape("é".as_bytes()), "\\303\\251");
+        assert_eq!(escape(b"plain.txt"), "plain.txt");
+    }
+
+    #[test]
+    fn test_path_roundtrip() {
+        let path = PathBuf::from("./My Documents/notes #1.txt");
+
+        assert_eq!(
+            encode_path(&path),
+            "./My\\040Documents/notes\\040\\0431.txt"
+        );
+        assert_eq!(decode_path(&encode_path(&path)), path);
+    }
+}
@@ -0,0 +1,214 @@
+//! Skipping files by pattern, like `mtree -X`.
+
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+use crate::matcher::glob_matches;
+
+/// A list of fnmatch(3) patterns for files to leave out.
+///
+/// As in mtree, a pattern containing a `/` is matched against the whole spec
+/// path, such as `./var/log/messages`, and any other pattern against the
+/// file name alone. `*`, `?` and `[...]` don't match a `/`. Everything below
+/// an excluded directory is excluded too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExcludeList {
+    patterns: Vec<String>,
+}
+
+impl ExcludeList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads one pattern per line, skipping empty lines, like the file given
+    /// to `-X`.
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut list = ExcludeList::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let pattern = line.strip_suffix('\r').unwrap_or(&line);
+
+            if !pattern.is_empty() {
+                list.patterns.push(pattern.to_owned());
+            }
+        }
+
+        Ok(list)
+    }
+
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the file at the spec path `path`, or one of the directories
+    /// it's in, matches a pattern. The root itself is never excluded.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+
+        let relative = path.strip_prefix(".").unwrap_or(path);
+        let mut current = PathBuf::from(".");
+
+        relative.iter().any(|name| {
+            current.push(name);
+            self.matches(&current)
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern.as_bytes(), path))
+    }
+}
+
+/// Matches `text` against a shell pattern, with `FNM_PATHNAME` semantics.
+pub(crate) fn fnmatch(pattern: &[u8], text: &[u8]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        b'*' => {
+            for idx in 0..=text.len() {
+                if fnmatch(rest, &text[idx..]) {
+                    return true;
+                }
+                if text.get(idx) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        b'?' => match text.split_first() {
+            Some((&c, text)) => c != b'/' && fnmatch(rest, text),
+            None => false,
+        },
+        b'[' => match (bracket(rest), text.split_first()) {
+            (Some((class, after)), Some((&c, text))) => {
+                c != b'/' && class.matches(c) && fnmatch(after, text)
+            }
+            (Some(_), None) => false,
+            // An unclosed bracket is a literal `[`.
+            (None, _) => text.first() == Some(&b'[') && fnmatch(rest, &text[1..]),
+        },
+        b'\\' if !rest.is_empty() => {
+            text.first() == rest.first() && fnmatch(&rest[1..], &text[1..])
+        }
+        c => text.first() == Some(&c) && fnmatch(rest, &text[1..]),
+    }
+}
+
+/// A bracket expression such as `[a-z_]` or `[!.]`.
+struct Class<'a> {
+    negated: bool,
+    body: &'a [u8],
+}
+
+impl Class<'_> {
+    fn matches(&self, c: u8) -> bool {
+        let mut body = self.body;
+        let mut found = false;
+
+        while let Some((start, rest)) = next_char(body) {
+            body = rest;
+
+            let end = match body {
+                [b'-', tail @ ..] if !tail.is_empty() => {
+                    let (end, tail) = next_char(tail).unwrap_or((start, tail));
+                    body = tail;
+                    end
+                }
+                _ => start,
+            };
+
+            found |= (start..=end).contains(&c);
+        }
+
+        found != self.negated
+    }
+}
+
+/// Splits the bracket expression after a `[` off `pattern`, returning it and
+/// the rest of the pattern, or `None` if it isn't closed.
+fn bracket(pattern: &[u8]) -> Option<(Class<'_>, &[u8])> {
+    let (negated, pattern) = match pattern.split_first() {
+        Some((b'!' | b'^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    // A `]` right after the opening bracket is part of the set.
+    let mut idx = usize::from(pattern.first() == Some(&b']'));
+    while idx < pattern.len() {
+        match pattern[idx] {
+            b'\\' => idx += 2,
+            b']' => {
+                let class = Class {
+                    negated,
+                    body: &pattern[..idx],
+                };
+                return Some((class, &pattern[idx + 1..]));
+            }
+            _ => idx += 1,
+        }
+    }
+
+    None
+}
+
+/// Takes one possibly escaped character off a bracket expression.
+fn next_char(body: &[u8]) -> Option<(u8, &[u8])> {
+    match body {
+        [b'\\', c, rest @ ..] => Some((*c, rest)),
+        [c, rest @ ..] => Some((*c, rest)),
+        [] => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnmatch() {
+        assert!(fnmatch(b"*.pyc", b"foo.pyc"));
+        assert!(!fnmatch(b"*.pyc", b"foo.py"));
+        assert!(fnmatch(b"./var/log/*", b"./var/log/messages"));
+        assert!(!fnmatch(b"./var/*", b"./var/log/messages"));
+        assert!(fnmatch(b"file?", b"file1"));
+        assert!(!fnmatch(b"a?b", b"a/b"));
+        assert!(fnmatch(b"[a-c]x", b"bx"));
+        assert!(!fnmatch(b"[!a-c]x", b"bx"));
+        assert!(fnmatch(b"[]]", b"]"));
+        assert!(fnmatch(b"[", b"["));
+        assert!(fnmatch(b"\\*", b"*"));
+        assert!(!fnmatch(b"\\*", b"a"));
+    }
+
+    #[test]
+    fn test_exclude_list() {
+        let list = ExcludeList::from_reader("./var/log/*\n\n*.pyc\r\n".as_bytes()).unwrap();
+        assert_eq!(list.patterns(), ["./var/log/*", "*.pyc"]);
+
+        assert!(list.is_excluded(Path::new("./var/log/messages")));
+        assert!(list.is_excluded(Path::new("var/log/old/messages")));
+        assert!(!list.is_excluded(Path::new("./var/log")));
+        assert!(list.is_excluded(Path::new("./usr/lib/foo.pyc")));
+        assert!(list.is_excluded(Path::new("./cache.pyc/data")));
+        assert!(!list.is_excluded(Path::new("./usr/lib/foo.py")));
+
+        assert!(!ExcludeList::new().pattern("*").is_excluded(Path::new(".")));
+    }
+}
@@ -0,0 +1,141 @@
+//! Tallying why lines fail to parse across a corpus of specs, to find out
+//! which constructs found in the wild are still unsupported.
+//!
+//! Only the [`Cause`] of each failure is kept, never the paths or values on
+//! the line, so a report can be shared without revealing what was in the
+//! specs it was gathered from.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Cause, ParseError, ParseOptions, Spec};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FailureReport {
+    pub specs: usize,
+    /// Specs with at least one line that failed to parse.
+    pub failed_specs: usize,
+    /// Logical lines, counting `\` continuations as part of one line.
+    pub lines: usize,
+    pub failed_lines: usize,
+    pub causes: BTreeMap<Cause, usize>,
+}
+
+impl FailureReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses `input` leniently, adding its failures to the report, and
+    /// returns what [`Spec::parse_lenient_with`] would.
+    pub fn record(&mut self, input: &str, options: &ParseOptions) -> (Spec, Vec<ParseError>) {
+        let (spec, errors) = Spec::parse_lenient_with(input, options);
+
+        self.specs += 1;
+        self.lines += spec.lines.len() + errors.len();
+        self.failed_lines += errors.len();
+        if !errors.is_empty() {
+            self.failed_specs += 1;
+        }
+
+        for err in &errors {
+            let diagnostic = err.diagnose(input, options);
+            *self
+                .causes
+                .entry(anonymize(input, &diagnostic.range, diagnostic.cause))
+                .or_default() += 1;
+        }
+
+        (spec, errors)
+    }
+
+    /// Adds the counts from `other`, such as a report gathered on another
+    /// thread.
+    pub fn merge(&mut self, other: FailureReport) {
+        self.specs += other.specs;
+        self.failed_specs += other.failed_specs;
+        self.lines += other.lines;
+        self.failed_lines += other.failed_lines;
+
+        for (cause, count) in other.causes {
+            *self.causes.entry(cause).or_default() += count;
+        }
+    }
+
+    /// The causes with how often they were seen, most common first.
+    pub fn most_common(&self) -> Vec<(&Cause, usize)> {
+        let mut causes: Vec<_> = self
+            .causes
+            .iter()
+            .map(|(cause, count)| (cause, *count))
+            .collect();
+        causes.sort_by_key(|&(_, count)| Reverse(count));
+
+        causes
+    }
+}
+
+/// A word that isn't `name=value` is usually the rest of a path with an
+/// unescaped space rather than a keyword, so its text isn't kept.
+fn anonymize(input: &str, range: &Range<usize>, cause: Cause) -> Cause {
+    match cause {
+        Cause::UnknownKeyword(_) if !input[range.end..].starts_with('=') => Cause::Unknown,
+        cause => cause,
+    }
+}
+
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} lines in {} of {} specs failed to parse",
+            self.failed_lines, self.lines, self.failed_specs, self.specs
+        )?;
+
+        for (cause, count) in self.most_common() {
+            writeln!(f, "{count:>8}  {cause}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeywordKind;
+
+    #[test]
+    fn test_failure_report() {
+        let mut report = FailureReport::new();
+
+        report.record("foo size=1\nbar time=x\n", &ParseOptions::default());
+        report.record(
+            "baz time=1.x\n/frob\nmy secret type=file\nqux foo=1\n",
+            &ParseOptions::default(),
+        );
+        report.record("quux uid=0\n", &ParseOptions::default());
+
+        assert_eq!(report.specs, 3);
+        assert_eq!(report.failed_specs, 2);
+        assert_eq!(report.lines, 7);
+        assert_eq!(report.failed_lines, 5);
+        assert_eq!(
+            report.most_common(),
+            vec![
+                (&Cause::InvalidValue(KeywordKind::Time), 2),
+                (&Cause::Unknown, 1),
+                (&Cause::UnknownCommand("frob".to_string()), 1),
+                (&Cause::UnknownKeyword("foo".to_string()), 1),
+            ]
+        );
+        assert!(!report.to_string().contains("secret"));
+
+        let mut total = FailureReport::new();
+        total.merge(report.clone());
+        total.merge(report);
+        assert_eq!(total.causes[&Cause::InvalidValue(KeywordKind::Time)], 4);
+    }
+}
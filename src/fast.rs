@@ -0,0 +1,363 @@
+//! A hand-written parser for the common shape of entry and `/set` lines.
+//!
+//! Building and running the combinator parser costs far more than the
+//! lines in large manifests need. This parser only accepts lines made of
+//! plain `name=value` words separated by spaces or tabs. Anything else,
+//! including every line it can't parse, is left to the combinator parser,
+//! so accepted lines always parse to the same result and errors are
+//! unchanged.
+
+use std::str::FromStr;
+
+use memchr::{memchr_iter, memchr2};
+
+use crate::{
+    Command, Device, Digest, DigestKind, Entry, EntryId, Flags, Keyword, Line, Mode, Span,
+    Timestamp, Type, UnsetTarget, base64, escape,
+};
+
+/// Splits `input` after each `\n`, like `str::split_inclusive('\n')`.
+pub(crate) fn physical_lines(input: &str) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    let ends = memchr_iter(b'\n', input.as_bytes()).map(|idx| idx + 1);
+
+    ends.chain([input.len()]).filter_map(move |end| {
+        if start == end {
+            return None;
+        }
+
+        let line = &input[start..end];
+        start = end;
+        Some(line)
+    })
+}
+
+/// Parses a trimmed, non-blank line that isn't a comment, or returns
+/// `None` to leave it to the combinator parser.
+pub(crate) fn parse_line(text: &str, span: &Span) -> Option<Line> {
+    // The combinator parser separates words by any whitespace.
+    let plain = text
+        .bytes()
+        .all(|b| b.is_ascii() && !matches!(b, b'\n' | b'\x0b' | b'\x0c' | b'\r'));
+    if !plain
+        && text
+            .chars()
+            .any(|c| c.is_whitespace() && c != ' ' && c != '\t')
+    {
+        return None;
+    }
+
+    let mut words = Words(text);
+    let first = words.next()?;
+
+    let line = match first {
+        "/set" => Line::Command {
+            command: Command::Set(keywords(words)?),
+            span: span.clone(),
+        },
+        "/unset" => {
+            let names: Vec<String> = words.map(str::to_string).collect();
+            let target = match names.iter().any(|name| name == "all") {
+                _ if names.is_empty() => return None,
+                true => UnsetTarget::All,
+                false => UnsetTarget::Keywords(names),
+            };
+
+            Line::Command {
+                command: Command::Unset(target),
+                span: span.clone(),
+            }
+        }
+        _ if first.starts_with('/') => return None,
+        path => Line::Entry {
+            id: EntryId::default(),
+            entry: Entry {
+                path: escape::decode_path(path),
+                keywords: keywords(words)?,
+            },
+            span: span.clone(),
+        },
+    };
+
+    Some(line)
+}
+
+/// The words of a line, separated by runs of spaces and tabs.
+struct Words<'a>(&'a str);
+
+impl<'a> Iterator for Words<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.0.trim_start_matches([' ', '\t']);
+        if rest.is_empty() {
+            return None;
+        }
+
+        let end = memchr2(b' ', b'\t', rest.as_bytes()).unwrap_or(rest.len());
+        self.0 = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+fn keywords<'a>(words: impl Iterator<Item = &'a str>) -> Option<Vec<Keyword>> {
+    words.map(keyword).collect()
+}
+
+fn keyword(word: &str) -> Option<Keyword> {
+    let Some((name, value)) = word.split_once('=') else {
+        return match word {
+            "optional" => Some(Keyword::Optional),
+            "ignore" => Some(Keyword::Ignore),
+            "nochange" => Some(Keyword::NoChange),
+            _ => None,
+        };
+    };
+
+    let keyword = match name {
+        "type" => Keyword::Type(Type::from_name(value)?),
+        "uid" => Keyword::Uid(int(value)?),
+        "gid" => Keyword::Gid(int(value)?),
+        "uname" => Keyword::Uname(escape::decode_str(non_empty(value)?).into()),
+        "gname" => Keyword::Gname(escape::decode_str(non_empty(value)?).into()),
+        "mode" => Keyword::Mode(parsed::<Mode>(value)?),
+        "flags" => Keyword::Flags(parsed::<Flags>(value)?),
+        "time" => Keyword::Time(timestamp(value)?),
+        "tar_time" => Keyword::TarTime(timestamp(value)?),
+        "size" => Keyword::Size(int(value)?),
+        "nlink" => Keyword::Nlink(int(value)?),
+        "inode" => Keyword::Inode(int(value)?),
+        "device" => Keyword::Device(parsed::<Device>(value)?),
+        "resdevice" => Keyword::ResDevice(parsed::<Device>(value)?),
+        "cksum" => Keyword::Cksum(int(value)?),
+        "link" => Keyword::Link(escape::decode_path(non_empty(value)?)),
+        "contents" => Keyword::Contents(escape::decode_path(non_empty(value)?)),
+        "hardlink" => Keyword::HardLink(escape::decode_path(non_empty(value)?)),
+        "tags" => {
+            let tags: Vec<String> = value.split(',').map(escape::decode_str).collect();
+            if tags.iter().any(String::is_empty) {
+                return None;
+            }
+            Keyword::Tags(tags)
+        }
+        "entrydigest" => Keyword::EntryDigest(digest(DigestKind::Sha256, value)?),
+        "selinux" => Keyword::Selinux(escape::decode_str(non_empty(value)?)),
+        _ => match name.strip_prefix("xattr.") {
+            Some(attr) if !attr.is_empty() => Keyword::Xattr {
+                name: attr.to_string(),
+                value: base64::decode(value)?,
+            },
+            Some(_) => return None,
+            None => Keyword::Digest(digest(DigestKind::from_name(name)?, value)?),
+        },
+    };
+
+    Some(keyword)
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn parsed<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok().filter(|_| !value.is_empty())
+}
+
+/// A decimal integer without leading zeros, as `chumsky::text::int` reads.
+fn int<T: FromStr>(value: &str) -> Option<T> {
+    let canonical = value == "0" || value.bytes().next().is_some_and(|b| b != b'0');
+
+    if !canonical || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+fn timestamp(value: &str) -> Option<Timestamp> {
+    let (secs, nanos) = match value.split_once('.') {
+        Some((secs, nanos)) => (secs, Some(nanos)),
+        None => (value, None),
+    };
+
+    let secs = match secs.strip_prefix('-') {
+        Some(abs) => {
+            int::<u64>(abs)?;
+            secs.parse::<i64>().ok()?
+        }
+        None => int::<i64>(secs)?,
+    };
+
+    let nanos = match nanos {
+        Some(nanos) if !nanos.is_empty() && nanos.bytes().all(|b| b.is_ascii_digit()) => {
+            nanos.parse::<u32>().ok()?
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    (nanos < 1_000_000_000).then(|| Timestamp::new(secs, nanos))
+}
+
+/// `Digest::from_hex` only accepts the hex digits the combinator parser
+/// reads.
+fn digest(kind: DigestKind, hex: &str) -> Option<Digest> {
+    Digest::from_hex(kind, hex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::Parser;
+
+    use super::*;
+    use crate::{ParseOptions, parse_command_with, parse_entry_with};
+
+    /// What the combinator parser makes of `text`, if anything.
+    fn combinator(text: &str) -> Option<Line> {
+        let options = ParseOptions::default();
+        let span = Span::default();
+
+        if text.starts_with('/') {
+            let command = parse_command_with(&options)
+                .parse(text)
+                .into_result()
+                .ok()?;
+            return Some(Line::Command { command, span });
+        }
+
+        let entry = parse_entry_with(&options).parse(text).into_result().ok()?;
+        Some(Line::Entry {
+            id: EntryId::default(),
+            entry,
+            span,
+        })
+    }
+
+    fn assert_same(line: &str) -> bool {
+        let fast = parse_line(line, &Span::default());
+        if fast.is_some() {
+            assert_eq!(fast, combinator(line), "{line}");
+        }
+
+        fast.is_some()
+    }
+
+    #[test]
+    fn test_fast_matches_combinator() {
+        let words = [
+            "type=file",
+            "type=fifo",
+            "type=filex",
+            "type=filesize=1",
+            "uid=0",
+            "uid=4294967295",
+            "uid=4294967296",
+            "uid=01",
+            "gid=-1",
+            "uname=root",
+            "uname=a=b",
+            "uname=J\\303\\274rgen\\040K",
+            "uname=",
+            "mode=0644",
+            "mode=u+rwx",
+            "mode=0999",
+            "flags=uchg,nodump",
+            "time=1769640177.434772208",
+            "time=-1.5",
+            "time=-0",
+            "time=12",
+            "time=1.1000000000",
+            "time=1.",
+            "time=.5",
+            "time=-",
+            "time=+5",
+            "time=1.5.6",
+            "time=99999999999999999999",
+            "tar_time=0",
+            "size=1x",
+            "size=1optional",
+            "nlink=1",
+            "inode=12",
+            "cksum=4294967295",
+            "device=native,4,1",
+            "resdevice=4",
+            "link=../lib/libc.so.6",
+            "link=a\\040b",
+            "link=",
+            "contents=blobs/ab",
+            "md5=d41d8cd98f00b204e9800998ecf8427e",
+            "md5=zz",
+            "sha1=da39a3ee",
+            "sha256digest=e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "rmd160=0000000000000000000000000000000000000000",
+            "entrydigest=e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "optional",
+            "optional=1",
+            "optionalignore",
+            "nochange",
+            "tags=a,b",
+            "tags=a\\040b,c",
+            "tags=a,,b",
+            "tags=a,",
+            "tags=",
+            "xattr.user.note=aGk=",
+            "xattr.security.selinux=",
+            "xattr.=aGk=",
+            "xattr.a=!",
+            "bogus=1",
+            "bogus",
+            "type=dir\u{a0}size=1",
+            "type=dir\x0bsize=1",
+            "type=dir\rsize=1",
+        ];
+
+        for word in words {
+            assert_same(&format!("./f {word}"));
+        }
+
+        let lines = [
+            "file\\040name\ttype=file   nlink=1",
+            "/set type=file uid=0",
+            "/set",
+            "/setuid=0",
+            "/unset uid gid",
+            "/unset uid all",
+            "/unset",
+            "/frob x",
+            "size=1 type=file",
+            ".",
+            "..",
+        ];
+
+        for line in lines {
+            assert_same(line);
+        }
+
+        for input in [
+            include_str!("../fixtures/go-mtree.mtree"),
+            include_str!("../fixtures/hello.mtree"),
+        ] {
+            for line in input.lines().map(str::trim) {
+                if !line.is_empty() && !line.starts_with('#') {
+                    assert!(assert_same(line), "{line}");
+                }
+            }
+        }
+
+        assert!(assert_same(
+            "./f size=1 time=1.5 md5=d41d8cd98f00b204e9800998ecf8427e"
+        ));
+        assert!(!assert_same("./f size=1x"));
+    }
+
+    #[test]
+    fn test_physical_lines() {
+        let input = "a\nb\r\n\nc";
+        assert_eq!(
+            physical_lines(input).collect::<Vec<_>>(),
+            input.split_inclusive('\n').collect::<Vec<_>>()
+        );
+        assert_eq!(physical_lines("").count(), 0);
+        assert_eq!(physical_lines("a\n").collect::<Vec<_>>(), vec!["a\n"]);
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A BSD file flag, as set by chflags(1).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileFlag {
+    Arch,
+    Nodump,
+    Opaque,
+    Sappnd,
+    Schg,
+    Sunlnk,
+    Uappnd,
+    Uchg,
+    Uunlnk,
+    Hidden,
+    Offline,
+    Rdonly,
+    Sparse,
+    System,
+    Reparse,
+    Snapshot,
+    /// A flag this crate doesn't know about, kept verbatim.
+    Other(String),
+}
+
+impl FileFlag {
+    pub fn name(&self) -> &str {
+        match self {
+            FileFlag::Arch => "arch",
+            FileFlag::Nodump => "nodump",
+            FileFlag::Opaque => "opaque",
+            FileFlag::Sappnd => "sappnd",
+            FileFlag::Schg => "schg",
+            FileFlag::Sunlnk => "sunlnk",
+            FileFlag::Uappnd => "uappnd",
+            FileFlag::Uchg => "uchg",
+            FileFlag::Uunlnk => "uunlnk",
+            FileFlag::Hidden => "hidden",
+            FileFlag::Offline => "offline",
+            FileFlag::Rdonly => "rdonly",
+            FileFlag::Sparse => "sparse",
+            FileFlag::System => "system",
+            FileFlag::Reparse => "reparse",
+            FileFlag::Snapshot => "snapshot",
+            FileFlag::Other(name) => name,
+        }
+    }
+
+    /// Parses a flag name, accepting the long aliases chflags(1) understands.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "arch" | "archived" => FileFlag::Arch,
+            "nodump" => FileFlag::Nodump,
+            "opaque" => FileFlag::Opaque,
+            "sappnd" | "sappend" => FileFlag::Sappnd,
+            "schg" | "schange" | "simmutable" => FileFlag::Schg,
+            "sunlnk" | "sunlink" => FileFlag::Sunlnk,
+            "uappnd" | "uappend" => FileFlag::Uappnd,
+            "uchg" | "uchange" | "uimmutable" => FileFlag::Uchg,
+            "uunlnk" | "uunlink" => FileFlag::Uunlnk,
+            "hidden" | "uhidden" => FileFlag::Hidden,
+            "offline" | "uoffline" => FileFlag::Offline,
+            "rdonly" | "readonly" | "urdonly" => FileFlag::Rdonly,
+            "sparse" | "usparse" => FileFlag::Sparse,
+            "system" | "usystem" => FileFlag::System,
+            "reparse" | "ureparse" => FileFlag::Reparse,
+            "snapshot" => FileFlag::Snapshot,
+            other => FileFlag::Other(other.to_string()),
+        }
+    }
+}
+
+/// The value of the `flags` keyword: a set of [`FileFlag`]s, written as a
+/// comma-separated list or `none`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flags(Vec<FileFlag>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidFlags;
+
+impl fmt::Display for InvalidFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid file flags")
+    }
+}
+
+impl std::error::Error for InvalidFlags {}
+
+impl Flags {
+    pub fn none() -> Self {
+        Flags::default()
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, flag: &FileFlag) -> bool {
+        self.0.contains(flag)
+    }
+
+    pub fn insert(&mut self, flag: FileFlag) {
+        if !self.contains(&flag) {
+            self.0.push(flag);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FileFlag> {
+        self.0.iter()
+    }
+}
+
+impl FromIterator<FileFlag> for Flags {
+    fn from_iter<I: IntoIterator<Item = FileFlag>>(iter: I) -> Self {
+        let mut flags = Flags::none();
+
+        for flag in iter {
+            flags.insert(flag);
+        }
+
+        flags
+    }
+}
+
+impl FromStr for Flags {
+    type Err = InvalidFlags;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(Flags::none());
+        }
+
+        if s.split(',').any(str::is_empty) {
+            return Err(InvalidFlags);
+        }
+
+        Ok(s.split(',').map(FileFlag::from_name).collect())
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_none() {
+            return f.write_str("none");
+        }
+
+        for (idx, flag) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(flag.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flags() {
+        assert_eq!("none".parse::<Flags>(), Ok(Flags::none()));
+        assert_eq!(
+            "uchg,schg".parse::<Flags>(),
+            Ok(Flags::from_iter([FileFlag::Uchg, FileFlag::Schg]))
+        );
+        assert_eq!(
+            "uimmutable,uchg,nodump".parse::<Flags>(),
+            Ok(Flags::from_iter([FileFlag::Uchg, FileFlag::Nodump]))
+        );
+        assert_eq!(
+            "frobnicate".parse::<Flags>(),
+            Ok(Flags::from_iter([FileFlag::Other(
+                "frobnicate".to_string()
+            )]))
+        );
+        assert_eq!("uchg,,schg".parse::<Flags>(), Err(InvalidFlags));
+    }
+
+    #[test]
+    fn test_display_flags() {
+        assert_eq!(Flags::none().to_string(), "none");
+        assert_eq!(
+            Flags::from_iter([FileFlag::Schg, FileFlag::Other("x".to_string())]).to_string(),
+            "schg,x"
+        );
+    }
+}
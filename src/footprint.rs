@@ -0,0 +1,170 @@
+use std::mem::size_of;
+use std::ops::Add;
+use std::sync::Arc;
+
+use crate::{Command, Device, Entry, FileFlag, Keyword, Line, Spec, UnsetTarget};
+
+/// Estimated heap usage of a [`Spec`], in bytes, broken down by component.
+///
+/// The numbers are based on allocation capacities and don't include
+/// allocator overhead, so they are a lower bound rather than an exact figure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    pub lines: usize,
+    pub paths: usize,
+    pub digests: usize,
+    pub keywords: usize,
+    pub other: usize,
+}
+
+impl MemoryFootprint {
+    pub fn total(&self) -> usize {
+        self.lines + self.paths + self.digests + self.keywords + self.other
+    }
+}
+
+impl Add for MemoryFootprint {
+    type Output = MemoryFootprint;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        MemoryFootprint {
+            lines: self.lines + rhs.lines,
+            paths: self.paths + rhs.paths,
+            digests: self.digests + rhs.digests,
+            keywords: self.keywords + rhs.keywords,
+            other: self.other + rhs.other,
+        }
+    }
+}
+
+impl Spec {
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint {
+            lines: self.lines.capacity() * size_of::<Line>(),
+            ..Default::default()
+        };
+
+        for line in &self.lines {
+            footprint = footprint + line_footprint(line);
+        }
+
+        footprint
+    }
+}
+
+fn line_footprint(line: &Line) -> MemoryFootprint {
+    match line {
+        Line::Comment { text, .. } => MemoryFootprint {
+            other: text.capacity(),
+            ..Default::default()
+        },
+        Line::Blank { .. } => MemoryFootprint::default(),
+        Line::Command { command, .. } => match command {
+            Command::Set(keywords) => keywords_footprint(keywords, keywords.capacity()),
+            Command::Unset(UnsetTarget::All) => MemoryFootprint::default(),
+            Command::Unset(UnsetTarget::Keywords(names)) => MemoryFootprint {
+                other: names.capacity() * size_of::<String>()
+                    + names.iter().map(String::capacity).sum::<usize>(),
+                ..Default::default()
+            },
+        },
+        Line::Entry { entry, .. } => entry_footprint(entry),
+    }
+}
+
+fn entry_footprint(entry: &Entry) -> MemoryFootprint {
+    let mut footprint = keywords_footprint(&entry.keywords, entry.keywords.capacity());
+    footprint.paths += entry.path.capacity();
+    footprint
+}
+
+fn keywords_footprint(keywords: &[Keyword], capacity: usize) -> MemoryFootprint {
+    let mut footprint = MemoryFootprint {
+        keywords: capacity * size_of::<Keyword>(),
+        ..Default::default()
+    };
+
+    for keyword in keywords {
+        match keyword {
+            Keyword::Type(_)
+            | Keyword::Uid(_)
+            | Keyword::Gid(_)
+            | Keyword::Mode(_)
+            | Keyword::Nlink(_)
+            | Keyword::Inode(_)
+            | Keyword::Time(_)
+            | Keyword::TarTime(_)
+            | Keyword::Size(_)
+            | Keyword::Cksum(_)
+            | Keyword::Optional
+            | Keyword::Ignore
+            | Keyword::NoChange => {}
+            // Names are shared between entries, so each gets its share.
+            Keyword::Uname(name) | Keyword::Gname(name) => {
+                footprint.other += (2 * size_of::<usize>() + name.len()) / Arc::strong_count(name)
+            }
+            Keyword::Raw { value, .. } => footprint.other += value.capacity(),
+            Keyword::Device(device) | Keyword::ResDevice(device) => {
+                if let Device::Parts { format, .. } = device {
+                    footprint.other += format.capacity();
+                }
+            }
+            Keyword::Flags(flags) => {
+                footprint.other += flags
+                    .iter()
+                    .map(|flag| match flag {
+                        FileFlag::Other(name) => size_of::<FileFlag>() + name.capacity(),
+                        _ => size_of::<FileFlag>(),
+                    })
+                    .sum::<usize>();
+            }
+            Keyword::Digest(digest) | Keyword::EntryDigest(digest) => {
+                // Digests are stored inline, so move their bytes out of the
+                // keyword slot they occupy.
+                let len = digest.as_bytes().len();
+                footprint.digests += len;
+                footprint.keywords -= len;
+            }
+            Keyword::Link(path) | Keyword::Contents(path) | Keyword::HardLink(path) => {
+                footprint.paths += path.capacity()
+            }
+            Keyword::Tags(tags) => {
+                footprint.other += tags.capacity() * size_of::<String>()
+                    + tags.iter().map(String::capacity).sum::<usize>();
+            }
+            Keyword::Selinux(label) => footprint.other += label.capacity(),
+            Keyword::Xattr { name, value } => {
+                footprint.other += name.capacity() + value.capacity();
+            }
+        }
+    }
+
+    footprint
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Spec;
+
+    #[test]
+    fn test_memory_footprint() {
+        let spec = Spec::parse(
+            "foo sha256=fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249\n\
+             bar link=foo\n",
+        )
+        .unwrap();
+
+        let footprint = spec.memory_footprint();
+        assert_eq!(footprint.digests, 32);
+        assert!(footprint.paths >= "foo".len() + "bar".len() + "foo".len());
+        assert!(footprint.keywords > 0);
+        assert_eq!(
+            footprint.total(),
+            footprint.lines
+                + footprint.paths
+                + footprint.digests
+                + footprint.keywords
+                + footprint.other
+        );
+    }
+}
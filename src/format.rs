@@ -0,0 +1,180 @@
+//! Re-emits a parsed spec in a normalized style, for use as a formatter over
+//! hand-edited spec files.
+
+use crate::resolve::SetState;
+use crate::{Command, Entry, Keyword, Line, ParseError, Spec, Type, escape};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    /// Separate the path and each keyword by a single space.
+    SingleSpace,
+    /// Start keywords at the given column, or one space after the path if it
+    /// is longer.
+    Column(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub alignment: Alignment,
+    /// Order keywords canonically, by [`KeywordKind`](crate::KeywordKind).
+    pub sort_keywords: bool,
+    /// Indent relative-form entries by this many spaces per directory level.
+    pub indent: Option<usize>,
+    /// Collapse runs of blank lines into one and drop leading and trailing
+    /// blank lines.
+    pub collapse_blank_lines: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            alignment: Alignment::SingleSpace,
+            sort_keywords: true,
+            indent: None,
+            collapse_blank_lines: true,
+        }
+    }
+}
+
+pub fn format_spec(spec: &Spec, options: &FormatOptions) -> String {
+    let mut out = String::new();
+    let mut state = SetState::new();
+    let mut depth = 0usize;
+    let mut pending_blank = false;
+
+    for line in &spec.lines {
+        let text = match line {
+            Line::Blank { .. } => {
+                if options.collapse_blank_lines {
+                    pending_blank = !out.is_empty();
+                } else {
+                    out.push('\n');
+                }
+                continue;
+            }
+            Line::Comment { text, .. } => format!("#{text}"),
+            Line::Command { command, .. } => {
+                state.apply(command);
+                format_command(command, options)
+            }
+            Line::Entry { entry, .. } => {
+                let is_relative = entry.path.components().count() == 1;
+
+                if entry.path.as_os_str() == ".." {
+                    depth = depth.saturating_sub(1);
+                }
+
+                let text = format_entry(entry, depth, options);
+
+                let is_dir = state
+                    .materialize(&entry.keywords)
+                    .contains(&Keyword::Type(Type::Dir));
+                if is_dir && is_relative && entry.path.as_os_str() != "." {
+                    depth += 1;
+                }
+
+                text
+            }
+        };
+
+        if pending_blank {
+            out.push('\n');
+            pending_blank = false;
+        }
+
+        out.push_str(&text);
+        out.push('\n');
+    }
+
+    out
+}
+
+pub fn format_str(input: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    Ok(format_spec(&Spec::parse(input)?, options))
+}
+
+fn sorted(keywords: &[Keyword], options: &FormatOptions) -> Vec<Keyword> {
+    let mut keywords = keywords.to_vec();
+
+    if options.sort_keywords {
+        keywords.sort_by_key(Keyword::kind);
+    }
+
+    keywords
+}
+
+fn format_command(command: &Command, options: &FormatOptions) -> String {
+    match command {
+        Command::Set(keywords) => Command::Set(sorted(keywords, options)).to_string(),
+        Command::Unset(_) => command.to_string(),
+    }
+}
+
+fn format_entry(entry: &Entry, depth: usize, options: &FormatOptions) -> String {
+    let mut text = " ".repeat(depth * options.indent.unwrap_or(0));
+    text.push_str(&escape::encode_path(&entry.path));
+
+    for (idx, keyword) in sorted(&entry.keywords, options).iter().enumerate() {
+        match options.alignment {
+            Alignment::Column(column) if idx == 0 && text.len() < column => {
+                text.push_str(&" ".repeat(column - text.len()));
+            }
+            _ => text.push(' '),
+        }
+
+        text.push_str(&keyword.to_string());
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_default() {
+        let input = "\n\n# header\n\n\n/set uid=0 type=file\nfoo   size=1   type=file\n\n";
+
+        assert_eq!(
+            format_str(input, &FormatOptions::default()).unwrap(),
+            "# header\n\n/set type=file uid=0\nfoo type=file size=1\n"
+        );
+    }
+
+    #[test]
+    fn test_format_aligned_and_indented() {
+        let input = ". type=dir\n\
+                     etc type=dir\n\
+                     passwd type=file size=10\n\
+                     ..\n\
+                     README type=file\n";
+
+        let options = FormatOptions {
+            alignment: Alignment::Column(16),
+            indent: Some(4),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format_str(input, &options).unwrap(),
+            ".               type=dir\n\
+             etc             type=dir\n\
+             \x20   passwd      type=file size=10\n\
+             ..\n\
+             README          type=file\n"
+        );
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let options = FormatOptions {
+            alignment: Alignment::Column(20),
+            indent: Some(4),
+            ..Default::default()
+        };
+
+        let once = format_str(". type=dir\na type=dir\nb size=1\n..\n", &options).unwrap();
+        assert_eq!(format_str(&once, &options).unwrap(), once);
+    }
+}
@@ -0,0 +1,106 @@
+//! An immutable, cheaply clonable view of a resolved spec.
+//!
+//! A [`FrozenSpec`] is `Send + Sync` and reference counted, so a single
+//! resolved manifest can be shared between worker threads without copying
+//! its entries. Nothing inside it can be mutated once it is built; to make
+//! changes, edit the [`Spec`] and freeze it again.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::only::normalize;
+use crate::{ResolvedEntry, Spec};
+
+#[derive(Clone, Debug)]
+pub struct FrozenSpec {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    entries: Vec<ResolvedEntry>,
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl FrozenSpec {
+    pub fn new(entries: Vec<ResolvedEntry>) -> Self {
+        let by_path = entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (normalize(&entry.path), idx))
+            .collect();
+
+        FrozenSpec {
+            inner: Arc::new(Inner { entries, by_path }),
+        }
+    }
+
+    pub fn entries(&self) -> &[ResolvedEntry] {
+        &self.inner.entries
+    }
+
+    /// The entry for `path`, with or without a leading `./`.
+    pub fn get(&self, path: &Path) -> Option<&ResolvedEntry> {
+        let idx = *self.inner.by_path.get(&normalize(path))?;
+        Some(&self.inner.entries[idx])
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+
+    /// Returns `true` if both handles share the same underlying entries.
+    pub fn ptr_eq(&self, other: &FrozenSpec) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Spec {
+    pub fn freeze(&self) -> FrozenSpec {
+        FrozenSpec::new(self.resolve())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::Keyword;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<Spec>();
+        assert_send_sync::<ResolvedEntry>();
+        assert_send_sync::<FrozenSpec>();
+    }
+
+    #[test]
+    fn test_frozen_shared_between_threads() {
+        let frozen = Spec::parse("/set uid=0\nfoo\nbar size=3\n")
+            .unwrap()
+            .freeze();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || frozen.get(Path::new("bar")).cloned())
+            })
+            .collect();
+
+        for handle in handles {
+            let bar = handle.join().unwrap().unwrap();
+            assert_eq!(bar.keywords, vec![Keyword::Uid(0), Keyword::Size(3)]);
+        }
+
+        assert_eq!(frozen.len(), 2);
+        assert!(frozen.ptr_eq(&frozen.clone()));
+    }
+}
@@ -0,0 +1,202 @@
+//! Glob patterns over whole spec paths, for [`Spec::glob`].
+
+use std::path::Path;
+
+use crate::exclude::fnmatch;
+use crate::matcher::PathMatcher;
+use crate::{ResolvedEntry, Spec};
+
+/// A glob pattern matched against the whole path of an entry, relative to
+/// the spec's root, such as `usr/lib/**/*.so`.
+///
+/// Within a path component, `*`, `?` and `[...]` work as in fnmatch(3). A
+/// `**` component matches any number of components, including none, and
+/// `{a,b}` matches either alternative. A leading `./` or `/` is ignored, as
+/// it is in paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    /// The pattern with its braces expanded.
+    alternatives: Vec<String>,
+}
+
+impl Glob {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        let pattern = pattern.into();
+        let relative = pattern
+            .strip_prefix("./")
+            .or_else(|| pattern.strip_prefix('/'))
+            .unwrap_or(&pattern);
+
+        Glob {
+            alternatives: expand_braces(relative),
+            pattern,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl PathMatcher for Glob {
+    fn matches(&self, path: &Path) -> bool {
+        let path = path.as_os_str().as_encoded_bytes();
+        let path = path.strip_prefix(b"./").unwrap_or(path);
+
+        self.alternatives
+            .iter()
+            .any(|pattern| segments_match(pattern.as_bytes(), path))
+    }
+}
+
+impl Spec {
+    /// The resolved entries whose paths match the glob `pattern`, see
+    /// [`Glob`]. Entries are in the order of the spec.
+    pub fn glob(&self, pattern: &str) -> Vec<ResolvedEntry> {
+        self.select(&Glob::new(pattern))
+    }
+}
+
+/// Matches `path` against `pattern` one `/`-separated segment at a time,
+/// with a `**` segment matching any number of them.
+pub(crate) fn segments_match(pattern: &[u8], path: &[u8]) -> bool {
+    let pattern: Vec<&[u8]> = pattern.split(|&c| c == b'/').collect();
+    let path: Vec<&[u8]> = path.split(|&c| c == b'/').collect();
+
+    fn go(pattern: &[&[u8]], path: &[&[u8]]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&b"**", rest)) => (0..=path.len()).any(|skip| go(rest, &path[skip..])),
+            Some((segment, rest)) => match path.split_first() {
+                Some((name, path)) => fnmatch(segment, name) && go(rest, path),
+                None => false,
+            },
+        }
+    }
+
+    go(&pattern, &path)
+}
+
+/// Expands the first `{a,b}` group in `pattern`, and recursively those in
+/// each result. A brace without a closing one, or a group without a comma,
+/// is left as it is.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some((open, close)) = brace_group(pattern) else {
+        return vec![pattern.to_owned()];
+    };
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    split_alternatives(&pattern[open + 1..close])
+        .into_iter()
+        .flat_map(|alternative| expand_braces(&format!("{prefix}{alternative}{suffix}")))
+        .collect()
+}
+
+/// The byte offsets of the first brace group with a comma at its top level.
+fn brace_group(pattern: &str) -> Option<(usize, usize)> {
+    let bytes = pattern.as_bytes();
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 1,
+            b'{' => {
+                let mut depth = 0;
+                let mut comma = false;
+
+                for (end, &c) in bytes.iter().enumerate().skip(idx) {
+                    match c {
+                        b'{' => depth += 1,
+                        b'}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                if comma {
+                                    return Some((idx, end));
+                                }
+                                break;
+                            }
+                        }
+                        b',' if depth == 1 => comma = true,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+
+    None
+}
+
+fn split_alternatives(body: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (idx, c) in body.bytes().enumerate() {
+        match c {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b',' if depth == 0 => {
+                alternatives.push(&body[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+
+    alternatives.push(&body[start..]);
+    alternatives
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let glob = Glob::new("usr/lib/**/*.so");
+        assert!(glob.matches(Path::new("./usr/lib/libc.so")));
+        assert!(glob.matches(Path::new("usr/lib/x86_64/gconv/UTF-16.so")));
+        assert!(!glob.matches(Path::new("./usr/lib/libc.so.6")));
+        assert!(!glob.matches(Path::new("./usr/libexec/a.so")));
+
+        let glob = Glob::new("./{bin,sbin}/*");
+        assert!(glob.matches(Path::new("./bin/ls")));
+        assert!(glob.matches(Path::new("./sbin/init")));
+        assert!(!glob.matches(Path::new("./usr/bin/ls")));
+        assert!(!glob.matches(Path::new("./bin")));
+
+        assert_eq!(expand_braces("a{b,{c,d}}e"), ["abe", "ace", "ade"]);
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("a{b,c"), ["a{b,c"]);
+    }
+
+    #[test]
+    fn test_spec_glob() {
+        let spec = Spec::parse(
+            "/set type=file\n\
+             ./usr type=dir\n\
+             ./usr/lib type=dir\n\
+             ./usr/lib/libc.so\n\
+             ./usr/lib/perl5/auto/POSIX.so\n\
+             ./usr/lib/libc.a\n",
+        )
+        .unwrap();
+
+        let paths: Vec<_> = spec
+            .glob("usr/lib/**/*.so")
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(
+            paths,
+            ["./usr/lib/libc.so", "./usr/lib/perl5/auto/POSIX.so"].map(PathBuf::from)
+        );
+        assert_eq!(spec.glob("**").len(), 5);
+    }
+}
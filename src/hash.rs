@@ -0,0 +1,165 @@
+//! Computing digests from file contents, behind the `hash` feature.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::Digest as _;
+
+use crate::{Digest, DigestKind};
+
+/// Incremental state for one digest algorithm.
+#[derive(Clone)]
+pub enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Rmd160(ripemd::Ripemd160),
+    Sha256(sha2::Sha256),
+    Sha384(sha2::Sha384),
+    Sha512(sha2::Sha512),
+    #[cfg(feature = "blake3")]
+    Blake3(blake3::Hasher),
+    #[cfg(feature = "xxh3")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(kind: DigestKind) -> Self {
+        match kind {
+            DigestKind::Md5 => Hasher::Md5(md5::Md5::new()),
+            DigestKind::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            DigestKind::Rmd160 => Hasher::Rmd160(ripemd::Ripemd160::new()),
+            DigestKind::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            DigestKind::Sha384 => Hasher::Sha384(sha2::Sha384::new()),
+            DigestKind::Sha512 => Hasher::Sha512(sha2::Sha512::new()),
+            #[cfg(feature = "blake3")]
+            DigestKind::Blake3 => Hasher::Blake3(blake3::Hasher::new()),
+            #[cfg(feature = "xxh3")]
+            DigestKind::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    pub fn kind(&self) -> DigestKind {
+        match self {
+            Hasher::Md5(_) => DigestKind::Md5,
+            Hasher::Sha1(_) => DigestKind::Sha1,
+            Hasher::Rmd160(_) => DigestKind::Rmd160,
+            Hasher::Sha256(_) => DigestKind::Sha256,
+            Hasher::Sha384(_) => DigestKind::Sha384,
+            Hasher::Sha512(_) => DigestKind::Sha512,
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(_) => DigestKind::Blake3,
+            #[cfg(feature = "xxh3")]
+            Hasher::Xxh3(_) => DigestKind::Xxh3,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Rmd160(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha384(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            #[cfg(feature = "xxh3")]
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finish(self) -> Digest {
+        let kind = self.kind();
+        let output = match self {
+            Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Rmd160(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha384(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            #[cfg(feature = "xxh3")]
+            Hasher::Xxh3(hasher) => hasher.digest().to_be_bytes().to_vec(),
+        };
+
+        Digest::from_bytes(kind, &output).expect("hash output matches the digest length")
+    }
+}
+
+/// Computes a digest of everything read from `reader` for each of `kinds`,
+/// reading the input only once.
+pub fn hash_reader(
+    mut reader: impl Read,
+    kinds: impl IntoIterator<Item = DigestKind>,
+) -> io::Result<Vec<Digest>> {
+    let mut hashers: Vec<Hasher> = kinds.into_iter().map(Hasher::new).collect();
+    let mut buf = [0u8; 8192];
+
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hashers
+                .iter_mut()
+                .for_each(|hasher| hasher.update(&buf[..n])),
+        }
+    }
+
+    Ok(hashers.into_iter().map(Hasher::finish).collect())
+}
+
+pub fn hash_file(
+    path: &Path,
+    kinds: impl IntoIterator<Item = DigestKind>,
+) -> io::Result<Vec<Digest>> {
+    hash_reader(File::open(path)?, kinds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_reader() {
+        let digests = hash_reader(&b""[..], DigestKind::ALL[..6].iter().copied()).unwrap();
+
+        assert_eq!(
+            digests.iter().map(Digest::to_string).collect::<Vec<_>>(),
+            vec![
+                "d41d8cd98f00b204e9800998ecf8427e",
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+                "9c1185a5c5e9fc54612808977ee8f548b2258d31",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_reader_abc() {
+        let digests = hash_reader(&b"abc"[..], [DigestKind::Sha256]).unwrap();
+
+        assert_eq!(
+            digests[0].to_string(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[cfg(all(feature = "blake3", feature = "xxh3"))]
+    #[test]
+    fn test_hash_reader_extended() {
+        let digests = hash_reader(&b"abc"[..], [DigestKind::Blake3, DigestKind::Xxh3]).unwrap();
+
+        assert_eq!(
+            digests.iter().map(Digest::to_string).collect::<Vec<_>>(),
+            vec![
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+                "78af5f94892f3950",
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Translating uids and gids between a user namespace and the host.
+//!
+//! A rootfs built by an unprivileged user in a user namespace has files
+//! owned by the user's subordinate ids, such as 100000, on the host, but by
+//! `uid=0` inside the namespace. [`SpecBuilder::id_map`] records the ids as
+//! seen inside, and [`Applier::id_map`] turns them back into host ids.
+//!
+//! [`SpecBuilder::id_map`]: crate::SpecBuilder::id_map
+//! [`Applier::id_map`]: crate::apply::Applier::id_map
+
+use crate::Keyword;
+
+/// A contiguous range of ids, as in a line of `/proc/<pid>/uid_map`: ids
+/// `inside..inside + count` in the namespace are `outside..outside + count`
+/// on the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdRange {
+    pub inside: u32,
+    pub outside: u32,
+    pub count: u32,
+}
+
+impl IdRange {
+    fn to_outside(self, id: u32) -> Option<u32> {
+        let offset = id.checked_sub(self.inside).filter(|&n| n < self.count)?;
+        self.outside.checked_add(offset)
+    }
+
+    fn to_inside(self, id: u32) -> Option<u32> {
+        let offset = id.checked_sub(self.outside).filter(|&n| n < self.count)?;
+        self.inside.checked_add(offset)
+    }
+}
+
+/// Separate uid and gid mappings. Ids that no range covers are left as
+/// they are, and `uname` and `gname` are never changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdMap {
+    uids: Vec<IdRange>,
+    gids: Vec<IdRange>,
+}
+
+impl IdMap {
+    pub fn new() -> Self {
+        IdMap::default()
+    }
+
+    /// Maps `count` uids starting at `inside` to those starting at
+    /// `outside`.
+    pub fn map_uids(mut self, inside: u32, outside: u32, count: u32) -> Self {
+        self.uids.push(IdRange {
+            inside,
+            outside,
+            count,
+        });
+        self
+    }
+
+    pub fn map_gids(mut self, inside: u32, outside: u32, count: u32) -> Self {
+        self.gids.push(IdRange {
+            inside,
+            outside,
+            count,
+        });
+        self
+    }
+
+    pub fn uid_inside(&self, outside: u32) -> Option<u32> {
+        self.uids.iter().find_map(|range| range.to_inside(outside))
+    }
+
+    pub fn uid_outside(&self, inside: u32) -> Option<u32> {
+        self.uids.iter().find_map(|range| range.to_outside(inside))
+    }
+
+    pub fn gid_inside(&self, outside: u32) -> Option<u32> {
+        self.gids.iter().find_map(|range| range.to_inside(outside))
+    }
+
+    pub fn gid_outside(&self, inside: u32) -> Option<u32> {
+        self.gids.iter().find_map(|range| range.to_outside(inside))
+    }
+
+    /// Rewrites a `uid` or `gid` holding a host id to the namespace's id.
+    pub fn keyword_inside(&self, keyword: &mut Keyword) {
+        match keyword {
+            Keyword::Uid(id) => *id = self.uid_inside(*id).unwrap_or(*id),
+            Keyword::Gid(id) => *id = self.gid_inside(*id).unwrap_or(*id),
+            _ => {}
+        }
+    }
+
+    /// Rewrites a `uid` or `gid` holding a namespace id to the host's id.
+    pub fn keyword_outside(&self, keyword: &mut Keyword) {
+        match keyword {
+            Keyword::Uid(id) => *id = self.uid_outside(*id).unwrap_or(*id),
+            Keyword::Gid(id) => *id = self.gid_outside(*id).unwrap_or(*id),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_map() {
+        let map = IdMap::new()
+            .map_uids(0, 100000, 65536)
+            .map_gids(0, 200000, 10)
+            .map_gids(10, 5, 1);
+
+        assert_eq!(map.uid_inside(100000), Some(0));
+        assert_eq!(map.uid_inside(165535), Some(65535));
+        assert_eq!(map.uid_inside(165536), None);
+        assert_eq!(map.uid_inside(99999), None);
+        assert_eq!(map.uid_outside(1000), Some(101000));
+        assert_eq!(map.gid_outside(10), Some(5));
+        assert_eq!(map.gid_inside(200009), Some(9));
+        assert_eq!(IdMap::new().map_uids(0, u32::MAX, 2).uid_outside(1), None);
+
+        let mut keywords = [Keyword::Uid(100000), Keyword::Gid(7), Keyword::Size(100000)];
+        keywords
+            .iter_mut()
+            .for_each(|keyword| map.keyword_inside(keyword));
+        assert_eq!(
+            keywords,
+            [Keyword::Uid(0), Keyword::Gid(7), Keyword::Size(100000)]
+        );
+
+        keywords
+            .iter_mut()
+            .for_each(|keyword| map.keyword_outside(keyword));
+        assert_eq!(
+            keywords,
+            [
+                Keyword::Uid(100000),
+                Keyword::Gid(200007),
+                Keyword::Size(100000)
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Skipping files listed in `.gitignore`-style files found in the tree being
+//! walked, for [`SpecBuilder::ignore_files`]. Patterns are matched by the
+//! `ignore` crate, so they follow git's rules.
+//!
+//! [`SpecBuilder::ignore_files`]: crate::SpecBuilder::ignore_files
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+use ::ignore::Match;
+use ::ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// The ignore files that apply to the directory being walked.
+pub(crate) struct Ignores {
+    names: Vec<OsString>,
+    /// The ignore files of the directories walked into, outermost first.
+    stack: Vec<Gitignore>,
+    /// `.git/info/exclude` and the user's global excludes file, which rank
+    /// below every ignore file in the tree.
+    fallback: Vec<Gitignore>,
+}
+
+impl Ignores {
+    /// Ignores for a walk of `root` honouring the files called `names`. If
+    /// one of them is `.gitignore`, the root's `.git/info/exclude` and git's
+    /// `core.excludesFile` apply as well.
+    pub(crate) fn new(root: &Path, names: &[OsString]) -> io::Result<Self> {
+        let mut fallback = Vec::new();
+
+        if names.iter().any(|name| name == ".gitignore") {
+            let mut builder = GitignoreBuilder::new(root);
+            add(&mut builder, &root.join(".git/info/exclude"))?;
+            fallback.push(builder.build().map_err(io::Error::other)?);
+
+            // A broken global excludes file is git's problem to report.
+            fallback.push(Gitignore::global().0);
+        }
+
+        Ok(Ignores {
+            names: names.to_vec(),
+            stack: Vec::new(),
+            fallback,
+        })
+    }
+
+    /// Reads the ignore files in `dir`, which is about to be walked, and
+    /// returns the depth to [`leave`](Self::leave) it at.
+    pub(crate) fn enter(&mut self, dir: &Path) -> io::Result<usize> {
+        let depth = self.stack.len();
+        let mut builder = GitignoreBuilder::new(dir);
+
+        for name in &self.names {
+            add(&mut builder, &dir.join(name))?;
+        }
+
+        let ignore = builder.build().map_err(io::Error::other)?;
+        if !ignore.is_empty() {
+            self.stack.push(ignore);
+        }
+
+        Ok(depth)
+    }
+
+    pub(crate) fn leave(&mut self, depth: usize) {
+        self.stack.truncate(depth);
+    }
+
+    /// Whether the file at `path`, below the directories entered, is
+    /// ignored. The innermost file with a matching pattern decides.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for ignore in self.stack.iter().rev().chain(&self.fallback) {
+            match ignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+}
+
+/// Adds the patterns in the file at `path`, if there is one. Invalid
+/// patterns are skipped, as git does.
+fn add(builder: &mut GitignoreBuilder, path: &Path) -> io::Result<()> {
+    let Some(err) = builder.add(path) else {
+        return Ok(());
+    };
+
+    match err.io_error() {
+        Some(io) if io.kind() == io::ErrorKind::NotFound => Ok(()),
+        Some(io) => Err(io::Error::new(io.kind(), err.to_string())),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_ignores() {
+        let dir = TempDir::new();
+        dir.file(
+            ".gitignore",
+            "build/\n*.o\n!keep.o\n/root.txt\ndocs/**/*.html\n",
+        );
+        dir.file(".git/info/exclude", "*.swp\n");
+        dir.file("src/.gitignore", "!main.o\n");
+
+        let names = [OsString::from(".gitignore")];
+        let mut ignores = Ignores::new(dir.path(), &names).unwrap();
+        let root = ignores.enter(dir.path()).unwrap();
+
+        let ignored = |ignores: &Ignores, path: &str, is_dir| {
+            ignores.is_ignored(&dir.path().join(path), is_dir)
+        };
+
+        assert!(ignored(&ignores, "build", true));
+        assert!(!ignored(&ignores, "build", false));
+        assert!(ignored(&ignores, "a.o", false));
+        assert!(!ignored(&ignores, "keep.o", false));
+        assert!(ignored(&ignores, "root.txt", false));
+        assert!(ignored(&ignores, "docs/api/v1/index.html", false));
+        assert!(!ignored(&ignores, "src/index.html", false));
+        assert!(ignored(&ignores, "notes.swp", false));
+
+        let src = ignores.enter(&dir.path().join("src")).unwrap();
+        assert!(!ignored(&ignores, "src/main.o", false));
+        assert!(ignored(&ignores, "src/lib.o", false));
+        assert!(!ignored(&ignores, "src/root.txt", false));
+
+        ignores.leave(src);
+        assert!(ignored(&ignores, "src/main.o", false));
+        ignores.leave(root);
+        assert!(!ignored(&ignores, "a.o", false));
+    }
+}
@@ -0,0 +1,101 @@
+//! Looking up resolved entries by path, for code that would otherwise build
+//! its own map from a spec.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::only::normalize;
+use crate::{ResolvedEntry, Spec};
+
+/// The resolved entries of a spec, sorted by path. Paths are looked up with
+/// or without the leading `./`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpecIndex {
+    entries: Vec<ResolvedEntry>,
+    by_path: BTreeMap<PathBuf, usize>,
+}
+
+impl SpecIndex {
+    /// Indexes the entries of `spec` with `/set` defaults applied. If a path
+    /// appears more than once, the last entry wins.
+    pub fn build(spec: &Spec) -> Self {
+        let entries = spec.resolve();
+        let by_path = entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (normalize(&entry.path), idx))
+            .collect();
+
+        SpecIndex { entries, by_path }
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&ResolvedEntry> {
+        let idx = *self.by_path.get(&normalize(path.as_ref()))?;
+        Some(&self.entries[idx])
+    }
+
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.by_path.contains_key(&normalize(path.as_ref()))
+    }
+
+    /// The entry at `path` and everything below it, sorted by path.
+    pub fn subtree(&self, path: impl AsRef<Path>) -> impl Iterator<Item = &ResolvedEntry> {
+        let root = normalize(path.as_ref());
+
+        // Paths compare component by component, so a subtree is contiguous.
+        self.by_path
+            .range(root.clone()..)
+            .take_while(move |(path, _)| path.starts_with(&root))
+            .map(|(_, &idx)| &self.entries[idx])
+    }
+
+    /// Every entry, in the order of the spec.
+    pub fn entries(&self) -> &[ResolvedEntry] {
+        &self.entries
+    }
+
+    /// The number of distinct paths.
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Keyword;
+
+    #[test]
+    fn test_spec_index() {
+        let spec = Spec::parse(
+            "/set uid=0\n\
+             ./usr type=dir\n\
+             ./usr/bin type=dir\n\
+             ./usr/bin/ls size=1\n\
+             ./usr\\040local type=dir\n\
+             ./usr/lib type=dir\n\
+             ./etc type=dir\n",
+        )
+        .unwrap();
+        let index = SpecIndex::build(&spec);
+
+        assert_eq!(index.len(), 6);
+        assert!(index.contains("usr/bin"));
+        assert!(!index.contains("./usr/sbin"));
+        assert_eq!(
+            index.get("usr/bin/ls").unwrap().keywords,
+            [Keyword::Uid(0), Keyword::Size(1)]
+        );
+
+        let subtree: Vec<_> = index
+            .subtree("./usr")
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect();
+        assert_eq!(subtree, ["./usr", "./usr/bin", "./usr/bin/ls", "./usr/lib"]);
+        assert_eq!(index.subtree("./var").count(), 0);
+    }
+}
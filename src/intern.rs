@@ -0,0 +1,103 @@
+//! Sharing repeated `uname` and `gname` values.
+//!
+//! In large specs the same few owner names appear on millions of lines.
+//! Parsing passes every line through an [`Interner`], so each distinct name
+//! is allocated once and the entries hold references to it.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{Command, Keyword, Line, Spec};
+
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// Returns the shared copy of `text`, adding it if it's new.
+    pub fn intern(&mut self, text: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(text) {
+            return shared.clone();
+        }
+
+        let shared: Arc<str> = Arc::from(text);
+        self.strings.insert(shared.clone());
+        shared
+    }
+
+    /// The number of distinct strings seen.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn keyword(&mut self, keyword: &mut Keyword) {
+        if let Keyword::Uname(name) | Keyword::Gname(name) = keyword {
+            *name = self.intern(name);
+        }
+    }
+
+    pub fn line(&mut self, line: &mut Line) {
+        let keywords = match line {
+            Line::Command {
+                command: Command::Set(keywords),
+                ..
+            } => keywords,
+            Line::Entry { entry, .. } => &mut entry.keywords,
+            _ => return,
+        };
+
+        keywords
+            .iter_mut()
+            .for_each(|keyword| self.keyword(keyword));
+    }
+}
+
+impl Spec {
+    /// Makes equal `uname` and `gname` values share one allocation, as
+    /// parsing does. Useful after building or editing a spec in code.
+    pub fn intern(&mut self) {
+        let mut interner = Interner::new();
+        self.lines.iter_mut().for_each(|line| interner.line(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interned_on_parse() {
+        let spec = Spec::parse(
+            "/set uname=root\n\
+             foo uname=root gname=wheel\n\
+             bar uname=root gname=wheel\n",
+        )
+        .unwrap();
+
+        let names: Vec<&Arc<str>> = spec
+            .entries()
+            .flat_map(|entry| &entry.keywords)
+            .filter_map(|keyword| match keyword {
+                Keyword::Uname(name) | Keyword::Gname(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        assert!(Arc::ptr_eq(names[0], names[2]));
+        assert!(Arc::ptr_eq(names[1], names[3]));
+        assert_eq!(Arc::strong_count(names[0]), 3);
+
+        let mut interner = Interner::new();
+        assert!(Arc::ptr_eq(&interner.intern("a"), &interner.intern("a")));
+        assert_eq!(interner.len(), 1);
+    }
+}
@@ -0,0 +1,362 @@
+//! An append-only change log on top of a base [`Spec`].
+//!
+//! Each record is a single line consisting of a timestamp, an operation and
+//! its payload:
+//!
+//! ```text
+//! @1769640373.412526597 + ./etc/passwd type=file size=1024 uid=0
+//! @1769640380.000000000 - ./etc/shadow
+//! ```
+//!
+//! Entries stored in a journal are expected to be flattened, i.e. to carry
+//! every keyword explicitly rather than relying on `/set` state.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chumsky::prelude::*;
+
+use crate::only::normalize;
+use crate::resolve::SetState;
+use crate::{
+    Command, Entry, Keyword, KeywordEdits, KeywordKind, Line, ParseError, ParseErrorCause, Span,
+    Spec, Timestamp, UnsetTarget, escape, parse_entry, parse_path, parse_timestamp,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JournalOp {
+    Put(Entry),
+    Remove(PathBuf),
+}
+
+impl JournalOp {
+    pub fn path(&self) -> &Path {
+        match self {
+            JournalOp::Put(entry) => &entry.path,
+            JournalOp::Remove(path) => path,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalRecord {
+    pub time: Timestamp,
+    pub op: JournalOp,
+}
+
+impl fmt::Display for JournalRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{} ", self.time)?;
+
+        match &self.op {
+            JournalOp::Put(entry) => write!(f, "+ {entry}"),
+            JournalOp::Remove(path) => write!(f, "- {}", escape::encode_path(path)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Journal {
+    pub records: Vec<JournalRecord>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal::default()
+    }
+
+    pub fn push(&mut self, time: Timestamp, op: JournalOp) {
+        self.records.push(JournalRecord { time, op });
+    }
+
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        for (idx, raw) in input.split_inclusive('\n').enumerate() {
+            let start = offset;
+            offset += raw.len();
+
+            let text = raw.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let span = Span {
+                start,
+                end: start + raw.trim_end().len(),
+                line: idx + 1,
+            };
+
+            records.push(parse_record(text).ok_or_else(|| ParseError {
+                span,
+                cause: ParseErrorCause::Syntax(text.to_string()),
+            })?);
+        }
+
+        Ok(Journal { records })
+    }
+
+    /// Applies every record in order on top of `base`. Records match entries
+    /// by resolved path, with or without a leading `./`. Replaced entries
+    /// stop inheriting `/set` keywords they don't carry, and new ones are
+    /// appended with their full path after an `/unset all`. A base in the
+    /// relative form is rewritten with full paths first.
+    pub fn apply(&self, base: &Spec) -> Spec {
+        self.replay(base, |_| true)
+    }
+
+    /// Reconstructs the spec as it was at `time`, applying only the records
+    /// that were made at or before it.
+    pub fn replay_until(&self, base: &Spec, time: Timestamp) -> Spec {
+        self.replay(base, |record| record.time <= time)
+    }
+
+    fn replay(&self, base: &Spec, mut include: impl FnMut(&JournalRecord) -> bool) -> Spec {
+        // Records name entries by their full paths, which entries of the
+        // relative form don't carry.
+        let mut spec = Spec::new(base.full_path_lines().into_owned());
+
+        for record in self.records.iter().filter(|record| include(record)) {
+            apply_op(&mut spec, &record.op);
+        }
+
+        spec
+    }
+}
+
+impl fmt::Display for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for record in &self.records {
+            writeln!(f, "{record}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_record(text: &str) -> Option<JournalRecord> {
+    let (time, rest) = text.strip_prefix('@')?.split_once(char::is_whitespace)?;
+    let time = parse_timestamp().parse(time).into_result().ok()?;
+    let rest = rest.trim_start();
+
+    let op = if let Some(entry) = rest.strip_prefix('+') {
+        JournalOp::Put(parse_entry().parse(entry.trim()).into_result().ok()?)
+    } else if let Some(path) = rest.strip_prefix('-') {
+        JournalOp::Remove(parse_path().parse(path.trim()).into_result().ok()?)
+    } else {
+        return None;
+    };
+
+    Some(JournalRecord { time, op })
+}
+
+fn apply_op(spec: &mut Spec, op: &JournalOp) {
+    match op {
+        JournalOp::Put(new) => put(spec, new),
+        JournalOp::Remove(path) => {
+            let path = normalize(path);
+            spec.lines.retain(|line| match line {
+                Line::Entry { entry, .. } => normalize(&entry.path) != path,
+                _ => true,
+            });
+        }
+    }
+}
+
+/// Replaces the entry for `new`'s path, or appends it, so that it resolves
+/// to exactly the keywords it carries.
+fn put(spec: &mut Spec, new: &Entry) {
+    let path = normalize(&new.path);
+    let existing = spec.lines.iter_mut().rev().find_map(|line| match line {
+        Line::Entry { id, entry, .. } if normalize(&entry.path) == path => {
+            entry.keywords = new.keywords.clone();
+            Some(*id)
+        }
+        _ => None,
+    });
+
+    let Some(id) = existing else {
+        let mut state = SetState::new();
+        for line in &spec.lines {
+            if let Line::Command { command, .. } = line {
+                state.apply(command);
+            }
+        }
+
+        if !state.keywords().is_empty() {
+            spec.lines.push(Line::Command {
+                command: Command::Unset(UnsetTarget::All),
+                span: Span::default(),
+            });
+        }
+        spec.lines.push(Line::Entry {
+            id: spec.next_entry_id(),
+            entry: Entry {
+                path,
+                keywords: new.keywords.clone(),
+            },
+            span: Span::default(),
+        });
+        return;
+    };
+
+    // Keywords the replaced entry would still pick up from `/set`.
+    let own: Vec<KeywordKind> = new.keywords.iter().map(Keyword::kind).collect();
+    let inherited: Vec<KeywordKind> = spec
+        .resolve()
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .map(|entry| {
+            entry
+                .keywords
+                .iter()
+                .map(Keyword::kind)
+                .filter(|kind| !own.contains(kind))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if !inherited.is_empty() {
+        spec.update_where(
+            |entry| entry.id == id,
+            &KeywordEdits {
+                set: Vec::new(),
+                unset: inherited,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Keyword;
+
+    fn at(secs: i64) -> Timestamp {
+        Timestamp::new(secs, 0)
+    }
+
+    fn sample() -> Journal {
+        let mut journal = Journal::new();
+        journal.push(
+            at(100),
+            JournalOp::Put(Entry {
+                path: PathBuf::from("foo"),
+                keywords: vec![Keyword::Size(2)],
+            }),
+        );
+        journal.push(
+            at(200),
+            JournalOp::Put(Entry {
+                path: PathBuf::from("baz"),
+                keywords: vec![Keyword::Uid(0)],
+            }),
+        );
+        journal.push(at(300), JournalOp::Remove(PathBuf::from("bar")));
+        journal
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let journal = sample();
+        let text = journal.to_string();
+
+        assert_eq!(
+            text,
+            "@100.000000000 + foo size=2\n\
+             @200.000000000 + baz uid=0\n\
+             @300.000000000 - bar\n"
+        );
+        assert_eq!(Journal::parse(&text), Ok(journal));
+    }
+
+    #[test]
+    fn test_journal_apply() {
+        let base = Spec::parse("foo size=1\nbar size=1\n").unwrap();
+        let journal = sample();
+
+        let latest = journal.apply(&base);
+        let paths: Vec<_> = latest.entries().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("foo"), PathBuf::from("./baz")]);
+        assert_eq!(
+            latest.find(Path::new("foo")).unwrap().keywords,
+            vec![Keyword::Size(2)]
+        );
+
+        let earlier = journal.replay_until(&base, at(150));
+        let paths: Vec<_> = earlier.entries().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("foo"), PathBuf::from("bar")]);
+    }
+
+    #[test]
+    fn test_journal_apply_resolved() {
+        let base = Spec::parse(
+            "/set type=file uid=0\n\
+             etc type=dir\n\
+             \x20   passwd size=1\n\
+             ..\n",
+        )
+        .unwrap();
+        let mut journal = Journal::new();
+        journal.push(
+            at(100),
+            JournalOp::Put(Entry {
+                path: PathBuf::from("./etc/passwd"),
+                keywords: vec![Keyword::Size(2)],
+            }),
+        );
+        journal.push(
+            at(200),
+            JournalOp::Put(Entry {
+                path: PathBuf::from("etc/group"),
+                keywords: vec![Keyword::Size(3)],
+            }),
+        );
+
+        let resolved: Vec<_> = journal
+            .apply(&base)
+            .resolve()
+            .into_iter()
+            .map(|entry| (entry.path, entry.keywords))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                (
+                    PathBuf::from("./etc"),
+                    vec![Keyword::Type(crate::Type::Dir), Keyword::Uid(0)]
+                ),
+                (PathBuf::from("./etc/passwd"), vec![Keyword::Size(2)]),
+                (PathBuf::from("./etc/group"), vec![Keyword::Size(3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_journal_apply_set_state() {
+        let base = Spec::parse("/set uid=0\n./a size=1 mode=0644\n").unwrap();
+        let mut journal = Journal::new();
+        for (secs, path, size) in [(100, "a", 2), (200, "./b", 3)] {
+            journal.push(
+                at(secs),
+                JournalOp::Put(Entry {
+                    path: PathBuf::from(path),
+                    keywords: vec![Keyword::Size(size)],
+                }),
+            );
+        }
+
+        let latest = journal.apply(&base);
+        let resolved: Vec<_> = latest
+            .resolve()
+            .into_iter()
+            .map(|entry| entry.keywords)
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![vec![Keyword::Size(2)], vec![Keyword::Size(3)]]
+        );
+        assert!(latest.to_string().ends_with("/unset all\n./b size=3\n"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Specs as JSON, for tools that would rather not parse the mtree format.
+//!
+//! Each entry becomes one object holding its full path and every keyword
+//! that applies to it, with `/set` defaults filled in, as the `serde`
+//! derives write them:
+//!
+//! ```text
+//! {"path":"./etc/passwd","keywords":[{"Type":"File"},{"Uid":0},{"Mode":420},"Optional"]}
+//! ```
+//!
+//! Paths are written as they are, not vis-encoded, so a spec with a path
+//! that isn't UTF-8 can't be written as JSON.
+
+use crate::{Entry, EntryId, Line, Span, Spec};
+
+impl Spec {
+    /// The resolved entries as a JSON array.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let mut json = serde_json::to_string_pretty(&resolved(self))?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// The resolved entries as newline-delimited JSON.
+    pub fn to_ndjson(&self) -> serde_json::Result<String> {
+        let mut json = String::new();
+
+        for entry in resolved(self) {
+            json += &serde_json::to_string(&entry)?;
+            json.push('\n');
+        }
+
+        Ok(json)
+    }
+
+    /// Reads a JSON array of entries, as written by [`Spec::to_json`]. The
+    /// spec has an entry with a full path for each of them.
+    pub fn from_json(input: &str) -> serde_json::Result<Spec> {
+        Ok(from_entries(serde_json::from_str(input)?))
+    }
+
+    /// Reads newline-delimited JSON entries, as written by
+    /// [`Spec::to_ndjson`]. Blank lines are skipped.
+    pub fn from_ndjson(input: &str) -> serde_json::Result<Spec> {
+        let entries = serde_json::Deserializer::from_str(input)
+            .into_iter()
+            .collect::<serde_json::Result<_>>()?;
+
+        Ok(from_entries(entries))
+    }
+}
+
+/// The resolved entries of `spec`, without their ids and spans.
+fn resolved(spec: &Spec) -> Vec<Entry> {
+    spec.resolve()
+        .into_iter()
+        .map(|entry| Entry {
+            path: entry.path,
+            keywords: entry.keywords,
+        })
+        .collect()
+}
+
+fn from_entries(entries: Vec<Entry>) -> Spec {
+    let lines = entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| Line::Entry {
+            id: EntryId(index as u64),
+            entry,
+            span: Span::default(),
+        })
+        .collect();
+
+    Spec::new(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let spec = Spec::parse(
+            "/set type=file uid=0\n\
+             . type=dir\n\
+             etc type=dir\n\
+             \x20   my\\040file mode=0644 size=12 optional\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.to_ndjson().unwrap(),
+            "{\"path\":\".\",\"keywords\":[{\"Type\":\"Dir\"},{\"Uid\":0}]}\n\
+             {\"path\":\"./etc\",\"keywords\":[{\"Type\":\"Dir\"},{\"Uid\":0}]}\n\
+             {\"path\":\"./etc/my file\",\"keywords\":[{\"Type\":\"File\"},{\"Uid\":0},\
+             {\"Mode\":420},{\"Size\":12},\"Optional\"]}\n"
+        );
+
+        let json = spec.to_json().unwrap();
+        assert!(json.starts_with("[\n  {\n    \"path\": \".\","));
+        assert_eq!(resolved(&Spec::from_json(&json).unwrap()), resolved(&spec));
+        assert_eq!(Spec::from_json("[]").unwrap(), Spec::default());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let spec = Spec::from_ndjson(
+            "{ \"path\": \"./caf\\u00e9\", \"keywords\": [{\"Size\": 12}, {\"Uname\": \"r\\u00f6\\u00f6t\"}] }\n\
+             \n\
+             {\"path\":\"./emoji\\ud83d\\ude00\",\"keywords\":[\"NoChange\"]}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            spec.to_string(),
+            "./caf\\303\\251 size=12 uname=rööt\n./emoji\\360\\237\\230\\200 nochange\n"
+        );
+
+        let err =
+            Spec::from_ndjson("{\"path\":\"a\",\"keywords\":[]}\n{\"path\":\"b\",}\n").unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert!(Spec::from_json("[{\"path\":\"a\",\"keywords\":[{\"Size\":\"x\"}]}]").is_err());
+        assert!(Spec::from_json("[{\"keywords\":[]}]").is_err());
+        assert!(Spec::from_json("{\"path\":\"a\",\"keywords\":[]}").is_err());
+    }
+}
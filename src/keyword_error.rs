@@ -0,0 +1,178 @@
+//! Why a `name=value` word isn't a keyword, for [`Keyword::from_str`].
+//!
+//! The keyword parser only says whether a word parsed. Like
+//! [`ParseError::diagnose`](crate::ParseError::diagnose), the reason is
+//! worked out afterwards from the word itself, so the parser stays fast for
+//! words that are fine.
+//!
+//! [`Keyword::from_str`]: crate::Keyword#impl-FromStr-for-Keyword
+
+use std::fmt;
+
+use chumsky::Parser;
+
+use crate::keyword_info::ValueType;
+use crate::{KeywordKind, parse_keyword, sanitize};
+
+/// A word that isn't a valid keyword, with the text that was wrong.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidKeyword {
+    /// The name before the `=`, which isn't a keyword mtree(5) knows.
+    UnknownName(String),
+    MissingValue(KeywordKind),
+    /// A value for a keyword that takes none, such as `optional=1`.
+    UnexpectedValue(KeywordKind, String),
+    /// A value that should be a decimal number, but isn't one or doesn't
+    /// fit.
+    InvalidInteger(KeywordKind, String),
+    /// A `seconds.nanoseconds` value whose seconds don't fit or whose
+    /// nanoseconds are a second or more.
+    TimestampOutOfRange(KeywordKind, String),
+    /// A digest that isn't hex or has the wrong length.
+    MalformedDigest(KeywordKind, String),
+    /// Any other value the keyword doesn't accept.
+    InvalidValue(KeywordKind, String),
+    /// Text after a complete keyword, such as a second keyword.
+    TrailingInput(String),
+}
+
+impl InvalidKeyword {
+    /// Works out what's wrong with `word`, which failed to parse as a
+    /// keyword.
+    pub(crate) fn classify(word: &str) -> Self {
+        if let Some((first, rest)) = word.split_once([' ', '\t'])
+            && parse_keyword().parse(first).into_result().is_ok()
+        {
+            return InvalidKeyword::TrailingInput(rest.to_string());
+        }
+
+        let (name, value) = match word.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (word, None),
+        };
+
+        let Some(kind) = KeywordKind::from_name(name) else {
+            return InvalidKeyword::UnknownName(name.to_string());
+        };
+
+        let Some(value) = value else {
+            return InvalidKeyword::MissingValue(kind);
+        };
+        let value = value.to_string();
+
+        match kind.info().value {
+            ValueType::None => InvalidKeyword::UnexpectedValue(kind, value),
+            ValueType::Number => InvalidKeyword::InvalidInteger(kind, value),
+            ValueType::Timestamp if is_timestamp_shaped(&value) => {
+                InvalidKeyword::TimestampOutOfRange(kind, value)
+            }
+            ValueType::Hex => InvalidKeyword::MalformedDigest(kind, value),
+            _ => InvalidKeyword::InvalidValue(kind, value),
+        }
+    }
+}
+
+/// Whether `value` is written like a timestamp, `-?digits(.digits)?`, so
+/// that failing to parse means it's out of range.
+fn is_timestamp_shaped(value: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let value = value.strip_prefix('-').unwrap_or(value);
+
+    match value.split_once('.') {
+        Some((secs, nanos)) => digits(secs) && digits(nanos),
+        None => digits(value),
+    }
+}
+
+impl fmt::Display for InvalidKeyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidKeyword::UnknownName(name) => {
+                write!(f, "unknown keyword `{}`", sanitize::text(name))
+            }
+            InvalidKeyword::MissingValue(kind) => write!(f, "`{}` needs a value", kind.name()),
+            InvalidKeyword::UnexpectedValue(kind, _) => {
+                write!(f, "`{}` takes no value", kind.name())
+            }
+            InvalidKeyword::InvalidInteger(kind, value) => {
+                write!(
+                    f,
+                    "invalid number for {} `{}`",
+                    kind.name(),
+                    sanitize::text(value)
+                )
+            }
+            InvalidKeyword::TimestampOutOfRange(kind, value) => {
+                write!(
+                    f,
+                    "{} out of range `{}`",
+                    kind.name(),
+                    sanitize::text(value)
+                )
+            }
+            InvalidKeyword::MalformedDigest(kind, value) => {
+                write!(f, "malformed {} `{}`", kind.name(), sanitize::text(value))
+            }
+            InvalidKeyword::InvalidValue(kind, value) => {
+                write!(f, "invalid {} `{}`", kind.name(), sanitize::text(value))
+            }
+            InvalidKeyword::TrailingInput(rest) => {
+                write!(f, "unexpected `{}` after keyword", sanitize::text(rest))
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidKeyword {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DigestKind, Keyword};
+
+    #[test]
+    fn test_invalid_keyword() {
+        let err = |word: &str| word.parse::<Keyword>().unwrap_err();
+
+        assert_eq!(
+            err("colour=red"),
+            InvalidKeyword::UnknownName("colour".to_string())
+        );
+        assert_eq!(err("uid"), InvalidKeyword::MissingValue(KeywordKind::Uid));
+        assert_eq!(
+            err("optional=1"),
+            InvalidKeyword::UnexpectedValue(KeywordKind::Optional, "1".to_string())
+        );
+        assert_eq!(
+            err("uid=99999999999"),
+            InvalidKeyword::InvalidInteger(KeywordKind::Uid, "99999999999".to_string())
+        );
+        assert_eq!(
+            err("size=ten"),
+            InvalidKeyword::InvalidInteger(KeywordKind::Size, "ten".to_string())
+        );
+        assert_eq!(
+            err("time=1.1000000000"),
+            InvalidKeyword::TimestampOutOfRange(KeywordKind::Time, "1.1000000000".to_string())
+        );
+        assert_eq!(
+            err("time=yesterday"),
+            InvalidKeyword::InvalidValue(KeywordKind::Time, "yesterday".to_string())
+        );
+        assert_eq!(
+            err("md5digest=abc"),
+            InvalidKeyword::MalformedDigest(
+                KeywordKind::Digest(DigestKind::Md5),
+                "abc".to_string()
+            )
+        );
+        assert_eq!(
+            err("uid=0 gid=0"),
+            InvalidKeyword::TrailingInput("gid=0".to_string())
+        );
+        assert_eq!(
+            err("uid=0 gid=0").to_string(),
+            "unexpected `gid=0` after keyword"
+        );
+    }
+}
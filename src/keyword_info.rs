@@ -0,0 +1,314 @@
+//! Descriptions of the keywords, for generating help text, shell completions
+//! and argument validation in tools built on this crate.
+
+use crate::{DigestKind, KeywordKind};
+
+/// The kind of value a keyword takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    /// The keyword takes no value, like `optional`.
+    None,
+    /// One of `block`, `char`, `dir`, `fifo`, `file`, `link` or `socket`.
+    FileType,
+    /// An unsigned decimal number.
+    Number,
+    /// A user or group name.
+    Name,
+    /// An octal or symbolic file mode.
+    Mode,
+    /// A comma-separated list of file flags.
+    Flags,
+    /// A device number, either raw or as `format,major,minor`.
+    Device,
+    /// Seconds since the epoch with nanoseconds, `secs.nanos`.
+    Timestamp,
+    /// A hex-encoded digest.
+    Hex,
+    /// Base64-encoded bytes.
+    Base64,
+    /// A vis-encoded path.
+    Path,
+    /// A comma-separated list of words.
+    List,
+    /// A security label, such as `system_u:object_r:bin_t:s0`.
+    Label,
+}
+
+/// An mtree implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// mtree(8) on NetBSD and FreeBSD.
+    Bsd,
+    /// libarchive's mtree reader and writer, as used by bsdtar and pacman.
+    Libarchive,
+    /// The Go implementation in `github.com/vbatts/go-mtree`, as used by
+    /// umoci.
+    GoMtree,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeywordInfo {
+    pub kind: KeywordKind,
+    pub name: &'static str,
+    /// Other names the parser accepts for the keyword.
+    pub aliases: &'static [&'static str],
+    pub value: ValueType,
+    /// A one-line description, suitable for help output.
+    pub description: &'static str,
+    /// The implementations that understand the keyword.
+    pub dialects: &'static [Dialect],
+}
+
+impl KeywordInfo {
+    pub fn supported_by(&self, dialect: Dialect) -> bool {
+        self.dialects.contains(&dialect)
+    }
+}
+
+const ALL_DIALECTS: &[Dialect] = &[Dialect::Bsd, Dialect::Libarchive, Dialect::GoMtree];
+
+const fn keyword(
+    kind: KeywordKind,
+    aliases: &'static [&'static str],
+    value: ValueType,
+    description: &'static str,
+) -> KeywordInfo {
+    KeywordInfo {
+        kind,
+        name: kind.name(),
+        aliases,
+        value,
+        description,
+        dialects: ALL_DIALECTS,
+    }
+}
+
+/// Every keyword, in the order mtree(5) lists them.
+pub const KEYWORDS: &[KeywordInfo] = &[
+    #[cfg(feature = "blake3")]
+    KeywordInfo {
+        dialects: &[],
+        ..keyword(
+            KeywordKind::Digest(DigestKind::Blake3),
+            &["blake3"],
+            ValueType::Hex,
+            "BLAKE3 digest of the file",
+        )
+    },
+    keyword(
+        KeywordKind::Cksum,
+        &[],
+        ValueType::Number,
+        "CRC checksum of the file, as computed by cksum(1)",
+    ),
+    KeywordInfo {
+        dialects: &[Dialect::Libarchive],
+        ..keyword(
+            KeywordKind::Contents,
+            &[],
+            ValueType::Path,
+            "file to read the contents from, instead of the entry's path",
+        )
+    },
+    keyword(
+        KeywordKind::Device,
+        &[],
+        ValueType::Device,
+        "device number of a block or character device",
+    ),
+    KeywordInfo {
+        dialects: &[],
+        ..keyword(
+            KeywordKind::EntryDigest,
+            &[],
+            ValueType::Hex,
+            "SHA-256 digest of the rest of the entry, for detecting edits",
+        )
+    },
+    keyword(KeywordKind::Flags, &[], ValueType::Flags, "file flags"),
+    keyword(KeywordKind::Gid, &[], ValueType::Number, "numeric group ID"),
+    keyword(KeywordKind::Gname, &[], ValueType::Name, "group name"),
+    KeywordInfo {
+        dialects: &[],
+        ..keyword(
+            KeywordKind::HardLink,
+            &[],
+            ValueType::Path,
+            "earlier entry that is a hard link to the same file",
+        )
+    },
+    keyword(
+        KeywordKind::Ignore,
+        &[],
+        ValueType::None,
+        "ignore any files below this one",
+    ),
+    keyword(KeywordKind::Inode, &[], ValueType::Number, "inode number"),
+    keyword(
+        KeywordKind::Link,
+        &[],
+        ValueType::Path,
+        "target of a symbolic link",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Md5),
+        &["md5"],
+        ValueType::Hex,
+        "MD5 digest of the file",
+    ),
+    keyword(
+        KeywordKind::Mode,
+        &[],
+        ValueType::Mode,
+        "file permissions, in octal or symbolic form",
+    ),
+    keyword(
+        KeywordKind::Nlink,
+        &[],
+        ValueType::Number,
+        "number of hard links",
+    ),
+    keyword(
+        KeywordKind::NoChange,
+        &[],
+        ValueType::None,
+        "only check that the file exists",
+    ),
+    keyword(
+        KeywordKind::Optional,
+        &[],
+        ValueType::None,
+        "don't report the file if it's missing",
+    ),
+    keyword(
+        KeywordKind::ResDevice,
+        &[],
+        ValueType::Device,
+        "device number of the device the file resides on",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Rmd160),
+        &["rmd160", "ripemd160digest"],
+        ValueType::Hex,
+        "RIPEMD-160 digest of the file",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Sha1),
+        &["sha1"],
+        ValueType::Hex,
+        "SHA-1 digest of the file",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Sha256),
+        &["sha256"],
+        ValueType::Hex,
+        "SHA-256 digest of the file",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Sha384),
+        &["sha384"],
+        ValueType::Hex,
+        "SHA-384 digest of the file",
+    ),
+    keyword(
+        KeywordKind::Digest(DigestKind::Sha512),
+        &["sha512"],
+        ValueType::Hex,
+        "SHA-512 digest of the file",
+    ),
+    KeywordInfo {
+        dialects: &[],
+        ..keyword(
+            KeywordKind::Selinux,
+            &[],
+            ValueType::Label,
+            "SELinux security context, from the `security.selinux` attribute",
+        )
+    },
+    keyword(KeywordKind::Size, &[], ValueType::Number, "size in bytes"),
+    KeywordInfo {
+        dialects: &[Dialect::Bsd],
+        ..keyword(
+            KeywordKind::Tags,
+            &[],
+            ValueType::List,
+            "tags for selecting entries, e.g. by package",
+        )
+    },
+    KeywordInfo {
+        dialects: &[Dialect::GoMtree],
+        ..keyword(
+            KeywordKind::TarTime,
+            &[],
+            ValueType::Timestamp,
+            "last modification time, truncated to whole seconds",
+        )
+    },
+    keyword(
+        KeywordKind::Time,
+        &[],
+        ValueType::Timestamp,
+        "last modification time",
+    ),
+    keyword(
+        KeywordKind::Type,
+        &[],
+        ValueType::FileType,
+        "type of the file",
+    ),
+    keyword(KeywordKind::Uid, &[], ValueType::Number, "numeric user ID"),
+    keyword(KeywordKind::Uname, &[], ValueType::Name, "user name"),
+    KeywordInfo {
+        dialects: &[Dialect::GoMtree],
+        ..keyword(
+            KeywordKind::Xattr,
+            &[],
+            ValueType::Base64,
+            "extended attribute, written as `xattr.<name>=<value>`",
+        )
+    },
+    #[cfg(feature = "xxh3")]
+    KeywordInfo {
+        dialects: &[],
+        ..keyword(
+            KeywordKind::Digest(DigestKind::Xxh3),
+            &["xxh3"],
+            ValueType::Hex,
+            "XXH3 hash of the file, for detecting accidental changes",
+        )
+    },
+];
+
+impl KeywordKind {
+    /// Describes the keyword.
+    pub fn info(&self) -> &'static KeywordInfo {
+        KEYWORDS
+            .iter()
+            .find(|info| info.kind == *self)
+            .expect("every keyword kind is described")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_info() {
+        for info in KEYWORDS {
+            assert_eq!(info.kind.info(), info);
+            assert_eq!(KeywordKind::from_name(info.name), Some(info.kind));
+
+            for alias in info.aliases {
+                assert_eq!(KeywordKind::from_name(alias), Some(info.kind), "{alias}");
+            }
+        }
+
+        let sha256 = KeywordKind::Digest(DigestKind::Sha256).info();
+        assert_eq!(sha256.name, "sha256digest");
+        assert_eq!(sha256.value, ValueType::Hex);
+
+        assert!(KeywordKind::Uid.info().supported_by(Dialect::Libarchive));
+        assert!(!KeywordKind::Tags.info().supported_by(Dialect::Libarchive));
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Sub};
+use std::str::FromStr;
+
+use crate::{DigestKind, KeywordKind};
+
+/// A set of keyword kinds, used to choose which keywords are recorded when
+/// generating a spec (like `mtree -k`).
+///
+/// Only keywords that describe a file can be selected; `optional`, `ignore`,
+/// `nochange`, `tags` and `contents` aren't part of any set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeywordSet(u32);
+
+impl KeywordSet {
+    pub const TYPE: KeywordSet = KeywordSet(1 << 0);
+    pub const UID: KeywordSet = KeywordSet(1 << 1);
+    pub const GID: KeywordSet = KeywordSet(1 << 2);
+    pub const UNAME: KeywordSet = KeywordSet(1 << 3);
+    pub const GNAME: KeywordSet = KeywordSet(1 << 4);
+    pub const MODE: KeywordSet = KeywordSet(1 << 5);
+    pub const FLAGS: KeywordSet = KeywordSet(1 << 6);
+    pub const NLINK: KeywordSet = KeywordSet(1 << 7);
+    pub const INODE: KeywordSet = KeywordSet(1 << 8);
+    pub const DEVICE: KeywordSet = KeywordSet(1 << 9);
+    pub const RESDEVICE: KeywordSet = KeywordSet(1 << 10);
+    pub const TIME: KeywordSet = KeywordSet(1 << 11);
+    pub const SIZE: KeywordSet = KeywordSet(1 << 12);
+    pub const CKSUM: KeywordSet = KeywordSet(1 << 13);
+    pub const LINK: KeywordSet = KeywordSet(1 << 14);
+    pub const MD5: KeywordSet = KeywordSet(1 << 15);
+    pub const SHA1: KeywordSet = KeywordSet(1 << 16);
+    pub const RMD160: KeywordSet = KeywordSet(1 << 17);
+    pub const SHA256: KeywordSet = KeywordSet(1 << 18);
+    pub const SHA384: KeywordSet = KeywordSet(1 << 19);
+    pub const SHA512: KeywordSet = KeywordSet(1 << 20);
+    pub const TAR_TIME: KeywordSet = KeywordSet(1 << 21);
+    /// Extended attributes, only read on Linux.
+    pub const XATTR: KeywordSet = KeywordSet(1 << 22);
+    /// The SELinux context, only read on Linux.
+    pub const SELINUX: KeywordSet = KeywordSet(1 << 23);
+    /// The `security.*` extended attributes, such as `security.capability`
+    /// and `security.ima`, as `xattr` keywords but without the others that
+    /// [`KeywordSet::XATTR`] records. Written `security` in a list of
+    /// keyword names.
+    pub const SECURITY: KeywordSet = KeywordSet(1 << 24);
+    /// The BLAKE3 digest, with the `blake3` feature.
+    pub const BLAKE3: KeywordSet = KeywordSet(1 << 25);
+    /// The XXH3 digest, with the `xxh3` feature.
+    pub const XXH3: KeywordSet = KeywordSet(1 << 26);
+
+    /// The keywords `mtree -c` records by default that this crate can read
+    /// from file metadata.
+    #[cfg(unix)]
+    pub const DEFAULT: KeywordSet = KeywordSet(
+        Self::TYPE.0
+            | Self::UID.0
+            | Self::GID.0
+            | Self::MODE.0
+            | Self::NLINK.0
+            | Self::TIME.0
+            | Self::SIZE.0
+            | Self::LINK.0,
+    );
+
+    /// The keywords `mtree -c` records by default that this crate can read
+    /// from file metadata. Outside Unix there are no owners or link counts
+    /// to read, and `mode` only tells whether a file is read-only.
+    #[cfg(not(unix))]
+    pub const DEFAULT: KeywordSet =
+        KeywordSet(Self::TYPE.0 | Self::MODE.0 | Self::TIME.0 | Self::SIZE.0 | Self::LINK.0);
+
+    /// A preset for validating backups: a file's type, size, modification
+    /// time, SHA-256 digest and symlink target. Ownership, permissions and
+    /// device numbers are left out, as they often differ after a restore
+    /// as another user or onto another disk.
+    ///
+    /// Pass it to both [`SpecBuilder::keywords`](crate::SpecBuilder::keywords)
+    /// and [`Verifier::keywords`](crate::verify::Verifier::keywords). Digests
+    /// are only computed with the `hash` feature.
+    pub const BACKUP: KeywordSet =
+        KeywordSet(Self::TYPE.0 | Self::SIZE.0 | Self::TIME.0 | Self::SHA256.0 | Self::LINK.0);
+
+    /// A preset for quick checks: a file's type, size, modification time
+    /// and permissions, none of which need its contents read. Pass it to
+    /// [`Verifier::keywords`](crate::verify::Verifier::keywords) for a
+    /// frequent sweep, then
+    /// [`Verifier::verify_flagged`](crate::verify::Verifier::verify_flagged)
+    /// to check what it found in full.
+    pub const QUICK: KeywordSet =
+        KeywordSet(Self::TYPE.0 | Self::SIZE.0 | Self::TIME.0 | Self::TAR_TIME.0 | Self::MODE.0);
+
+    /// The keywords BSD mtree factors out into `/set` lines, and so
+    /// [`WriteOptions::factor_sets`](crate::write::WriteOptions::factor_sets).
+    pub const FACTORED: KeywordSet = KeywordSet(
+        Self::TYPE.0
+            | Self::UID.0
+            | Self::GID.0
+            | Self::UNAME.0
+            | Self::GNAME.0
+            | Self::MODE.0
+            | Self::FLAGS.0
+            | Self::NLINK.0,
+    );
+
+    pub const fn empty() -> Self {
+        KeywordSet(0)
+    }
+
+    pub const fn all() -> Self {
+        KeywordSet((1 << 27) - 1)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: KeywordSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn contains_kind(&self, kind: KeywordKind) -> bool {
+        let set = KeywordSet::from_kind(kind);
+        !set.is_empty() && self.contains(set)
+    }
+
+    pub fn insert(&mut self, other: KeywordSet) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: KeywordSet) {
+        self.0 &= !other.0;
+    }
+
+    /// The set containing just `kind`, or an empty set if `kind` can't be
+    /// selected.
+    pub fn from_kind(kind: KeywordKind) -> Self {
+        match kind {
+            KeywordKind::Type => KeywordSet::TYPE,
+            KeywordKind::Uid => KeywordSet::UID,
+            KeywordKind::Gid => KeywordSet::GID,
+            KeywordKind::Uname => KeywordSet::UNAME,
+            KeywordKind::Gname => KeywordSet::GNAME,
+            KeywordKind::Mode => KeywordSet::MODE,
+            KeywordKind::Flags => KeywordSet::FLAGS,
+            KeywordKind::Nlink => KeywordSet::NLINK,
+            KeywordKind::Inode => KeywordSet::INODE,
+            KeywordKind::Device => KeywordSet::DEVICE,
+            KeywordKind::ResDevice => KeywordSet::RESDEVICE,
+            KeywordKind::Time => KeywordSet::TIME,
+            KeywordKind::TarTime => KeywordSet::TAR_TIME,
+            KeywordKind::Size => KeywordSet::SIZE,
+            KeywordKind::Cksum => KeywordSet::CKSUM,
+            KeywordKind::Link => KeywordSet::LINK,
+            KeywordKind::Xattr => KeywordSet::XATTR,
+            KeywordKind::Selinux => KeywordSet::SELINUX,
+            KeywordKind::Digest(DigestKind::Md5) => KeywordSet::MD5,
+            KeywordKind::Digest(DigestKind::Sha1) => KeywordSet::SHA1,
+            KeywordKind::Digest(DigestKind::Rmd160) => KeywordSet::RMD160,
+            KeywordKind::Digest(DigestKind::Sha256) => KeywordSet::SHA256,
+            KeywordKind::Digest(DigestKind::Sha384) => KeywordSet::SHA384,
+            KeywordKind::Digest(DigestKind::Sha512) => KeywordSet::SHA512,
+            #[cfg(feature = "blake3")]
+            KeywordKind::Digest(DigestKind::Blake3) => KeywordSet::BLAKE3,
+            #[cfg(feature = "xxh3")]
+            KeywordKind::Digest(DigestKind::Xxh3) => KeywordSet::XXH3,
+            KeywordKind::Contents
+            | KeywordKind::HardLink
+            | KeywordKind::Optional
+            | KeywordKind::Ignore
+            | KeywordKind::NoChange
+            | KeywordKind::Tags
+            | KeywordKind::EntryDigest => KeywordSet::empty(),
+        }
+    }
+
+    /// The digest algorithms in the set.
+    pub fn digests(&self) -> impl Iterator<Item = DigestKind> + '_ {
+        DigestKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| self.contains_kind(KeywordKind::Digest(*kind)))
+    }
+}
+
+impl Default for KeywordSet {
+    fn default() -> Self {
+        KeywordSet::DEFAULT
+    }
+}
+
+impl BitOr for KeywordSet {
+    type Output = KeywordSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        KeywordSet(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for KeywordSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for KeywordSet {
+    type Output = KeywordSet;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        KeywordSet(self.0 & rhs.0)
+    }
+}
+
+impl Sub for KeywordSet {
+    type Output = KeywordSet;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        KeywordSet(self.0 & !rhs.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownKeyword(pub String);
+
+impl fmt::Display for UnknownKeyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown keyword: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownKeyword {}
+
+/// Parses a list of keyword names separated by commas or spaces, as given to
+/// `mtree -k`. As in BSD mtree, `all` stands for every keyword.
+impl FromStr for KeywordSet {
+    type Err = UnknownKeyword;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split([',', ' '])
+            .filter(|name| !name.is_empty())
+            .try_fold(KeywordSet::empty(), |set, name| {
+                if name == "all" {
+                    return Ok(KeywordSet::all());
+                } else if name == "security" {
+                    return Ok(set | KeywordSet::SECURITY);
+                }
+
+                let kind = KeywordKind::from_name(name)
+                    .map(KeywordSet::from_kind)
+                    .filter(|kind| !kind.is_empty())
+                    .ok_or_else(|| UnknownKeyword(name.to_string()))?;
+
+                Ok(set | kind)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_set() {
+        let mut set = KeywordSet::TYPE | KeywordSet::SHA256 | KeywordSet::SIZE;
+
+        assert!(set.contains(KeywordSet::TYPE | KeywordSet::SIZE));
+        assert!(set.contains_kind(KeywordKind::Digest(DigestKind::Sha256)));
+        assert!(!set.contains_kind(KeywordKind::Uid));
+        assert!(!set.contains_kind(KeywordKind::Tags));
+        assert_eq!(set.digests().collect::<Vec<_>>(), [DigestKind::Sha256]);
+
+        set.remove(KeywordSet::SIZE);
+        assert_eq!(set, KeywordSet::TYPE | KeywordSet::SHA256);
+        assert_eq!(KeywordSet::all() - KeywordSet::all(), KeywordSet::empty());
+    }
+
+    #[test]
+    fn test_keyword_set_from_str() {
+        assert_eq!(
+            "type,sha256 size".parse(),
+            Ok(KeywordSet::TYPE | KeywordSet::SHA256 | KeywordSet::SIZE)
+        );
+        assert_eq!(
+            "type,bogus".parse::<KeywordSet>(),
+            Err(UnknownKeyword("bogus".to_string()))
+        );
+        assert!("tags".parse::<KeywordSet>().is_err());
+        assert_eq!("size,all".parse(), Ok(KeywordSet::all()));
+        assert_eq!(
+            "selinux security".parse(),
+            Ok(KeywordSet::SELINUX | KeywordSet::SECURITY)
+        );
+    }
+}
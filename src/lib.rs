@@ -1,25 +1,469 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use chrono::{DateTime, Utc};
+use chumsky::Boxed;
 use chumsky::prelude::*;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+pub mod action_log;
+pub mod analysis;
+#[cfg(unix)]
+pub mod apply;
+pub mod arch;
+#[cfg(feature = "tar")]
+pub mod archive;
+mod base64;
+#[cfg(feature = "binary")]
+pub mod binary;
+pub mod borrowed;
+pub mod cas;
+#[cfg(feature = "hash")]
+pub mod chain;
+pub mod cksum;
+pub mod comments;
+pub mod concat;
+pub mod create;
+pub mod device;
+pub mod diagnostic;
+pub mod diff;
+pub mod digest;
+#[cfg(target_os = "linux")]
+pub mod dirfd;
+pub mod edit;
+pub mod entry_builder;
+pub mod entry_digest;
+pub mod escape;
+pub mod exclude;
+pub mod failure_report;
+#[cfg(feature = "fast")]
+mod fast;
+pub mod flags;
+pub mod footprint;
+pub mod format;
+pub mod frozen;
+pub mod glob;
+#[cfg(feature = "hash")]
+pub mod hash;
+pub mod idmap;
+#[cfg(feature = "ignore")]
+mod ignore;
+pub mod index;
+pub mod intern;
+pub mod journal;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod keyword_error;
+pub mod keyword_info;
+pub mod keyword_set;
+pub mod limits;
+pub mod line;
+pub mod lint;
+pub mod matcher;
+pub mod merge;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod mode;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+#[cfg(feature = "oci")]
+pub mod oci;
+pub mod only;
+#[cfg(feature = "pacman")]
+pub mod pacman;
+pub mod progress;
+pub mod reader;
+pub mod refresh;
+pub mod resolve;
+pub mod rollup;
+pub mod root;
+pub mod roundtrip;
+pub mod sample;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+pub mod sanitize;
+pub mod signature;
+pub mod size_stats;
+pub mod snapshot;
+pub mod spec;
+pub mod split;
+pub mod sync;
+pub mod terminal;
+#[cfg(test)]
+mod testutil;
+pub mod time;
+pub mod transform;
+pub mod tree;
+pub mod update;
+pub mod v1;
+pub mod validate;
+pub mod verify;
+pub mod write;
+pub mod xattr;
+
+pub use action_log::{ActionLog, ActionRecord};
+pub use analysis::{AnalysisOptions, Anomaly};
+pub use borrowed::{EntryRef, KeywordRef, LineRef, parse_lines_ref};
+pub use cas::ContentStore;
+pub use create::SpecBuilder;
+pub use device::Device;
+pub use diagnostic::{Cause, Diagnostic, DiagnosticGroup};
+pub use diff::{
+    ChangeKind, ChangeRecord, EntryChange, KeywordChange, Rename, RenameConfidence, SpecDiff, diff,
+};
+pub use digest::{Digest, DigestKind};
+pub use edit::SpecEditor;
+pub use entry_builder::EntryBuilder;
+pub use exclude::ExcludeList;
+pub use failure_report::FailureReport;
+pub use flags::{FileFlag, Flags};
+pub use footprint::MemoryFootprint;
+pub use frozen::FrozenSpec;
+pub use glob::Glob;
+pub use idmap::{IdMap, IdRange};
+pub use index::SpecIndex;
+pub use intern::Interner;
+pub use keyword_error::InvalidKeyword;
+pub use keyword_info::{Dialect, KeywordInfo, ValueType};
+pub use keyword_set::KeywordSet;
+pub use limits::{LimitExceeded, ParseLimits};
+pub use line::{
+    EntryId, Line, ParseError, ParseErrorCause, ParseOptions, Span, parse_lines,
+    parse_lines_lenient, parse_lines_lenient_with, parse_lines_with,
+};
+pub use lint::{LintFinding, Linter, Rule, Severity};
+pub use matcher::{GlobMatcher, LiteralMatcher, PathMatcher};
+pub use merge::{MergeConflict, MergePolicy, MergeReport};
+pub use mode::Mode;
+pub use only::OnlyList;
+pub use progress::{Progress, ProgressCounter, ProgressEvent};
+pub use reader::{ReadError, SpecReader};
+pub use resolve::ResolvedEntry;
+pub use root::{AbsolutePaths, RootPath};
+pub use roundtrip::Fidelity;
+pub use sample::Sampling;
+pub use signature::{Header, Layout, SpecFormat};
+pub use size_stats::{KeywordStats, SizeStats};
+pub use snapshot::{Snapshot, SpecStore};
+pub use spec::Spec;
+pub use split::SplitLimit;
+pub use sync::{SyncAction, SyncPlan, SyncPlanner};
+pub use terminal::{ColorChoice, TerminalFormatter};
+pub use time::Timestamp;
+pub use transform::Ownership;
+pub use tree::{DuplicateEntry, Node, NodeId, Tree};
+pub use update::KeywordEdits;
+pub use validate::{Charset, ValidationIssue, ValidationOptions};
+pub use verify::{
+    DeferredCheck, PackageSummary, ReportDelta, VerifyFinding, VerifyReport, VerifySummary,
+};
+pub use write::{
+    PathStyle, SpecWriter, TimePrecision, WriteOptions, write_spec, write_spec_atomic,
+    write_spec_with,
+};
+
+/// Entries order by path, then by their keywords in order. See
+/// [`Spec::sort`] for putting a spec in that order.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry {
     pub path: PathBuf,
     pub keywords: Vec<Keyword>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Keywords order by [`KeywordKind`], then by value: numbers and times
+/// numerically, names and paths bytewise, and extended attributes by name.
+/// A [`Keyword::Raw`] value comes after the parsed values of its kind.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Keyword {
     Type(Type),
     Uid(u32),
-    Time(chrono::DateTime<Utc>),
+    Gid(u32),
+    Uname(Arc<str>),
+    Gname(Arc<str>),
+    Mode(Mode),
+    Flags(Flags),
+    Nlink(u64),
+    Inode(u64),
+    Device(Device),
+    ResDevice(Device),
+    Time(Timestamp),
+    /// go-mtree's modification time truncated to whole seconds, as a tar
+    /// header stores it.
+    TarTime(Timestamp),
     Size(u64),
-    Sha256(String),
+    Cksum(u32),
+    Digest(Digest),
     Link(PathBuf),
+    /// Where the file's contents can be found, relative to the spec.
+    Contents(PathBuf),
+    /// The spec path of an earlier entry that is a hard link to the same
+    /// file, see [`SpecBuilder::hard_links`].
+    HardLink(PathBuf),
+    Optional,
+    Ignore,
+    NoChange,
+    Tags(Vec<String>),
+    /// A SHA-256 digest of the rest of the entry, see
+    /// [`Entry::canonical_form`].
+    EntryDigest(Digest),
+    /// The SELinux context in the `security.selinux` extended attribute,
+    /// without its trailing NUL.
+    Selinux(String),
+    /// An extended attribute, written `xattr.<name>=<base64 value>` as
+    /// go-mtree does.
+    Xattr {
+        name: String,
+        value: Vec<u8>,
+    },
+    /// A value that doesn't parse as the keyword's type, kept as written.
+    /// Only produced when [`ParseOptions::raw_values`] is set, or for empty
+    /// values when [`ParseOptions::empty_values`] is.
+    Raw {
+        kind: KeywordKind,
+        value: String,
+    },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl Keyword {
+    /// The canonical name of every keyword, sorted. Aliases such as `md5`
+    /// are listed in [`KeywordInfo::aliases`].
+    pub const ALL_NAMES: &'static [&'static str] = &[
+        #[cfg(feature = "blake3")]
+        "blake3digest",
+        "cksum",
+        "contents",
+        "device",
+        "entrydigest",
+        "flags",
+        "gid",
+        "gname",
+        "hardlink",
+        "ignore",
+        "inode",
+        "link",
+        "md5digest",
+        "mode",
+        "nlink",
+        "nochange",
+        "optional",
+        "resdevice",
+        "rmd160digest",
+        "selinux",
+        "sha1digest",
+        "sha256digest",
+        "sha384digest",
+        "sha512digest",
+        "size",
+        "tags",
+        "tar_time",
+        "time",
+        "type",
+        "uid",
+        "uname",
+        "xattr",
+        #[cfg(feature = "xxh3")]
+        "xxh3digest",
+    ];
+
+    /// The canonical `key=value` text of the keyword, as written in specs.
+    /// The same as its `Display` output.
+    pub fn to_spec_string(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn kind(&self) -> KeywordKind {
+        match self {
+            Keyword::Type(_) => KeywordKind::Type,
+            Keyword::Uid(_) => KeywordKind::Uid,
+            Keyword::Gid(_) => KeywordKind::Gid,
+            Keyword::Uname(_) => KeywordKind::Uname,
+            Keyword::Gname(_) => KeywordKind::Gname,
+            Keyword::Mode(_) => KeywordKind::Mode,
+            Keyword::Flags(_) => KeywordKind::Flags,
+            Keyword::Nlink(_) => KeywordKind::Nlink,
+            Keyword::Inode(_) => KeywordKind::Inode,
+            Keyword::Device(_) => KeywordKind::Device,
+            Keyword::ResDevice(_) => KeywordKind::ResDevice,
+            Keyword::Time(_) => KeywordKind::Time,
+            Keyword::TarTime(_) => KeywordKind::TarTime,
+            Keyword::Size(_) => KeywordKind::Size,
+            Keyword::Cksum(_) => KeywordKind::Cksum,
+            Keyword::Digest(digest) => KeywordKind::Digest(digest.kind()),
+            Keyword::Link(_) => KeywordKind::Link,
+            Keyword::Contents(_) => KeywordKind::Contents,
+            Keyword::HardLink(_) => KeywordKind::HardLink,
+            Keyword::Optional => KeywordKind::Optional,
+            Keyword::Ignore => KeywordKind::Ignore,
+            Keyword::NoChange => KeywordKind::NoChange,
+            Keyword::Tags(_) => KeywordKind::Tags,
+            Keyword::EntryDigest(_) => KeywordKind::EntryDigest,
+            Keyword::Selinux(_) => KeywordKind::Selinux,
+            Keyword::Xattr { .. } => KeywordKind::Xattr,
+            Keyword::Raw { kind, .. } => *kind,
+        }
+    }
+
+    /// Whether `self` and `other` set the same property of a file, so that
+    /// a line with both has a duplicate. Digests of different kinds and
+    /// extended attributes with different names are different properties.
+    pub fn same_property(&self, other: &Keyword) -> bool {
+        match (self, other) {
+            (Keyword::Xattr { name: a, .. }, Keyword::Xattr { name: b, .. }) => a == b,
+            _ => self.kind() == other.kind(),
+        }
+    }
+}
+
+impl Ord for Keyword {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.kind()
+            .cmp(&other.kind())
+            .then_with(|| match (self, other) {
+                (Keyword::Type(a), Keyword::Type(b)) => a.cmp(b),
+                (Keyword::Uid(a), Keyword::Uid(b)) | (Keyword::Gid(a), Keyword::Gid(b)) => a.cmp(b),
+                (Keyword::Uname(a), Keyword::Uname(b)) | (Keyword::Gname(a), Keyword::Gname(b)) => {
+                    a.cmp(b)
+                }
+                (Keyword::Mode(a), Keyword::Mode(b)) => a.cmp(b),
+                (Keyword::Flags(a), Keyword::Flags(b)) => a.cmp(b),
+                (Keyword::Nlink(a), Keyword::Nlink(b))
+                | (Keyword::Inode(a), Keyword::Inode(b))
+                | (Keyword::Size(a), Keyword::Size(b)) => a.cmp(b),
+                (Keyword::Device(a), Keyword::Device(b))
+                | (Keyword::ResDevice(a), Keyword::ResDevice(b)) => a.cmp(b),
+                (Keyword::Time(a), Keyword::Time(b))
+                | (Keyword::TarTime(a), Keyword::TarTime(b)) => a.cmp(b),
+                (Keyword::Cksum(a), Keyword::Cksum(b)) => a.cmp(b),
+                (Keyword::Digest(a), Keyword::Digest(b))
+                | (Keyword::EntryDigest(a), Keyword::EntryDigest(b)) => a.cmp(b),
+                (Keyword::Link(a), Keyword::Link(b))
+                | (Keyword::Contents(a), Keyword::Contents(b))
+                | (Keyword::HardLink(a), Keyword::HardLink(b)) => a.cmp(b),
+                (Keyword::Tags(a), Keyword::Tags(b)) => a.cmp(b),
+                (Keyword::Selinux(a), Keyword::Selinux(b)) => a.cmp(b),
+                (
+                    Keyword::Xattr { name, value },
+                    Keyword::Xattr {
+                        name: other_name,
+                        value: other_value,
+                    },
+                ) => (name, value).cmp(&(other_name, other_value)),
+                (Keyword::Raw { value: a, .. }, Keyword::Raw { value: b, .. }) => a.cmp(b),
+                (Keyword::Raw { .. }, _) => Ordering::Greater,
+                (_, Keyword::Raw { .. }) => Ordering::Less,
+                // The remaining keywords of a kind have no value.
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl PartialOrd for Keyword {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeywordKind {
+    Type,
+    Uid,
+    Gid,
+    Uname,
+    Gname,
+    Mode,
+    Flags,
+    Nlink,
+    Inode,
+    Device,
+    ResDevice,
+    Time,
+    TarTime,
+    Size,
+    Cksum,
+    Digest(DigestKind),
+    Link,
+    Contents,
+    HardLink,
+    Optional,
+    Ignore,
+    NoChange,
+    Tags,
+    EntryDigest,
+    Selinux,
+    Xattr,
+}
+
+impl KeywordKind {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            KeywordKind::Type => "type",
+            KeywordKind::Uid => "uid",
+            KeywordKind::Gid => "gid",
+            KeywordKind::Uname => "uname",
+            KeywordKind::Gname => "gname",
+            KeywordKind::Mode => "mode",
+            KeywordKind::Flags => "flags",
+            KeywordKind::Nlink => "nlink",
+            KeywordKind::Inode => "inode",
+            KeywordKind::Device => "device",
+            KeywordKind::ResDevice => "resdevice",
+            KeywordKind::Time => "time",
+            KeywordKind::TarTime => "tar_time",
+            KeywordKind::Size => "size",
+            KeywordKind::Cksum => "cksum",
+            KeywordKind::Digest(kind) => kind.name(),
+            KeywordKind::Link => "link",
+            KeywordKind::Contents => "contents",
+            KeywordKind::HardLink => "hardlink",
+            KeywordKind::Optional => "optional",
+            KeywordKind::Ignore => "ignore",
+            KeywordKind::NoChange => "nochange",
+            KeywordKind::Tags => "tags",
+            KeywordKind::EntryDigest => "entrydigest",
+            KeywordKind::Selinux => "selinux",
+            KeywordKind::Xattr => "xattr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "type" => Some(KeywordKind::Type),
+            "uid" => Some(KeywordKind::Uid),
+            "gid" => Some(KeywordKind::Gid),
+            "uname" => Some(KeywordKind::Uname),
+            "gname" => Some(KeywordKind::Gname),
+            "mode" => Some(KeywordKind::Mode),
+            "flags" => Some(KeywordKind::Flags),
+            "nlink" => Some(KeywordKind::Nlink),
+            "inode" => Some(KeywordKind::Inode),
+            "device" => Some(KeywordKind::Device),
+            "resdevice" => Some(KeywordKind::ResDevice),
+            "time" => Some(KeywordKind::Time),
+            "tar_time" => Some(KeywordKind::TarTime),
+            "size" => Some(KeywordKind::Size),
+            "cksum" => Some(KeywordKind::Cksum),
+            "link" => Some(KeywordKind::Link),
+            "contents" => Some(KeywordKind::Contents),
+            "hardlink" => Some(KeywordKind::HardLink),
+            "optional" => Some(KeywordKind::Optional),
+            "ignore" => Some(KeywordKind::Ignore),
+            "nochange" => Some(KeywordKind::NoChange),
+            "tags" => Some(KeywordKind::Tags),
+            "entrydigest" => Some(KeywordKind::EntryDigest),
+            "selinux" => Some(KeywordKind::Selinux),
+            "xattr" => Some(KeywordKind::Xattr),
+            _ => DigestKind::from_name(name).map(KeywordKind::Digest),
+        }
+    }
+}
+
+/// File types order as they are listed, which is alphabetical.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Block,
     Char,
@@ -30,10 +474,157 @@ pub enum Type {
     Socket,
 }
 
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", escape::encode_path(&self.path))?;
+
+        for keyword in &self.keywords {
+            write!(f, " {keyword}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Keyword::Type(ty) => write!(f, "type={ty}"),
+            Keyword::Uid(uid) => write!(f, "uid={uid}"),
+            Keyword::Gid(gid) => write!(f, "gid={gid}"),
+            Keyword::Uname(name) => write!(f, "uname={}", escape::encode_str(name)),
+            Keyword::Gname(name) => write!(f, "gname={}", escape::encode_str(name)),
+            Keyword::Mode(mode) => write!(f, "mode={mode}"),
+            Keyword::Flags(flags) => write!(f, "flags={flags}"),
+            Keyword::Nlink(nlink) => write!(f, "nlink={nlink}"),
+            Keyword::Inode(inode) => write!(f, "inode={inode}"),
+            Keyword::Device(device) => write!(f, "device={device}"),
+            Keyword::ResDevice(device) => write!(f, "resdevice={device}"),
+            Keyword::Time(time) => write!(f, "time={time}"),
+            Keyword::TarTime(time) => write!(f, "tar_time={time}"),
+            Keyword::Size(size) => write!(f, "size={size}"),
+            Keyword::Cksum(cksum) => write!(f, "cksum={cksum}"),
+            Keyword::Digest(digest) => write!(f, "{}={digest}", digest.kind()),
+            Keyword::Link(path) => write!(f, "link={}", escape::encode_path(path)),
+            Keyword::Contents(path) => write!(f, "contents={}", escape::encode_path(path)),
+            Keyword::HardLink(path) => write!(f, "hardlink={}", escape::encode_path(path)),
+            Keyword::Optional => f.write_str("optional"),
+            Keyword::Ignore => f.write_str("ignore"),
+            Keyword::NoChange => f.write_str("nochange"),
+            Keyword::Tags(tags) => {
+                let tags: Vec<String> = tags.iter().map(|tag| escape::encode_str(tag)).collect();
+                write!(f, "tags={}", tags.join(","))
+            }
+            Keyword::EntryDigest(digest) => write!(f, "entrydigest={digest}"),
+            Keyword::Selinux(label) => write!(f, "selinux={}", escape::encode_str(label)),
+            Keyword::Xattr { name, value } => {
+                write!(f, "xattr.{name}={}", base64::encode(value))
+            }
+            Keyword::Raw { kind, value } => write!(f, "{}={value}", kind.name()),
+        }
+    }
+}
+
+impl Type {
+    /// The name of every file type, sorted.
+    pub const ALL_NAMES: &'static [&'static str] =
+        &["block", "char", "dir", "fifo", "file", "link", "socket"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Block => "block",
+            Type::Char => "char",
+            Type::Dir => "dir",
+            Type::Fifo => "fifo",
+            Type::File => "file",
+            Type::Link => "link",
+            Type::Socket => "socket",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "block" => Some(Type::Block),
+            "char" => Some(Type::Char),
+            "dir" => Some(Type::Dir),
+            "fifo" => Some(Type::Fifo),
+            "file" => Some(Type::File),
+            "link" => Some(Type::Link),
+            "socket" => Some(Type::Socket),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownType(pub String);
+
+impl fmt::Display for UnknownType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown type: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownType {}
+
+impl FromStr for Type {
+    type Err = UnknownType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Type::from_name(s).ok_or_else(|| UnknownType(s.to_string()))
+    }
+}
+
+/// Parses a single keyword in its `key=value` form, such as `mode=0644`.
+impl FromStr for Keyword {
+    type Err = InvalidKeyword;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_keyword()
+            .parse(s)
+            .into_result()
+            .map_err(|_| InvalidKeyword::classify(s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     Set(Vec<Keyword>),
-    Unset,
+    Unset(UnsetTarget),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnsetTarget {
+    All,
+    Keywords(Vec<String>),
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Set(keywords) => {
+                f.write_str("/set")?;
+
+                for keyword in keywords {
+                    write!(f, " {keyword}")?;
+                }
+
+                Ok(())
+            }
+            Command::Unset(UnsetTarget::All) => f.write_str("/unset all"),
+            Command::Unset(UnsetTarget::Keywords(names)) => {
+                write!(f, "/unset {}", names.join(" "))
+            }
+        }
+    }
 }
 
 pub fn parse_type<'src>() -> impl Parser<'src, &'src str, Type> {
@@ -48,35 +639,87 @@ pub fn parse_type<'src>() -> impl Parser<'src, &'src str, Type> {
     ))
 }
 
-pub fn parse_timestamp<'src>() -> impl Parser<'src, &'src str, DateTime<Utc>> {
-    // TODO: do we reeeally need to handle negatives?
-    let number_i64 = text::int::<_, extra::Err<EmptyErr>>(10)
+pub fn parse_timestamp<'src>() -> impl Parser<'src, &'src str, Timestamp> {
+    timestamp(false)
+}
+
+/// Seconds since the epoch, negative for files from before 1970, with an
+/// optional fraction.
+fn timestamp<'src>(saturate: bool) -> impl Parser<'src, &'src str, Timestamp> {
+    // Seconds out of range are `Err` with the timestamp to saturate to.
+    let secs = just::<_, _, extra::Err<EmptyErr>>('-')
+        .or_not()
+        .then(text::int(10))
         .to_slice()
-        .try_map(|s: &str, _| s.parse::<i64>().map_err(|_| EmptyErr::default()));
+        .try_map(move |s: &str, _| match s.parse::<i64>() {
+            Ok(secs) => Ok(Ok(secs)),
+            Err(_) if saturate && s.starts_with('-') => Ok(Err(Timestamp::MIN)),
+            Err(_) if saturate => Ok(Err(Timestamp::MAX)),
+            Err(_) => Err(EmptyErr::default()),
+        });
 
-    let number_u32 = text::int::<_, extra::Err<EmptyErr>>(10)
+    // The fraction is a count of nanoseconds, as BSD mtree, libarchive and
+    // go-mtree read it, so `.5` is 5 nanoseconds. It's usually zero-padded
+    // to nine digits, which `text::int` rejects.
+    let nanos = text::digits::<_, extra::Err<EmptyErr>>(10)
+        .at_least(1)
         .to_slice()
         .try_map(|s: &str, _| s.parse::<u32>().map_err(|_| EmptyErr::default()));
 
-    number_i64
-        .then_ignore(just('.'))
-        .then(number_u32)
-        .try_map(|(secs, nsecs), _| {
-            DateTime::from_timestamp(secs, nsecs).ok_or(EmptyErr::default())
+    secs.then(just('.').ignore_then(nanos).or_not())
+        .try_map(|(secs, nsecs), _| match (secs, nsecs.unwrap_or(0)) {
+            (_, nsecs) if nsecs >= 1_000_000_000 => Err(EmptyErr::default()),
+            (Ok(secs), nsecs) => Ok(Timestamp::new(secs, nsecs)),
+            (Err(saturated), _) => Ok(saturated),
         })
 }
 
 pub fn parse_path<'src>() -> impl Parser<'src, &'src str, PathBuf> {
-    none_of(" \t") // <-- NOTE: this will backfire
-        .repeated()
-        .at_least(1)
-        .to_slice()
-        .validate(|x: &str, _, _| PathBuf::from(x))
+    value().map(escape::decode_path)
+}
+
+/// A keyword value or path, up to the next space or tab. Whitespace in file
+/// names and other text is always escaped, as `\040` and so on, so this
+/// never stops inside one; the escapes are left for the caller to decode.
+pub(crate) fn value<'src>() -> impl Parser<'src, &'src str, &'src str> + Clone {
+    none_of(" \t").repeated().at_least(1).to_slice()
 }
 
 pub fn parse_keyword<'src>() -> impl Parser<'src, &'src str, Keyword> {
+    keyword(&ParseOptions::default())
+}
+
+/// The keyword parser for `options`.
+pub fn parse_keyword_with<'src>(
+    options: &ParseOptions,
+) -> Boxed<'src, 'src, &'src str, Keyword, extra::Default> {
+    let typed = keyword(options);
+
+    if options.raw_values || options.empty_values {
+        raw_keyword(typed, options.raw_values).boxed()
+    } else {
+        typed.boxed()
+    }
+}
+
+fn keyword<'src>(options: &ParseOptions) -> impl Parser<'src, &'src str, Keyword> + use<'src> {
+    let ParseOptions {
+        saturate_ids,
+        saturate_times,
+        strict_digests,
+        ..
+    } = *options;
+
     let type_value = parse_type();
 
+    let id = text::int::<_, extra::Err<EmptyErr>>(10)
+        .to_slice()
+        .try_map(move |s: &str, _| match s.parse::<u32>() {
+            Ok(id) => Ok(id),
+            Err(_) if saturate_ids => Ok(u32::MAX),
+            Err(_) => Err(EmptyErr::default()),
+        });
+
     let number_u32 = text::int::<_, extra::Err<EmptyErr>>(10)
         .to_slice()
         .try_map(|s: &str, _| s.parse::<u32>().map_err(|_| EmptyErr::default()));
@@ -85,7 +728,48 @@ pub fn parse_keyword<'src>() -> impl Parser<'src, &'src str, Keyword> {
         .to_slice()
         .try_map(|s: &str, _| s.parse::<u64>().map_err(|_| EmptyErr::default()));
 
-    let timestamp = parse_timestamp();
+    let name = value().map(|s: &str| Arc::<str>::from(escape::decode_str(s)));
+
+    let mode = value().try_map(|s: &str, _| s.parse::<Mode>().map_err(|_| EmptyErr::default()));
+
+    let flags = value().try_map(|s: &str, _| s.parse::<Flags>().map_err(|_| EmptyErr::default()));
+
+    let device = value().try_map(|s: &str, _| s.parse::<Device>().map_err(|_| EmptyErr::default()));
+
+    let digest_kind = choice((
+        choice((just("md5digest"), just("md5"))).to(DigestKind::Md5),
+        choice((just("sha1digest"), just("sha1"))).to(DigestKind::Sha1),
+        choice((
+            just("rmd160digest"),
+            just("ripemd160digest"),
+            just("rmd160"),
+        ))
+        .to(DigestKind::Rmd160),
+        choice((just("sha256digest"), just("sha256"))).to(DigestKind::Sha256),
+        choice((just("sha384digest"), just("sha384"))).to(DigestKind::Sha384),
+        choice((just("sha512digest"), just("sha512"))).to(DigestKind::Sha512),
+    ));
+    #[cfg(feature = "blake3")]
+    let digest_kind =
+        digest_kind.or(choice((just("blake3digest"), just("blake3"))).to(DigestKind::Blake3));
+    #[cfg(feature = "xxh3")]
+    let digest_kind =
+        digest_kind.or(choice((just("xxh3digest"), just("xxh3"))).to(DigestKind::Xxh3));
+
+    let digest_value = any()
+        .filter(move |c: &char| match strict_digests {
+            true => c.is_ascii_digit() || ('a'..='f').contains(c),
+            false => c.is_ascii_hexdigit(),
+        })
+        .repeated()
+        .at_least(1)
+        .to_slice();
+
+    let tag = none_of(" \t,")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .map(escape::decode_str);
 
     let path = parse_path();
 
@@ -96,24 +780,136 @@ pub fn parse_keyword<'src>() -> impl Parser<'src, &'src str, Keyword> {
             .map(|ty| Keyword::Type(ty)),
         just("uid")
             .ignore_then(just("="))
-            .ignore_then(number_u32)
+            .ignore_then(id.clone())
             .map(|uid| Keyword::Uid(uid)),
+        just("gid")
+            .ignore_then(just("="))
+            .ignore_then(id)
+            .map(Keyword::Gid),
+        just("uname")
+            .ignore_then(just("="))
+            .ignore_then(name.clone())
+            .map(Keyword::Uname),
+        just("gname")
+            .ignore_then(just("="))
+            .ignore_then(name)
+            .map(Keyword::Gname),
+        just("mode")
+            .ignore_then(just("="))
+            .ignore_then(mode)
+            .map(Keyword::Mode),
+        just("flags")
+            .ignore_then(just("="))
+            .ignore_then(flags)
+            .map(Keyword::Flags),
         just("time")
             .ignore_then(just("="))
-            .ignore_then(timestamp)
+            .ignore_then(timestamp(saturate_times))
             .map(|time| Keyword::Time(time)),
+        just("tar_time")
+            .ignore_then(just("="))
+            .ignore_then(timestamp(saturate_times))
+            .map(Keyword::TarTime),
         just("size")
             .ignore_then(just("="))
-            .ignore_then(number_u64)
+            .ignore_then(number_u64.clone())
             .map(|size| Keyword::Size(size)),
-        choice((just("sha256digest"), just("sha256")))
+        just("nlink")
+            .ignore_then(just("="))
+            .ignore_then(number_u64.clone())
+            .map(Keyword::Nlink),
+        just("inode")
             .ignore_then(just("="))
-            .ignore_then(text::ident())
-            .map(|sha256: &str| Keyword::Sha256(sha256.to_string())),
+            .ignore_then(number_u64)
+            .map(Keyword::Inode),
+        just("device")
+            .ignore_then(just("="))
+            .ignore_then(device.clone())
+            .map(Keyword::Device),
+        just("resdevice")
+            .ignore_then(just("="))
+            .ignore_then(device)
+            .map(Keyword::ResDevice),
+        just("cksum")
+            .ignore_then(just("="))
+            .ignore_then(number_u32)
+            .map(Keyword::Cksum),
+        digest_kind
+            .then_ignore(just("="))
+            .then(digest_value.clone())
+            .try_map(|(kind, hex): (DigestKind, &str), _| {
+                Digest::from_hex(kind, hex)
+                    .map(Keyword::Digest)
+                    .map_err(|_| EmptyErr::default())
+            }),
         just("link")
             .ignore_then(just("="))
             .ignore_then(path)
             .map(|path: PathBuf| Keyword::Link(path)),
+        just("contents")
+            .ignore_then(just("="))
+            .ignore_then(parse_path())
+            .map(Keyword::Contents),
+        just("hardlink")
+            .ignore_then(just("="))
+            .ignore_then(parse_path())
+            .map(Keyword::HardLink),
+        just("optional").to(Keyword::Optional),
+        just("ignore").to(Keyword::Ignore),
+        just("nochange").to(Keyword::NoChange),
+        just("tags")
+            .ignore_then(just("="))
+            .ignore_then(tag.separated_by(just(',')).at_least(1).collect())
+            .map(Keyword::Tags),
+        just("entrydigest")
+            .ignore_then(just("="))
+            .ignore_then(digest_value)
+            .try_map(|hex: &str, _| {
+                Digest::from_hex(DigestKind::Sha256, hex)
+                    .map(Keyword::EntryDigest)
+                    .map_err(|_| EmptyErr::default())
+            }),
+        just("selinux")
+            .ignore_then(just("="))
+            .ignore_then(value())
+            .map(|label: &str| Keyword::Selinux(escape::decode_str(label))),
+        just("xattr.")
+            .ignore_then(none_of(" \t=").repeated().at_least(1).to_slice())
+            .then_ignore(just("="))
+            .then(none_of(" \t").repeated().to_slice())
+            .try_map(|(name, value): (&str, &str), _| {
+                let value = base64::decode(value).ok_or(EmptyErr::default())?;
+                Ok(Keyword::Xattr {
+                    name: name.to_string(),
+                    value,
+                })
+            }),
+    ))
+}
+
+/// Falls back to [`Keyword::Raw`] for a known keyword whose value `typed`
+/// doesn't accept: any value if `any_value`, otherwise only an empty one.
+fn raw_keyword<'src>(
+    typed: impl Parser<'src, &'src str, Keyword>,
+    any_value: bool,
+) -> impl Parser<'src, &'src str, Keyword> {
+    let kind = none_of(" \t=")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .try_map(|name: &str, _| KeywordKind::from_name(name).ok_or(EmptyErr::default()));
+
+    let value = none_of(" \t")
+        .repeated()
+        .to_slice()
+        .filter(move |value: &&str| any_value || value.is_empty())
+        .map(|value: &str| value.to_string());
+
+    choice((
+        typed.then_ignore(word_end()),
+        kind.then_ignore(just('='))
+            .then(value)
+            .map(|(kind, value)| Keyword::Raw { kind, value }),
     ))
 }
 
@@ -121,11 +917,101 @@ pub fn parse_keywords<'src>() -> impl Parser<'src, &'src str, Vec<Keyword>> {
     parse_keyword().separated_by(text::whitespace()).collect()
 }
 
+pub fn parse_keywords_with<'src>(
+    options: &ParseOptions,
+) -> impl Parser<'src, &'src str, Vec<Keyword>> {
+    let reject_duplicates = options.reject_duplicates;
+
+    let keyword = if options.ignore_unknown_keywords {
+        let unknown = none_of(" \t")
+            .repeated()
+            .at_least(1)
+            .to_slice()
+            .filter(|word: &&str| is_unknown_keyword(word))
+            .to(None);
+
+        choice((
+            parse_keyword_with(options)
+                .then_ignore(word_end())
+                .map(Some),
+            unknown,
+        ))
+        .boxed()
+    } else {
+        parse_keyword_with(options).map(Some).boxed()
+    };
+
+    keyword
+        .separated_by(text::whitespace())
+        .collect::<Vec<_>>()
+        .try_map(move |keywords, _| {
+            let keywords: Vec<Keyword> = keywords.into_iter().flatten().collect();
+            match reject_duplicates && first_duplicate(&keywords).is_some() {
+                true => Err(EmptyErr::default()),
+                false => Ok(keywords),
+            }
+        })
+}
+
+/// A keyword must take up the whole word, or `size=1x` would be read as
+/// `size=1` followed by garbage.
+fn word_end<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
+    one_of(" \t").ignored().or(end()).rewind()
+}
+
+/// Whether `word` names a keyword this crate doesn't know, which
+/// [`ParseOptions::ignore_unknown_keywords`] skips.
+pub(crate) fn is_unknown_keyword(word: &str) -> bool {
+    let name = word.split_once('=').map_or(word, |(name, _)| name);
+    !name.starts_with("xattr.") && KeywordKind::from_name(name).is_none()
+}
+
+/// The index of the first keyword that repeats an earlier one, see
+/// [`Keyword::same_property`].
+pub(crate) fn first_duplicate(keywords: &[Keyword]) -> Option<usize> {
+    (1..keywords.len()).find(|&idx| {
+        keywords[..idx]
+            .iter()
+            .any(|earlier| earlier.same_property(&keywords[idx]))
+    })
+}
+
 pub fn parse_command<'src>() -> impl Parser<'src, &'src str, Command> {
-    let unset = just("unset").to(Command::Unset);
+    command(parse_keywords())
+}
+
+pub fn parse_command_with<'src>(options: &ParseOptions) -> impl Parser<'src, &'src str, Command> {
+    command(parse_keywords_with(options))
+}
+
+fn command<'src>(
+    keywords: impl Parser<'src, &'src str, Vec<Keyword>>,
+) -> impl Parser<'src, &'src str, Command> {
+    let keyword_name = none_of(" \t")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .map(|name: &str| name.to_string());
+
+    let unset = just("unset")
+        .ignore_then(text::whitespace().at_least(1))
+        .ignore_then(
+            keyword_name
+                .separated_by(text::whitespace().at_least(1))
+                .at_least(1)
+                .collect::<Vec<String>>(),
+        )
+        .map(|names| {
+            if names.iter().any(|name| name == "all") {
+                UnsetTarget::All
+            } else {
+                UnsetTarget::Keywords(names)
+            }
+        })
+        .map(Command::Unset);
     let set = just("set")
         .ignore_then(text::whitespace())
-        .ignore_then(parse_keywords())
+        .ignore_then(keywords)
         .map(Command::Set);
 
     just('/')
@@ -134,8 +1020,17 @@ pub fn parse_command<'src>() -> impl Parser<'src, &'src str, Command> {
 }
 
 pub fn parse_entry<'src>() -> impl Parser<'src, &'src str, Entry> {
+    entry(parse_keywords())
+}
+
+pub fn parse_entry_with<'src>(options: &ParseOptions) -> impl Parser<'src, &'src str, Entry> {
+    entry(parse_keywords_with(options))
+}
+
+fn entry<'src>(
+    keywords: impl Parser<'src, &'src str, Vec<Keyword>>,
+) -> impl Parser<'src, &'src str, Entry> {
     let path = parse_path();
-    let keywords = parse_keywords();
 
     path.padded()
         .then(keywords)
@@ -157,17 +1052,80 @@ mod tests {
         assert_eq!(parse_type().parse("socket").into_result(), Ok(Type::Socket));
     }
 
+    #[test]
+    fn test_all_names() {
+        assert!(Keyword::ALL_NAMES.is_sorted());
+        assert_eq!(Keyword::ALL_NAMES.len(), keyword_info::KEYWORDS.len());
+
+        for name in Keyword::ALL_NAMES {
+            let kind = KeywordKind::from_name(name).unwrap();
+            assert_eq!(kind.name(), *name);
+        }
+
+        assert!(Type::ALL_NAMES.is_sorted());
+        for name in Type::ALL_NAMES {
+            let ty = Type::from_name(name).unwrap();
+            assert_eq!(parse_type().parse(name).into_result(), Ok(ty.clone()));
+            assert_eq!(ty.name(), *name);
+        }
+        assert_eq!(Type::from_name("directory"), None);
+    }
+
     #[test]
     fn test_parse_timestamp() {
         assert_eq!(
             parse_timestamp().parse("1630456800.0").into_result(),
-            Ok(DateTime::from_timestamp(1630456800, 0).unwrap())
+            Ok(Timestamp::new(1630456800, 0))
         );
         assert_eq!(
             parse_timestamp()
                 .parse("1769640177.434772208")
                 .into_result(),
-            Ok(DateTime::from_timestamp(1769640177, 434772208).unwrap())
+            Ok(Timestamp::new(1769640177, 434772208))
+        );
+        assert_eq!(
+            parse_timestamp()
+                .parse("1769203027.000000005")
+                .into_result(),
+            Ok(Timestamp::new(1769203027, 5))
+        );
+        assert_eq!(
+            parse_timestamp().parse("1769203027.5").into_result(),
+            Ok(Timestamp::new(1769203027, 5))
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp_edge_cases() {
+        assert_eq!(
+            parse_timestamp().parse("1630456800").into_result(),
+            Ok(Timestamp::new(1630456800, 0))
+        );
+        assert_eq!(
+            parse_timestamp().parse("-86400.000000000").into_result(),
+            Ok(Timestamp::new(-86400, 0))
+        );
+        assert_eq!(
+            parse_timestamp().parse("-1").into_result(),
+            Ok(Timestamp::new(-1, 0))
+        );
+        assert!(
+            parse_timestamp()
+                .parse("1630456800.")
+                .into_result()
+                .is_err()
+        );
+        assert!(parse_timestamp().parse("--1").into_result().is_err());
+
+        let options = ParseOptions {
+            saturate_times: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_keyword_with(&options)
+                .parse("time=-99999999999999999999.0")
+                .into_result(),
+            Ok(Keyword::Time(Timestamp::MIN))
         );
     }
 
@@ -181,6 +1139,10 @@ mod tests {
             parse_path().parse("../../foo.bar").into_result(),
             Ok(PathBuf::from("../../foo.bar"))
         );
+        assert_eq!(
+            parse_path().parse("foo\\040bar").into_result(),
+            Ok(PathBuf::from("foo bar"))
+        );
     }
 
     #[test]
@@ -231,21 +1193,124 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_ownership_keywords() {
+        assert_eq!(
+            parse_keyword().parse("gid=0").into_result(),
+            Ok(Keyword::Gid(0))
+        );
+        assert_eq!(
+            parse_keyword().parse("gid=1000").into_result(),
+            Ok(Keyword::Gid(1000))
+        );
+        assert_eq!(
+            parse_keyword().parse("uname=root").into_result(),
+            Ok(Keyword::Uname("root".into()))
+        );
+        assert_eq!(
+            parse_keyword().parse("gname=wheel").into_result(),
+            Ok(Keyword::Gname("wheel".into()))
+        );
+        assert_eq!(
+            parse_keywords()
+                .parse("uid=0 uname=root gid=0 gname=wheel")
+                .into_result(),
+            Ok(vec![
+                Keyword::Uid(0),
+                Keyword::Uname("root".into()),
+                Keyword::Gid(0),
+                Keyword::Gname("wheel".into())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_mode_keyword() {
+        assert_eq!(
+            parse_keyword().parse("mode=0755").into_result(),
+            Ok(Keyword::Mode(Mode::new(0o755).unwrap()))
+        );
+        assert_eq!(
+            parse_keyword().parse("mode=644").into_result(),
+            Ok(Keyword::Mode(Mode::new(0o644).unwrap()))
+        );
+        assert_eq!(
+            parse_keyword().parse("mode=u=rwx,g=rx,o=rx").into_result(),
+            Ok(Keyword::Mode(Mode::new(0o755).unwrap()))
+        );
+        assert!(parse_keyword().parse("mode=0999").into_result().is_err());
+    }
+
+    #[test]
+    fn test_parse_flags_keyword() {
+        assert_eq!(
+            parse_keyword().parse("flags=none").into_result(),
+            Ok(Keyword::Flags(Flags::none()))
+        );
+        assert_eq!(
+            parse_keyword().parse("flags=uchg,schg").into_result(),
+            Ok(Keyword::Flags(Flags::from_iter([
+                FileFlag::Uchg,
+                FileFlag::Schg
+            ])))
+        );
+    }
+
+    #[test]
+    fn test_parse_inode_keywords() {
+        assert_eq!(
+            parse_keyword().parse("nlink=2").into_result(),
+            Ok(Keyword::Nlink(2))
+        );
+        assert_eq!(
+            parse_keyword().parse("inode=1234567").into_result(),
+            Ok(Keyword::Inode(1234567))
+        );
+        assert_eq!(
+            parse_keyword().parse("resdevice=66306").into_result(),
+            Ok(Keyword::ResDevice(Device::Number(66306)))
+        );
+    }
+
+    #[test]
+    fn test_parse_device_keyword() {
+        assert_eq!(
+            parse_keyword().parse("device=native,8,0").into_result(),
+            Ok(Keyword::Device(Device::Parts {
+                format: "native".to_string(),
+                major: 8,
+                minor: 0,
+                subunit: None
+            }))
+        );
+        assert_eq!(
+            parse_keywords()
+                .parse("type=block device=native,8,1 mode=0660")
+                .into_result(),
+            Ok(vec![
+                Keyword::Type(Type::Block),
+                Keyword::Device(Device::Parts {
+                    format: "native".to_string(),
+                    major: 8,
+                    minor: 1,
+                    subunit: None
+                }),
+                Keyword::Mode(Mode::new(0o660).unwrap())
+            ])
+        );
+    }
+
     #[test]
     fn test_parse_timestamp_keyword() {
         assert_eq!(
             parse_keyword().parse("time=1630456800.0").into_result(),
-            Ok(Keyword::Time(
-                DateTime::from_timestamp(1630456800, 0).unwrap()
-            ))
+            Ok(Keyword::Time(Timestamp::new(1630456800, 0)))
         );
         assert_eq!(
             parse_keyword()
                 .parse("time=1769640177.434772208")
                 .into_result(),
-            Ok(Keyword::Time(
-                DateTime::from_timestamp(1769640177, 434772208).unwrap()
-            ))
+            Ok(Keyword::Time(Timestamp::new(1769640177, 434772208)))
         );
     }
 
@@ -265,14 +1330,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_str() {
+        assert_eq!("socket".parse(), Ok(Type::Socket));
+        assert_eq!("sock".parse::<Type>(), Err(UnknownType("sock".to_string())));
+
+        let keyword: Keyword = "mode=0644".parse().unwrap();
+        assert_eq!(keyword, Keyword::Mode("0644".parse().unwrap()));
+        assert_eq!(keyword.to_spec_string(), "mode=0644");
+        assert!("mode=0644 uid=0".parse::<Keyword>().is_err());
+        assert!("mode=".parse::<Keyword>().is_err());
+    }
+
+    #[test]
+    fn test_parse_cksum_keyword() {
+        assert_eq!(
+            parse_keyword().parse("cksum=930766865").into_result(),
+            Ok(Keyword::Cksum(930766865))
+        );
+        assert!(
+            parse_keyword()
+                .parse("cksum=4294967296")
+                .into_result()
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_parse_sha256_keyword() {
         assert_eq!(
             parse_keyword()
                 .parse("sha256=fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249")
                 .into_result(),
-            Ok(Keyword::Sha256(
-                "fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249".to_string()
+            Ok(Keyword::Digest(
+                Digest::from_hex(
+                    DigestKind::Sha256,
+                    "fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249"
+                )
+                .unwrap()
             ))
         );
         assert_eq!(
@@ -281,10 +1376,69 @@ mod tests {
                     "sha256digest=fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249"
                 )
                 .into_result(),
-            Ok(Keyword::Sha256(
-                "fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249".to_string()
+            Ok(Keyword::Digest(
+                Digest::from_hex(
+                    DigestKind::Sha256,
+                    "fd9849d9364b9b9aabed88a8aa8e007d7450c3ad3a17aee0617dd24959464249"
+                )
+                .unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_digest_keywords() {
+        assert_eq!(
+            parse_keyword()
+                .parse("md5digest=d41d8cd98f00b204e9800998ecf8427e")
+                .into_result(),
+            Ok(Keyword::Digest(
+                Digest::from_hex(DigestKind::Md5, "d41d8cd98f00b204e9800998ecf8427e").unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_keyword()
+                .parse("sha1=da39a3ee5e6b4b0d3255bfef95601890afd80709")
+                .into_result(),
+            Ok(Keyword::Digest(
+                Digest::from_hex(DigestKind::Sha1, "da39a3ee5e6b4b0d3255bfef95601890afd80709")
+                    .unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_keyword()
+                .parse("rmd160digest=9c1185a5c5e9fc54612808977ee8f548b2258d31")
+                .into_result(),
+            Ok(Keyword::Digest(
+                Digest::from_hex(
+                    DigestKind::Rmd160,
+                    "9c1185a5c5e9fc54612808977ee8f548b2258d31"
+                )
+                .unwrap()
             ))
         );
+        assert_eq!(
+            parse_keyword().parse("sha384=38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b").into_result(),
+            Ok(Keyword::Digest(Digest::from_hex(DigestKind::Sha384, "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b").unwrap()))
+        );
+        assert_eq!(
+            parse_keyword().parse("sha512digest=cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e").into_result(),
+            Ok(Keyword::Digest(Digest::from_hex(DigestKind::Sha512, "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e").unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_parse_contents_keyword() {
+        assert_eq!(
+            parse_keyword()
+                .parse("contents=objects/ab/c\\040d")
+                .into_result(),
+            Ok(Keyword::Contents(PathBuf::from("objects/ab/c d")))
+        );
+        assert_eq!(
+            Keyword::Contents(PathBuf::from("objects/ab/c d")).to_string(),
+            "contents=objects/ab/c\\040d"
+        );
     }
 
     #[test]
@@ -303,6 +1457,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_escaped_values() {
+        assert_eq!(
+            parse_entry()
+                .parse("./a\\040b link=foo\\040bar mode=0644 uname=J\\303\\274rgen\\040K tags=x\\040y,z")
+                .into_result(),
+            Ok(Entry {
+                path: PathBuf::from("./a b"),
+                keywords: vec![
+                    Keyword::Link(PathBuf::from("foo bar")),
+                    Keyword::Mode("0644".parse().unwrap()),
+                    Keyword::Uname("Jürgen K".into()),
+                    Keyword::Tags(vec!["x y".to_string(), "z".to_string()]),
+                ]
+            })
+        );
+
+        for keyword in [
+            Keyword::Uname("Jürgen K".into()),
+            Keyword::Gname("domain users".into()),
+            Keyword::Tags(vec!["x y".to_string(), "a\\b".to_string()]),
+        ] {
+            assert_eq!(keyword.to_string().parse::<Keyword>(), Ok(keyword));
+        }
+    }
+
+    #[test]
+    fn test_parse_verification_keywords() {
+        assert_eq!(
+            parse_keywords()
+                .parse("optional ignore nochange tags=base,doc")
+                .into_result(),
+            Ok(vec![
+                Keyword::Optional,
+                Keyword::Ignore,
+                Keyword::NoChange,
+                Keyword::Tags(vec!["base".to_string(), "doc".to_string()])
+            ])
+        );
+        assert!(parse_keyword().parse("tags=").into_result().is_err());
+        assert_eq!(
+            Keyword::Tags(vec!["base".to_string(), "doc".to_string()]).to_string(),
+            "tags=base,doc"
+        );
+    }
+
     #[test]
     fn test_parse_keywords() {
         assert_eq!(parse_keywords().parse("").into_result(), Ok(vec![]));
@@ -319,7 +1519,7 @@ mod tests {
             Ok(vec![
                 Keyword::Type(Type::Dir),
                 Keyword::Size(384),
-                Keyword::Time(DateTime::from_timestamp(1769640373, 412526597).unwrap())
+                Keyword::Time(Timestamp::new(1769640373, 412526597))
             ])
         );
 
@@ -330,7 +1530,7 @@ mod tests {
             Ok(vec![
                 Keyword::Type(Type::Link),
                 Keyword::Size(24),
-                Keyword::Time(DateTime::from_timestamp(1769203307, 589764008).unwrap())
+                Keyword::Time(Timestamp::new(1769203307, 589764008))
             ])
         );
 
@@ -341,8 +1541,8 @@ mod tests {
                 .into_result(),
             Ok(vec![
                 Keyword::Size(10931),
-                Keyword::Time(DateTime::from_timestamp(1769203027, 452198079).unwrap()),
-                Keyword::Sha256("014bb31e83d5c2e76aea1cc6e82217346ab41362f32cb355ad0f5c10aa0aeaff".to_string())
+                Keyword::Time(Timestamp::new(1769203027, 452198079)),
+                Keyword::Digest(Digest::from_hex(DigestKind::Sha256, "014bb31e83d5c2e76aea1cc6e82217346ab41362f32cb355ad0f5c10aa0aeaff").unwrap())
             ])
         );
     }
@@ -356,14 +1556,25 @@ mod tests {
             Ok(Command::Set(vec![
                 Keyword::Type(Type::Dir),
                 Keyword::Size(384),
-                Keyword::Time(DateTime::from_timestamp(1769640373, 412526597).unwrap())
+                Keyword::Time(Timestamp::new(1769640373, 412526597))
             ]))
         );
 
         assert_eq!(
-            parse_command().parse("/unset").into_result(),
-            Ok(Command::Unset)
+            parse_command().parse("/unset all").into_result(),
+            Ok(Command::Unset(UnsetTarget::All))
+        );
+
+        assert_eq!(
+            parse_command().parse("/unset type uid gid").into_result(),
+            Ok(Command::Unset(UnsetTarget::Keywords(vec![
+                "type".to_string(),
+                "uid".to_string(),
+                "gid".to_string()
+            ])))
         );
+
+        assert!(parse_command().parse("/unset").into_result().is_err());
     }
 
     #[test]
@@ -376,7 +1587,7 @@ mod tests {
                 path: PathBuf::from("LICENSE"),
                 keywords: vec![
                     Keyword::Size(10931),
-                    Keyword::Time(DateTime::from_timestamp(1769203027, 452198079).unwrap())
+                    Keyword::Time(Timestamp::new(1769203027, 452198079))
                 ]
             })
         );
@@ -390,12 +1601,166 @@ mod tests {
                 path: PathBuf::from("LICENSE"),
                 keywords: vec![
                     Keyword::Size(10931),
-                    Keyword::Time(DateTime::from_timestamp(1769203027, 452198079).unwrap()),
-                    Keyword::Sha256("014bb31e83d5c2e76aea1cc6e82217346ab41362f32cb355ad0f5c10aa0aeaff".to_string())
+                    Keyword::Time(Timestamp::new(1769203027, 452198079)),
+                    Keyword::Digest(Digest::from_hex(DigestKind::Sha256, "014bb31e83d5c2e76aea1cc6e82217346ab41362f32cb355ad0f5c10aa0aeaff").unwrap())
                 ]
             })
         );
     }
+
+    #[test]
+    fn test_parse_raw_values() {
+        let options = ParseOptions {
+            raw_values: true,
+            ..ParseOptions::default()
+        };
+
+        assert!(
+            parse_entry()
+                .parse("foo uid=4294967296")
+                .into_result()
+                .is_err()
+        );
+        assert_eq!(
+            parse_entry_with(&options)
+                .parse("foo uid=4294967296 size=1x mode=0644 bogus=1")
+                .into_result(),
+            Err(vec![EmptyErr::default()])
+        );
+        assert_eq!(
+            parse_entry_with(&options)
+                .parse("foo uid=4294967296 size=1x mode=0644")
+                .into_result(),
+            Ok(Entry {
+                path: PathBuf::from("foo"),
+                keywords: vec![
+                    Keyword::Raw {
+                        kind: KeywordKind::Uid,
+                        value: "4294967296".to_string()
+                    },
+                    Keyword::Raw {
+                        kind: KeywordKind::Size,
+                        value: "1x".to_string()
+                    },
+                    Keyword::Mode(Mode::new(0o644).unwrap()),
+                ]
+            })
+        );
+
+        let spec = Spec::parse_with("/set gid=-1\nfoo\n", &options).unwrap();
+        assert_eq!(spec.to_string(), "/set gid=-1\nfoo\n");
+        assert!(Spec::parse("/set gid=-1\n").is_err());
+    }
+
+    #[test]
+    fn test_go_mtree() {
+        let input = include_str!("../fixtures/go-mtree.mtree");
+        let spec = Spec::parse(input).unwrap();
+        assert!(roundtrip::check(input).is_lossless());
+
+        let entries = spec.resolve();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>(),
+            [
+                ".",
+                "./bin",
+                "./bin/ping",
+                "./bin/sh",
+                "./etc",
+                "./etc/hostname"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let ping = &entries[2];
+        assert_eq!(
+            ping.get(KeywordKind::TarTime),
+            Some(&Keyword::TarTime(Timestamp::new(1700000000, 0)))
+        );
+        assert_eq!(
+            ping.get(KeywordKind::Xattr),
+            Some(&Keyword::Xattr {
+                name: "security.capability".to_string(),
+                value: vec![1, 0, 0, 2, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            })
+        );
+
+        let xattrs: Vec<_> = entries[5]
+            .keywords
+            .iter()
+            .filter(|keyword| keyword.kind() == KeywordKind::Xattr)
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(xattrs, ["xattr.user.origin=YnVpbGQ=", "xattr.user.note="]);
+
+        assert!(
+            parse_keyword()
+                .parse("xattr.user.x=Zm9")
+                .into_result()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_display_entry() {
+        assert_eq!(
+            Entry {
+                path: PathBuf::from("LICENSE"),
+                keywords: vec![
+                    Keyword::Type(Type::File),
+                    Keyword::Size(10931),
+                    Keyword::Time(Timestamp::new(1769203027, 452198079))
+                ]
+            }
+            .to_string(),
+            "LICENSE type=file size=10931 time=1769203027.452198079"
+        );
+    }
+
+    #[test]
+    fn test_keyword_order() {
+        let mut keywords = vec![
+            Keyword::Size(10),
+            Keyword::Raw {
+                kind: KeywordKind::Size,
+                value: "x".into(),
+            },
+            Keyword::Size(9),
+            Keyword::Optional,
+            Keyword::Xattr {
+                name: "user.b".into(),
+                value: vec![],
+            },
+            Keyword::Xattr {
+                name: "user.a".into(),
+                value: vec![1],
+            },
+            Keyword::Type(Type::File),
+            Keyword::Type(Type::Dir),
+        ];
+        keywords.sort();
+
+        assert_eq!(
+            keywords.iter().map(Keyword::to_string).collect::<Vec<_>>(),
+            [
+                "type=dir",
+                "type=file",
+                "size=9",
+                "size=10",
+                "size=x",
+                "optional",
+                "xattr.user.a=AQ==",
+                "xattr.user.b=",
+            ]
+        );
+
+        let set: std::collections::HashSet<_> = keywords.iter().cloned().collect();
+        assert_eq!(set.len(), keywords.len());
+        assert!(set.contains(&Keyword::Size(9)));
+    }
 }
 
 // // fn parse<'a>() -> impl Parser<'a, &'a str, Vec<Entry>> {
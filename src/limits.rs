@@ -0,0 +1,80 @@
+//! Bounds on what [`SpecReader::with_limits`] reads, for specs from sources
+//! that can't be trusted not to exhaust memory.
+//!
+//! [`SpecReader::with_limits`]: crate::SpecReader::with_limits
+
+use std::fmt;
+
+/// Limits on the input of a [`SpecReader`](crate::SpecReader). `None` means
+/// no limit, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ParseLimits {
+    /// The longest logical line, in bytes, with continuations joined and
+    /// without the line terminator. Longer lines aren't read in full.
+    pub max_line_len: Option<usize>,
+    pub max_entries: Option<u64>,
+    /// The most keywords on one entry or `/set` line.
+    pub max_keywords: Option<usize>,
+    /// The most bytes of input, counted after decompression, so that a
+    /// small gzip-compressed spec can't expand without bound.
+    pub max_input_size: Option<u64>,
+}
+
+impl ParseLimits {
+    /// Limits well beyond the specs of whole systems, which only input
+    /// meant to exhaust memory reaches.
+    pub const fn untrusted() -> Self {
+        ParseLimits {
+            max_line_len: Some(64 * 1024),
+            max_entries: Some(10_000_000),
+            max_keywords: Some(256),
+            max_input_size: Some(4 << 30),
+        }
+    }
+}
+
+/// Which of the [`ParseLimits`] the input went over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// The logical line starting on `line` is too long.
+    LineLength {
+        line: usize,
+        limit: usize,
+    },
+    /// The entry on `line` is one too many.
+    Entries {
+        line: usize,
+        limit: u64,
+    },
+    /// The entry or `/set` line on `line` has too many keywords.
+    Keywords {
+        line: usize,
+        limit: usize,
+    },
+    InputSize {
+        limit: u64,
+    },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::LineLength { line, limit } => {
+                write!(f, "line {line} is longer than {limit} bytes")
+            }
+            LimitExceeded::Entries { line, limit } => {
+                write!(f, "more than {limit} entries, at line {line}")
+            }
+            LimitExceeded::Keywords { line, limit } => {
+                write!(f, "more than {limit} keywords on line {line}")
+            }
+            LimitExceeded::InputSize { limit } => {
+                write!(f, "input is larger than {limit} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
@@ -0,0 +1,625 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use chumsky::prelude::*;
+
+use crate::intern::Interner;
+use crate::{
+    Command, Entry, InvalidKeyword, Keyword, is_unknown_keyword, parse_command_with,
+    parse_entry_with, parse_keyword_with, sanitize,
+};
+
+/// Location of a logical line in the source text.
+///
+/// `start` and `end` are byte offsets into the original input, and `line` is
+/// the 1-based number of the first physical line. A logical line may span
+/// several physical lines when it uses `\` continuations.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl Span {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn slice<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.range()]
+    }
+}
+
+/// Identifies an entry within a [`Spec`](crate::Spec).
+///
+/// Ids are assigned in order when a spec is parsed and stay attached to their
+/// entry when other lines are inserted, removed or reordered, so they can be
+/// used to refer to an entry across edits even if its path changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryId(pub u64);
+
+impl fmt::Display for EntryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Line {
+    Comment {
+        text: String,
+        span: Span,
+    },
+    Blank {
+        span: Span,
+    },
+    Command {
+        command: Command,
+        span: Span,
+    },
+    Entry {
+        id: EntryId,
+        entry: Entry,
+        span: Span,
+    },
+}
+
+impl Line {
+    pub fn span(&self) -> &Span {
+        match self {
+            Line::Comment { span, .. }
+            | Line::Blank { span }
+            | Line::Command { span, .. }
+            | Line::Entry { span, .. } => span,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Line::Comment { text, .. } => write!(f, "#{text}"),
+            Line::Blank { .. } => Ok(()),
+            Line::Command { command, .. } => write!(f, "{command}"),
+            Line::Entry { entry, .. } => write!(f, "{entry}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ParseOptions {
+    /// Keep keyword values that don't parse as their type, such as an
+    /// out-of-range uid, as [`Keyword::Raw`](crate::Keyword::Raw) rather than
+    /// rejecting the line.
+    pub raw_values: bool,
+    /// Read uid and gid values too large for 32 bits, which some systems
+    /// write, as `u32::MAX` rather than rejecting the line.
+    /// [`Spec::validate`](crate::Spec::validate) reports them.
+    pub saturate_ids: bool,
+    /// Read `time` seconds too large for 64 bits, as found on corrupt
+    /// filesystems, as [`Timestamp::MAX`](crate::Timestamp::MAX), or
+    /// [`Timestamp::MIN`](crate::Timestamp::MIN) if negative, rather than
+    /// rejecting the line.
+    pub saturate_times: bool,
+    /// Skip keywords this crate doesn't know, as mtree(8) does after
+    /// warning, rather than rejecting the line.
+    pub ignore_unknown_keywords: bool,
+    /// Keep keywords with an empty value, such as `uname=`, as
+    /// [`Keyword::Raw`](crate::Keyword::Raw) rather than rejecting the line.
+    pub empty_values: bool,
+    /// Reject lines that give the same keyword twice, such as
+    /// `size=1 size=2`.
+    pub reject_duplicates: bool,
+    /// Reject lines with spaces or tabs before the line terminator.
+    pub reject_trailing_whitespace: bool,
+    /// Only accept digests in lowercase hex, as mtree(8) and go-mtree write
+    /// them.
+    pub strict_digests: bool,
+}
+
+impl ParseOptions {
+    /// Accepts as much as possible, for reading specs from tools that
+    /// don't quite agree on the format.
+    pub fn lenient() -> Self {
+        ParseOptions {
+            raw_values: true,
+            saturate_ids: true,
+            saturate_times: true,
+            ignore_unknown_keywords: true,
+            empty_values: true,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Rejects everything mtree(8) wouldn't write, for linting.
+    pub fn strict() -> Self {
+        ParseOptions {
+            reject_duplicates: true,
+            reject_trailing_whitespace: true,
+            strict_digests: true,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Whether lines the fast parser accepts parse the same way under these
+    /// options. It doesn't look for duplicates or uppercase digests.
+    #[cfg(feature = "fast")]
+    fn fast_path(&self) -> bool {
+        !self.reject_duplicates && !self.strict_digests
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub span: Span,
+    pub cause: ParseErrorCause,
+}
+
+/// Why a line was rejected, with the text that was wrong. For where in the
+/// line that text is, see [`ParseError::diagnose`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorCause {
+    /// A word that isn't a valid keyword.
+    Keyword(InvalidKeyword),
+    /// A keyword that repeats an earlier one on the line, see
+    /// [`ParseOptions::reject_duplicates`].
+    DuplicateKeyword(String),
+    /// A line starting with `/` other than `/set` or `/unset`.
+    UnknownCommand(String),
+    /// See [`ParseOptions::reject_trailing_whitespace`].
+    TrailingWhitespace,
+    /// Nothing more specific could be found; holds the whole line.
+    Syntax(String),
+}
+
+impl ParseError {
+    /// An error for the logical line `text`, which failed to parse with
+    /// `options`. Working out the cause takes another look at the line,
+    /// which is only worth doing once it's known to be bad.
+    pub(crate) fn new(text: &str, span: Span, options: &ParseOptions) -> Self {
+        ParseError {
+            span,
+            cause: ParseErrorCause::classify(text, options),
+        }
+    }
+}
+
+impl ParseErrorCause {
+    fn classify(text: &str, options: &ParseOptions) -> Self {
+        if options.reject_trailing_whitespace && text.ends_with([' ', '\t']) {
+            return ParseErrorCause::TrailingWhitespace;
+        }
+
+        let mut words = text.split_whitespace();
+        let syntax = || ParseErrorCause::Syntax(text.trim().to_string());
+
+        let Some(first) = words.next() else {
+            return syntax();
+        };
+
+        match first.strip_prefix('/') {
+            Some("set") | None => {}
+            Some("unset") => return syntax(),
+            Some(command) => return ParseErrorCause::UnknownCommand(command.to_string()),
+        }
+
+        let parser = parse_keyword_with(options);
+        let mut seen: Vec<Keyword> = Vec::new();
+
+        for word in words {
+            if options.ignore_unknown_keywords && is_unknown_keyword(word) {
+                continue;
+            }
+
+            let Ok(keyword) = parser.parse(word).into_result() else {
+                return ParseErrorCause::Keyword(InvalidKeyword::classify(word));
+            };
+
+            if options.reject_duplicates
+                && seen.iter().any(|earlier| earlier.same_property(&keyword))
+            {
+                return ParseErrorCause::DuplicateKeyword(word.to_string());
+            }
+            seen.push(keyword);
+        }
+
+        syntax()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not parse line {}: {}", self.span.line, self.cause)
+    }
+}
+
+impl fmt::Display for ParseErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorCause::Keyword(err) => write!(f, "{err}"),
+            ParseErrorCause::DuplicateKeyword(word) => {
+                write!(f, "duplicate keyword `{}`", sanitize::text(word))
+            }
+            ParseErrorCause::UnknownCommand(name) => {
+                write!(f, "unknown command `/{}`", sanitize::text(name))
+            }
+            ParseErrorCause::TrailingWhitespace => f.write_str("trailing whitespace"),
+            ParseErrorCause::Syntax(_) => f.write_str("invalid syntax"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Joins physical lines into logical lines, following `\` continuations.
+#[derive(Debug, Default)]
+pub(crate) struct LineJoiner {
+    offset: usize,
+    line: usize,
+    current: Option<(String, Span)>,
+}
+
+impl LineJoiner {
+    /// Adds the next physical line, including its line terminator, and
+    /// returns the logical line it completes, if any. A line without
+    /// continuations is borrowed from `raw`.
+    pub(crate) fn push<'a>(&mut self, raw: &'a str) -> Option<(Cow<'a, str>, Span)> {
+        let start = self.offset;
+        self.offset += raw.len();
+        self.line += 1;
+
+        let content = raw.trim_end_matches(['\n', '\r']);
+        let end = start + content.len();
+
+        let line = self.line;
+        let head = content.trim_end().strip_suffix('\\');
+
+        let (mut text, mut span) = match (self.current.take(), head) {
+            (Some(current), _) => current,
+            (None, Some(_)) => (String::new(), Span { start, end, line }),
+            (None, None) => return Some((Cow::Borrowed(content), Span { start, end, line })),
+        };
+        span.end = end;
+
+        match head {
+            Some(head) => {
+                text.push_str(head);
+                text.push(' ');
+                self.current = Some((text, span));
+                None
+            }
+            None => {
+                text.push_str(content);
+                Some((Cow::Owned(text), span))
+            }
+        }
+    }
+
+    /// The logical line being joined, if the last physical line ended in a
+    /// continuation.
+    pub(crate) fn pending(&self) -> Option<&(String, Span)> {
+        self.current.as_ref()
+    }
+
+    /// Returns a logical line left unfinished by a continuation on the last
+    /// physical line.
+    pub(crate) fn finish(&mut self) -> Option<(String, Span)> {
+        self.current.take()
+    }
+}
+
+/// Splits `input` into logical lines, joining `\` continuations, and yields
+/// the text of each one together with its span.
+pub(crate) fn logical_lines(input: &str) -> Vec<(Cow<'_, str>, Span)> {
+    let mut joiner = LineJoiner::default();
+    #[cfg(feature = "fast")]
+    let physical = crate::fast::physical_lines(input);
+    #[cfg(not(feature = "fast"))]
+    let physical = input.split_inclusive('\n');
+
+    let mut lines: Vec<_> = physical.filter_map(|raw| joiner.push(raw)).collect();
+    lines.extend(joiner.finish().map(|(text, span)| (Cow::Owned(text), span)));
+
+    lines
+}
+
+/// Parses a single logical line. Entries are given the default [`EntryId`];
+/// [`parse_lines`] numbers them in order of appearance.
+pub fn parse_line(text: &str, span: Span) -> Result<Line, ParseError> {
+    parse_line_with(text, span, &ParseOptions::default())
+}
+
+pub fn parse_line_with(text: &str, span: Span, options: &ParseOptions) -> Result<Line, ParseError> {
+    if options.reject_trailing_whitespace && text.ends_with([' ', '\t']) {
+        return Err(ParseError::new(text, span, options));
+    }
+
+    let trimmed = text.trim();
+
+    if trimmed.is_empty() {
+        return Ok(Line::Blank { span });
+    }
+
+    if let Some(comment) = trimmed.strip_prefix('#') {
+        return Ok(Line::Comment {
+            text: comment.to_string(),
+            span,
+        });
+    }
+
+    #[cfg(feature = "fast")]
+    if options.fast_path()
+        && let Some(line) = crate::fast::parse_line(trimmed, &span)
+    {
+        return Ok(line);
+    }
+
+    if trimmed.starts_with('/') {
+        return match parse_command_with(options).parse(trimmed).into_result() {
+            Ok(command) => Ok(Line::Command { command, span }),
+            Err(_) => Err(ParseError::new(text, span, options)),
+        };
+    }
+
+    match parse_entry_with(options).parse(trimmed).into_result() {
+        Ok(entry) => Ok(Line::Entry {
+            id: EntryId::default(),
+            entry,
+            span,
+        }),
+        Err(_) => Err(ParseError::new(text, span, options)),
+    }
+}
+
+pub fn parse_lines(input: &str) -> Result<Vec<Line>, ParseError> {
+    parse_lines_with(input, &ParseOptions::default())
+}
+
+pub fn parse_lines_with(input: &str, options: &ParseOptions) -> Result<Vec<Line>, ParseError> {
+    let mut next_id = 0;
+    let mut interner = Interner::new();
+
+    logical_lines(input)
+        .into_iter()
+        .map(|(text, span)| {
+            let mut line = parse_line_with(&text, span, options)?;
+            interner.line(&mut line);
+
+            if let Line::Entry { id, .. } = &mut line {
+                *id = EntryId(next_id);
+                next_id += 1;
+            }
+
+            Ok(line)
+        })
+        .collect()
+}
+
+/// Like [`parse_lines`], but leaves out lines that can't be parsed and
+/// carries on, returning an error for each of them. The text of a bad line
+/// is at `err.span.slice(input)`.
+///
+/// Entries are numbered as if the bad lines weren't there.
+pub fn parse_lines_lenient(input: &str) -> (Vec<Line>, Vec<ParseError>) {
+    parse_lines_lenient_with(input, &ParseOptions::default())
+}
+
+pub fn parse_lines_lenient_with(
+    input: &str,
+    options: &ParseOptions,
+) -> (Vec<Line>, Vec<ParseError>) {
+    let mut next_id = 0;
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    let mut interner = Interner::new();
+
+    for (text, span) in logical_lines(input) {
+        match parse_line_with(&text, span, options) {
+            Ok(mut line) => {
+                interner.line(&mut line);
+                if let Line::Entry { id, .. } = &mut line {
+                    *id = EntryId(next_id);
+                    next_id += 1;
+                }
+
+                lines.push(line);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (lines, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{Keyword, KeywordKind};
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "# comment\n\n/set uid=0\nfoo size=1\n";
+
+        assert_eq!(
+            parse_lines(input),
+            Ok(vec![
+                Line::Comment {
+                    text: " comment".to_string(),
+                    span: Span {
+                        start: 0,
+                        end: 9,
+                        line: 1
+                    }
+                },
+                Line::Blank {
+                    span: Span {
+                        start: 10,
+                        end: 10,
+                        line: 2
+                    }
+                },
+                Line::Command {
+                    command: Command::Set(vec![Keyword::Uid(0)]),
+                    span: Span {
+                        start: 11,
+                        end: 21,
+                        line: 3
+                    }
+                },
+                Line::Entry {
+                    id: EntryId(0),
+                    entry: Entry {
+                        path: PathBuf::from("foo"),
+                        keywords: vec![Keyword::Size(1)]
+                    },
+                    span: Span {
+                        start: 22,
+                        end: 32,
+                        line: 4
+                    }
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_lines_continuation() {
+        let input = "foo \\\n    size=1 uid=0\nbar\n";
+        let lines = parse_lines(input).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            Line::Entry {
+                id: EntryId(0),
+                entry: Entry {
+                    path: PathBuf::from("foo"),
+                    keywords: vec![Keyword::Size(1), Keyword::Uid(0)]
+                },
+                span: Span {
+                    start: 0,
+                    end: 22,
+                    line: 1
+                }
+            }
+        );
+        assert_eq!(lines[1].span().line, 3);
+        assert!(matches!(lines[1], Line::Entry { id: EntryId(1), .. }));
+        assert_eq!(lines[0].span().slice(input), "foo \\\n    size=1 uid=0");
+    }
+
+    #[test]
+    fn test_parse_lines_error() {
+        assert_eq!(
+            parse_lines("foo size=1\nbar size=x\n"),
+            Err(ParseError {
+                span: Span {
+                    start: 11,
+                    end: 21,
+                    line: 2
+                },
+                cause: ParseErrorCause::Keyword(InvalidKeyword::InvalidInteger(
+                    KeywordKind::Size,
+                    "x".to_string()
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error_cause() {
+        let cause = |input: &str, options: &ParseOptions| {
+            parse_lines_with(input, options).unwrap_err().cause
+        };
+        let default = ParseOptions::default();
+
+        assert_eq!(
+            cause("/frob uid=0", &default),
+            ParseErrorCause::UnknownCommand("frob".to_string())
+        );
+        assert_eq!(
+            cause("./a frob=1", &default),
+            ParseErrorCause::Keyword(InvalidKeyword::UnknownName("frob".to_string()))
+        );
+        assert_eq!(
+            cause("./a uid=0 uid=1", &ParseOptions::strict()),
+            ParseErrorCause::DuplicateKeyword("uid=1".to_string())
+        );
+        assert_eq!(
+            cause("./a uid=0 ", &ParseOptions::strict()),
+            ParseErrorCause::TrailingWhitespace
+        );
+        assert_eq!(
+            cause("/unset", &default),
+            ParseErrorCause::Syntax("/unset".to_string())
+        );
+        assert_eq!(
+            parse_lines("./a frob=1").unwrap_err().to_string(),
+            "could not parse line 1: unknown keyword `frob`"
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let keywords = |input: &str, options: &ParseOptions| {
+            parse_lines_with(input, options).map(|lines| match &lines[0] {
+                Line::Entry { entry, .. } => entry.keywords.clone(),
+                other => panic!("{other:?}"),
+            })
+        };
+        let lenient = ParseOptions::lenient();
+        let strict = ParseOptions::strict();
+
+        assert_eq!(
+            keywords("foo bogus=1 size=1 frob uname= xattr.a=aGk=", &lenient),
+            Ok(vec![
+                Keyword::Size(1),
+                Keyword::Raw {
+                    kind: crate::KeywordKind::Uname,
+                    value: String::new()
+                },
+                Keyword::Xattr {
+                    name: "a".to_string(),
+                    value: b"hi".to_vec()
+                }
+            ])
+        );
+        assert!(keywords("foo size=1bogus", &lenient).is_ok_and(|keywords| {
+            matches!(&keywords[..], [Keyword::Raw { value, .. }] if value == "1bogus")
+        }));
+
+        let empty = ParseOptions {
+            empty_values: true,
+            ..ParseOptions::default()
+        };
+        assert!(keywords("foo uname=", &empty).is_ok());
+        assert!(keywords("foo size=1x", &empty).is_err());
+        assert!(keywords("foo bogus=1", &empty).is_err());
+
+        for input in [
+            "foo size=1 size=2",
+            "/set uid=0 uid=0",
+            "foo size=1 ",
+            "foo md5=D41D8CD98F00B204E9800998ECF8427E",
+        ] {
+            assert!(parse_lines_with(input, &strict).is_err(), "{input}");
+            assert!(parse_lines(input).is_ok(), "{input}");
+        }
+        assert!(
+            keywords(
+                "foo md5=d41d8cd98f00b204e9800998ecf8427e sha1=da39a3ee5e6b4b0d3255bfef95601890afd80709 \\\n    xattr.a=aGk= xattr.b=aGk=",
+                &strict
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Checking a spec against rules of good practice, for gating CI on the
+//! quality of generated manifests.
+//!
+//! A [`Linter`] runs a set of [`Rule`]s over the resolved entries and adds
+//! every [`ValidationIssue`](crate::ValidationIssue) as an error.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::sanitize;
+use crate::{Keyword, KeywordKind, Mode, ResolvedEntry, Span, Spec, Type};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+pub trait Rule: Send + Sync {
+    /// A short kebab-case name, used to refer to the rule in output and
+    /// in [`Linter::without`].
+    fn name(&self) -> &'static str;
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Describes what's wrong with `entry`, if anything. Messages name the
+    /// path, as [`ValidationIssue`](crate::ValidationIssue) messages do.
+    fn check(&self, entry: &ResolvedEntry) -> Option<String>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintFinding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {}: {} [{}]",
+            self.span.line, self.severity, self.message, self.rule
+        )
+    }
+}
+
+/// The name of the findings made from [`Spec::validate`] issues.
+pub const VALIDATION_RULE: &str = "invalid";
+
+#[derive(Clone)]
+pub struct Linter {
+    rules: Vec<Arc<dyn Rule>>,
+    validate: bool,
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.rules.iter().map(|rule| rule.name()).collect();
+        f.debug_struct("Linter")
+            .field("rules", &names)
+            .field("validate", &self.validate)
+            .finish()
+    }
+}
+
+/// The built-in rules, plus validation.
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+            .rule(MissingType)
+            .rule(MissingDigest)
+            .rule(WorldWritable)
+            .rule(AbsoluteLink)
+    }
+}
+
+impl Linter {
+    /// A linter with no rules, which only reports validation issues.
+    pub fn new() -> Self {
+        Linter {
+            rules: Vec::new(),
+            validate: true,
+        }
+    }
+
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    /// Turns off the rule called `name`, or validation if it's
+    /// [`VALIDATION_RULE`].
+    pub fn without(mut self, name: &str) -> Self {
+        self.rules.retain(|rule| rule.name() != name);
+        self.validate &= name != VALIDATION_RULE;
+        self
+    }
+
+    /// Reports findings in the order of the lines they're about.
+    pub fn lint(&self, spec: &Spec) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+
+        if self.validate {
+            findings.extend(spec.validate().into_iter().map(|issue| LintFinding {
+                rule: VALIDATION_RULE,
+                severity: Severity::Error,
+                path: issue.path().to_path_buf(),
+                span: issue.span().clone(),
+                message: issue.message(),
+            }));
+        }
+
+        for entry in spec.resolve() {
+            for rule in &self.rules {
+                if let Some(message) = rule.check(&entry) {
+                    findings.push(LintFinding {
+                        rule: rule.name(),
+                        severity: rule.severity(),
+                        path: entry.path.clone(),
+                        span: entry.span.clone(),
+                        message,
+                    });
+                }
+            }
+        }
+
+        findings.sort_by_key(|finding| finding.span.start);
+        findings
+    }
+}
+
+impl Spec {
+    /// Lints the spec with the built-in rules.
+    pub fn lint(&self) -> Vec<LintFinding> {
+        Linter::default().lint(self)
+    }
+}
+
+fn file_type(entry: &ResolvedEntry) -> Option<&Type> {
+    match entry.get(KeywordKind::Type)? {
+        Keyword::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+/// Entries without a `type`, which verification can't check properly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MissingType;
+
+impl Rule for MissingType {
+    fn name(&self) -> &'static str {
+        "missing-type"
+    }
+
+    fn check(&self, entry: &ResolvedEntry) -> Option<String> {
+        file_type(entry)
+            .is_none()
+            .then(|| format!("{} has no type", sanitize::path(&entry.path)))
+    }
+}
+
+/// Regular files without a digest, whose contents can't be verified.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MissingDigest;
+
+impl Rule for MissingDigest {
+    fn name(&self) -> &'static str {
+        "missing-digest"
+    }
+
+    fn check(&self, entry: &ResolvedEntry) -> Option<String> {
+        let has_digest = entry
+            .keywords
+            .iter()
+            .any(|keyword| matches!(keyword, Keyword::Digest(_)));
+
+        (file_type(entry) == Some(&Type::File) && !has_digest)
+            .then(|| format!("{} has no digest", sanitize::path(&entry.path)))
+    }
+}
+
+/// Files anyone can write to. Symbolic links, whose mode doesn't matter,
+/// and directories with the sticky bit, like `/tmp`, are fine.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorldWritable;
+
+impl Rule for WorldWritable {
+    fn name(&self) -> &'static str {
+        "world-writable"
+    }
+
+    fn check(&self, entry: &ResolvedEntry) -> Option<String> {
+        let Some(Keyword::Mode(mode)) = entry.get(KeywordKind::Mode) else {
+            return None;
+        };
+
+        let exempt = match file_type(entry) {
+            Some(Type::Link) => true,
+            Some(Type::Dir) => mode.bits() & Mode::STICKY != 0,
+            _ => false,
+        };
+
+        (mode.bits() & 0o002 != 0 && !exempt).then(|| {
+            format!(
+                "{} is world-writable, mode {mode}",
+                sanitize::path(&entry.path)
+            )
+        })
+    }
+}
+
+/// Symbolic links to absolute paths, which point outside the tree once
+/// it's unpacked somewhere other than `/`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AbsoluteLink;
+
+impl Rule for AbsoluteLink {
+    fn name(&self) -> &'static str {
+        "absolute-link"
+    }
+
+    fn check(&self, entry: &ResolvedEntry) -> Option<String> {
+        let Some(Keyword::Link(target)) = entry.get(KeywordKind::Link) else {
+            return None;
+        };
+
+        target.is_absolute().then(|| {
+            format!(
+                "{} links to the absolute path {}",
+                sanitize::path(&entry.path),
+                sanitize::path(target)
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let spec = Spec::parse(
+            "/set type=file mode=0644\n\
+             . type=dir mode=0755\n\
+             good sha256=e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n\
+             bare\n\
+             tmp type=dir mode=1777\n\
+             ..\n\
+             open type=dir mode=0777\n\
+             ..\n\
+             lib type=link mode=0777 link=/usr/lib\n\
+             rel type=link link=../lib\n\
+             /unset type\n\
+             what size=1\n\
+             what size=2\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.lint()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 4: warning: ./bare has no digest [missing-digest]",
+                "line 7: warning: ./open is world-writable, mode 0777 [world-writable]",
+                "line 9: warning: ./lib links to the absolute path /usr/lib [absolute-link]",
+                "line 13: error: ./what is already described on line 12 [invalid]",
+                "line 13: warning: ./what has no type [missing-type]",
+            ]
+        );
+
+        struct Big;
+
+        impl Rule for Big {
+            fn name(&self) -> &'static str {
+                "big"
+            }
+
+            fn severity(&self) -> Severity {
+                Severity::Info
+            }
+
+            fn check(&self, entry: &ResolvedEntry) -> Option<String> {
+                entry
+                    .keywords
+                    .contains(&Keyword::Size(2))
+                    .then(|| "big file".to_string())
+            }
+        }
+
+        let findings = Linter::new().without(VALIDATION_RULE).rule(Big).lint(&spec);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Info);
+        assert_eq!(findings[0].span.line, 13);
+        assert!(Linter::default().without("missing-type").lint(&spec).len() == 4);
+    }
+}
@@ -0,0 +1,206 @@
+//! Selecting spec paths, shared by excludes, only-lists, the `select`
+//! options of [`SpecBuilder`](crate::SpecBuilder) and
+//! [`Verifier`](crate::verify::Verifier), and [`Spec::select`].
+//!
+//! Paths are compared the way the walker spells them, as `./a/b`, so `a/b`
+//! and `./a/b` always match alike.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::exclude::fnmatch;
+use crate::only::normalize;
+use crate::{ExcludeList, OnlyList, ResolvedEntry, Spec};
+
+pub trait PathMatcher: Send + Sync {
+    /// Whether the spec path `path` matches.
+    fn matches(&self, path: &Path) -> bool;
+}
+
+impl<F: Fn(&Path) -> bool + Send + Sync> PathMatcher for F {
+    fn matches(&self, path: &Path) -> bool {
+        self(path)
+    }
+}
+
+/// Matches excluded paths, including everything below an excluded directory.
+impl PathMatcher for ExcludeList {
+    fn matches(&self, path: &Path) -> bool {
+        self.is_excluded(path)
+    }
+}
+
+/// Matches listed paths and the directories leading up to them.
+impl PathMatcher for OnlyList {
+    fn matches(&self, path: &Path) -> bool {
+        self.contains(path)
+    }
+}
+
+/// A single fnmatch(3) pattern, with the same rules as [`ExcludeList`]: a
+/// pattern containing a `/` is matched against the whole path and any other
+/// against the file name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobMatcher {
+    pattern: String,
+}
+
+impl GlobMatcher {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        GlobMatcher {
+            pattern: pattern.into(),
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl PathMatcher for GlobMatcher {
+    fn matches(&self, path: &Path) -> bool {
+        glob_matches(self.pattern.as_bytes(), &normalize(path))
+    }
+}
+
+pub(crate) fn glob_matches(pattern: &[u8], path: &Path) -> bool {
+    let path = path.as_os_str().as_encoded_bytes();
+
+    if pattern.contains(&b'/') {
+        fnmatch(pattern, path)
+    } else {
+        let name = path
+            .iter()
+            .rposition(|&c| c == b'/')
+            .map_or(path, |idx| &path[idx + 1..]);
+        fnmatch(pattern, name)
+    }
+}
+
+/// Matches exactly the paths it holds, and nothing above or below them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LiteralMatcher {
+    paths: HashSet<PathBuf>,
+}
+
+impl LiteralMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>) {
+        self.paths.insert(normalize(path.as_ref()));
+    }
+}
+
+impl<P: AsRef<Path>> FromIterator<P> for LiteralMatcher {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut matcher = LiteralMatcher::new();
+        for path in iter {
+            matcher.insert(path);
+        }
+        matcher
+    }
+}
+
+impl PathMatcher for LiteralMatcher {
+    fn matches(&self, path: &Path) -> bool {
+        self.paths.contains(&normalize(path))
+    }
+}
+
+/// A regular expression, matched against the raw bytes of the whole path.
+/// It isn't anchored, so `\.so$` matches any shared library.
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+pub struct RegexMatcher {
+    regex: regex::bytes::Regex,
+}
+
+#[cfg(feature = "regex")]
+impl RegexMatcher {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(RegexMatcher {
+            regex: regex::bytes::Regex::new(pattern)?,
+        })
+    }
+}
+
+#[cfg(feature = "regex")]
+impl PathMatcher for RegexMatcher {
+    fn matches(&self, path: &Path) -> bool {
+        self.regex
+            .is_match(normalize(path).as_os_str().as_encoded_bytes())
+    }
+}
+
+/// A shared matcher that builders can store and clone.
+#[derive(Clone)]
+pub(crate) struct Selection(pub(crate) Arc<dyn PathMatcher>);
+
+impl fmt::Debug for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Selection(..)")
+    }
+}
+
+impl Spec {
+    /// The resolved entries whose paths match `matcher`.
+    pub fn select(&self, matcher: &dyn PathMatcher) -> Vec<ResolvedEntry> {
+        let mut entries = self.resolve();
+        entries.retain(|entry| matcher.matches(&entry.path));
+        entries
+    }
+
+    /// The resolved entries with a tag matching `matcher`. Each tag is
+    /// matched as if it were a file name, so globs without a `/` work.
+    pub fn select_tagged(&self, matcher: &dyn PathMatcher) -> Vec<ResolvedEntry> {
+        let mut entries = self.resolve();
+        entries.retain(|entry| {
+            entry
+                .tags()
+                .iter()
+                .any(|tag| matcher.matches(Path::new(tag)))
+        });
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matchers() {
+        let glob = GlobMatcher::new("*.so");
+        assert!(glob.matches(Path::new("usr/lib/libc.so")));
+        assert!(!glob.matches(Path::new("./usr/lib/libc.so.6")));
+        assert!(GlobMatcher::new("./usr/*").matches(Path::new("usr/bin")));
+
+        let literal: LiteralMatcher = ["./etc/passwd"].into_iter().collect();
+        assert!(literal.matches(Path::new("etc/passwd")));
+        assert!(!literal.matches(Path::new("./etc")));
+
+        let spec = Spec::parse(
+            "./usr/lib/libc.so type=file tags=libc\n\
+             ./usr/bin/ls type=file tags=coreutils,base\n\
+             ./usr/bin/cat type=file tags=coreutils\n",
+        )
+        .unwrap();
+
+        let paths = |entries: Vec<ResolvedEntry>| -> Vec<PathBuf> {
+            entries.into_iter().map(|entry| entry.path).collect()
+        };
+        assert_eq!(
+            paths(spec.select(&GlobMatcher::new("./usr/bin/*"))),
+            ["./usr/bin/ls", "./usr/bin/cat"].map(PathBuf::from)
+        );
+        assert_eq!(
+            paths(spec.select_tagged(&GlobMatcher::new("b*"))),
+            [PathBuf::from("./usr/bin/ls")]
+        );
+        assert_eq!(spec.select(&|path: &Path| path.ends_with("cat")).len(), 1);
+    }
+}
@@ -0,0 +1,311 @@
+//! Combining several specs into one, e.g. per-package specs into a manifest
+//! for a whole root filesystem.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::sanitize;
+use crate::{Entry, EntryId, Keyword, Line, Span, Spec, Timestamp};
+
+/// What [`Spec::merge`] does when both specs give a keyword of the same
+/// entry different values.
+#[derive(Clone, Default)]
+pub enum MergePolicy {
+    /// Keep the value from the spec `merge` is called on.
+    #[default]
+    PreferLeft,
+    /// Keep the value from the spec passed to `merge`.
+    PreferRight,
+    /// Keep the values of whichever entry has the later `time`. An entry
+    /// without one counts as older, and ties keep the left value.
+    PreferNewer,
+    /// Fail with a [`MergeConflict`].
+    Error,
+    /// Keep the keyword the closure returns, which should be of the same
+    /// kind as the two in conflict.
+    Custom(Arc<dyn Fn(&MergeConflict) -> Keyword + Send + Sync>),
+}
+
+impl MergePolicy {
+    pub fn custom(resolve: impl Fn(&MergeConflict) -> Keyword + Send + Sync + 'static) -> Self {
+        MergePolicy::Custom(Arc::new(resolve))
+    }
+}
+
+impl fmt::Debug for MergePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergePolicy::PreferLeft => f.write_str("PreferLeft"),
+            MergePolicy::PreferRight => f.write_str("PreferRight"),
+            MergePolicy::PreferNewer => f.write_str("PreferNewer"),
+            MergePolicy::Error => f.write_str("Error"),
+            MergePolicy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub path: PathBuf,
+    pub left: Keyword,
+    pub right: Keyword,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: conflicting values {} and {}",
+            sanitize::path(&self.path),
+            sanitize::text(&self.left.to_string()),
+            sanitize::text(&self.right.to_string())
+        )
+    }
+}
+
+impl std::error::Error for MergeConflict {}
+
+/// The result of [`Spec::merge_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeReport {
+    pub spec: Spec,
+    /// Every conflict the policy settled, in the order they were found.
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeReport {
+    /// The paths with at least one conflict, each once.
+    pub fn conflicting_paths(&self) -> Vec<&Path> {
+        let mut paths: Vec<&Path> = Vec::new();
+        for conflict in &self.conflicts {
+            if paths.last() != Some(&conflict.path.as_path()) {
+                paths.push(&conflict.path);
+            }
+        }
+        paths
+    }
+}
+
+impl Spec {
+    /// Combines the entries of two specs. An entry in both gets the keywords
+    /// of both, with conflicts settled by `policy`.
+    ///
+    /// The result lists every entry by its full path with `/set` defaults
+    /// applied: first the entries of `self`, then those only in `other`.
+    pub fn merge(&self, other: &Spec, policy: MergePolicy) -> Result<Spec, Box<MergeConflict>> {
+        self.merge_report(other, policy).map(|report| report.spec)
+    }
+
+    /// Like [`Spec::merge`], but also lists the conflicts that were settled.
+    pub fn merge_report(
+        &self,
+        other: &Spec,
+        policy: MergePolicy,
+    ) -> Result<MergeReport, Box<MergeConflict>> {
+        let mut entries: Vec<Entry> = self
+            .resolve()
+            .into_iter()
+            .map(|entry| Entry {
+                path: entry.path,
+                keywords: entry.keywords,
+            })
+            .collect();
+
+        let mut conflicts = Vec::new();
+        let mut by_path: HashMap<PathBuf, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (entry.path.clone(), idx))
+            .collect();
+
+        for right in other.resolve() {
+            let Some(&idx) = by_path.get(&right.path) else {
+                by_path.insert(right.path.clone(), entries.len());
+                entries.push(Entry {
+                    path: right.path,
+                    keywords: right.keywords,
+                });
+                continue;
+            };
+
+            let left = &mut entries[idx];
+            let right_is_newer = time(&right.keywords) > time(&left.keywords);
+
+            for keyword in right.keywords {
+                let existing = left
+                    .keywords
+                    .iter_mut()
+                    .find(|existing| existing.kind() == keyword.kind());
+
+                match existing {
+                    None => left.keywords.push(keyword),
+                    Some(existing) if *existing == keyword => {}
+                    Some(existing) => {
+                        let conflict = MergeConflict {
+                            path: left.path.clone(),
+                            left: existing.clone(),
+                            right: keyword,
+                        };
+
+                        match &policy {
+                            MergePolicy::PreferLeft => {}
+                            MergePolicy::PreferRight => *existing = conflict.right.clone(),
+                            MergePolicy::PreferNewer => {
+                                if right_is_newer {
+                                    *existing = conflict.right.clone();
+                                }
+                            }
+                            MergePolicy::Error => return Err(Box::new(conflict)),
+                            MergePolicy::Custom(resolve) => *existing = resolve(&conflict),
+                        }
+
+                        conflicts.push(conflict);
+                    }
+                }
+            }
+        }
+
+        let lines = entries
+            .into_iter()
+            .enumerate()
+            .map(|(idx, entry)| Line::Entry {
+                id: EntryId(idx as u64),
+                entry,
+                span: Span::default(),
+            })
+            .collect();
+
+        Ok(MergeReport {
+            spec: Spec::new(lines),
+            conflicts,
+        })
+    }
+}
+
+fn time(keywords: &[Keyword]) -> Option<Timestamp> {
+    keywords.iter().find_map(|keyword| match keyword {
+        Keyword::Time(time) => Some(*time),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn keywords(spec: &Spec, path: &str) -> Vec<Keyword> {
+        spec.entries()
+            .find(|entry| entry.path == Path::new(path))
+            .unwrap()
+            .keywords
+            .clone()
+    }
+
+    #[test]
+    fn test_merge() {
+        let left = Spec::parse(
+            "/set uid=0\n\
+             ./usr/bin/ls mode=0755\n\
+             ./usr/bin/cat mode=0755\n",
+        )
+        .unwrap();
+        let right = Spec::parse(
+            "usr type=dir\n\
+             \x20   bin type=dir\n\
+             \x20       ls mode=0555 size=100\n",
+        )
+        .unwrap();
+
+        let merged = left.merge(&right, MergePolicy::PreferLeft).unwrap();
+        assert_eq!(
+            merged
+                .entries()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("./usr/bin/ls"),
+                PathBuf::from("./usr/bin/cat"),
+                PathBuf::from("./usr"),
+                PathBuf::from("./usr/bin"),
+            ]
+        );
+        assert_eq!(
+            merged.to_string().lines().next(),
+            Some("./usr/bin/ls uid=0 mode=0755 size=100")
+        );
+
+        let merged = left.merge(&right, MergePolicy::PreferRight).unwrap();
+        assert_eq!(
+            keywords(&merged, "./usr/bin/ls"),
+            vec![
+                Keyword::Uid(0),
+                Keyword::Mode("0555".parse().unwrap()),
+                Keyword::Size(100)
+            ]
+        );
+
+        assert_eq!(
+            left.merge(&right, MergePolicy::Error),
+            Err(Box::new(MergeConflict {
+                path: PathBuf::from("./usr/bin/ls"),
+                left: Keyword::Mode("0755".parse().unwrap()),
+                right: Keyword::Mode("0555".parse().unwrap()),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_merge_report() {
+        let left = Spec::parse(
+            "./a mode=0644 time=100.0
+./b mode=0644 size=1
+",
+        )
+        .unwrap();
+        let right = Spec::parse(
+            "./a mode=0600 time=200.0
+./b mode=0600 size=2
+",
+        )
+        .unwrap();
+
+        let report = left.merge_report(&right, MergePolicy::PreferNewer).unwrap();
+        assert_eq!(
+            keywords(&report.spec, "./a"),
+            [
+                Keyword::Mode("0600".parse().unwrap()),
+                Keyword::Time(Timestamp::new(200, 0)),
+            ]
+        );
+        assert_eq!(
+            keywords(&report.spec, "./b"),
+            [Keyword::Mode("0644".parse().unwrap()), Keyword::Size(1)]
+        );
+        assert_eq!(report.conflicts.len(), 4);
+        assert_eq!(
+            report.conflicting_paths(),
+            [Path::new("./a"), Path::new("./b")]
+        );
+
+        let largest = MergePolicy::custom(|conflict| match (&conflict.left, &conflict.right) {
+            (Keyword::Size(left), Keyword::Size(right)) => Keyword::Size(*left.max(right)),
+            _ => conflict.left.clone(),
+        });
+        let merged = left.merge(&right, largest).unwrap();
+        assert_eq!(
+            keywords(&merged, "./b"),
+            [Keyword::Mode("0644".parse().unwrap()), Keyword::Size(2)]
+        );
+    }
+
+    #[test]
+    fn test_merge_identical() {
+        let spec = Spec::parse("/set uid=0\nfoo size=1\n").unwrap();
+
+        let merged = spec.merge(&spec, MergePolicy::Error).unwrap();
+        assert_eq!(merged.entries().count(), 1);
+    }
+}
@@ -0,0 +1,110 @@
+//! Parsing specs straight from a memory-mapped file, so that the spec of a
+//! whole system, often a gigabyte or more, is never copied into a `String`.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{LineRef, ParseError, ReadError, Spec, parse_lines_ref};
+
+/// A spec file mapped into memory. [`MappedSpec::lines`] parses it with
+/// [`parse_lines_ref`], so names and paths borrow from the map rather than
+/// from a copy of the file.
+///
+/// The file must not be changed while it's mapped: the text would change
+/// under the parsed lines, and a file truncated by another process can
+/// crash this one.
+#[derive(Debug)]
+pub struct MappedSpec {
+    map: Mmap,
+}
+
+impl MappedSpec {
+    /// Maps the file at `path`, which has to be UTF-8 text. Gzip-compressed
+    /// specs can't be parsed in place; read them with
+    /// [`Spec::from_reader`].
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // The map is read-only, and not changing the file while it's mapped
+        // is left to the caller, as documented above.
+        let map = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential)?;
+
+        std::str::from_utf8(&map).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(MappedSpec { map })
+    }
+
+    pub fn text(&self) -> &str {
+        // Checked to be UTF-8 when the file was opened.
+        unsafe { std::str::from_utf8_unchecked(&self.map) }
+    }
+
+    /// Parses the file without copying names and paths out of it.
+    pub fn lines(&self) -> Result<Vec<LineRef<'_>>, ParseError> {
+        parse_lines_ref(self.text())
+    }
+
+    pub fn to_spec(&self) -> Result<Spec, ParseError> {
+        Spec::parse(self.text())
+    }
+}
+
+impl Spec {
+    /// Parses the file at `path` through a memory map, without reading it
+    /// into memory first. See [`MappedSpec`] for what the file has to be.
+    pub fn parse_mmap(path: impl AsRef<Path>) -> Result<Spec, ReadError> {
+        Ok(MappedSpec::open(path)?.to_spec()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use crate::{Line, parse_lines};
+
+    const SPEC: &str = "#mtree\n/set uid=0\n./bin type=dir\n./bin/ls size=1 \\\n    mode=0755\n";
+
+    #[test]
+    fn test_parse_mmap() {
+        let dir = TempDir::new();
+        let path = dir.file("spec", SPEC);
+
+        assert_eq!(Spec::parse_mmap(&path).unwrap(), Spec::parse(SPEC).unwrap());
+
+        let mapped = MappedSpec::open(&path).unwrap();
+        let lines: Vec<Line> = mapped
+            .lines()
+            .unwrap()
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        assert_eq!(lines, parse_lines(SPEC).unwrap());
+
+        let empty = dir.file("empty", "");
+        assert_eq!(Spec::parse_mmap(&empty).unwrap(), Spec::default());
+    }
+
+    #[test]
+    fn test_parse_mmap_errors() {
+        let dir = TempDir::new();
+
+        assert!(matches!(
+            Spec::parse_mmap(dir.path().join("missing")),
+            Err(ReadError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+
+        let path = dir.path().join("binary");
+        std::fs::write(&path, b"./a\n\xff\n").unwrap();
+        assert!(matches!(
+            Spec::parse_mmap(&path),
+            Err(ReadError::Io(err)) if err.kind() == io::ErrorKind::InvalidData
+        ));
+
+        let path = dir.file("bad", "./a size=x\n");
+        assert!(matches!(Spec::parse_mmap(&path), Err(ReadError::Parse(_))));
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// File permission bits, as stored in the `mode` keyword.
+///
+/// Only the permission, setuid/setgid and sticky bits are kept; the file type
+/// is described separately by the `type` keyword.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mode(u32);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidMode;
+
+impl fmt::Display for InvalidMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid file mode")
+    }
+}
+
+impl std::error::Error for InvalidMode {}
+
+impl Mode {
+    pub const MASK: u32 = 0o7777;
+
+    pub const SETUID: u32 = 0o4000;
+    pub const SETGID: u32 = 0o2000;
+    pub const STICKY: u32 = 0o1000;
+
+    pub fn new(bits: u32) -> Option<Self> {
+        (bits & !Self::MASK == 0).then_some(Mode(bits))
+    }
+
+    /// Keeps only the bits of `st_mode` that a `Mode` describes.
+    pub fn from_st_mode(st_mode: u32) -> Self {
+        Mode(st_mode & Self::MASK)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    fn parse_octal(s: &str) -> Result<Self, InvalidMode> {
+        let bits = u32::from_str_radix(s, 8).map_err(|_| InvalidMode)?;
+        Mode::new(bits).ok_or(InvalidMode)
+    }
+
+    /// Parses chmod(1)-style symbolic modes such as `u=rwx,g=rx,o=rx`,
+    /// starting from no permissions.
+    fn parse_symbolic(s: &str) -> Result<Self, InvalidMode> {
+        let mut bits = 0;
+
+        for clause in s.split(',') {
+            let op_idx = clause.find(['=', '+', '-']).ok_or(InvalidMode)?;
+            let (who, rest) = clause.split_at(op_idx);
+            let (op, perms) = rest.split_at(1);
+
+            let who = if who.is_empty() { "a" } else { who };
+            let mut mask = 0;
+            for c in who.chars() {
+                mask |= match c {
+                    'u' => 0o4700,
+                    'g' => 0o2070,
+                    'o' => 0o0007,
+                    'a' => 0o6777,
+                    _ => return Err(InvalidMode),
+                };
+            }
+
+            let mut value = 0;
+            for c in perms.chars() {
+                value |= match c {
+                    'r' => 0o0444,
+                    'w' => 0o0222,
+                    'x' => 0o0111,
+                    's' => Self::SETUID | Self::SETGID,
+                    't' => Self::STICKY,
+                    _ => return Err(InvalidMode),
+                };
+            }
+
+            if perms.contains('t') {
+                mask |= Self::STICKY;
+            }
+
+            match op {
+                "=" => bits = (bits & !mask) | (value & mask),
+                "+" => bits |= value & mask,
+                _ => bits &= !(value & mask),
+            }
+        }
+
+        Ok(Mode(bits))
+    }
+}
+
+impl FromStr for Mode {
+    type Err = InvalidMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(InvalidMode);
+        }
+
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            Mode::parse_octal(s)
+        } else {
+            Mode::parse_symbolic(s)
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04o}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_octal() {
+        assert_eq!("0755".parse::<Mode>().map(|m| m.bits()), Ok(0o755));
+        assert_eq!("755".parse::<Mode>().map(|m| m.bits()), Ok(0o755));
+        assert_eq!("4755".parse::<Mode>().map(|m| m.bits()), Ok(0o4755));
+        assert_eq!("0".parse::<Mode>().map(|m| m.bits()), Ok(0));
+        assert_eq!("0789".parse::<Mode>(), Err(InvalidMode));
+        assert_eq!("17777".parse::<Mode>(), Err(InvalidMode));
+    }
+
+    #[test]
+    fn test_mode_symbolic() {
+        assert_eq!(
+            "u=rwx,g=rx,o=rx".parse::<Mode>().map(|m| m.bits()),
+            Ok(0o755)
+        );
+        assert_eq!("a=r,u+w".parse::<Mode>().map(|m| m.bits()), Ok(0o644));
+        assert_eq!("u=rwxs,go=x".parse::<Mode>().map(|m| m.bits()), Ok(0o4711));
+        assert_eq!("a=rwxt,o-w".parse::<Mode>().map(|m| m.bits()), Ok(0o1775));
+        assert_eq!("u=q".parse::<Mode>(), Err(InvalidMode));
+        assert_eq!("rwx".parse::<Mode>(), Err(InvalidMode));
+    }
+
+    #[test]
+    fn test_mode_display() {
+        assert_eq!(Mode::new(0o755).unwrap().to_string(), "0755");
+        assert_eq!(Mode::new(0o4755).unwrap().to_string(), "4755");
+        assert_eq!(Mode::from_st_mode(0o100644), Mode::new(0o644).unwrap());
+    }
+}
@@ -0,0 +1,196 @@
+//! Parsing and verification for tokio, behind the `tokio` feature.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use futures_core::Stream;
+use tokio::io::AsyncBufRead;
+
+use crate::reader::{LineParser, ReadError};
+use crate::verify::Verifier;
+use crate::{Line, Spec, VerifyReport};
+
+/// Like [`SpecReader`](crate::SpecReader), but reads from an `AsyncBufRead`
+/// and yields the lines as a `Stream`.
+#[derive(Debug)]
+pub struct AsyncSpecReader<R> {
+    reader: R,
+    parser: LineParser,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncSpecReader<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncSpecReader {
+            reader,
+            parser: LineParser::default(),
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Takes the physical line read so far out of the buffer.
+    fn take_line(&mut self) -> io::Result<String> {
+        String::from_utf8(std::mem::take(&mut self.buf))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncSpecReader<R> {
+    type Item = Result<Line, ReadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        while !this.done {
+            let available = match ready!(Pin::new(&mut this.reader).poll_fill_buf(cx)) {
+                Ok(available) => available,
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            };
+
+            if available.is_empty() {
+                this.done = true;
+
+                let last = if this.buf.is_empty() {
+                    None
+                } else {
+                    match this.take_line() {
+                        Ok(text) => this.parser.push(&text),
+                        Err(err) => return Poll::Ready(Some(Err(err.into()))),
+                    }
+                };
+
+                return Poll::Ready(last.or_else(|| this.parser.finish()).map(|line| Ok(line?)));
+            }
+
+            let Some(idx) = available.iter().position(|&c| c == b'\n') else {
+                let len = available.len();
+                this.buf.extend_from_slice(available);
+                Pin::new(&mut this.reader).consume(len);
+                continue;
+            };
+
+            this.buf.extend_from_slice(&available[..=idx]);
+            Pin::new(&mut this.reader).consume(idx + 1);
+
+            let text = match this.take_line() {
+                Ok(text) => text,
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            };
+
+            if let Some(line) = this.parser.push(&text) {
+                return Poll::Ready(Some(line.map_err(ReadError::from)));
+            }
+        }
+
+        Poll::Ready(None)
+    }
+}
+
+impl Verifier {
+    /// Runs [`Verifier::verify`] on tokio's blocking thread pool, so reading
+    /// and hashing the files doesn't hold up the runtime.
+    pub async fn verify_async(&self, spec: Spec) -> io::Result<VerifyReport> {
+        let verifier = self.clone();
+
+        tokio::task::spawn_blocking(move || verifier.verify(&spec))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+/// Computes digests of the file at `path` like
+/// [`hash_file`](crate::hash::hash_file), reading it asynchronously.
+#[cfg(feature = "hash")]
+pub async fn hash_file(
+    path: impl AsRef<std::path::Path>,
+    kinds: impl IntoIterator<Item = crate::DigestKind>,
+) -> io::Result<Vec<crate::Digest>> {
+    use crate::hash::Hasher;
+    use tokio::io::AsyncReadExt;
+
+    let mut hashers: Vec<Hasher> = kinds.into_iter().map(Hasher::new).collect();
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buf = vec![0u8; 64 * 1024];
+
+    loop {
+        match file.read(&mut buf).await? {
+            0 => break,
+            n => hashers
+                .iter_mut()
+                .for_each(|hasher| hasher.update(&buf[..n])),
+        }
+    }
+
+    Ok(hashers.into_iter().map(Hasher::finish).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use crate::{RootPath, parse_lines};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_async_spec_reader() {
+        let input = "# comment\n/set uid=0\nfoo size=1 \\\n    mode=0644\nbar \\\n";
+
+        let lines = block_on(async {
+            let mut reader = AsyncSpecReader::new(input.as_bytes());
+            let mut lines = Vec::new();
+
+            while let Some(line) =
+                std::future::poll_fn(|cx| Pin::new(&mut reader).poll_next(cx)).await
+            {
+                lines.push(line.unwrap());
+            }
+
+            lines
+        });
+
+        assert_eq!(lines, parse_lines(input).unwrap());
+    }
+
+    #[test]
+    fn test_verify_async() {
+        let dir = TempDir::new();
+        dir.file("file", "hello");
+
+        let spec = Spec::parse("./file type=file size=4\n").unwrap();
+        let report = block_on(Verifier::new(RootPath::new(dir.path())).verify_async(spec)).unwrap();
+
+        assert_eq!(report.findings.len(), 1);
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn test_hash_file() {
+        let dir = TempDir::new();
+        let path = dir.file("file", "abc");
+
+        let kinds = [crate::DigestKind::Sha256];
+        assert_eq!(
+            block_on(hash_file(&path, kinds)).unwrap(),
+            crate::hash::hash_file(&path, kinds).unwrap()
+        );
+    }
+}
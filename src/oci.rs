@@ -0,0 +1,161 @@
+//! OCI image layers: the mtree of a layer blob and the differences between
+//! two layers, much like umoci does with go-mtree.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{member_keywords, member_type, normalize};
+use crate::reader::decompressed;
+use crate::{Entry, EntryId, Keyword, KeywordSet, Line, Span, Spec, SpecDiff, diff};
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layer {
+    /// The files the layer adds or replaces, by full path in archive order.
+    pub spec: Spec,
+    /// Paths the layer deletes from the layers below it.
+    pub whiteouts: Vec<PathBuf>,
+    /// Directories whose contents from the layers below are hidden.
+    pub opaque: Vec<PathBuf>,
+}
+
+impl Layer {
+    /// Reads a layer blob, recording `keywords` for each of its members.
+    /// The blob may be a plain or a gzip-compressed tar archive.
+    ///
+    /// Tar headers store whole seconds, which go into `time`, or into
+    /// `tar_time` instead if that's selected, as go-mtree does. Whiteout files aren't entries of the spec but are
+    /// listed in [`Layer::whiteouts`] and [`Layer::opaque`].
+    pub fn read(blob: impl Read, keywords: KeywordSet) -> io::Result<Layer> {
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut by_path: HashMap<PathBuf, usize> = HashMap::new();
+        let mut whiteouts = Vec::new();
+        let mut opaque = Vec::new();
+
+        let mut archive = tar::Archive::new(decompressed(blob)?);
+        for member in archive.entries()? {
+            let mut member = member?;
+            let path = spec_path(&normalize(&member.path()?));
+
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                let parent = path.parent().unwrap_or(Path::new("."));
+
+                if name == OPAQUE_WHITEOUT {
+                    opaque.push(parent.to_path_buf());
+                    continue;
+                }
+                if let Some(deleted) = name.strip_prefix(WHITEOUT_PREFIX) {
+                    whiteouts.push(parent.join(deleted));
+                    continue;
+                }
+            }
+
+            let Some(ty) = member_type(member.header().entry_type()) else {
+                continue;
+            };
+
+            let mut found = member_keywords(&mut member, ty, keywords)?;
+            if keywords.contains(KeywordSet::TAR_TIME) {
+                found = found
+                    .into_iter()
+                    .map(|keyword| match keyword {
+                        Keyword::Time(time) => Keyword::TarTime(time),
+                        keyword => keyword,
+                    })
+                    .collect();
+            }
+            found.retain(|keyword| keywords.contains_kind(keyword.kind()));
+
+            let entry = Entry {
+                path: path.clone(),
+                keywords: found,
+            };
+
+            // A later member with the same path replaces the earlier one.
+            match by_path.get(&path) {
+                Some(&idx) => entries[idx] = entry,
+                None => {
+                    by_path.insert(path, entries.len());
+                    entries.push(entry);
+                }
+            }
+        }
+
+        let lines = entries
+            .into_iter()
+            .enumerate()
+            .map(|(idx, entry)| Line::Entry {
+                id: EntryId(idx as u64),
+                entry,
+                span: Span::default(),
+            })
+            .collect();
+
+        Ok(Layer {
+            spec: Spec::new(lines),
+            whiteouts,
+            opaque,
+        })
+    }
+
+    /// The entries that differ between this layer and `newer`. Whiteouts
+    /// aren't part of the comparison.
+    pub fn diff(&self, newer: &Layer) -> SpecDiff {
+        diff(&self.spec, &newer.spec)
+    }
+}
+
+fn spec_path(normalized: &Path) -> PathBuf {
+    if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        Path::new(".").join(normalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1700000000);
+            header.set_entry_type(tar::EntryType::Regular);
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_layer() {
+        let keywords = KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::CKSUM;
+
+        let old = layer(&[("etc/motd", b"hello"), ("etc/hosts", b"")]);
+        let old = Layer::read(old.as_slice(), keywords).unwrap();
+        assert_eq!(
+            old.spec.to_string(),
+            "./etc/motd type=file size=5 cksum=3287646509\n\
+             ./etc/hosts type=file size=0 cksum=4294967295\n"
+        );
+
+        let new = layer(&[
+            ("etc/motd", b"hello!"),
+            ("etc/.wh.hosts", b""),
+            ("var/cache/.wh..wh..opq", b""),
+        ]);
+        let new = Layer::read(new.as_slice(), keywords).unwrap();
+        assert_eq!(new.whiteouts, [PathBuf::from("./etc/hosts")]);
+        assert_eq!(new.opaque, [PathBuf::from("./var/cache")]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! Restricting a walk to a set of files, like `mtree -O`.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// The only files to create entries for or verify.
+///
+/// As in mtree, the directories leading up to a listed file are included so
+/// it can be reached, but the contents of a listed directory aren't.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OnlyList {
+    listed: HashSet<PathBuf>,
+    parents: HashSet<PathBuf>,
+}
+
+impl OnlyList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads one spec path per line, skipping empty lines, like the file
+    /// given to `-O`.
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut list = OnlyList::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let path = line.strip_suffix('\r').unwrap_or(&line);
+
+            if !path.is_empty() {
+                list.insert(path);
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Adds a spec path, with or without the leading `./`.
+    pub fn insert(&mut self, path: impl AsRef<Path>) {
+        let path = normalize(path.as_ref());
+
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() || !self.parents.insert(parent.to_path_buf()) {
+                break;
+            }
+        }
+
+        self.listed.insert(path);
+    }
+
+    pub fn len(&self) -> usize {
+        self.listed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listed.is_empty()
+    }
+
+    /// Whether `path` is listed or leads to a listed file. The root is always
+    /// included.
+    pub fn contains(&self, path: &Path) -> bool {
+        let path = normalize(path);
+
+        path == Path::new(".") || self.listed.contains(&path) || self.parents.contains(&path)
+    }
+}
+
+impl<P: AsRef<Path>> FromIterator<P> for OnlyList {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut list = OnlyList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<P: AsRef<Path>> Extend<P> for OnlyList {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        for path in iter {
+            self.insert(path);
+        }
+    }
+}
+
+/// Spells `path` as `./a/b`, the way the walker names files.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    Path::new(".").join(path.strip_prefix(".").unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_list() {
+        let list = OnlyList::from_reader("./usr/bin/ls\n\netc/passwd\r\n".as_bytes()).unwrap();
+        assert_eq!(list.len(), 2);
+
+        for path in [
+            ".",
+            "./usr",
+            "usr/bin",
+            "./usr/bin/ls",
+            "./etc",
+            "./etc/passwd",
+        ] {
+            assert!(list.contains(Path::new(path)), "{path}");
+        }
+        for path in ["./usr/bin/cat", "./usr/lib", "./etc/passwd/x", "./var"] {
+            assert!(!list.contains(Path::new(path)), "{path}");
+        }
+
+        assert_eq!(
+            ["./usr/bin/ls", "etc/passwd"]
+                .into_iter()
+                .collect::<OnlyList>(),
+            list
+        );
+    }
+}
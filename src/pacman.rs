@@ -0,0 +1,186 @@
+//! Checking installed Arch Linux packages against the mtree files pacman
+//! keeps for them in its local database, like `paccheck --file-properties`.
+//!
+//! Each installed package has a directory `<name>-<version>` under
+//! [`LOCAL_DB`] whose gzip-compressed `mtree` file is its `.MTREE`, with
+//! paths relative to the root the package was installed into.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::verify::Verifier;
+use crate::{PackageSummary, ReadError, Spec, VerifyReport};
+
+/// Where pacman keeps the database of installed packages.
+pub const LOCAL_DB: &str = "/var/lib/pacman/local";
+
+/// A package in pacman's local database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalPackage {
+    pub name: String,
+    /// The full version, `[epoch:]pkgver-pkgrel`.
+    pub version: String,
+    /// The package's directory in the database.
+    pub path: PathBuf,
+}
+
+impl LocalPackage {
+    /// Reads the package's `mtree` file, leaving out the package's metadata
+    /// files as [`Spec::from_arch_mtree`] does.
+    pub fn read_mtree(&self) -> Result<Spec, ReadError> {
+        Spec::from_arch_mtree(File::open(self.path.join("mtree"))?)
+    }
+}
+
+/// The outcome of checking one package.
+#[derive(Debug)]
+pub struct PackageReport {
+    pub package: LocalPackage,
+    /// The findings, or why the package's `mtree` couldn't be read.
+    pub report: Result<VerifyReport, ReadError>,
+    /// Counts of the package's files by state. All zero if the `mtree`
+    /// couldn't be read.
+    pub summary: PackageSummary,
+}
+
+/// The packages in the local database at `db`, such as [`LOCAL_DB`], sorted
+/// by name. Entries that aren't package directories, like
+/// `ALPM_DB_VERSION`, are skipped.
+pub fn local_packages(db: impl AsRef<Path>) -> io::Result<Vec<LocalPackage>> {
+    let mut packages = Vec::new();
+
+    for entry in fs::read_dir(db)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let dir_name = entry.file_name();
+        let Some((name, version)) = dir_name.to_str().and_then(split_name) else {
+            continue;
+        };
+
+        packages.push(LocalPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            path: entry.path(),
+        });
+    }
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+/// Splits `<name>-<pkgver>-<pkgrel>` into the name and the version. Neither
+/// `pkgver` nor `pkgrel` may contain a `-`, but names can.
+fn split_name(dir_name: &str) -> Option<(&str, &str)> {
+    let mut parts = dir_name.rsplitn(3, '-');
+    let (_, _, name) = (parts.next()?, parts.next()?, parts.next()?);
+
+    match name.is_empty() {
+        true => None,
+        false => Some((name, &dir_name[name.len() + 1..])),
+    }
+}
+
+/// Checks each of `packages` against the files under the verifier's root,
+/// returning a report per package in the same order.
+///
+/// With the `rayon` feature, packages are checked in parallel on rayon's
+/// thread pool. A package whose `mtree` can't be read doesn't stop the
+/// others.
+pub fn verify_packages(packages: &[LocalPackage], verifier: &Verifier) -> Vec<PackageReport> {
+    let check = |package: &LocalPackage| {
+        let (report, summary) = match package.read_mtree() {
+            Ok(spec) => {
+                let report = verifier.verify(&spec);
+                let summary = report.package_summary(&package.name, &spec);
+                (Ok(report), summary)
+            }
+            Err(err) => {
+                let summary = PackageSummary {
+                    package: package.name.clone(),
+                    ..Default::default()
+                };
+                (Err(err), summary)
+            }
+        };
+
+        PackageReport {
+            package: package.clone(),
+            report,
+            summary,
+        }
+    };
+
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+
+        packages.par_iter().map(check).collect()
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        packages.iter().map(check).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RootPath;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_split_name() {
+        assert_eq!(split_name("bash-5.2.037-1"), Some(("bash", "5.2.037-1")));
+        assert_eq!(
+            split_name("python-pip-1:24.0-2"),
+            Some(("python-pip", "1:24.0-2"))
+        );
+        assert_eq!(split_name("5.2-1"), None);
+        assert_eq!(split_name("ALPM_DB_VERSION"), None);
+    }
+
+    #[test]
+    fn test_verify_packages() {
+        let root = TempDir::new();
+        root.file("usr/bin/hello", "hello\n");
+
+        let db = TempDir::new();
+        db.file("ALPM_DB_VERSION", "9\n");
+        db.file(
+            "hello-1.0-1/mtree",
+            "/set type=file\n\
+             ./.PKGINFO size=100\n\
+             ./usr type=dir\n\
+             ./usr/bin type=dir\n\
+             ./usr/bin/hello size=6\n",
+        );
+        db.file("gone-2.0-1/mtree", "./usr type=dir\n./usr/lib type=dir\n");
+        db.file("broken-1-1/desc", "%NAME%\nbroken\n");
+
+        let packages = local_packages(db.path()).unwrap();
+        assert_eq!(
+            packages
+                .iter()
+                .map(|package| (package.name.as_str(), package.version.as_str()))
+                .collect::<Vec<_>>(),
+            [("broken", "1-1"), ("gone", "2.0-1"), ("hello", "1.0-1")]
+        );
+
+        let verifier = Verifier::new(RootPath::new(root.path()));
+        let reports = verify_packages(&packages, &verifier);
+
+        assert!(matches!(reports[0].report, Err(ReadError::Io(_))));
+        assert_eq!(reports[0].summary.total(), 0);
+
+        assert_eq!(reports[1].summary.missing, 1);
+        assert_eq!(reports[1].summary.clean, 1);
+
+        assert!(reports[2].report.as_ref().unwrap().is_clean());
+        assert_eq!(reports[2].summary.total(), 3);
+    }
+}
@@ -0,0 +1,159 @@
+//! Reporting how far [`SpecBuilder::build`] and [`Verifier::verify`] have
+//! got, so that a CLI or TUI can show a progress bar.
+//!
+//! [`SpecBuilder::build`]: crate::SpecBuilder::build
+//! [`Verifier::verify`]: crate::verify::Verifier::verify
+
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+
+/// Receives progress updates. With the `rayon` feature, `file` and `bytes`
+/// may be called from several threads at once.
+pub trait Progress: Send + Sync {
+    /// The number of files that will be processed, once it's known.
+    fn start(&self, _files: u64) {}
+
+    /// Processing of the file at spec path `path` is starting.
+    fn file(&self, _path: &Path) {}
+
+    /// `bytes` more bytes of file contents have been read. A file is read
+    /// once for `cksum` and once more for digests, so this can add up to
+    /// more than the files' sizes.
+    fn bytes(&self, _bytes: u64) {}
+}
+
+/// Ignores all updates.
+impl Progress for () {}
+
+impl<P: Progress + ?Sized> Progress for Arc<P> {
+    fn start(&self, files: u64) {
+        (**self).start(files)
+    }
+
+    fn file(&self, path: &Path) {
+        (**self).file(path)
+    }
+
+    fn bytes(&self, bytes: u64) {
+        (**self).bytes(bytes)
+    }
+}
+
+/// A progress update, for sending over a channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Start(u64),
+    File(PathBuf),
+    Bytes(u64),
+}
+
+/// Sends every update as a [`ProgressEvent`], ignoring a closed channel.
+impl Progress for mpsc::Sender<ProgressEvent> {
+    fn start(&self, files: u64) {
+        let _ = self.send(ProgressEvent::Start(files));
+    }
+
+    fn file(&self, path: &Path) {
+        let _ = self.send(ProgressEvent::File(path.to_path_buf()));
+    }
+
+    fn bytes(&self, bytes: u64) {
+        let _ = self.send(ProgressEvent::Bytes(bytes));
+    }
+}
+
+/// Keeps running totals that another thread can poll, e.g. to redraw a
+/// progress bar a few times a second. Pass it in an [`Arc`] to keep a
+/// handle to it.
+#[derive(Debug, Default)]
+pub struct ProgressCounter {
+    total: AtomicU64,
+    files: AtomicU64,
+    bytes: AtomicU64,
+    current: Mutex<PathBuf>,
+}
+
+impl ProgressCounter {
+    pub fn new() -> Self {
+        ProgressCounter::default()
+    }
+
+    /// The number of files to process, or 0 if not known yet.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// The number of files started so far.
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes of file contents read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The spec path of the file started most recently.
+    pub fn current(&self) -> PathBuf {
+        self.current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+impl Progress for ProgressCounter {
+    fn start(&self, files: u64) {
+        self.total.store(files, Ordering::Relaxed);
+    }
+
+    fn file(&self, path: &Path) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        *self.current.lock().unwrap_or_else(|err| err.into_inner()) = path.to_path_buf();
+    }
+
+    fn bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+/// A shared [`Progress`] that builders can store and clone.
+#[derive(Clone)]
+pub(crate) struct ProgressSink(pub(crate) Arc<dyn Progress>);
+
+impl Default for ProgressSink {
+    fn default() -> Self {
+        ProgressSink(Arc::new(()))
+    }
+}
+
+impl fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressSink(..)")
+    }
+}
+
+/// Reports the bytes read through it.
+pub(crate) struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a dyn Progress,
+}
+
+impl<'a, R> ProgressReader<'a, R> {
+    pub(crate) fn new(inner: R, progress: &'a dyn Progress) -> Self {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.progress.bytes(n as u64);
+        }
+        Ok(n)
+    }
+}
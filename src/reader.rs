@@ -0,0 +1,406 @@
+//! Parsing specs incrementally from a reader.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::intern::Interner;
+use crate::limits::{LimitExceeded, ParseLimits};
+use crate::line::{LineJoiner, parse_line};
+use crate::{Command, EntryId, Line, ParseError, Span, Spec};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Limit(LimitExceeded),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{err}"),
+            ReadError::Parse(err) => write!(f, "{err}"),
+            ReadError::Limit(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::Parse(err) => Some(err),
+            ReadError::Limit(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> Self {
+        ReadError::Parse(err)
+    }
+}
+
+impl From<LimitExceeded> for ReadError {
+    fn from(err: LimitExceeded) -> Self {
+        ReadError::Limit(err)
+    }
+}
+
+/// Parses a spec one line at a time, holding only the current logical line
+/// in memory.
+///
+/// Yields the same lines, with the same spans and entry ids, as
+/// [`parse_lines`](crate::parse_lines) on the whole input. A parse error
+/// doesn't stop the iteration, but a read error or going over a limit
+/// does.
+#[derive(Debug)]
+pub struct SpecReader<R> {
+    reader: R,
+    parser: LineParser,
+    buf: String,
+    done: bool,
+    limits: ParseLimits,
+    /// Bytes and physical lines read so far.
+    read: u64,
+    lines: usize,
+}
+
+impl<R: BufRead> SpecReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, ParseLimits::default())
+    }
+
+    /// Stops with [`ReadError::Limit`] once the input goes over one of
+    /// `limits`. Lines are read no further than the line length limit, so
+    /// an overlong line is never held in memory.
+    pub fn with_limits(reader: R, limits: ParseLimits) -> Self {
+        SpecReader {
+            reader,
+            parser: LineParser::default(),
+            buf: String::new(),
+            done: false,
+            limits,
+            read: 0,
+            lines: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for SpecReader<R> {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buf.clear();
+
+            let line = match self.read_line() {
+                Ok(0) => {
+                    self.done = true;
+                    self.parser.finish().map(|line| Ok(line?))
+                }
+                Ok(_) => self.parser.push(&self.buf).map(|line| Ok(line?)),
+                Err(err) => Some(Err(err)),
+            };
+
+            if let Some(line) = line {
+                let line = line.and_then(|line| Ok(self.parser.check(line, &self.limits)?));
+                self.done |= matches!(line, Err(ReadError::Io(_) | ReadError::Limit(_)));
+                return Some(line);
+            }
+        }
+
+        None
+    }
+}
+
+impl<R: BufRead> SpecReader<R> {
+    /// Reads the next physical line into `buf`, no further than the limits
+    /// allow.
+    fn read_line(&mut self) -> Result<usize, ReadError> {
+        let limits = self.limits;
+        // Room for a line terminator, and a byte more to tell that a line
+        // or the input is too long.
+        let line_cap = limits.max_line_len.map_or(u64::MAX, |max| max as u64 + 3);
+        let input_cap = limits
+            .max_input_size
+            .map_or(u64::MAX, |max| max.saturating_sub(self.read) + 1);
+
+        let read = (&mut self.reader)
+            .take(line_cap.min(input_cap))
+            .read_line(&mut self.buf)?;
+        self.read += read as u64;
+        self.lines += 1;
+
+        if let Some(limit) = limits.max_input_size
+            && self.read > limit
+        {
+            return Err(LimitExceeded::InputSize { limit }.into());
+        }
+
+        if let Some(limit) = limits.max_line_len {
+            let content = self.buf.trim_end_matches(['\n', '\r']);
+            if self.parser.pending_len() + content.len() > limit {
+                let line = self.parser.pending_line().unwrap_or(self.lines);
+                return Err(LimitExceeded::LineLength { line, limit }.into());
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+/// Passes `reader` through, decompressing it first if it's gzip-compressed,
+/// like Arch Linux `.MTREE` files. Decompression needs the `gzip` feature;
+/// without it, compressed input is an [`io::ErrorKind::InvalidData`] error.
+pub fn decompressed<'a>(reader: impl Read + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut reader = BufReader::new(reader);
+
+    if !reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        return Ok(Box::new(reader));
+    }
+
+    #[cfg(feature = "gzip")]
+    {
+        Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(
+            reader,
+        ))))
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "input is gzip-compressed, which needs the `gzip` feature",
+        ))
+    }
+}
+
+impl Spec {
+    /// Reads a whole spec, decompressing it first if needed (see
+    /// [`decompressed`]). Stops at the first parse error.
+    pub fn from_reader(reader: impl Read) -> Result<Spec, ReadError> {
+        Self::from_reader_with_limits(reader, ParseLimits::default())
+    }
+
+    /// Like [`Spec::from_reader`], but stops once the decompressed input
+    /// goes over one of `limits`.
+    pub fn from_reader_with_limits(
+        reader: impl Read,
+        limits: ParseLimits,
+    ) -> Result<Spec, ReadError> {
+        let lines =
+            SpecReader::with_limits(decompressed(reader)?, limits).collect::<Result<_, _>>()?;
+        Ok(Spec::new(lines))
+    }
+}
+
+/// Parses physical lines as they're read, numbering the entries.
+#[derive(Debug, Default)]
+pub(crate) struct LineParser {
+    joiner: LineJoiner,
+    interner: Interner,
+    next_id: u64,
+}
+
+impl LineParser {
+    /// Adds the next physical line, including its line terminator, and
+    /// parses the logical line it completes, if any.
+    pub(crate) fn push(&mut self, raw: &str) -> Option<Result<Line, ParseError>> {
+        let (text, span) = self.joiner.push(raw)?;
+        Some(self.parse(&text, span))
+    }
+
+    /// Parses a logical line left unfinished at the end of the input.
+    pub(crate) fn finish(&mut self) -> Option<Result<Line, ParseError>> {
+        let (text, span) = self.joiner.finish()?;
+        Some(self.parse(&text, span))
+    }
+
+    /// The length and first line of a logical line still being joined.
+    pub(crate) fn pending_len(&self) -> usize {
+        self.joiner.pending().map_or(0, |(text, _)| text.len())
+    }
+
+    pub(crate) fn pending_line(&self) -> Option<usize> {
+        self.joiner.pending().map(|(_, span)| span.line)
+    }
+
+    /// Checks a parsed line against the entry and keyword limits.
+    pub(crate) fn check(&self, line: Line, limits: &ParseLimits) -> Result<Line, LimitExceeded> {
+        let keywords = match &line {
+            Line::Entry { id, entry, span } => {
+                if let Some(limit) = limits.max_entries
+                    && id.0 >= limit
+                {
+                    return Err(LimitExceeded::Entries {
+                        line: span.line,
+                        limit,
+                    });
+                }
+                entry.keywords.len()
+            }
+            Line::Command {
+                command: Command::Set(keywords),
+                ..
+            } => keywords.len(),
+            _ => 0,
+        };
+
+        match limits.max_keywords {
+            Some(limit) if keywords > limit => Err(LimitExceeded::Keywords {
+                line: line.span().line,
+                limit,
+            }),
+            _ => Ok(line),
+        }
+    }
+
+    fn parse(&mut self, text: &str, span: Span) -> Result<Line, ParseError> {
+        let mut line = parse_line(text, span)?;
+        self.interner.line(&mut line);
+
+        if let Line::Entry { id, .. } = &mut line {
+            *id = EntryId(self.next_id);
+            self.next_id += 1;
+        }
+
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_lines;
+
+    #[test]
+    fn test_spec_reader() {
+        let input = "# comment\r\n\n/set uid=0\nfoo size=1 \\\n    mode=0644\nbar\n..\nbaz \\\n";
+
+        let lines: Vec<Line> = SpecReader::new(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, parse_lines(input).unwrap());
+    }
+
+    #[test]
+    fn test_spec_reader_errors() {
+        let mut reader = SpecReader::new("foo size=x\nbar\n".as_bytes());
+
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReadError::Parse(ParseError { span, .. }))) if span.line == 1
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Ok(Line::Entry { id: EntryId(0), .. }))
+        ));
+        assert!(reader.next().is_none());
+
+        let mut reader = SpecReader::new(&b"foo\n\xff\nbar\n"[..]);
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(reader.next(), Some(Err(ReadError::Io(_)))));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_limits() {
+        let read = |input: &str, limits: ParseLimits| {
+            SpecReader::with_limits(input.as_bytes(), limits)
+                .map(|line| line.map(|_| ()))
+                .collect::<Vec<_>>()
+        };
+        let limit = |err: &Result<(), ReadError>| match err {
+            Err(ReadError::Limit(err)) => Some(err.clone()),
+            _ => None,
+        };
+
+        let lines = ParseLimits {
+            max_line_len: Some(12),
+            ..ParseLimits::default()
+        };
+        let results = read("./a size=1\n./b \\\n  size=22\n./c\n", lines);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            limit(&results[1]),
+            Some(LimitExceeded::LineLength { line: 2, limit: 12 })
+        );
+        assert_eq!(results.len(), 2);
+
+        // A line without a terminator is cut off rather than read whole.
+        let long = format!("./{}", "x".repeat(100_000));
+        let mut reader = SpecReader::with_limits(long.as_bytes(), lines);
+        assert!(matches!(reader.next(), Some(Err(ReadError::Limit(_)))));
+        assert!(reader.buf.len() < 20);
+        assert!(reader.next().is_none());
+
+        let entries = ParseLimits {
+            max_entries: Some(2),
+            max_keywords: Some(2),
+            ..ParseLimits::default()
+        };
+        let results = read("/set uid=0 gid=0\n./a\n./b\n./c\n", entries);
+        assert_eq!(
+            limit(&results[3]),
+            Some(LimitExceeded::Entries { line: 4, limit: 2 })
+        );
+        let results = read("./a uid=0 gid=0 mode=0644\n", entries);
+        assert_eq!(
+            limit(&results[0]),
+            Some(LimitExceeded::Keywords { line: 1, limit: 2 })
+        );
+
+        let size = ParseLimits {
+            max_input_size: Some(8),
+            ..ParseLimits::default()
+        };
+        let results = read("./a\n./b\n./cc\n", size);
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            limit(&results[2]),
+            Some(LimitExceeded::InputSize { limit: 8 })
+        );
+    }
+
+    #[test]
+    fn test_from_reader() {
+        // `/set type=file\n./a size=1\n`, compressed by gzip.
+        let compressed: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xd3, 0x2f, 0x4e, 0x2d,
+            0x51, 0x28, 0xa9, 0x2c, 0x48, 0xb5, 0x4d, 0xcb, 0xcc, 0x49, 0xe5, 0xd2, 0xd3, 0x4f,
+            0x54, 0x28, 0xce, 0xac, 0x4a, 0xb5, 0x35, 0xe4, 0x02, 0x00, 0x4a, 0x5a, 0x33, 0xb6,
+            0x1a, 0x00, 0x00, 0x00,
+        ];
+        let plain = "/set type=file\n./a size=1\n";
+
+        assert_eq!(
+            Spec::from_reader(plain.as_bytes()).unwrap(),
+            Spec::parse(plain).unwrap()
+        );
+
+        #[cfg(feature = "gzip")]
+        assert_eq!(
+            Spec::from_reader(compressed).unwrap(),
+            Spec::parse(plain).unwrap()
+        );
+
+        #[cfg(not(feature = "gzip"))]
+        assert!(matches!(
+            Spec::from_reader(compressed),
+            Err(ReadError::Io(err)) if err.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+}
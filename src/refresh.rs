@@ -0,0 +1,102 @@
+//! Bringing a spec up to date with the tree it describes, without reading
+//! files that haven't changed.
+
+use std::io;
+use std::path::PathBuf;
+
+use crate::diff::{SpecDiff, diff};
+use crate::{KeywordSet, Spec, SpecBuilder};
+
+impl Spec {
+    /// Walks the tree at `root` again and builds a new spec of it with the
+    /// keywords this one records, along with what changed since.
+    ///
+    /// `cksum` and digests are copied from this spec for files whose size
+    /// and modification time are unchanged, as with
+    /// [`SpecBuilder::reuse`], so only new and modified files are read.
+    /// That needs this spec to record `size` and `time`; without them every
+    /// file is read again.
+    pub fn refresh(&self, root: impl Into<PathBuf>) -> io::Result<(Spec, SpecDiff)> {
+        let keywords = self
+            .resolve()
+            .iter()
+            .flat_map(|entry| &entry.keywords)
+            .fold(KeywordSet::empty(), |set, keyword| {
+                set | KeywordSet::from_kind(keyword.kind())
+            });
+
+        let spec = SpecBuilder::from_dir(root)
+            .keywords(keywords)
+            .reuse(self)
+            .build()?;
+        let changes = diff(self, &spec);
+
+        Ok((spec, changes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::Keyword;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_refresh() {
+        let dir = TempDir::new();
+        dir.file("a", "unchanged");
+        dir.file("b", "old");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::TIME | KeywordSet::CKSUM)
+            .build()
+            .unwrap();
+
+        // A stale checksum for a file that otherwise looks unchanged is kept,
+        // which shows the file wasn't read again.
+        let mut stale = spec.clone();
+        for entry in stale.entries_mut() {
+            if entry.path.ends_with("a") {
+                for keyword in &mut entry.keywords {
+                    if let Keyword::Cksum(cksum) = keyword {
+                        *cksum = 1;
+                    }
+                }
+            }
+        }
+
+        dir.file("b", "new contents");
+        dir.file("c", "added");
+
+        let (refreshed, changes) = stale.refresh(dir.path()).unwrap();
+        let resolved = refreshed.resolve();
+
+        assert_eq!(resolved[1].path, PathBuf::from("./a"));
+        assert_eq!(resolved[1].cksum(), Some(1));
+        assert_eq!(
+            resolved[2].cksum(),
+            Some(crate::cksum::cksum(&b"new contents"[..]).unwrap())
+        );
+
+        assert_eq!(
+            changes
+                .added
+                .iter()
+                .map(|entry| &entry.path)
+                .collect::<Vec<_>>(),
+            [&PathBuf::from("./c")]
+        );
+        // The root directory may have changed too, as `c` was added to it.
+        assert_eq!(
+            changes
+                .changed
+                .iter()
+                .map(|change| &change.path)
+                .filter(|path| *path != Path::new("."))
+                .collect::<Vec<_>>(),
+            [&PathBuf::from("./b")]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::signature::{Layout, detect_layout};
+use crate::{
+    Command, Digest, DigestKind, Entry, EntryId, Keyword, KeywordKind, Line, Mode, Span, Spec,
+    Timestamp, Tree, Type, UnsetTarget,
+};
+
+/// An entry with the `/set` state in effect at its position applied, so
+/// `keywords` holds every keyword that applies to the path.
+///
+/// Resolved entries order like [`Entry`](crate::Entry), by path and then by
+/// keywords, with the id and span only breaking ties.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResolvedEntry {
+    pub id: EntryId,
+    pub path: PathBuf,
+    pub keywords: Vec<Keyword>,
+    pub span: Span,
+}
+
+impl Ord for ResolvedEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.path, &self.keywords, self.id, &self.span).cmp(&(
+            &other.path,
+            &other.keywords,
+            other.id,
+            &other.span,
+        ))
+    }
+}
+
+impl PartialOrd for ResolvedEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ResolvedEntry {
+    pub fn get(&self, kind: KeywordKind) -> Option<&Keyword> {
+        self.keywords.iter().find(|keyword| keyword.kind() == kind)
+    }
+
+    /// Whether a missing file should be tolerated rather than reported.
+    pub fn is_optional(&self) -> bool {
+        self.get(KeywordKind::Optional).is_some()
+    }
+
+    /// Whether the entry and everything below it should be skipped.
+    pub fn is_ignored(&self) -> bool {
+        self.get(KeywordKind::Ignore).is_some()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self.get(KeywordKind::Tags) {
+            Some(Keyword::Tags(tags)) => tags,
+            _ => &[],
+        }
+    }
+
+    pub fn file_type(&self) -> Option<Type> {
+        match self.get(KeywordKind::Type)? {
+            Keyword::Type(ty) => Some(ty.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        match self.get(KeywordKind::Size)? {
+            Keyword::Size(size) => Some(*size),
+            _ => None,
+        }
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        match self.get(KeywordKind::Uid)? {
+            Keyword::Uid(uid) => Some(*uid),
+            _ => None,
+        }
+    }
+
+    pub fn gid(&self) -> Option<u32> {
+        match self.get(KeywordKind::Gid)? {
+            Keyword::Gid(gid) => Some(*gid),
+            _ => None,
+        }
+    }
+
+    pub fn uname(&self) -> Option<&str> {
+        match self.get(KeywordKind::Uname)? {
+            Keyword::Uname(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn gname(&self) -> Option<&str> {
+        match self.get(KeywordKind::Gname)? {
+            Keyword::Gname(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn mode(&self) -> Option<Mode> {
+        match self.get(KeywordKind::Mode)? {
+            Keyword::Mode(mode) => Some(*mode),
+            _ => None,
+        }
+    }
+
+    /// The modification time from `time`, or failing that `tar_time`.
+    pub fn mtime(&self) -> Option<Timestamp> {
+        match self
+            .get(KeywordKind::Time)
+            .or_else(|| self.get(KeywordKind::TarTime))?
+        {
+            Keyword::Time(time) | Keyword::TarTime(time) => Some(*time),
+            _ => None,
+        }
+    }
+
+    /// The target of a symlink.
+    pub fn link(&self) -> Option<&Path> {
+        match self.get(KeywordKind::Link)? {
+            Keyword::Link(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn cksum(&self) -> Option<u32> {
+        match self.get(KeywordKind::Cksum)? {
+            Keyword::Cksum(cksum) => Some(*cksum),
+            _ => None,
+        }
+    }
+
+    pub fn digest(&self, kind: DigestKind) -> Option<&Digest> {
+        match self.get(KeywordKind::Digest(kind))? {
+            Keyword::Digest(digest) => Some(digest),
+            _ => None,
+        }
+    }
+
+    pub fn sha256(&self) -> Option<&Digest> {
+        self.digest(DigestKind::Sha256)
+    }
+}
+
+/// The cumulative keyword state built up by `/set` and `/unset` commands.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetState {
+    keywords: Vec<Keyword>,
+}
+
+impl SetState {
+    pub fn new() -> Self {
+        SetState::default()
+    }
+
+    pub fn keywords(&self) -> &[Keyword] {
+        &self.keywords
+    }
+
+    pub fn apply(&mut self, command: &Command) {
+        match command {
+            Command::Set(keywords) => overlay(&mut self.keywords, keywords),
+            Command::Unset(UnsetTarget::All) => self.keywords.clear(),
+            Command::Unset(UnsetTarget::Keywords(names)) => {
+                let kinds: Vec<KeywordKind> = names
+                    .iter()
+                    .filter_map(|name| KeywordKind::from_name(name))
+                    .collect();
+                // `xattr.<name>` unsets just that attribute.
+                let xattrs: Vec<&str> = names
+                    .iter()
+                    .filter_map(|name| name.strip_prefix("xattr."))
+                    .collect();
+
+                self.keywords.retain(|keyword| match keyword {
+                    Keyword::Xattr { name, .. } => {
+                        !kinds.contains(&KeywordKind::Xattr) && !xattrs.contains(&name.as_str())
+                    }
+                    keyword => !kinds.contains(&keyword.kind()),
+                });
+            }
+        }
+    }
+
+    pub fn materialize(&self, keywords: &[Keyword]) -> Vec<Keyword> {
+        let mut resolved = self.keywords.clone();
+        overlay(&mut resolved, keywords);
+        resolved
+    }
+}
+
+impl Tree {
+    /// Returns the nodes that are described by an entry, with their full
+    /// paths, in depth-first order.
+    ///
+    /// This follows the relative form of mtree(5) even in specs without `..`
+    /// entries, so an entry inside a `type=dir` block always gets the path
+    /// of its directory prepended.
+    pub fn resolved(&self) -> Vec<ResolvedEntry> {
+        self.iter()
+            .filter_map(|node| {
+                Some(ResolvedEntry {
+                    id: node.id?,
+                    path: node.path.clone(),
+                    keywords: node.keywords.clone(),
+                    span: node.span.clone()?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Adds `keywords` to `base`, replacing any keyword for the same property,
+/// see [`Keyword::same_property`].
+pub(crate) fn overlay(base: &mut Vec<Keyword>, keywords: &[Keyword]) {
+    for keyword in keywords {
+        match base.iter_mut().find(|k| k.same_property(keyword)) {
+            Some(existing) => *existing = keyword.clone(),
+            None => base.push(keyword.clone()),
+        }
+    }
+}
+
+impl Spec {
+    /// Applies the `/set` state to every entry and gives it its full path.
+    ///
+    /// Paths follow mtree(5), through a [`Tree`]: a bare name is placed in
+    /// the current directory, a bare-named `type=dir` entry descends into
+    /// it and `..` goes back up, while a name with a `/` is a full path from
+    /// the root. Paths come out spelled `./a/b`, in depth-first order, and
+    /// where several entries describe one path only the last is kept.
+    /// Everything that works on resolved entries goes through here, so they
+    /// all agree on which path an entry describes.
+    pub fn resolve(&self) -> Vec<ResolvedEntry> {
+        Tree::from_spec(self).resolved()
+    }
+
+    /// The spec's lines with every entry named by its full path. Specs in
+    /// the relative form are resolved, so their entries carry their
+    /// defaults and the comments and commands are dropped; other specs are
+    /// borrowed as they are.
+    pub(crate) fn full_path_lines(&self) -> Cow<'_, [Line]> {
+        if detect_layout(&self.lines) != Layout::Relative {
+            return Cow::Borrowed(&self.lines);
+        }
+
+        self.resolve()
+            .into_iter()
+            .map(|entry| Line::Entry {
+                id: entry.id,
+                entry: Entry {
+                    path: entry.path,
+                    keywords: entry.keywords,
+                },
+                span: entry.span,
+            })
+            .collect()
+    }
+
+    /// Resolves the spec and orders the entries by `priority`, lowest first.
+    /// Entries with equal priority keep the order [`Spec::resolve`] gives
+    /// them, which is depth-first tree order rather than the order of the
+    /// lines in the spec.
+    pub fn resolve_by_priority<P: Ord>(
+        &self,
+        priority: impl FnMut(&ResolvedEntry) -> P,
+    ) -> Vec<ResolvedEntry> {
+        let mut resolved = self.resolve();
+        resolved.sort_by_cached_key(priority);
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Type;
+
+    #[test]
+    fn test_resolve() {
+        let spec = Spec::parse(
+            "/set type=file uid=0\n\
+             foo size=1\n\
+             bar type=dir\n\
+             /unset uid\n\
+             baz\n\
+             /set uid=5\n\
+             qux uid=7\n\
+             /unset all\n\
+             quux\n",
+        )
+        .unwrap();
+
+        let resolved: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .map(|entry| (entry.path, entry.keywords))
+            .collect();
+
+        assert_eq!(
+            resolved,
+            vec![
+                (
+                    PathBuf::from("./foo"),
+                    vec![Keyword::Type(Type::File), Keyword::Uid(0), Keyword::Size(1)]
+                ),
+                (
+                    PathBuf::from("./bar"),
+                    vec![Keyword::Type(Type::Dir), Keyword::Uid(0)]
+                ),
+                (PathBuf::from("./bar/baz"), vec![Keyword::Type(Type::File)]),
+                (
+                    PathBuf::from("./bar/qux"),
+                    vec![Keyword::Type(Type::File), Keyword::Uid(7)]
+                ),
+                (PathBuf::from("./bar/quux"), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accessors() {
+        let spec = Spec::parse(
+            "/set type=file uid=0 gname=wheel\n\
+             foo size=3 mode=0644 tar_time=10.0 \
+             sha256=ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n\
+             bar type=link link=foo time=20.500000000\n",
+        )
+        .unwrap();
+        let resolved = spec.resolve();
+
+        let foo = &resolved[0];
+        assert_eq!(foo.file_type(), Some(Type::File));
+        assert_eq!(foo.size(), Some(3));
+        assert_eq!(foo.uid(), Some(0));
+        assert_eq!(foo.gid(), None);
+        assert_eq!(foo.gname(), Some("wheel"));
+        assert_eq!(foo.mode(), Mode::new(0o644));
+        assert_eq!(foo.mtime(), Some(Timestamp::new(10, 0)));
+        assert_eq!(
+            foo.sha256().map(Digest::to_string).as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(foo.link(), None);
+
+        let bar = &resolved[1];
+        assert_eq!(bar.file_type(), Some(Type::Link));
+        assert_eq!(bar.link(), Some(Path::new("foo")));
+        assert_eq!(bar.mtime(), Some(Timestamp::new(20, 500_000_000)));
+        assert_eq!(bar.sha256(), None);
+    }
+
+    #[test]
+    fn test_resolve_span() {
+        let spec = Spec::parse("/set uid=0\nfoo\n").unwrap();
+        let resolved = spec.resolve();
+
+        assert_eq!(resolved[0].span.line, 2);
+        assert_eq!(resolved[0].get(KeywordKind::Uid), Some(&Keyword::Uid(0)));
+    }
+
+    #[test]
+    fn test_resolve_by_priority() {
+        let spec = Spec::parse("var/cache/a\nusr/bin/ls\netc/passwd\nvar/cache/b\n").unwrap();
+
+        let paths: Vec<_> = spec
+            .resolve_by_priority(|entry| {
+                if entry.path.starts_with("./usr/bin") || entry.path.starts_with("./etc") {
+                    0
+                } else if entry.path.starts_with("./var/cache") {
+                    2
+                } else {
+                    1
+                }
+            })
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("./usr/bin/ls"),
+                PathBuf::from("./etc/passwd"),
+                PathBuf::from("./var/cache/a"),
+                PathBuf::from("./var/cache/b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_resolved() {
+        let spec = Spec::parse("/set uid=0\netc type=dir\n    passwd\n..\n./usr/bin/ls\n").unwrap();
+
+        let resolved: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .map(|entry| (entry.id, entry.path))
+            .collect();
+
+        assert_eq!(
+            resolved,
+            vec![
+                (EntryId(0), PathBuf::from("./etc")),
+                (EntryId(1), PathBuf::from("./etc/passwd")),
+                (EntryId(3), PathBuf::from("./usr/bin/ls")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_verification_keywords() {
+        let spec = Spec::parse(
+            "/set optional tags=base
+foo ignore
+/unset optional
+bar
+",
+        )
+        .unwrap();
+        let resolved = spec.resolve();
+
+        assert!(resolved[0].is_optional());
+        assert!(resolved[0].is_ignored());
+        assert_eq!(resolved[0].tags(), ["base".to_string()]);
+        assert!(!resolved[1].is_optional());
+        assert!(!resolved[1].is_ignored());
+    }
+
+    #[test]
+    fn test_unset_xattr() {
+        let spec = Spec::parse(
+            "/set xattr.user.a=YQ== xattr.user.b=Yg== uid=0\n\
+             /unset xattr.user.a\n\
+             foo\n\
+             /unset xattr\n\
+             bar\n",
+        )
+        .unwrap();
+        let resolved = spec.resolve();
+
+        assert_eq!(
+            resolved[0].keywords,
+            vec![
+                Keyword::Xattr {
+                    name: "user.b".to_string(),
+                    value: b"b".to_vec(),
+                },
+                Keyword::Uid(0),
+            ]
+        );
+        assert_eq!(resolved[1].keywords, vec![Keyword::Uid(0)]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::{Keyword, NodeId, Spec, Tree};
+
+impl Tree {
+    /// Computes the total `size` of all non-directory descendants of each
+    /// directory, keyed by the directory's full path.
+    pub fn rollup_sizes(&self) -> BTreeMap<PathBuf, u64> {
+        let mut totals = BTreeMap::new();
+        self.rollup_node(self.root(), &mut totals);
+        totals
+    }
+
+    fn rollup_node(&self, id: NodeId, totals: &mut BTreeMap<PathBuf, u64>) -> u64 {
+        let node = self.node(id);
+
+        if !node.is_dir() && node.children.is_empty() {
+            return node
+                .keywords
+                .iter()
+                .find_map(|keyword| match keyword {
+                    Keyword::Size(size) => Some(*size),
+                    _ => None,
+                })
+                .unwrap_or(0);
+        }
+
+        let total = node
+            .children
+            .iter()
+            .map(|child| self.rollup_node(*child, totals))
+            .sum();
+
+        totals.insert(node.path.clone(), total);
+        total
+    }
+}
+
+impl Spec {
+    /// Computes cumulative file sizes per directory without touching the
+    /// filesystem. See [`Tree::rollup_sizes`].
+    pub fn rollup_sizes(&self) -> BTreeMap<PathBuf, u64> {
+        Tree::from_spec(self).rollup_sizes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_sizes() {
+        let spec = Spec::parse(
+            "/set type=file\n\
+             . type=dir size=4096\n\
+             etc type=dir size=4096\n\
+             passwd size=100\n\
+             ssl type=dir\n\
+             cert.pem size=1000\n\
+             ..\n\
+             ..\n\
+             README size=10\n\
+             empty type=dir\n",
+        )
+        .unwrap();
+
+        let sizes = spec.rollup_sizes();
+
+        assert_eq!(
+            sizes.into_iter().collect::<Vec<_>>(),
+            vec![
+                (PathBuf::from("."), 1110),
+                (PathBuf::from("./empty"), 0),
+                (PathBuf::from("./etc"), 1100),
+                (PathBuf::from("./etc/ssl"), 1000),
+            ]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Mapping spec paths onto a directory on disk.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::sanitize;
+
+/// Maximum number of symlinks followed while resolving a single path, as on
+/// Linux.
+const MAX_LINKS: usize = 40;
+
+/// How absolute paths in a spec, such as `/usr/bin/ls`, are treated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AbsolutePaths {
+    /// Absolute paths are an error.
+    #[default]
+    Reject,
+    /// Absolute paths are taken relative to the root, like `./usr/bin/ls`.
+    UnderRoot,
+}
+
+/// A directory that spec paths are resolved against.
+///
+/// Spec paths are always relative to the root, never to the current working
+/// directory: a leading `./` is stripped, and `..` components that would
+/// climb out of the root are rejected.
+///
+/// With [`RootPath::jail`] enabled, symlinks in the directories leading up to
+/// a file are also resolved within the root, as if it were a chroot: absolute
+/// link targets start at the root and `..` stops there. The final component is
+/// never followed, so a symlink entry is checked as a symlink.
+#[derive(Clone, Debug)]
+pub struct RootPath {
+    path: PathBuf,
+    absolute: AbsolutePaths,
+    jail: bool,
+}
+
+#[derive(Debug)]
+pub enum RootError {
+    /// The spec path is absolute and [`AbsolutePaths::Reject`] is in effect.
+    Absolute(PathBuf),
+    /// The spec path uses `..` to leave the root.
+    EscapesRoot(PathBuf),
+    /// Too many symlinks were followed while resolving the path.
+    TooManyLinks(PathBuf),
+    Io(io::Error),
+}
+
+impl fmt::Display for RootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RootError::Absolute(path) => write!(f, "{}: absolute path", sanitize::path(path)),
+            RootError::EscapesRoot(path) => {
+                write!(f, "{}: path escapes the root", sanitize::path(path))
+            }
+            RootError::TooManyLinks(path) => {
+                write!(
+                    f,
+                    "{}: too many levels of symbolic links",
+                    sanitize::path(path)
+                )
+            }
+            RootError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RootError {}
+
+impl From<io::Error> for RootError {
+    fn from(err: io::Error) -> Self {
+        RootError::Io(err)
+    }
+}
+
+impl From<RootError> for io::Error {
+    fn from(err: RootError) -> Self {
+        match err {
+            RootError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
+
+impl RootPath {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        RootPath {
+            path: path.into(),
+            absolute: AbsolutePaths::default(),
+            jail: false,
+        }
+    }
+
+    pub fn absolute_paths(mut self, absolute: AbsolutePaths) -> Self {
+        self.absolute = absolute;
+        self
+    }
+
+    pub fn jail(mut self, jail: bool) -> Self {
+        self.jail = jail;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_jail(&self) -> bool {
+        self.jail
+    }
+
+    /// Returns the location on disk of the spec path `path`.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, RootError> {
+        let components = self.components(path)?;
+
+        if !self.jail {
+            return Ok(self.path.join(components.iter().collect::<PathBuf>()));
+        }
+
+        let mut resolved: Vec<OsString> = Vec::new();
+        let mut pending = components;
+        pending.reverse();
+        let mut links = 0;
+
+        while let Some(name) = pending.pop() {
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+
+            resolved.push(name);
+
+            // The last component isn't followed.
+            if pending.is_empty() {
+                break;
+            }
+
+            let current = self.path.join(resolved.iter().collect::<PathBuf>());
+            if !fs::symlink_metadata(&current).is_ok_and(|meta| meta.is_symlink()) {
+                continue;
+            }
+
+            links += 1;
+            if links > MAX_LINKS {
+                return Err(RootError::TooManyLinks(path.to_path_buf()));
+            }
+
+            let target = fs::read_link(&current)?;
+            resolved.pop();
+
+            if target.has_root() {
+                resolved.clear();
+            }
+
+            for component in target.components().rev() {
+                match component {
+                    Component::Normal(name) => pending.push(name.to_os_string()),
+                    Component::ParentDir => pending.push(OsString::from("..")),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(self.path.join(resolved.iter().collect::<PathBuf>()))
+    }
+
+    /// Normalizes a spec path into a path relative to the root, without
+    /// touching the filesystem.
+    pub(crate) fn relative(&self, path: &Path) -> Result<PathBuf, RootError> {
+        Ok(self.components(path)?.iter().collect())
+    }
+
+    /// Normalizes a spec path into plain components, without touching the
+    /// filesystem.
+    fn components(&self, path: &Path) -> Result<Vec<OsString>, RootError> {
+        let mut components = Vec::new();
+
+        for component in path.components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => {
+                    if self.absolute == AbsolutePaths::Reject {
+                        return Err(RootError::Absolute(path.to_path_buf()));
+                    }
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if components.pop().is_none() {
+                        return Err(RootError::EscapesRoot(path.to_path_buf()));
+                    }
+                }
+                Component::Normal(name) => components.push(name.to_os_string()),
+            }
+        }
+
+        Ok(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_resolve() {
+        let root = RootPath::new("/srv/root");
+
+        assert_eq!(
+            root.resolve(Path::new("./usr/bin/ls")).unwrap(),
+            PathBuf::from("/srv/root/usr/bin/ls")
+        );
+        assert_eq!(
+            root.resolve(Path::new("usr/lib/../bin")).unwrap(),
+            PathBuf::from("/srv/root/usr/bin")
+        );
+        assert_eq!(
+            root.resolve(Path::new(".")).unwrap(),
+            PathBuf::from("/srv/root")
+        );
+        assert!(matches!(
+            root.resolve(Path::new("./usr/../../etc")),
+            Err(RootError::EscapesRoot(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_absolute() {
+        let path = Path::new("/usr/bin/ls");
+
+        assert!(matches!(
+            RootPath::new("/srv/root").resolve(path),
+            Err(RootError::Absolute(_))
+        ));
+        assert_eq!(
+            RootPath::new("/srv/root")
+                .absolute_paths(AbsolutePaths::UnderRoot)
+                .resolve(path)
+                .unwrap(),
+            PathBuf::from("/srv/root/usr/bin/ls")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_jail() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        dir.file("real/file", "");
+        symlink("/real", dir.path().join("absolute")).unwrap();
+        symlink("../../../real", dir.path().join("climbing")).unwrap();
+        symlink("file", dir.path().join("real/last")).unwrap();
+
+        let root = RootPath::new(dir.path()).jail(true);
+        let expected = dir.path().join("real/file");
+
+        assert_eq!(
+            root.resolve(Path::new("./absolute/file")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            root.resolve(Path::new("./climbing/file")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            root.resolve(Path::new("./real/last")).unwrap(),
+            dir.path().join("real/last")
+        );
+
+        symlink("loop", dir.path().join("loop")).unwrap();
+        assert!(matches!(
+            root.resolve(Path::new("./loop/file")),
+            Err(RootError::TooManyLinks(_))
+        ));
+    }
+}
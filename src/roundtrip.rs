@@ -0,0 +1,74 @@
+//! Checking that a spec survives being parsed and written back out, for
+//! testing the crate against a corpus of real specs.
+
+use crate::{ParseError, Spec, SpecDiff, diff};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fidelity {
+    /// Writing the parsed spec gives back the input byte for byte.
+    Identical,
+    /// The output is formatted differently but describes the same entries.
+    Equivalent,
+    /// The output describes different entries. The diff goes from the input
+    /// to the output, so `removed` lists what was lost.
+    Lossy(SpecDiff),
+    /// The input didn't parse.
+    Invalid(ParseError),
+    /// The input parsed but the output didn't.
+    Unreadable(ParseError),
+}
+
+impl Fidelity {
+    /// Whether nothing was lost, though formatting may have changed.
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, Fidelity::Identical | Fidelity::Equivalent)
+    }
+}
+
+/// Parses `input`, writes it back out, parses the output and compares the
+/// three.
+pub fn check(input: &str) -> Fidelity {
+    let spec = match Spec::parse(input) {
+        Ok(spec) => spec,
+        Err(err) => return Fidelity::Invalid(err),
+    };
+
+    let output = spec.to_string();
+    if output == input {
+        return Fidelity::Identical;
+    }
+
+    let reparsed = match Spec::parse(&output) {
+        Ok(reparsed) => reparsed,
+        Err(err) => return Fidelity::Unreadable(err),
+    };
+
+    let diff = diff(&spec, &reparsed);
+    if diff.is_empty() {
+        Fidelity::Equivalent
+    } else {
+        Fidelity::Lossy(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check("#mtree\n/set uid=0\n./usr/bin/ls type=file mode=0755\n"),
+            Fidelity::Identical
+        );
+
+        let fidelity = check("/set uid=0\n./usr/bin/ls type=file mode=0755");
+        assert_eq!(fidelity, Fidelity::Equivalent);
+        assert!(fidelity.is_lossless());
+
+        assert!(matches!(
+            check("./foo size=abc\n"),
+            Fidelity::Invalid(ParseError { .. })
+        ));
+    }
+}
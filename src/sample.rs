@@ -0,0 +1,131 @@
+use crate::{Keyword, KeywordKind, Mode, ResolvedEntry, Spec};
+
+/// Selects a deterministic, seeded subset of entries for spot checks.
+///
+/// Whether an entry is picked depends only on the seed and its path, so the
+/// same seed always yields the same sample. Changing the seed between runs
+/// (e.g. using the day number) spreads coverage over the whole spec.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sampling {
+    pub fraction: f64,
+    pub seed: u64,
+    /// Always include setuid and setgid entries, regardless of the fraction.
+    pub include_setuid: bool,
+}
+
+impl Sampling {
+    pub fn new(fraction: f64) -> Self {
+        Sampling {
+            fraction: fraction.clamp(0.0, 1.0),
+            seed: 0,
+            include_setuid: true,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn include_setuid(mut self, include: bool) -> Self {
+        self.include_setuid = include;
+        self
+    }
+
+    pub fn includes(&self, entry: &ResolvedEntry) -> bool {
+        if self.include_setuid && is_setuid(entry) {
+            return true;
+        }
+
+        let bytes = entry.path.as_os_str().as_encoded_bytes();
+        let position = mix(self.seed, bytes) as f64 / u64::MAX as f64;
+
+        position < self.fraction
+    }
+
+    pub fn select(&self, entries: Vec<ResolvedEntry>) -> Vec<ResolvedEntry> {
+        entries
+            .into_iter()
+            .filter(|entry| self.includes(entry))
+            .collect()
+    }
+}
+
+impl Spec {
+    pub fn sample(&self, sampling: &Sampling) -> Vec<ResolvedEntry> {
+        sampling.select(self.resolve())
+    }
+}
+
+fn is_setuid(entry: &ResolvedEntry) -> bool {
+    match entry.get(KeywordKind::Mode) {
+        Some(Keyword::Mode(mode)) => mode.bits() & (Mode::SETUID | Mode::SETGID) != 0,
+        _ => false,
+    }
+}
+
+/// FNV-1a over the seed and `bytes`, followed by a splitmix64 finalizer so
+/// similar paths end up far apart.
+fn mix(seed: u64, bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+
+    for byte in seed.to_le_bytes().iter().chain(bytes) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(count: usize) -> Spec {
+        let text: String = (0..count).map(|i| format!("file{i} size=1\n")).collect();
+        Spec::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn test_sample_fraction() {
+        let spec = spec(1000);
+
+        assert!(spec.sample(&Sampling::new(0.0)).is_empty());
+        assert_eq!(spec.sample(&Sampling::new(1.0)).len(), 1000);
+
+        let sampled = spec.sample(&Sampling::new(0.1)).len();
+        assert!((50..150).contains(&sampled), "sampled {sampled}");
+    }
+
+    #[test]
+    fn test_sample_seed() {
+        let spec = spec(200);
+        let paths = |seed| -> Vec<_> {
+            spec.sample(&Sampling::new(0.25).seed(seed))
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect()
+        };
+
+        assert_eq!(paths(1), paths(1));
+        assert_ne!(paths(1), paths(2));
+    }
+
+    #[test]
+    fn test_sample_setuid() {
+        let spec = Spec::parse("su mode=4755\nls mode=0755\n").unwrap();
+
+        let sampled = spec.sample(&Sampling::new(0.0));
+        assert_eq!(sampled.len(), 1);
+        assert_eq!(sampled[0].path.to_str(), Some("./su"));
+
+        assert!(
+            spec.sample(&Sampling::new(0.0).include_setuid(false))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Confining the current thread with Landlock, on Linux.
+//!
+//! Verification only ever needs to read the tree it checks, so a process
+//! embedding the verifier can give up everything else before it starts. This
+//! limits what a bug, or a malicious tree, can do with the process's
+//! privileges.
+
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+const ACCESS_FS_REFER: u64 = 1 << 13;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// The outcome of [`Sandbox::restrict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SandboxStatus {
+    /// The restrictions are in place, using the given Landlock ABI version.
+    Enforced { abi: u32 },
+    /// The kernel doesn't support Landlock, so nothing was restricted.
+    Unsupported,
+}
+
+/// A set of directories the current thread keeps read-only access to.
+///
+/// Restrictions apply to the calling thread and any threads or processes it
+/// starts afterwards, and can't be lifted. Threads that already exist are
+/// unaffected, so this should be called before spawning workers.
+#[derive(Clone, Debug, Default)]
+pub struct Sandbox {
+    read_only: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Sandbox::default()
+    }
+
+    /// Allows reading files and listing directories beneath `path`.
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_only.push(path.into());
+        self
+    }
+
+    /// Denies the current thread all filesystem access other than reading
+    /// beneath the allowed paths.
+    pub fn restrict(&self) -> io::Result<SandboxStatus> {
+        let abi = match abi_version() {
+            Some(abi) => abi,
+            None => return Ok(SandboxStatus::Unsupported),
+        };
+
+        let mut handled = ACCESS_FS_EXECUTE
+            | ACCESS_FS_WRITE_FILE
+            | ACCESS_FS_READ_FILE
+            | ACCESS_FS_READ_DIR
+            | ACCESS_FS_REMOVE_DIR
+            | ACCESS_FS_REMOVE_FILE
+            | ACCESS_FS_MAKE_CHAR
+            | ACCESS_FS_MAKE_DIR
+            | ACCESS_FS_MAKE_REG
+            | ACCESS_FS_MAKE_SOCK
+            | ACCESS_FS_MAKE_FIFO
+            | ACCESS_FS_MAKE_BLOCK
+            | ACCESS_FS_MAKE_SYM;
+        if abi >= 2 {
+            handled |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= ACCESS_FS_TRUNCATE;
+        }
+
+        let attr = RulesetAttr {
+            handled_access_fs: handled,
+        };
+        let ruleset = cvt(unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                size_of::<RulesetAttr>(),
+                0u32,
+            )
+        })?;
+        let ruleset = unsafe { OwnedFd::from_raw_fd(ruleset as i32) };
+
+        for path in &self.read_only {
+            add_read_rule(&ruleset, path)?;
+        }
+
+        cvt(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } as libc::c_long)?;
+        cvt(unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) })?;
+
+        Ok(SandboxStatus::Enforced { abi })
+    }
+}
+
+/// Restricts the current thread to reading beneath `root`.
+pub fn restrict_to_read_only(root: &Path) -> io::Result<SandboxStatus> {
+    Sandbox::new().allow_read(root).restrict()
+}
+
+fn add_read_rule(ruleset: &OwnedFd, path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let is_dir = std::fs::File::from(fd.try_clone()?).metadata()?.is_dir();
+    let attr = PathBeneathAttr {
+        allowed_access: if is_dir {
+            ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR
+        } else {
+            ACCESS_FS_READ_FILE
+        },
+        parent_fd: fd.as_raw_fd(),
+    };
+
+    cvt(unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    })?;
+
+    Ok(())
+}
+
+fn abi_version() -> Option<u32> {
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+
+    u32::try_from(abi).ok().filter(|abi| *abi > 0)
+}
+
+fn cvt(result: libc::c_long) -> io::Result<libc::c_long> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_restrict() {
+        let dir = TempDir::new();
+        let file = dir.file("file", "hello");
+        let root = dir.path().to_path_buf();
+
+        // Landlock only restricts the calling thread, so do it on a separate
+        // one to keep the rest of the test binary unaffected.
+        std::thread::spawn(move || {
+            if restrict_to_read_only(&root).unwrap() == SandboxStatus::Unsupported {
+                return;
+            }
+
+            assert_eq!(fs::read_to_string(&file).unwrap(), "hello");
+            assert!(fs::read_dir(&root).is_ok());
+            assert!(fs::write(&file, "changed").is_err());
+            assert!(fs::write(root.join("new"), "").is_err());
+            assert!(fs::read_dir("/").is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
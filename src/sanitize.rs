@@ -0,0 +1,129 @@
+//! Making untrusted text, such as file names, safe to show in reports.
+//!
+//! Control characters in a file name can move the cursor or change colours
+//! on a terminal, and bidi overrides can make `evil\u{202e}gpj.exe` display
+//! as `evilexe.jpg`. Both are shown as octal escapes instead, as in a spec
+//! (`\033`), along with backslashes and bytes that aren't valid UTF-8, so
+//! every name displays unambiguously. Other characters are left as they are.
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+
+/// Text that displays with unsafe characters escaped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sanitized<'a>(Cow<'a, [u8]>);
+
+pub fn path(path: &Path) -> Sanitized<'_> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    };
+
+    #[cfg(not(unix))]
+    let bytes = match path.to_string_lossy() {
+        Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+        Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+    };
+
+    Sanitized(bytes)
+}
+
+pub fn text(text: &str) -> Sanitized<'_> {
+    Sanitized(Cow::Borrowed(text.as_bytes()))
+}
+
+impl Sanitized<'_> {
+    /// The sanitized text with HTML's special characters replaced by
+    /// entities, for embedding in an HTML report.
+    pub fn html(&self) -> String {
+        let mut out = String::new();
+
+        for c in self.to_string().chars() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                c => out.push(c),
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Sanitized<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if c == '\\' {
+                    f.write_str("\\\\")?;
+                } else if is_unsafe(c) {
+                    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                        write!(f, "\\{byte:03o}")?;
+                    }
+                } else {
+                    f.write_char(c)?;
+                }
+            }
+
+            for byte in chunk.invalid() {
+                write!(f, "\\{byte:03o}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `c` could change how the text around it is displayed.
+fn is_unsafe(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{061c}'
+                | '\u{200e}'
+                | '\u{200f}'
+                | '\u{2028}'
+                | '\u{2029}'
+                | '\u{202a}'..='\u{202e}'
+                | '\u{2066}'..='\u{2069}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(text("plain/é.txt").to_string(), "plain/é.txt");
+        assert_eq!(
+            text("evil\u{202e}gpj.exe").to_string(),
+            "evil\\342\\200\\256gpj.exe"
+        );
+        assert_eq!(text("a\x1b[31mb\nc").to_string(), "a\\033[31mb\\012c");
+        assert_eq!(text("back\\slash").to_string(), "back\\\\slash");
+
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let name = Path::new(OsStr::from_bytes(b"bad\xffname"));
+            assert_eq!(path(name).to_string(), "bad\\377name");
+        }
+    }
+
+    #[test]
+    fn test_sanitize_html() {
+        assert_eq!(
+            text("<b>\"tom & jerry's\"\u{7}").html(),
+            "&lt;b&gt;&quot;tom &amp; jerry&#39;s&quot;\\007"
+        );
+    }
+}
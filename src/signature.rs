@@ -0,0 +1,176 @@
+//! Telling apart the forms a spec can be written in.
+//!
+//! `mtree -c` starts a spec with comments naming the user, machine, tree and
+//! date it was made for, then names each entry relative to the directory
+//! above it, with `..` moving back up. libarchive and NetBSD's v2 format put
+//! a `#mtree` signature, optionally with a version such as `v2.0`, on the
+//! first line and give every entry its full path.
+
+use crate::resolve::SetState;
+use crate::{Keyword, Line, Spec, Type};
+
+/// How entries name their paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Each entry is named relative to the last `type=dir` entry, and `..`
+    /// moves back up a directory.
+    Relative,
+    /// Each entry holds its path from the root of the tree.
+    FullPath,
+}
+
+/// The `# user: ...` style comments `mtree -c` writes at the top of a spec.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub user: Option<String>,
+    pub machine: Option<String>,
+    pub tree: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecFormat {
+    /// Whether the first line is a `#mtree` signature.
+    pub signature: bool,
+    /// The version after the signature, without its `v`, e.g. `2.0`.
+    pub version: Option<String>,
+    pub header: Header,
+    pub layout: Layout,
+}
+
+impl Spec {
+    /// Works out which form the spec is written in. A spec counts as
+    /// [`Layout::Relative`] if where an entry goes depends on the entries
+    /// before it: it has a `..` entry, or a bare name follows a bare-named
+    /// directory and so is placed inside it.
+    pub fn detect_format(&self) -> SpecFormat {
+        let mut format = SpecFormat {
+            signature: false,
+            version: None,
+            header: Header::default(),
+            layout: detect_layout(&self.lines),
+        };
+
+        if let Some(Line::Comment { text, .. }) = self.lines.first()
+            && let Some(rest) = text.strip_prefix("mtree")
+            && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            format.signature = true;
+            format.version = rest
+                .split_whitespace()
+                .next()
+                .map(|version| version.strip_prefix('v').unwrap_or(version).to_string());
+        }
+
+        let leading = self
+            .lines
+            .iter()
+            .take_while(|line| matches!(line, Line::Comment { .. } | Line::Blank { .. }));
+
+        for line in leading {
+            let Line::Comment { text, .. } = line else {
+                continue;
+            };
+            let Some((field, value)) = text.split_once(':') else {
+                continue;
+            };
+
+            let value = Some(value.trim().to_string());
+            match field.trim() {
+                "user" => format.header.user = value,
+                "machine" => format.header.machine = value,
+                "tree" => format.header.tree = value,
+                "date" => format.header.date = value,
+                _ => {}
+            }
+        }
+
+        format
+    }
+}
+
+pub(crate) fn detect_layout(lines: &[Line]) -> Layout {
+    let mut state = SetState::new();
+    let mut in_dir = false;
+
+    for line in lines {
+        match line {
+            Line::Command { command, .. } => state.apply(command),
+            Line::Entry { entry, .. } => {
+                if entry.path.as_os_str() == ".." {
+                    return Layout::Relative;
+                }
+
+                let bare = entry.path.components().count() == 1 && entry.path.as_os_str() != ".";
+                if bare && in_dir {
+                    return Layout::Relative;
+                }
+                in_dir |= bare
+                    && state
+                        .materialize(&entry.keywords)
+                        .contains(&Keyword::Type(Type::Dir));
+            }
+            _ => {}
+        }
+    }
+
+    Layout::FullPath
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        let spec = Spec::parse(
+            "#\t   user: root\n\
+             #\tmachine: build\n\
+             #\t   tree: /usr\n\
+             #\t   date: Mon Jan  1 00:00:00 2024\n\
+             \n\
+             /set type=file uid=0\n\
+             .               type=dir\n\
+             bin             type=dir\n\
+             ls              size=1\n\
+             ..\n\
+             share           type=dir\n\
+             ..\n",
+        )
+        .unwrap();
+
+        let format = spec.detect_format();
+        assert!(!format.signature);
+        assert_eq!(format.layout, Layout::Relative);
+        assert_eq!(
+            format.header,
+            Header {
+                user: Some("root".to_string()),
+                machine: Some("build".to_string()),
+                tree: Some("/usr".to_string()),
+                date: Some("Mon Jan  1 00:00:00 2024".to_string()),
+            }
+        );
+        assert_eq!(
+            spec.resolve()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>(),
+            [".", "./bin", "./bin/ls", "./share"].map(PathBuf::from)
+        );
+
+        let spec = Spec::parse("#mtree v2.0\n./bin type=dir\n./bin/ls type=file\n").unwrap();
+        let format = spec.detect_format();
+        assert!(format.signature);
+        assert_eq!(format.version.as_deref(), Some("2.0"));
+        assert_eq!(format.layout, Layout::FullPath);
+        assert_eq!(spec.resolve()[1].path, PathBuf::from("./bin/ls"));
+
+        let spec = Spec::parse("bin type=dir\nls\n").unwrap();
+        assert_eq!(spec.detect_format().layout, Layout::Relative);
+        let spec = Spec::parse("/set type=file\nbin\nls\n").unwrap();
+        assert_eq!(spec.detect_format().layout, Layout::FullPath);
+    }
+}
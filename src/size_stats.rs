@@ -0,0 +1,146 @@
+//! Where the bytes of a spec's text go, for choosing which keywords to
+//! record in very large manifests.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::{KeywordKind, Spec, escape};
+
+/// The output size of one keyword kind, counting the space before each
+/// occurrence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeywordStats {
+    pub kind: KeywordKind,
+    /// How many entries have the keyword.
+    pub count: usize,
+    /// Bytes the keyword takes when written out on every entry.
+    pub bytes: usize,
+    /// Bytes saved by writing its most common value once in a `/set` line
+    /// instead. Entries without the keyword would need an `/unset`, which
+    /// isn't accounted for, so this is an upper bound.
+    pub set_savings: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeStats {
+    /// The size of the spec as it is now.
+    pub total: usize,
+    /// The size with every entry listing all of its keywords, with no `/set`
+    /// lines or comments.
+    pub unfactored: usize,
+    /// Bytes taken by paths and line breaks in the unfactored form.
+    pub paths: usize,
+    /// Largest first.
+    pub keywords: Vec<KeywordStats>,
+}
+
+impl SizeStats {
+    /// The combined [`KeywordStats::set_savings`] of every keyword.
+    pub fn set_savings(&self) -> usize {
+        self.keywords.iter().map(|stats| stats.set_savings).sum()
+    }
+}
+
+impl Spec {
+    pub fn size_stats(&self) -> SizeStats {
+        let mut stats = SizeStats {
+            total: self.to_string().len(),
+            ..Default::default()
+        };
+
+        let mut kinds: Vec<KeywordKind> = Vec::new();
+        let mut values: HashMap<KeywordKind, HashMap<String, usize>> = HashMap::new();
+
+        for entry in self.resolve() {
+            stats.paths += escape::encode_path(&entry.path).len() + 1;
+
+            for keyword in &entry.keywords {
+                let kind = keyword.kind();
+                if !kinds.contains(&kind) {
+                    kinds.push(kind);
+                }
+
+                *values
+                    .entry(kind)
+                    .or_default()
+                    .entry(keyword.to_string())
+                    .or_default() += 1;
+            }
+        }
+
+        stats.keywords = kinds
+            .into_iter()
+            .map(|kind| {
+                let values = &values[&kind];
+                let width = |text: &String| text.len() + 1;
+
+                let bytes = values.iter().map(|(text, count)| width(text) * count).sum();
+                let set_savings = values
+                    .iter()
+                    .map(|(text, count)| width(text) * (count - 1))
+                    .max()
+                    .unwrap_or(0);
+
+                KeywordStats {
+                    kind,
+                    count: values.values().sum(),
+                    bytes,
+                    set_savings,
+                }
+            })
+            .collect();
+
+        // A stable sort keeps ties in order of first appearance.
+        stats.keywords.sort_by_key(|stats| Reverse(stats.bytes));
+        stats.unfactored = stats.paths
+            + stats
+                .keywords
+                .iter()
+                .map(|stats| stats.bytes)
+                .sum::<usize>();
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DigestKind;
+
+    #[test]
+    fn test_size_stats() {
+        let spec = Spec::parse(
+            "/set uid=0 mode=0644\n\
+             ./a sha256digest=e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n\
+             ./b uid=1000\n\
+             ./c\n",
+        )
+        .unwrap();
+
+        let stats = spec.size_stats();
+        assert_eq!(stats.total, spec.to_string().len());
+        assert_eq!(stats.paths, 12);
+
+        let kinds: Vec<_> = stats.keywords.iter().map(|stats| stats.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                KeywordKind::Digest(DigestKind::Sha256),
+                KeywordKind::Mode,
+                KeywordKind::Uid,
+            ]
+        );
+
+        // ` mode=0644` three times, of which two are saved by a `/set`.
+        let mode = &stats.keywords[1];
+        assert_eq!((mode.count, mode.bytes, mode.set_savings), (3, 30, 20));
+        // ` uid=0` twice and ` uid=1000` once.
+        let uid = &stats.keywords[2];
+        assert_eq!((uid.count, uid.bytes, uid.set_savings), (3, 21, 6));
+
+        assert_eq!(stats.keywords[0].set_savings, 0);
+        assert_eq!(stats.set_savings(), 26);
+        assert_eq!(stats.unfactored, 12 + 78 + 30 + 21);
+    }
+}
@@ -0,0 +1,113 @@
+//! Copy-on-write snapshots of a [`Spec`] that is being edited concurrently.
+//!
+//! [`SpecStore::snapshot`] hands out a cheap, immutable reference to the
+//! current version. Edits made through [`SpecStore::edit`] never affect
+//! snapshots taken earlier: the spec is only cloned when an edit happens
+//! while a snapshot of the current version is still alive.
+
+use std::ops::Deref;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::Spec;
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    spec: Arc<Spec>,
+    version: u64,
+}
+
+impl Snapshot {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl Deref for Snapshot {
+    type Target = Spec;
+
+    fn deref(&self) -> &Self::Target {
+        &self.spec
+    }
+}
+
+#[derive(Debug)]
+pub struct SpecStore {
+    current: RwLock<Snapshot>,
+}
+
+impl SpecStore {
+    pub fn new(spec: Spec) -> Self {
+        SpecStore {
+            current: RwLock::new(Snapshot {
+                spec: Arc::new(spec),
+                version: 0,
+            }),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .version
+    }
+
+    /// Applies `f` to the current spec and publishes the result as a new
+    /// version.
+    pub fn edit<R>(&self, f: impl FnOnce(&mut Spec) -> R) -> R {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+
+        let result = f(Arc::make_mut(&mut current.spec));
+        current.version += 1;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::thread;
+
+    use super::*;
+    use crate::Line;
+
+    #[test]
+    fn test_snapshot_isolated_from_edits() {
+        let store = SpecStore::new(Spec::parse("foo\nbar\n").unwrap());
+        let before = store.snapshot();
+
+        store.edit(|spec| {
+            spec.lines.retain(
+                |line| !matches!(line, Line::Entry { entry, .. } if entry.path == Path::new("bar")),
+            )
+        });
+
+        let after = store.snapshot();
+
+        assert_eq!(before.entries().count(), 2);
+        assert_eq!(after.entries().count(), 1);
+        assert_eq!(before.version(), 0);
+        assert_eq!(after.version(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_across_threads() {
+        let store = SpecStore::new(Spec::parse("foo\n").unwrap());
+        let snapshot = store.snapshot();
+
+        let reader = thread::spawn(move || snapshot.entries().count());
+
+        store.edit(|spec| spec.lines.clear());
+
+        assert_eq!(reader.join().unwrap(), 1);
+        assert_eq!(store.snapshot().entries().count(), 0);
+    }
+}
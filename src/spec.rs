@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::comments::attached_comments;
+use crate::{
+    Entry, EntryId, Line, ParseError, ParseOptions, parse_lines, parse_lines_lenient,
+    parse_lines_lenient_with, parse_lines_with,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spec {
+    pub lines: Vec<Line>,
+}
+
+impl Spec {
+    pub fn new(lines: Vec<Line>) -> Self {
+        Spec { lines }
+    }
+
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        parse_lines(input).map(Spec::new)
+    }
+
+    pub fn parse_with(input: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        parse_lines_with(input, options).map(Spec::new)
+    }
+
+    /// Parses as much of `input` as possible, returning the lines that
+    /// couldn't be parsed as errors alongside the rest of the spec.
+    pub fn parse_lenient(input: &str) -> (Self, Vec<ParseError>) {
+        let (lines, errors) = parse_lines_lenient(input);
+        (Spec::new(lines), errors)
+    }
+
+    pub fn parse_lenient_with(input: &str, options: &ParseOptions) -> (Self, Vec<ParseError>) {
+        let (lines, errors) = parse_lines_lenient_with(input, options);
+        (Spec::new(lines), errors)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { entry, .. } => Some(entry),
+            _ => None,
+        })
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut Entry> {
+        self.lines.iter_mut().filter_map(|line| match line {
+            Line::Entry { entry, .. } => Some(entry),
+            _ => None,
+        })
+    }
+
+    pub fn entry(&self, id: EntryId) -> Option<&Entry> {
+        self.lines.iter().find_map(|line| match line {
+            Line::Entry {
+                id: other, entry, ..
+            } if *other == id => Some(entry),
+            _ => None,
+        })
+    }
+
+    pub fn entry_mut(&mut self, id: EntryId) -> Option<&mut Entry> {
+        self.lines.iter_mut().find_map(|line| match line {
+            Line::Entry {
+                id: other, entry, ..
+            } if *other == id => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Puts the spec in canonical order, so that two specs of the same tree
+    /// compare equal however they were written: every entry gets its full
+    /// path and all of its keywords, including those from `/set`, and
+    /// entries are sorted by path, so each directory is followed by its
+    /// contents, with their keywords sorted (see [`Keyword`](crate::Keyword)
+    /// for the order). Comments before the first entry or command are kept
+    /// in place, and those directly above an entry move with it; commands
+    /// and other comments are dropped.
+    pub fn sort(&mut self) {
+        let mut entries = self.resolve();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let header = self
+            .lines
+            .iter()
+            .take_while(|line| matches!(line, Line::Comment { .. } | Line::Blank { .. }))
+            .count();
+
+        let mut comments = HashMap::new();
+        for (idx, line) in self.lines.iter().enumerate() {
+            if let Line::Entry { id, .. } = line {
+                let range = attached_comments(&self.lines, idx);
+                comments.insert(*id, range.start.max(header)..range.end);
+            }
+        }
+
+        let mut lines: Vec<Line> = self.lines[..header].to_vec();
+        for mut resolved in entries {
+            if let Some(range) = comments.get(&resolved.id) {
+                lines.extend_from_slice(&self.lines[range.clone()]);
+            }
+
+            resolved.keywords.sort();
+            lines.push(Line::Entry {
+                id: resolved.id,
+                entry: Entry {
+                    path: resolved.path,
+                    keywords: resolved.keywords,
+                },
+                span: resolved.span,
+            });
+        }
+
+        self.lines = lines;
+    }
+
+    pub fn entry_ids(&self) -> impl Iterator<Item = EntryId> + '_ {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { id, .. } => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Returns an id not used by any entry in the spec, for use when
+    /// inserting new entries.
+    pub fn next_entry_id(&self) -> EntryId {
+        self.entry_ids()
+            .max()
+            .map_or(EntryId(0), |EntryId(id)| EntryId(id + 1))
+    }
+
+    pub fn find_id(&self, path: &Path) -> Option<EntryId> {
+        self.lines.iter().rev().find_map(|line| match line {
+            Line::Entry { id, entry, .. } if entry.path == path => Some(*id),
+            _ => None,
+        })
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&Entry> {
+        self.entries().filter(|entry| entry.path == path).last()
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Spec {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Spec::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_entry_ids_survive_edits() {
+        let mut spec = Spec::parse("foo\nbar\nbaz\n").unwrap();
+        let baz = spec.find_id(Path::new("baz")).unwrap();
+
+        spec.lines.remove(0);
+        spec.entry_mut(baz).unwrap().path = PathBuf::from("renamed");
+
+        assert_eq!(baz, EntryId(2));
+        assert_eq!(spec.entry(baz).unwrap().path, PathBuf::from("renamed"));
+        assert_eq!(spec.next_entry_id(), EntryId(3));
+        assert_eq!(spec.entry(EntryId(0)), None);
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let input = "foo size=1
+bar size=x
+/set bogus
+baz
+";
+        let (spec, errors) = Spec::parse_lenient(input);
+
+        assert_eq!(spec.to_string(), "foo size=1\nbaz\n");
+        assert_eq!(spec.find_id(Path::new("baz")), Some(EntryId(1)));
+
+        let bad: Vec<_> = errors
+            .iter()
+            .map(|err| (err.span.line, err.span.slice(input)))
+            .collect();
+        assert_eq!(bad, vec![(2, "bar size=x"), (3, "/set bogus")]);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut relative = Spec::parse(
+            "# header\n\
+             /set type=file uid=0\n\
+             . type=dir\n\
+             b size=1\n\
+             a type=dir\n\
+             \x20   z uid=1 size=2\n\
+             ..\n",
+        )
+        .unwrap();
+        let mut full = Spec::parse(
+            "# header\n\
+             ./b uid=0 size=1 type=file\n\
+             . type=dir uid=0\n\
+             # dropped\n\
+             \n\
+             ./a/z type=file size=2 uid=1\n\
+             # the a directory\n\
+             ./a uid=0 type=dir\n",
+        )
+        .unwrap();
+
+        relative.sort();
+        full.sort();
+
+        assert_eq!(
+            relative.to_string(),
+            "# header\n\
+             . type=dir uid=0\n\
+             ./a type=dir uid=0\n\
+             ./a/z type=file uid=1 size=2\n\
+             ./b type=file uid=0 size=1\n"
+        );
+        assert_eq!(
+            full.to_string(),
+            "# header\n\
+             . type=dir uid=0\n\
+             # the a directory\n\
+             ./a type=dir uid=0\n\
+             ./a/z type=file uid=1 size=2\n\
+             ./b type=file uid=0 size=1\n"
+        );
+
+        full.lines.remove(2);
+        assert_eq!(full.to_string(), relative.to_string());
+
+        let entries: BTreeSet<_> = full.entries().cloned().collect();
+        assert_eq!(
+            entries.first().map(|entry| &entry.path),
+            Some(&PathBuf::from("."))
+        );
+    }
+}
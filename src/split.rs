@@ -0,0 +1,124 @@
+//! Splitting a spec into several smaller ones, for channels that limit the
+//! size of each metadata file.
+
+use crate::resolve::SetState;
+use crate::{Command, Line, Span, Spec};
+
+/// How large each part from [`Spec::split`] may be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitLimit {
+    /// At most this many entries per part.
+    Entries(usize),
+    /// At most this many bytes of text per part.
+    Bytes(usize),
+}
+
+impl Spec {
+    /// Splits the spec into parts within `limit` that each stand on their
+    /// own: a part after the first begins with a `/set` line holding the
+    /// defaults in effect at that point. Resolving the parts in order gives
+    /// the same entries as resolving the whole spec.
+    ///
+    /// A part always holds at least one entry, so a single entry larger than
+    /// a byte limit gets a part to itself. Specs in the relative form are
+    /// split as if written with full paths and their defaults applied to
+    /// each entry, since a part can't start inside a directory.
+    pub fn split(&self, limit: SplitLimit) -> Vec<Spec> {
+        let lines = self.full_path_lines();
+
+        let size = |line: &Line| match limit {
+            SplitLimit::Entries(_) => usize::from(matches!(line, Line::Entry { .. })),
+            SplitLimit::Bytes(_) => line.to_string().len() + 1,
+        };
+        let max = match limit {
+            SplitLimit::Entries(max) | SplitLimit::Bytes(max) => max,
+        };
+
+        let mut parts = Vec::new();
+        let mut part: Vec<Line> = Vec::new();
+        let mut part_size = 0;
+        let mut has_entry = false;
+        // Whether the part was started by a split and its `/set` line is yet
+        // to be written. Commands until then are folded into that line.
+        let mut needs_set = false;
+        let mut state = SetState::new();
+
+        for line in lines.iter() {
+            if has_entry && part_size + size(line) > max {
+                parts.push(Spec::new(std::mem::take(&mut part)));
+                part_size = 0;
+                has_entry = false;
+                needs_set = true;
+            }
+
+            match line {
+                Line::Command { command, .. } => {
+                    state.apply(command);
+                    if needs_set {
+                        continue;
+                    }
+                }
+                Line::Entry { .. } => {
+                    if needs_set && !state.keywords().is_empty() {
+                        let set = Line::Command {
+                            command: Command::Set(state.keywords().to_vec()),
+                            span: Span::default(),
+                        };
+                        part_size += size(&set);
+                        part.push(set);
+                    }
+                    needs_set = false;
+                    has_entry = true;
+                }
+                _ => {}
+            }
+
+            part_size += size(line);
+            part.push(line.clone());
+        }
+
+        if !part.is_empty() {
+            parts.push(Spec::new(part));
+        }
+
+        parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let spec = Spec::parse(
+            "/set uid=0 mode=0644\n\
+             ./a\n\
+             ./b\n\
+             /set mode=0755\n\
+             ./c\n\
+             ./d\n\
+             ./e\n",
+        )
+        .unwrap();
+
+        let parts = spec.split(SplitLimit::Entries(2));
+        let texts: Vec<String> = parts.iter().map(Spec::to_string).collect();
+        assert_eq!(
+            texts,
+            [
+                "/set uid=0 mode=0644\n./a\n./b\n/set mode=0755\n",
+                "/set uid=0 mode=0755\n./c\n./d\n",
+                "/set uid=0 mode=0755\n./e\n",
+            ]
+        );
+
+        let resolved: Vec<_> = parts.iter().flat_map(Spec::resolve).collect();
+        assert_eq!(resolved, spec.resolve());
+
+        for part in spec.split(SplitLimit::Bytes(30)) {
+            assert!(part.to_string().len() <= 30);
+        }
+        assert_eq!(spec.split(SplitLimit::Bytes(1)).len(), 5);
+    }
+}
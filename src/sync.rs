@@ -0,0 +1,399 @@
+//! Planning the transfer of a tree described by a spec, rsync style.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::sanitize;
+use crate::verify::Verifier;
+use crate::{Keyword, KeywordKind, Mode, ResolvedEntry, RootPath, Spec, Timestamp, Type};
+use crate::{VerifyFinding, VerifyReport};
+
+/// A step in bringing a destination tree in line with a spec. Paths are spec
+/// paths, in both the source and the destination.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Removes the file, with everything beneath it.
+    Delete {
+        path: PathBuf,
+    },
+    MakeDir {
+        path: PathBuf,
+    },
+    /// Copies a regular file's contents from the source, replacing the file
+    /// at the destination if there is one.
+    Copy {
+        path: PathBuf,
+    },
+    /// Creates a symlink, replacing the file at the destination if there is
+    /// one.
+    Link {
+        path: PathBuf,
+        target: PathBuf,
+    },
+    /// Changes the owner, the group or both.
+    Chown {
+        path: PathBuf,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+    Chmod {
+        path: PathBuf,
+        mode: Mode,
+    },
+    /// Sets the modification time.
+    Touch {
+        path: PathBuf,
+        time: Timestamp,
+    },
+}
+
+impl SyncAction {
+    pub fn path(&self) -> &Path {
+        match self {
+            SyncAction::Delete { path }
+            | SyncAction::MakeDir { path }
+            | SyncAction::Copy { path }
+            | SyncAction::Link { path, .. }
+            | SyncAction::Chown { path, .. }
+            | SyncAction::Chmod { path, .. }
+            | SyncAction::Touch { path, .. } => path,
+        }
+    }
+
+    /// The order actions are carried out in: files are created before their
+    /// metadata is set, and times come last because creating files changes
+    /// their parent's.
+    fn phase(&self) -> u8 {
+        match self {
+            SyncAction::Delete { .. } => 0,
+            SyncAction::MakeDir { .. } => 1,
+            SyncAction::Copy { .. } | SyncAction::Link { .. } => 2,
+            SyncAction::Chown { .. } => 3,
+            SyncAction::Chmod { .. } => 4,
+            SyncAction::Touch { .. } => 5,
+        }
+    }
+}
+
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = sanitize::path(self.path());
+
+        match self {
+            SyncAction::Delete { .. } => write!(f, "{path}: delete"),
+            SyncAction::MakeDir { .. } => write!(f, "{path}: mkdir"),
+            SyncAction::Copy { .. } => write!(f, "{path}: copy"),
+            SyncAction::Link { target, .. } => {
+                write!(f, "{path}: link -> {}", sanitize::path(target))
+            }
+            SyncAction::Chown { uid, gid, .. } => {
+                write!(f, "{path}: chown ")?;
+                if let Some(uid) = uid {
+                    write!(f, "{uid}")?;
+                }
+                if let Some(gid) = gid {
+                    write!(f, ":{gid}")?;
+                }
+                Ok(())
+            }
+            SyncAction::Chmod { mode, .. } => write!(f, "{path}: chmod {mode}"),
+            SyncAction::Touch { time, .. } => write!(f, "{path}: touch {time}"),
+        }
+    }
+}
+
+/// The actions needed to make a destination tree match a spec.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+    pub actions: Vec<SyncAction>,
+    /// Differences a sync can't fix, such as a missing device node or a file
+    /// that couldn't be read.
+    pub skipped: Vec<VerifyFinding>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// The regular files whose contents need copying.
+    pub fn copies(&self) -> impl Iterator<Item = &Path> {
+        self.actions.iter().filter_map(|action| match action {
+            SyncAction::Copy { path } => Some(path.as_path()),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for action in &self.actions {
+            writeln!(f, "{action}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out how to turn a destination tree into the one a spec describes,
+/// without changing anything, so deployment tools can use a spec as the
+/// source of truth and carry out the transfer themselves.
+///
+/// Files are compared with a [`Verifier`], so the same keywords are checked.
+/// A regular file whose contents differ is copied again along with all of
+/// its metadata.
+#[derive(Clone, Debug)]
+pub struct SyncPlanner {
+    dest: RootPath,
+    delete: bool,
+}
+
+impl SyncPlanner {
+    pub fn new(dest: RootPath) -> Self {
+        SyncPlanner {
+            dest,
+            delete: false,
+        }
+    }
+
+    /// Also deletes files that aren't in the spec, like `rsync --delete`.
+    /// Off by default.
+    pub fn delete(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+
+    pub fn plan(&self, spec: &Spec) -> SyncPlan {
+        let entries = spec.resolve();
+        let report = Verifier::new(self.dest.clone())
+            .extras(self.delete)
+            .verify_entries(&entries);
+
+        plan_from_report(&entries, report)
+    }
+}
+
+fn plan_from_report(entries: &[ResolvedEntry], report: VerifyReport) -> SyncPlan {
+    let by_path: HashMap<&Path, &ResolvedEntry> = entries
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry))
+        .collect();
+
+    let mut plan = SyncPlan::default();
+
+    for findings in report.findings.chunk_by(|a, b| a.path() == b.path()) {
+        let path = findings[0].path();
+        let Some(entry) = by_path.get(path).copied() else {
+            for finding in findings {
+                match finding {
+                    VerifyFinding::Extra { path } => {
+                        plan.actions.push(SyncAction::Delete { path: path.clone() })
+                    }
+                    _ => plan.skipped.push(finding.clone()),
+                }
+            }
+            continue;
+        };
+
+        let mismatched = |kind: KeywordKind| {
+            findings
+                .iter()
+                .any(|finding| finding.keyword() == Some(kind))
+        };
+        let missing = findings
+            .iter()
+            .any(|finding| matches!(finding, VerifyFinding::Missing { .. }));
+        let replace = mismatched(KeywordKind::Type);
+        let recopy = is_file(entry)
+            && findings.iter().any(|finding| {
+                matches!(
+                    finding.keyword(),
+                    Some(KeywordKind::Size | KeywordKind::Cksum | KeywordKind::Digest(_))
+                )
+            });
+
+        if missing || replace || recopy || mismatched(KeywordKind::Link) {
+            if replace {
+                plan.actions.push(SyncAction::Delete {
+                    path: path.to_path_buf(),
+                });
+            }
+
+            match create(entry) {
+                Some(action) => plan.actions.push(action),
+                None => {
+                    plan.skipped.extend(findings.iter().cloned());
+                    continue;
+                }
+            }
+
+            plan.actions.extend(metadata(entry, |_| true));
+            continue;
+        }
+
+        plan.actions.extend(metadata(entry, mismatched));
+        plan.skipped.extend(
+            findings
+                .iter()
+                .filter(|finding| !finding.keyword().is_some_and(|kind| settable(entry, kind)))
+                .cloned(),
+        );
+    }
+
+    plan.actions.sort_by_key(SyncAction::phase);
+    plan
+}
+
+fn is_file(entry: &ResolvedEntry) -> bool {
+    matches!(
+        entry.get(KeywordKind::Type),
+        None | Some(Keyword::Type(Type::File))
+    )
+}
+
+fn is_link(entry: &ResolvedEntry) -> bool {
+    entry.get(KeywordKind::Type) == Some(&Keyword::Type(Type::Link))
+}
+
+/// Whether a sync can set the `kind` keyword of `entry`. Symlinks only get
+/// their ownership set.
+fn settable(entry: &ResolvedEntry, kind: KeywordKind) -> bool {
+    match kind {
+        KeywordKind::Uid | KeywordKind::Gid => true,
+        KeywordKind::Mode | KeywordKind::Time => !is_link(entry),
+        _ => false,
+    }
+}
+
+/// The action that creates the file `entry` describes, if a sync can.
+fn create(entry: &ResolvedEntry) -> Option<SyncAction> {
+    let path = entry.path.clone();
+
+    match entry.get(KeywordKind::Type) {
+        None | Some(Keyword::Type(Type::File)) => Some(SyncAction::Copy { path }),
+        Some(Keyword::Type(Type::Dir)) => Some(SyncAction::MakeDir { path }),
+        Some(Keyword::Type(Type::Link)) => match entry.get(KeywordKind::Link) {
+            Some(Keyword::Link(target)) => Some(SyncAction::Link {
+                path,
+                target: target.clone(),
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The actions that set the metadata of `entry` for the keywords `wanted`
+/// selects.
+fn metadata(entry: &ResolvedEntry, wanted: impl Fn(KeywordKind) -> bool) -> Vec<SyncAction> {
+    let path = &entry.path;
+    let wanted = |kind| settable(entry, kind) && wanted(kind);
+    let mut actions = Vec::new();
+
+    let uid = match entry.get(KeywordKind::Uid) {
+        Some(Keyword::Uid(uid)) if wanted(KeywordKind::Uid) => Some(*uid),
+        _ => None,
+    };
+    let gid = match entry.get(KeywordKind::Gid) {
+        Some(Keyword::Gid(gid)) if wanted(KeywordKind::Gid) => Some(*gid),
+        _ => None,
+    };
+    if uid.is_some() || gid.is_some() {
+        actions.push(SyncAction::Chown {
+            path: path.clone(),
+            uid,
+            gid,
+        });
+    }
+
+    if let Some(Keyword::Mode(mode)) = entry.get(KeywordKind::Mode)
+        && wanted(KeywordKind::Mode)
+    {
+        actions.push(SyncAction::Chmod {
+            path: path.clone(),
+            mode: *mode,
+        });
+    }
+
+    if let Some(Keyword::Time(time)) = entry.get(KeywordKind::Time)
+        && wanted(KeywordKind::Time)
+    {
+        actions.push(SyncAction::Touch {
+            path: path.clone(),
+            time: *time,
+        });
+    }
+
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_sync_plan() {
+        let dest = TempDir::new();
+        dest.file("a", "hello");
+        dest.file("b", "x");
+        dest.file("stray/file", "");
+
+        let spec = Spec::parse(
+            ". type=dir
+             a type=file size=5
+             b type=file size=3 mode=0644 uid=0
+             dir type=dir mode=0700
+                 link type=link link=../a
+             ..
+",
+        )
+        .unwrap();
+
+        let path = PathBuf::from;
+        let plan = SyncPlanner::new(RootPath::new(dest.path()))
+            .delete(true)
+            .plan(&spec);
+        assert_eq!(
+            plan.actions,
+            vec![
+                SyncAction::Delete {
+                    path: path("./stray")
+                },
+                SyncAction::MakeDir {
+                    path: path("./dir")
+                },
+                SyncAction::Copy { path: path("./b") },
+                SyncAction::Link {
+                    path: path("./dir/link"),
+                    target: path("../a"),
+                },
+                SyncAction::Chown {
+                    path: path("./b"),
+                    uid: Some(0),
+                    gid: None,
+                },
+                SyncAction::Chmod {
+                    path: path("./b"),
+                    mode: "0644".parse().unwrap(),
+                },
+                SyncAction::Chmod {
+                    path: path("./dir"),
+                    mode: "0700".parse().unwrap(),
+                },
+            ]
+        );
+        assert!(plan.skipped.is_empty());
+        assert_eq!(plan.copies().collect::<Vec<_>>(), vec![Path::new("./b")]);
+        assert!(plan.to_string().contains("./dir/link: link -> ../a\n"));
+
+        let plan = SyncPlanner::new(RootPath::new(dest.path())).plan(&spec);
+        assert!(
+            !plan
+                .actions
+                .iter()
+                .any(|action| matches!(action, SyncAction::Delete { .. }))
+        );
+    }
+}
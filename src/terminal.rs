@@ -0,0 +1,226 @@
+//! Verification reports for terminals, with colours and long paths
+//! shortened to fit the width of the screen.
+
+use std::borrow::Cow;
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+use crate::{VerifyFinding, VerifyReport, sanitize};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+
+/// Paths are never shortened below this many characters, however narrow the
+/// terminal.
+const MIN_PATH_WIDTH: usize = 16;
+
+/// When to use colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+/// Formats verification reports one finding per line, as `path: problem`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminalFormatter {
+    color: bool,
+    width: Option<usize>,
+}
+
+impl TerminalFormatter {
+    /// Plain output, with paths never shortened.
+    pub fn new() -> Self {
+        TerminalFormatter::default()
+    }
+
+    /// Output for stdout: colours as [`ColorChoice::Auto`] decides, and
+    /// lines fitted to `COLUMNS` if stdout is a terminal.
+    pub fn for_stdout() -> Self {
+        let width = io::stdout()
+            .is_terminal()
+            .then(|| env::var("COLUMNS").ok()?.parse().ok())
+            .flatten();
+
+        TerminalFormatter {
+            color: ColorChoice::Auto.enabled(),
+            width,
+        }
+    }
+
+    pub fn color(mut self, choice: ColorChoice) -> Self {
+        self.color = choice.enabled();
+        self
+    }
+
+    /// Shortens paths in the middle so lines fit in `width` columns.
+    pub fn width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn format_finding(&self, finding: &VerifyFinding) -> String {
+        let (detail, styled) = match finding {
+            VerifyFinding::Missing { .. } => ("missing".to_string(), self.paint(RED, "missing")),
+            VerifyFinding::Extra { .. } => ("extra".to_string(), self.paint(YELLOW, "extra")),
+            VerifyFinding::Mismatch {
+                expected, actual, ..
+            } => {
+                let name = expected.kind().name();
+                let expected = sanitize::text(&expected.to_string()).to_string();
+                let actual = sanitize::text(&actual.to_string()).to_string();
+
+                (
+                    format!("{name} expected {expected}, found {actual}"),
+                    format!(
+                        "{} expected {}, found {}",
+                        self.paint(BOLD, name),
+                        self.paint(GREEN, &expected),
+                        self.paint(RED, &actual)
+                    ),
+                )
+            }
+            VerifyFinding::Error { message, .. } => {
+                let message = sanitize::text(message).to_string();
+                let styled = self.paint(MAGENTA, &message);
+                (message, styled)
+            }
+        };
+
+        let path = sanitize::path(finding.path()).to_string();
+        let path = match self.width {
+            Some(width) => {
+                // The detail always fits, and paths give way to it.
+                let room = width.saturating_sub(detail.chars().count() + 2);
+                shorten(&path, room.max(MIN_PATH_WIDTH))
+            }
+            None => Cow::Borrowed(path.as_str()),
+        };
+
+        format!("{path}: {styled}")
+    }
+
+    /// Every finding, followed by a line counting them by kind.
+    pub fn format_report(&self, report: &VerifyReport) -> String {
+        let mut out = String::new();
+
+        let mut counts = [(0, "missing"), (0, "changed"), (0, "extra"), (0, "errors")];
+
+        for finding in &report.findings {
+            out.push_str(&self.format_finding(finding));
+            out.push('\n');
+
+            let idx = match finding {
+                VerifyFinding::Missing { .. } => 0,
+                VerifyFinding::Mismatch { .. } => 1,
+                VerifyFinding::Extra { .. } => 2,
+                VerifyFinding::Error { .. } => 3,
+            };
+            counts[idx].0 += 1;
+        }
+
+        let summary: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, what)| format!("{count} {what}"))
+            .collect();
+
+        if summary.is_empty() {
+            out.push_str(&self.paint(GREEN, "ok"));
+        } else {
+            out.push_str(&self.paint(BOLD, summary.join(", ")));
+        }
+        out.push('\n');
+
+        out
+    }
+
+    fn paint(&self, style: &str, text: impl fmt::Display) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Cuts characters out of the middle of `text` so it's at most `width`
+/// characters long, keeping both ends.
+fn shorten(text: &str, width: usize) -> Cow<'_, str> {
+    let len = text.chars().count();
+    if len <= width {
+        return Cow::Borrowed(text);
+    }
+
+    let keep = width.saturating_sub(1);
+    let tail = keep / 2;
+    let head = keep - tail;
+
+    let mut out: String = text.chars().take(head).collect();
+    out.push('…');
+    out.extend(text.chars().skip(len - tail));
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::Keyword;
+
+    #[test]
+    fn test_terminal_formatter() {
+        let report = VerifyReport {
+            findings: vec![
+                VerifyFinding::Missing {
+                    path: PathBuf::from("./usr/share/doc/some-package/changelog.gz"),
+                },
+                VerifyFinding::Mismatch {
+                    path: PathBuf::from("./etc/passwd"),
+                    expected: Keyword::Size(10),
+                    actual: Keyword::Size(12),
+                },
+            ],
+        };
+
+        let plain = TerminalFormatter::new().width(Some(32));
+        assert_eq!(
+            plain.format_report(&report),
+            "./usr/share…hangelog.gz: missing\n\
+             ./etc/passwd: size expected size=10, found size=12\n\
+             1 missing, 1 changed\n"
+        );
+        assert_eq!(
+            TerminalFormatter::new().format_report(&VerifyReport::default()),
+            "ok\n"
+        );
+
+        let colored = TerminalFormatter::new().color(ColorChoice::Always);
+        assert_eq!(
+            colored.format_finding(&report.findings[0]),
+            "./usr/share/doc/some-package/changelog.gz: \x1b[31mmissing\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// A point in time as stored in the `time` keyword: seconds since the Unix
+/// epoch plus a nanosecond fraction.
+///
+/// This is deliberately independent of any date/time crate; conversions to
+/// and from `chrono` are available behind the `chrono` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32,
+}
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+impl Timestamp {
+    /// Creates a timestamp, carrying any whole seconds in `nanos` over into
+    /// `secs`.
+    pub const fn new(secs: i64, nanos: u32) -> Self {
+        Timestamp {
+            secs: secs + (nanos / NANOS_PER_SEC) as i64,
+            nanos: nanos % NANOS_PER_SEC,
+        }
+    }
+
+    /// The latest timestamp, which [`ParseOptions::saturate_times`]
+    /// gives times too large to store.
+    ///
+    /// [`ParseOptions::saturate_times`]: crate::ParseOptions::saturate_times
+    pub const MAX: Timestamp = Timestamp {
+        secs: i64::MAX,
+        nanos: NANOS_PER_SEC - 1,
+    };
+
+    /// The earliest timestamp, which [`ParseOptions::saturate_times`]
+    /// gives times too far before the epoch to store.
+    ///
+    /// [`ParseOptions::saturate_times`]: crate::ParseOptions::saturate_times
+    pub const MIN: Timestamp = Timestamp {
+        secs: i64::MIN,
+        nanos: 0,
+    };
+
+    /// The timestamp as a [`SystemTime`], or `None` if it's out of the
+    /// platform's range.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let time = if self.secs >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(self.secs as u64))?
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(self.secs.unsigned_abs()))?
+        };
+
+        time.checked_add(Duration::from_nanos(u64::from(self.nanos)))
+    }
+
+    /// The time in the `SOURCE_DATE_EPOCH` environment variable, which
+    /// reproducible builds set to the time of the last change to the
+    /// source. `None` if it isn't set or isn't a decimal number of seconds.
+    pub fn source_date_epoch() -> Option<Timestamp> {
+        parse_epoch(&std::env::var("SOURCE_DATE_EPOCH").ok()?)
+    }
+}
+
+fn parse_epoch(value: &str) -> Option<Timestamp> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(Timestamp::new(value.parse().ok()?, 0))
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => Timestamp::new(since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                let secs = -(before.as_secs() as i64);
+
+                match before.subsec_nanos() {
+                    0 => Timestamp::new(secs, 0),
+                    nanos => Timestamp::new(secs - 1, NANOS_PER_SEC - nanos),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:09}", self.secs, self.nanos)
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use chrono::{DateTime, Utc};
+
+    use super::Timestamp;
+
+    impl Timestamp {
+        pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+            DateTime::from_timestamp(self.secs, self.nanos)
+        }
+
+        /// Like [`Timestamp::to_datetime`], but clamps times chrono can't
+        /// represent to its earliest or latest date.
+        pub fn to_datetime_saturating(&self) -> DateTime<Utc> {
+            self.to_datetime().unwrap_or(if self.secs < 0 {
+                DateTime::<Utc>::MIN_UTC
+            } else {
+                DateTime::<Utc>::MAX_UTC
+            })
+        }
+    }
+
+    impl From<DateTime<Utc>> for Timestamp {
+        fn from(time: DateTime<Utc>) -> Self {
+            Timestamp::new(time.timestamp(), time.timestamp_subsec_nanos())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_new() {
+        assert_eq!(
+            Timestamp::new(1, 1_500_000_000),
+            Timestamp {
+                secs: 2,
+                nanos: 500_000_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_timestamp_display() {
+        assert_eq!(
+            Timestamp::new(1630456800, 0).to_string(),
+            "1630456800.000000000"
+        );
+        assert_eq!(
+            Timestamp::new(1769640177, 434772208).to_string(),
+            "1769640177.434772208"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_to_system_time() {
+        assert_eq!(
+            Timestamp::new(-1, 500_000_000).to_system_time(),
+            Some(SystemTime::UNIX_EPOCH - Duration::from_millis(500))
+        );
+        assert_eq!(
+            Timestamp::from(SystemTime::UNIX_EPOCH - Duration::from_millis(500)),
+            Timestamp::new(-1, 500_000_000)
+        );
+        assert_eq!(
+            Timestamp::from(SystemTime::UNIX_EPOCH + Duration::new(1769640177, 434772208)),
+            Timestamp::new(1769640177, 434772208)
+        );
+    }
+
+    #[test]
+    fn test_parse_epoch() {
+        assert_eq!(
+            parse_epoch("1769640177"),
+            Some(Timestamp::new(1769640177, 0))
+        );
+        assert_eq!(parse_epoch("0"), Some(Timestamp::new(0, 0)));
+        assert_eq!(parse_epoch(""), None);
+        assert_eq!(parse_epoch("-1"), None);
+        assert_eq!(parse_epoch("1.5"), None);
+        assert_eq!(parse_epoch("99999999999999999999"), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_timestamp_chrono() {
+        let time = chrono::DateTime::from_timestamp(1769640177, 434772208).unwrap();
+
+        assert_eq!(Timestamp::from(time).to_datetime(), Some(time));
+        assert_eq!(
+            Timestamp::MAX.to_datetime_saturating(),
+            chrono::DateTime::<chrono::Utc>::MAX_UTC
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::comments::attached_comments;
+use crate::only::normalize;
+use crate::signature::{Layout, detect_layout};
+use crate::{
+    Command, EntryId, Flags, Keyword, KeywordKind, KeywordSet, Line, ResolvedEntry, Spec, Type,
+    UnsetTarget,
+};
+
+/// The owner to assign when normalizing ownership.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ownership {
+    pub uid: u32,
+    pub gid: u32,
+    pub uname: Arc<str>,
+    pub gname: Arc<str>,
+}
+
+impl Default for Ownership {
+    /// `root:wheel`, as used for most BSD system files.
+    fn default() -> Self {
+        Ownership {
+            uid: 0,
+            gid: 0,
+            uname: "root".into(),
+            gname: "wheel".into(),
+        }
+    }
+}
+
+impl Ownership {
+    fn rewrite(&self, keyword: &mut Keyword) {
+        match keyword {
+            Keyword::Uid(uid) => *uid = self.uid,
+            Keyword::Gid(gid) => *gid = self.gid,
+            Keyword::Uname(name) => *name = self.uname.clone(),
+            Keyword::Gname(name) => *name = self.gname.clone(),
+            Keyword::Flags(flags) => *flags = Flags::none(),
+            _ => {}
+        }
+    }
+}
+
+impl Spec {
+    /// Rewrites every ownership keyword in entries and `/set` commands to
+    /// `owner` and clears any file flags, the way a tree packaged under
+    /// `fakeroot` would be described.
+    ///
+    /// Only keywords that are present are rewritten; entries that don't
+    /// declare or inherit an owner are left without one.
+    pub fn normalize_ownership(&mut self, owner: &Ownership) {
+        for line in &mut self.lines {
+            let keywords = match line {
+                Line::Command {
+                    command: Command::Set(keywords),
+                    ..
+                } => keywords,
+                Line::Entry { entry, .. } => &mut entry.keywords,
+                _ => continue,
+            };
+
+            keywords
+                .iter_mut()
+                .for_each(|keyword| owner.rewrite(keyword));
+        }
+    }
+
+    /// Removes the entries for which `keep` returns false, along with their
+    /// comments. `keep` sees each entry resolved, with its full path.
+    ///
+    /// In a spec of relative entries, removing a directory also removes
+    /// everything below it, since those entries are only placed by being
+    /// nested in it. `/set` and `/unset` commands are always kept, as they
+    /// also apply to the entries that follow.
+    pub fn retain_entries(&mut self, mut keep: impl FnMut(&ResolvedEntry) -> bool) {
+        let resolved = self.resolve();
+        let removed: HashSet<EntryId> = resolved
+            .iter()
+            .filter(|entry| !keep(entry))
+            .map(|entry| entry.id)
+            .collect();
+
+        if removed.is_empty() {
+            return;
+        }
+
+        let dirs: HashSet<EntryId> = match detect_layout(&self.lines) {
+            Layout::Relative => resolved
+                .iter()
+                .filter(|entry| entry.keywords.contains(&Keyword::Type(Type::Dir)))
+                .map(|entry| entry.id)
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        // How many directories deep we are inside a removed one.
+        let mut skipping = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+
+        for line in self.lines.drain(..) {
+            match &line {
+                Line::Entry { entry, .. } if entry.path.as_os_str() == ".." && skipping > 0 => {
+                    skipping -= 1;
+                    continue;
+                }
+                Line::Entry { id, entry, .. } => {
+                    let opens_dir = dirs.contains(id)
+                        && entry.path.components().count() == 1
+                        && entry.path.as_os_str() != ".";
+
+                    if skipping > 0 || removed.contains(id) {
+                        if skipping == 0 {
+                            lines.truncate(attached_comments(&lines, lines.len()).start);
+                        }
+                        if opens_dir {
+                            skipping += 1;
+                        }
+                        continue;
+                    }
+                }
+                Line::Comment { .. } | Line::Blank { .. } if skipping > 0 => continue,
+                _ => {}
+            }
+
+            lines.push(line);
+        }
+
+        self.lines = lines;
+    }
+
+    /// Removes the keywords of the kinds in `keywords` from every entry and
+    /// `/set` command, such as [`KeywordSet::TIME`] to leave timestamps out
+    /// of a manifest that is committed. `/unset` commands no longer name
+    /// them either; commands left with nothing to do are removed.
+    pub fn strip_keywords(&mut self, keywords: KeywordSet) {
+        let stripped = |kind: KeywordKind| keywords.contains_kind(kind);
+
+        self.lines.retain_mut(|line| match line {
+            Line::Entry { entry, .. } => {
+                entry.keywords.retain(|keyword| !stripped(keyword.kind()));
+                true
+            }
+            Line::Command {
+                command: Command::Set(set),
+                ..
+            } => {
+                set.retain(|keyword| !stripped(keyword.kind()));
+                !set.is_empty()
+            }
+            Line::Command {
+                command: Command::Unset(UnsetTarget::Keywords(names)),
+                ..
+            } => {
+                names.retain(|name| !KeywordKind::from_name(name).is_some_and(stripped));
+                !names.is_empty()
+            }
+            _ => true,
+        });
+    }
+
+    /// Moves the entries at or below `from` to the same place below `to`,
+    /// e.g. from `./build/root` to `./usr`, returning how many were moved.
+    /// `hardlink` keywords that point there follow them.
+    ///
+    /// Only entries written with a full path are moved. In a spec of
+    /// relative entries, where a path is a single name placed by the
+    /// directories above it, rename the directory entry instead.
+    pub fn rewrite_paths(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> usize {
+        let (from, to) = (normalize(from.as_ref()), normalize(to.as_ref()));
+        let relative = detect_layout(&self.lines) == Layout::Relative;
+        let rewrite = |path: &Path| -> Option<PathBuf> {
+            if relative && path.components().count() == 1 {
+                return None;
+            }
+
+            let rest = normalize(path).strip_prefix(&from).ok()?.to_path_buf();
+            let moved = match rest.as_os_str().is_empty() {
+                true => to.clone(),
+                false => to.join(rest),
+            };
+
+            // Keep the entry's spelling, with or without a leading `./`.
+            match moved.strip_prefix(".") {
+                Ok(bare) if !path.starts_with(".") && !bare.as_os_str().is_empty() => {
+                    Some(bare.to_path_buf())
+                }
+                _ => Some(moved),
+            }
+        };
+
+        let mut moved = 0;
+
+        for entry in self.entries_mut() {
+            if let Some(path) = rewrite(&entry.path) {
+                entry.path = path;
+                moved += 1;
+            }
+
+            for keyword in &mut entry.keywords {
+                if let Keyword::HardLink(target) = keyword
+                    && let Some(path) = rewrite(target)
+                {
+                    *target = path;
+                }
+            }
+        }
+
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ownership() {
+        let mut spec = Spec::parse(
+            "/set uid=1000 gid=1000 uname=alice gname=users\n\
+             foo flags=uchg\n\
+             bar uid=1001 uname=bob size=3\n",
+        )
+        .unwrap();
+
+        spec.normalize_ownership(&Ownership::default());
+
+        for entry in spec.resolve() {
+            assert_eq!(entry.get(KeywordKind::Uid), Some(&Keyword::Uid(0)));
+            assert_eq!(entry.get(KeywordKind::Gid), Some(&Keyword::Gid(0)));
+            assert_eq!(
+                entry.get(KeywordKind::Uname),
+                Some(&Keyword::Uname("root".into()))
+            );
+            assert_eq!(
+                entry.get(KeywordKind::Gname),
+                Some(&Keyword::Gname("wheel".into()))
+            );
+        }
+
+        assert_eq!(
+            spec.find(std::path::Path::new("foo")).unwrap().keywords,
+            vec![Keyword::Flags(Flags::none())]
+        );
+
+        assert_eq!(
+            spec.find(std::path::Path::new("bar")).unwrap().keywords[2],
+            Keyword::Size(3)
+        );
+    }
+
+    #[test]
+    fn test_retain_entries() {
+        let mut spec = Spec::parse(
+            "/set type=file\n\
+             . type=dir\n\
+             # build output\n\
+             build type=dir\n\
+             \x20   obj type=dir\n\
+             /set uid=0\n\
+             \x20       a.o\n\
+             \x20   ..\n\
+             \x20   # kept\n\
+             \x20   log\n\
+             ..\n\
+             src type=dir\n\
+             \x20   main.c\n\
+             ..\n",
+        )
+        .unwrap();
+
+        spec.retain_entries(|entry| !entry.path.starts_with("./build"));
+
+        assert_eq!(
+            spec.to_string(),
+            "/set type=file\n\
+             . type=dir\n\
+             /set uid=0\n\
+             src type=dir\n\
+             main.c\n\
+             ..\n"
+        );
+
+        let mut spec = Spec::parse("./a size=1\n# b\n./b size=2\n./c size=3\n").unwrap();
+        spec.retain_entries(|entry| entry.size() != Some(2));
+        assert_eq!(spec.to_string(), "./a size=1\n./c size=3\n");
+    }
+
+    #[test]
+    fn test_strip_keywords() {
+        let mut spec = Spec::parse(
+            "/set uid=0 time=1.0\n\
+             ./a time=2.0 size=1\n\
+             /unset time\n\
+             /unset time uid\n\
+             ./b time=3.0 uid=1\n",
+        )
+        .unwrap();
+
+        spec.strip_keywords(KeywordSet::TIME | KeywordSet::UID);
+
+        assert_eq!(spec.to_string(), "./a size=1\n./b\n");
+    }
+
+    #[test]
+    fn test_rewrite_paths() {
+        let mut spec = Spec::parse(
+            "./build/root type=dir\n\
+             ./build/root/bin/ls hardlink=./build/root/bin/dir\n\
+             build/root/etc type=dir\n\
+             ./build/rootfs type=dir\n",
+        )
+        .unwrap();
+
+        assert_eq!(spec.rewrite_paths("build/root", "./usr"), 3);
+        assert_eq!(
+            spec.to_string(),
+            "./usr type=dir\n\
+             ./usr/bin/ls hardlink=./usr/bin/dir\n\
+             usr/etc type=dir\n\
+             ./build/rootfs type=dir\n"
+        );
+    }
+}
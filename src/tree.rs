@@ -0,0 +1,297 @@
+//! Directory hierarchy reconstructed from a spec.
+//!
+//! In the relative form of mtree(5), a bare name is relative to the current
+//! directory, a `type=dir` entry descends into that directory, and a `..`
+//! line returns to its parent. Names containing a `/` are full paths relative
+//! to the root and don't change the current directory.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+
+use crate::resolve::SetState;
+use crate::{EntryId, Keyword, Line, Span, Spec, Type};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub name: OsString,
+    pub path: PathBuf,
+    pub keywords: Vec<Keyword>,
+    /// The entry describing this node, if any. Directories that only appear
+    /// as part of a longer path have none.
+    pub id: Option<EntryId>,
+    pub span: Option<Span>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    /// `children` by name. Directories in large manifests can have many
+    /// thousands of children, too many to search one by one.
+    by_name: HashMap<OsString, NodeId>,
+}
+
+impl Node {
+    pub fn is_dir(&self) -> bool {
+        self.keywords.contains(&Keyword::Type(Type::Dir))
+    }
+}
+
+/// Two entries describing the same path. The later one is the one that
+/// ends up in the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateEntry {
+    pub node: NodeId,
+    pub earlier: Span,
+    pub later: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    duplicates: Vec<DuplicateEntry>,
+}
+
+impl Tree {
+    pub fn from_spec(spec: &Spec) -> Self {
+        let mut tree = Tree {
+            nodes: vec![Node {
+                name: OsString::from("."),
+                path: PathBuf::from("."),
+                keywords: Vec::new(),
+                id: None,
+                span: None,
+                parent: None,
+                children: Vec::new(),
+                by_name: HashMap::new(),
+            }],
+            duplicates: Vec::new(),
+        };
+
+        let mut state = SetState::new();
+        let mut cwd = tree.root();
+
+        for line in &spec.lines {
+            match line {
+                Line::Command { command, .. } => state.apply(command),
+                Line::Entry {
+                    id: entry_id,
+                    entry,
+                    span,
+                } => {
+                    if entry.path.as_os_str() == ".." {
+                        cwd = tree.node(cwd).parent.unwrap_or(cwd);
+                        continue;
+                    }
+
+                    let keywords = state.materialize(&entry.keywords);
+                    let is_dir = keywords.contains(&Keyword::Type(Type::Dir));
+                    let is_full_path = entry.path.components().count() > 1;
+
+                    let id = if is_full_path {
+                        tree.insert_path(tree.root(), &entry.path)
+                    } else if entry.path.as_os_str() == "." {
+                        tree.root()
+                    } else {
+                        tree.insert_path(cwd, &entry.path)
+                    };
+
+                    let node = &mut tree.nodes[id.0];
+                    if let Some(earlier) = node.span.replace(span.clone()) {
+                        tree.duplicates.push(DuplicateEntry {
+                            node: id,
+                            earlier,
+                            later: span.clone(),
+                        });
+                    }
+                    node.keywords = keywords;
+                    node.id = Some(*entry_id);
+
+                    if is_dir && !is_full_path {
+                        cwd = id;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        tree
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = &Node> {
+        self.node(id).children.iter().map(|child| self.node(*child))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let mut id = self.root();
+
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::Normal(name) => id = *self.node(id).by_name.get(name)?,
+                _ => return None,
+            }
+        }
+
+        Some(id)
+    }
+
+    /// Entries whose path an earlier entry already described, in the order
+    /// they appear in the spec.
+    pub fn duplicates(&self) -> &[DuplicateEntry] {
+        &self.duplicates
+    }
+
+    /// Iterates over all nodes in depth-first order, starting at the root.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            tree: self,
+            stack: vec![self.root()],
+        }
+    }
+
+    fn insert_path(&mut self, mut id: NodeId, path: &Path) -> NodeId {
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => id = self.child_or_insert(id, name),
+                Component::ParentDir => id = self.node(id).parent.unwrap_or(id),
+                _ => {}
+            }
+        }
+
+        id
+    }
+
+    fn child_or_insert(&mut self, parent: NodeId, name: &OsStr) -> NodeId {
+        if let Some(id) = self.node(parent).by_name.get(name) {
+            return *id;
+        }
+
+        let id = NodeId(self.nodes.len());
+        let path = self.node(parent).path.join(name);
+        let parent_node = &mut self.nodes[parent.0];
+        parent_node.by_name.insert(name.to_os_string(), id);
+        parent_node.children.push(id);
+
+        self.nodes.push(Node {
+            name: name.to_os_string(),
+            path,
+            keywords: Vec::new(),
+            id: None,
+            span: None,
+            parent: Some(parent),
+            children: Vec::new(),
+            by_name: HashMap::new(),
+        });
+
+        id
+    }
+}
+
+pub struct Iter<'a> {
+    tree: &'a Tree,
+    stack: Vec<NodeId>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        let node = self.tree.node(id);
+
+        self.stack.extend(node.children.iter().rev());
+
+        Some(node)
+    }
+}
+
+impl<'a> IntoIterator for &'a Tree {
+    type Item = &'a Node;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_relative() {
+        let spec = Spec::parse(
+            "/set type=file\n\
+             . type=dir\n\
+             etc type=dir\n\
+             \x20   passwd size=10\n\
+             \x20   ssl type=dir\n\
+             \x20       cert.pem\n\
+             \x20   ..\n\
+             \x20   hosts\n\
+             ..\n\
+             README\n",
+        )
+        .unwrap();
+
+        let tree = Tree::from_spec(&spec);
+        let paths: Vec<_> = tree.iter().map(|node| node.path.clone()).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("."),
+                PathBuf::from("./etc"),
+                PathBuf::from("./etc/passwd"),
+                PathBuf::from("./etc/ssl"),
+                PathBuf::from("./etc/ssl/cert.pem"),
+                PathBuf::from("./etc/hosts"),
+                PathBuf::from("./README"),
+            ]
+        );
+
+        let passwd = tree.find(Path::new("./etc/passwd")).unwrap();
+        assert_eq!(
+            tree.node(passwd).keywords,
+            vec![Keyword::Type(Type::File), Keyword::Size(10)]
+        );
+        assert_eq!(tree.node(passwd).span.as_ref().unwrap().line, 4);
+        assert_eq!(tree.children(tree.root()).count(), 2);
+    }
+
+    #[test]
+    fn test_tree_full_paths() {
+        let spec = Spec::parse(
+            "./usr type=dir\n\
+             ./usr/bin/ls type=file\n\
+             ./usr/lib type=dir\n",
+        )
+        .unwrap();
+
+        let tree = Tree::from_spec(&spec);
+        let bin = tree.find(Path::new("./usr/bin")).unwrap();
+
+        assert!(tree.node(bin).keywords.is_empty());
+        assert_eq!(tree.children(bin).count(), 1);
+        assert!(tree.node(tree.find(Path::new("usr/lib")).unwrap()).is_dir());
+        assert_eq!(tree.find(Path::new("./usr/sbin")), None);
+        assert_eq!(tree.len(), 5);
+    }
+}
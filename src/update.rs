@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::comments::attached_comments;
+use crate::resolve::{SetState, overlay};
+use crate::{Command, EntryId, Keyword, KeywordKind, Line, ResolvedEntry, Span, Spec, UnsetTarget};
+
+/// A set of keyword changes to apply to entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeywordEdits {
+    pub set: Vec<Keyword>,
+    pub unset: Vec<KeywordKind>,
+}
+
+impl KeywordEdits {
+    pub fn new() -> Self {
+        KeywordEdits::default()
+    }
+
+    pub fn set(mut self, keyword: Keyword) -> Self {
+        self.set.push(keyword);
+        self
+    }
+
+    pub fn unset(mut self, kind: KeywordKind) -> Self {
+        self.unset.push(kind);
+        self
+    }
+}
+
+impl Spec {
+    /// Applies `edits` to every entry whose resolved form, as given by
+    /// [`Spec::resolve`], matches `predicate`, returning the number of
+    /// entries changed. Entries overridden by a later entry for the same
+    /// path aren't offered to `predicate`.
+    ///
+    /// Keywords set by an edit are written onto the entry itself, so they
+    /// take precedence over `/set` state. When an edit unsets a keyword the
+    /// entry would otherwise inherit from `/set`, the entry is wrapped in an
+    /// `/unset` and a `/set` restoring the previous state, so surrounding
+    /// entries keep resolving to the same values.
+    pub fn update_where(
+        &mut self,
+        mut predicate: impl FnMut(&ResolvedEntry) -> bool,
+        edits: &KeywordEdits,
+    ) -> usize {
+        let mut resolved: HashMap<EntryId, ResolvedEntry> = self
+            .resolve()
+            .into_iter()
+            .map(|entry| (entry.id, entry))
+            .collect();
+        let mut state = SetState::new();
+        let mut updated = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+
+        for line in self.lines.drain(..) {
+            let (id, mut entry, span) = match line {
+                Line::Command { ref command, .. } => {
+                    state.apply(command);
+                    lines.push(line);
+                    continue;
+                }
+                Line::Entry { id, entry, span } if entry.path.as_os_str() != ".." => {
+                    (id, entry, span)
+                }
+                line => {
+                    lines.push(line);
+                    continue;
+                }
+            };
+
+            if !resolved
+                .remove(&id)
+                .is_some_and(|resolved| predicate(&resolved))
+            {
+                lines.push(Line::Entry { id, entry, span });
+                continue;
+            }
+
+            overlay(&mut entry.keywords, &edits.set);
+            entry
+                .keywords
+                .retain(|keyword| !edits.unset.contains(&keyword.kind()));
+
+            let inherited: Vec<Keyword> = state
+                .keywords()
+                .iter()
+                .filter(|keyword| edits.unset.contains(&keyword.kind()))
+                .cloned()
+                .collect();
+
+            if inherited.is_empty() {
+                lines.push(Line::Entry { id, entry, span });
+            } else {
+                let names = inherited
+                    .iter()
+                    .map(|keyword| keyword.kind().name().to_string())
+                    .collect();
+
+                // The `/unset` goes above the entry's comments, so they stay
+                // attached to it.
+                let comments = lines.split_off(attached_comments(&lines, lines.len()).start);
+
+                lines.push(Line::Command {
+                    command: Command::Unset(UnsetTarget::Keywords(names)),
+                    span: Span::default(),
+                });
+                lines.extend(comments);
+                lines.push(Line::Entry { id, entry, span });
+                lines.push(Line::Command {
+                    command: Command::Set(inherited),
+                    span: Span::default(),
+                });
+            }
+
+            updated += 1;
+        }
+
+        self.lines = lines;
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    #[test]
+    fn test_update_where_set() {
+        let mut spec = Spec::parse(
+            "./usr type=dir\n\
+             ./usr/bin/ls uid=1000\n\
+             ./etc/passwd uid=1000\n",
+        )
+        .unwrap();
+
+        let updated = spec.update_where(
+            |entry| entry.path.starts_with("./usr"),
+            &KeywordEdits::new().set(Keyword::Uid(0)),
+        );
+
+        assert_eq!(updated, 2);
+
+        let uids: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .map(|entry| {
+                let uid = entry.get(KeywordKind::Uid).cloned();
+                (entry.path, uid)
+            })
+            .collect();
+
+        assert_eq!(
+            uids,
+            vec![
+                (PathBuf::from("./usr"), Some(Keyword::Uid(0))),
+                (PathBuf::from("./usr/bin/ls"), Some(Keyword::Uid(0))),
+                (PathBuf::from("./etc/passwd"), Some(Keyword::Uid(1000))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_where_unset_inherited() {
+        let mut spec = Spec::parse("/set uid=0 size=1\nfoo\nbar\nbaz\n").unwrap();
+
+        spec.update_where(
+            |entry| entry.path == Path::new("./bar"),
+            &KeywordEdits::new().unset(KeywordKind::Uid),
+        );
+
+        let resolved: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .map(|entry| entry.keywords)
+            .collect();
+
+        assert_eq!(
+            resolved,
+            vec![
+                vec![Keyword::Uid(0), Keyword::Size(1)],
+                vec![Keyword::Size(1)],
+                vec![Keyword::Size(1), Keyword::Uid(0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_where_relative() {
+        let mut spec = Spec::parse(
+            "bin type=dir\n\
+             \x20   ls size=1\n\
+             ..\n\
+             ls size=2\n",
+        )
+        .unwrap();
+
+        let updated = spec.update_where(
+            |entry| entry.path == Path::new("./bin/ls"),
+            &KeywordEdits::new().set(Keyword::Size(3)),
+        );
+
+        assert_eq!(updated, 1);
+        let sizes: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .map(|entry| (entry.path, entry.get(KeywordKind::Size).cloned()))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (PathBuf::from("./bin"), None),
+                (PathBuf::from("./bin/ls"), Some(Keyword::Size(3))),
+                (PathBuf::from("./ls"), Some(Keyword::Size(2))),
+            ]
+        );
+    }
+}
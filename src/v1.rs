@@ -0,0 +1,248 @@
+//! A stable form of the AST, for users who need their code and stored specs
+//! to keep working across releases.
+//!
+//! The main AST changes as keywords and value types are added. The types
+//! here don't: a change to them would go in a new `v2` module, with `v1`
+//! kept alongside it. Keyword values are kept as the text written in a
+//! spec, so keywords supported in later releases still fit.
+//!
+//! Positions and entry ids aren't part of the stable form. Converting back
+//! numbers entries in order and leaves spans at their default.
+
+use std::path::PathBuf;
+
+use crate::{Command, EntryId, InvalidKeyword, Span, UnsetTarget, escape};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spec {
+    pub lines: Vec<Line>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Line {
+    Blank,
+    /// The text after the `#`.
+    Comment(String),
+    Set(Vec<Keyword>),
+    /// The keyword names to unset, where `all` unsets everything.
+    Unset(Vec<String>),
+    Entry(Entry),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entry {
+    pub path: PathBuf,
+    pub keywords: Vec<Keyword>,
+}
+
+/// A keyword as written in a spec, such as `mode=0644` or `optional`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyword {
+    /// The name before the `=`, including the attribute name of an
+    /// `xattr.<name>` keyword.
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl From<&crate::Keyword> for Keyword {
+    fn from(keyword: &crate::Keyword) -> Self {
+        let text = keyword.to_string();
+
+        match text.split_once('=') {
+            Some((name, value)) => Keyword {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+            },
+            None => Keyword {
+                name: text,
+                value: None,
+            },
+        }
+    }
+}
+
+impl TryFrom<&Keyword> for crate::Keyword {
+    type Error = InvalidKeyword;
+
+    fn try_from(keyword: &Keyword) -> Result<Self, Self::Error> {
+        match &keyword.value {
+            Some(value) => format!("{}={value}", keyword.name).parse(),
+            None => keyword.name.parse(),
+        }
+    }
+}
+
+impl From<&crate::Entry> for Entry {
+    fn from(entry: &crate::Entry) -> Self {
+        Entry {
+            path: entry.path.clone(),
+            keywords: entry.keywords.iter().map(Keyword::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<&Entry> for crate::Entry {
+    type Error = InvalidKeyword;
+
+    fn try_from(entry: &Entry) -> Result<Self, Self::Error> {
+        Ok(crate::Entry {
+            path: entry.path.clone(),
+            keywords: keywords(&entry.keywords)?,
+        })
+    }
+}
+
+impl From<&crate::Spec> for Spec {
+    fn from(spec: &crate::Spec) -> Self {
+        let lines = spec
+            .lines
+            .iter()
+            .map(|line| match line {
+                crate::Line::Blank { .. } => Line::Blank,
+                crate::Line::Comment { text, .. } => Line::Comment(text.clone()),
+                crate::Line::Command {
+                    command: Command::Set(keywords),
+                    ..
+                } => Line::Set(keywords.iter().map(Keyword::from).collect()),
+                crate::Line::Command {
+                    command: Command::Unset(UnsetTarget::All),
+                    ..
+                } => Line::Unset(vec!["all".to_string()]),
+                crate::Line::Command {
+                    command: Command::Unset(UnsetTarget::Keywords(names)),
+                    ..
+                } => Line::Unset(names.clone()),
+                crate::Line::Entry { entry, .. } => Line::Entry(Entry::from(entry)),
+            })
+            .collect();
+
+        Spec { lines }
+    }
+}
+
+impl TryFrom<&Spec> for crate::Spec {
+    type Error = InvalidKeyword;
+
+    fn try_from(spec: &Spec) -> Result<Self, Self::Error> {
+        let mut next_id = 0;
+        let span = Span::default;
+
+        let lines = spec
+            .lines
+            .iter()
+            .map(|line| {
+                let line = match line {
+                    Line::Blank => crate::Line::Blank { span: span() },
+                    Line::Comment(text) => crate::Line::Comment {
+                        text: text.clone(),
+                        span: span(),
+                    },
+                    Line::Set(set) => crate::Line::Command {
+                        command: Command::Set(keywords(set)?),
+                        span: span(),
+                    },
+                    Line::Unset(names) => {
+                        let target = if names.iter().any(|name| name == "all") {
+                            UnsetTarget::All
+                        } else {
+                            UnsetTarget::Keywords(names.clone())
+                        };
+
+                        crate::Line::Command {
+                            command: Command::Unset(target),
+                            span: span(),
+                        }
+                    }
+                    Line::Entry(entry) => {
+                        next_id += 1;
+                        crate::Line::Entry {
+                            id: EntryId(next_id - 1),
+                            entry: crate::Entry::try_from(entry)?,
+                            span: span(),
+                        }
+                    }
+                };
+
+                Ok(line)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(crate::Spec::new(lines))
+    }
+}
+
+fn keywords(keywords: &[Keyword]) -> Result<Vec<crate::Keyword>, InvalidKeyword> {
+    keywords.iter().map(crate::Keyword::try_from).collect()
+}
+
+impl Entry {
+    /// The entry as a line of a spec.
+    pub fn to_spec_string(&self) -> String {
+        let mut out = escape::encode_path(&self.path);
+
+        for keyword in &self.keywords {
+            out.push(' ');
+            out.push_str(&keyword.name);
+            if let Some(value) = &keyword.value {
+                out.push('=');
+                out.push_str(value);
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_round_trip() {
+        let input = "# header\n\
+                     \n\
+                     /set type=file uid=0\n\
+                     ./etc type=dir mode=0755\n\
+                     ./etc/passwd size=10 optional xattr.user.note=aGk=\n\
+                     /unset all\n";
+        let spec = crate::Spec::parse(input).unwrap();
+        let stable = Spec::from(&spec);
+
+        assert_eq!(stable.lines[0], Line::Comment(" header".to_string()));
+        assert_eq!(stable.lines[5], Line::Unset(vec!["all".to_string()]));
+
+        let Line::Entry(passwd) = &stable.lines[4] else {
+            panic!("expected an entry");
+        };
+        assert_eq!(
+            passwd.keywords[2],
+            Keyword {
+                name: "xattr.user.note".to_string(),
+                value: Some("aGk=".to_string()),
+            }
+        );
+        assert_eq!(
+            passwd.to_spec_string(),
+            "./etc/passwd size=10 optional xattr.user.note=aGk="
+        );
+
+        let back = crate::Spec::try_from(&stable).unwrap();
+        assert_eq!(back.to_string(), spec.to_string());
+        assert_eq!(
+            back.entry_ids().collect::<Vec<_>>(),
+            spec.entry_ids().collect::<Vec<_>>()
+        );
+
+        let bad = Spec {
+            lines: vec![Line::Set(vec![Keyword {
+                name: "uid".to_string(),
+                value: Some("x".to_string()),
+            }])],
+        };
+        assert!(crate::Spec::try_from(&bad).is_err());
+    }
+}
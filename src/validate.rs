@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::sanitize;
+use crate::{EntryId, Keyword, KeywordKind, Line, Span, Spec, Timestamp, Tree, Type};
+
+/// The characters allowed in a keyword value.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Charset {
+    #[default]
+    Any,
+    /// `0-9a-f` only.
+    LowerHex,
+    /// `0-9a-fA-F`.
+    Hex,
+    /// The POSIX portable filename character set, `A-Za-z0-9._-`.
+    Portable,
+    Custom(fn(char) -> bool),
+}
+
+impl Charset {
+    pub fn allows(&self, value: &str) -> bool {
+        match self {
+            Charset::Any => true,
+            Charset::LowerHex => value
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)),
+            Charset::Hex => value.chars().all(|c| c.is_ascii_hexdigit()),
+            Charset::Portable => value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')),
+            Charset::Custom(allows) => value.chars().all(allows),
+        }
+    }
+}
+
+/// Deployment-specific limits checked by [`Spec::validate_with`].
+#[derive(Clone, Debug, Default)]
+pub struct ValidationOptions {
+    /// Maximum length of a full path, in bytes.
+    pub max_path_len: Option<usize>,
+    /// Maximum length of a single path component, in bytes.
+    pub max_component_len: Option<usize>,
+    /// Characters allowed in `uname` and `gname` values.
+    pub name_charset: Charset,
+    /// The latest plausible `time`. Corrupt filesystems report times
+    /// centuries ahead.
+    pub latest_time: Option<Timestamp>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// An entry's parent is present in the spec but isn't a directory.
+    NonDirectoryParent {
+        path: PathBuf,
+        span: Span,
+        parent: PathBuf,
+        parent_span: Span,
+    },
+    /// Two entries resolve to the same path. The later one replaces the
+    /// earlier one.
+    DuplicatePath {
+        path: PathBuf,
+        span: Span,
+        earlier_span: Span,
+    },
+    PathTooLong {
+        path: PathBuf,
+        span: Span,
+        len: usize,
+        max: usize,
+    },
+    ComponentTooLong {
+        path: PathBuf,
+        span: Span,
+        len: usize,
+        max: usize,
+    },
+    /// A keyword value contains characters its [`Charset`] doesn't allow.
+    InvalidCharacters {
+        path: PathBuf,
+        span: Span,
+        keyword: KeywordKind,
+        value: String,
+    },
+    /// A uid or gid is `u32::MAX`, which chown(2) takes to mean "leave
+    /// unchanged". [`ParseOptions::saturate_ids`](crate::ParseOptions)
+    /// gives ids too large to store this value.
+    IdOutOfRange {
+        path: PathBuf,
+        span: Span,
+        keyword: KeywordKind,
+    },
+    /// A `time` is later than [`ValidationOptions::latest_time`], or is
+    /// [`Timestamp::MAX`] or [`Timestamp::MIN`], which
+    /// [`ParseOptions::saturate_times`] gives times out of range.
+    ///
+    /// [`ParseOptions::saturate_times`]: crate::ParseOptions::saturate_times
+    TimeOutOfRange {
+        path: PathBuf,
+        span: Span,
+        time: Timestamp,
+    },
+    /// An entry gives the same keyword twice with different values. Only
+    /// the last one takes effect.
+    ConflictingValues {
+        path: PathBuf,
+        span: Span,
+        first: Keyword,
+        second: Keyword,
+    },
+    /// A keyword that doesn't apply to the entry's type, such as `link` on
+    /// a directory.
+    IncompatibleKeyword {
+        path: PathBuf,
+        span: Span,
+        keyword: KeywordKind,
+        ty: Type,
+    },
+}
+
+impl ValidationIssue {
+    pub fn path(&self) -> &Path {
+        match self {
+            ValidationIssue::NonDirectoryParent { path, .. }
+            | ValidationIssue::DuplicatePath { path, .. }
+            | ValidationIssue::PathTooLong { path, .. }
+            | ValidationIssue::ComponentTooLong { path, .. }
+            | ValidationIssue::InvalidCharacters { path, .. }
+            | ValidationIssue::IdOutOfRange { path, .. }
+            | ValidationIssue::TimeOutOfRange { path, .. }
+            | ValidationIssue::ConflictingValues { path, .. }
+            | ValidationIssue::IncompatibleKeyword { path, .. } => path,
+        }
+    }
+
+    /// The line of the entry the issue is about.
+    pub fn span(&self) -> &Span {
+        match self {
+            ValidationIssue::NonDirectoryParent { span, .. }
+            | ValidationIssue::DuplicatePath { span, .. }
+            | ValidationIssue::PathTooLong { span, .. }
+            | ValidationIssue::ComponentTooLong { span, .. }
+            | ValidationIssue::InvalidCharacters { span, .. }
+            | ValidationIssue::IdOutOfRange { span, .. }
+            | ValidationIssue::TimeOutOfRange { span, .. }
+            | ValidationIssue::ConflictingValues { span, .. }
+            | ValidationIssue::IncompatibleKeyword { span, .. } => span,
+        }
+    }
+}
+
+impl ValidationIssue {
+    /// What's wrong, without the line number.
+    pub fn message(&self) -> String {
+        match self {
+            ValidationIssue::NonDirectoryParent {
+                path,
+                parent,
+                parent_span,
+                ..
+            } => format!(
+                "{} is inside {}, which is not a directory (line {})",
+                sanitize::path(path),
+                sanitize::path(parent),
+                parent_span.line
+            ),
+            ValidationIssue::DuplicatePath {
+                path, earlier_span, ..
+            } => format!(
+                "{} is already described on line {}",
+                sanitize::path(path),
+                earlier_span.line
+            ),
+            ValidationIssue::PathTooLong { path, len, max, .. } => format!(
+                "path {} is {len} bytes long, the limit is {max}",
+                sanitize::path(path)
+            ),
+            ValidationIssue::ComponentTooLong { path, len, max, .. } => format!(
+                "{} has a {len}-byte component, the limit is {max}",
+                sanitize::path(path)
+            ),
+            ValidationIssue::InvalidCharacters {
+                path,
+                keyword,
+                value,
+                ..
+            } => format!(
+                "{} value `{}` of {} contains disallowed characters",
+                keyword.name(),
+                sanitize::text(value),
+                sanitize::path(path)
+            ),
+            ValidationIssue::IdOutOfRange { path, keyword, .. } => format!(
+                "{} of {} is out of range",
+                keyword.name(),
+                sanitize::path(path)
+            ),
+            ValidationIssue::TimeOutOfRange { path, time, .. } => {
+                format!("time {time} of {} is out of range", sanitize::path(path))
+            }
+            ValidationIssue::ConflictingValues {
+                path,
+                first,
+                second,
+                ..
+            } => format!(
+                "{} has both {} and {}",
+                sanitize::path(path),
+                sanitize::text(&first.to_string()),
+                sanitize::text(&second.to_string())
+            ),
+            ValidationIssue::IncompatibleKeyword {
+                path, keyword, ty, ..
+            } => format!(
+                "{} is of type {ty}, which can't have {}",
+                sanitize::path(path),
+                keyword.name()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.span().line, self.message())
+    }
+}
+
+impl Spec {
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_with(&ValidationOptions::default())
+    }
+
+    pub fn validate_with(&self, options: &ValidationOptions) -> Vec<ValidationIssue> {
+        let tree = Tree::from_spec(self);
+        let mut issues = Vec::new();
+
+        check_hierarchy(&tree, &mut issues);
+        check_duplicates(&tree, &mut issues);
+        check_limits(&tree, options, &mut issues);
+        check_conflicts(self, &tree, &mut issues);
+        check_types(&tree, &mut issues);
+
+        issues
+    }
+}
+
+fn check_limits(tree: &Tree, options: &ValidationOptions, issues: &mut Vec<ValidationIssue>) {
+    for node in tree.iter() {
+        let Some(span) = &node.span else {
+            continue;
+        };
+
+        let len = node.path.as_os_str().len();
+        if let Some(max) = options.max_path_len.filter(|max| len > *max) {
+            issues.push(ValidationIssue::PathTooLong {
+                path: node.path.clone(),
+                span: span.clone(),
+                len,
+                max,
+            });
+        }
+
+        let longest = node
+            .path
+            .iter()
+            .map(|component| component.len())
+            .max()
+            .unwrap_or(0);
+        if let Some(max) = options.max_component_len.filter(|max| longest > *max) {
+            issues.push(ValidationIssue::ComponentTooLong {
+                path: node.path.clone(),
+                span: span.clone(),
+                len: longest,
+                max,
+            });
+        }
+
+        for keyword in &node.keywords {
+            match keyword {
+                Keyword::Uname(value) | Keyword::Gname(value)
+                    if !options.name_charset.allows(value) =>
+                {
+                    issues.push(ValidationIssue::InvalidCharacters {
+                        path: node.path.clone(),
+                        span: span.clone(),
+                        keyword: keyword.kind(),
+                        value: value.to_string(),
+                    });
+                }
+                Keyword::Uid(u32::MAX) | Keyword::Gid(u32::MAX) => {
+                    issues.push(ValidationIssue::IdOutOfRange {
+                        path: node.path.clone(),
+                        span: span.clone(),
+                        keyword: keyword.kind(),
+                    });
+                }
+                Keyword::Time(time)
+                    if *time == Timestamp::MAX
+                        || *time == Timestamp::MIN
+                        || options.latest_time.is_some_and(|latest| *time > latest) =>
+                {
+                    issues.push(ValidationIssue::TimeOutOfRange {
+                        path: node.path.clone(),
+                        span: span.clone(),
+                        time: *time,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Looks for keywords given twice on the same line. Keywords from `/set`
+/// lines are meant to be overridden, so they don't count.
+fn check_conflicts(spec: &Spec, tree: &Tree, issues: &mut Vec<ValidationIssue>) {
+    let paths: HashMap<EntryId, &PathBuf> = tree
+        .iter()
+        .filter_map(|node| Some((node.id?, &node.path)))
+        .collect();
+
+    for line in &spec.lines {
+        let Line::Entry { id, entry, span } = line else {
+            continue;
+        };
+        let path = paths.get(id).copied().unwrap_or(&entry.path);
+
+        for (idx, second) in entry.keywords.iter().enumerate() {
+            let first = entry.keywords[..idx]
+                .iter()
+                .rfind(|first| first.same_property(second));
+
+            if let Some(first) = first.filter(|first| *first != second) {
+                issues.push(ValidationIssue::ConflictingValues {
+                    path: path.clone(),
+                    span: span.clone(),
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// The types a keyword makes sense for, if it doesn't apply to every type.
+fn applies_to(kind: KeywordKind) -> Option<&'static [Type]> {
+    match kind {
+        KeywordKind::Link => Some(&[Type::Link]),
+        KeywordKind::Device => Some(&[Type::Block, Type::Char]),
+        KeywordKind::HardLink => Some(&[
+            Type::File,
+            Type::Link,
+            Type::Block,
+            Type::Char,
+            Type::Fifo,
+            Type::Socket,
+        ]),
+        KeywordKind::Contents | KeywordKind::Cksum | KeywordKind::Digest(_) => Some(&[Type::File]),
+        _ => None,
+    }
+}
+
+fn check_types(tree: &Tree, issues: &mut Vec<ValidationIssue>) {
+    for node in tree.iter() {
+        let Some(span) = &node.span else {
+            continue;
+        };
+
+        let Some(ty) = node.keywords.iter().find_map(|keyword| match keyword {
+            Keyword::Type(ty) => Some(ty),
+            _ => None,
+        }) else {
+            continue;
+        };
+
+        for keyword in &node.keywords {
+            let kind = keyword.kind();
+            if applies_to(kind).is_some_and(|types| !types.contains(ty)) {
+                issues.push(ValidationIssue::IncompatibleKeyword {
+                    path: node.path.clone(),
+                    span: span.clone(),
+                    keyword: kind,
+                    ty: ty.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn check_duplicates(tree: &Tree, issues: &mut Vec<ValidationIssue>) {
+    for duplicate in tree.duplicates() {
+        issues.push(ValidationIssue::DuplicatePath {
+            path: tree.node(duplicate.node).path.clone(),
+            span: duplicate.later.clone(),
+            earlier_span: duplicate.earlier.clone(),
+        });
+    }
+}
+
+fn check_hierarchy(tree: &Tree, issues: &mut Vec<ValidationIssue>) {
+    for node in tree.iter() {
+        let Some(parent) = node.parent.map(|id| tree.node(id)) else {
+            continue;
+        };
+
+        let parent_type = parent.keywords.iter().find_map(|keyword| match keyword {
+            Keyword::Type(ty) => Some(ty),
+            _ => None,
+        });
+
+        if matches!(parent_type, Some(ty) if *ty != Type::Dir) {
+            issues.push(ValidationIssue::NonDirectoryParent {
+                path: node.path.clone(),
+                span: node.span.clone().unwrap_or_default(),
+                parent: parent.path.clone(),
+                parent_span: parent.span.clone().unwrap_or_default(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+
+    #[test]
+    fn test_validate_non_directory_parent() {
+        let spec = Spec::parse(
+            "./etc type=dir\n\
+             ./etc/passwd type=file\n\
+             ./etc/passwd/oops type=file\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.validate(),
+            vec![ValidationIssue::NonDirectoryParent {
+                path: PathBuf::from("./etc/passwd/oops"),
+                span: Span {
+                    start: 38,
+                    end: 65,
+                    line: 3
+                },
+                parent: PathBuf::from("./etc/passwd"),
+                parent_span: Span {
+                    start: 15,
+                    end: 37,
+                    line: 2
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_limits() {
+        let spec = Spec::parse(
+            "/set uname=root gname=wh\\040eel\n\
+             ./a-very-long-directory-name/file size=1\n",
+        )
+        .unwrap();
+
+        let options = ValidationOptions {
+            max_path_len: Some(20),
+            max_component_len: Some(16),
+            name_charset: Charset::Portable,
+            ..ValidationOptions::default()
+        };
+
+        let issues = spec.validate_with(&options);
+        let path = PathBuf::from("./a-very-long-directory-name/file");
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| match issue {
+                    ValidationIssue::PathTooLong { len, .. } => format!("path {len}"),
+                    ValidationIssue::ComponentTooLong { len, .. } => format!("component {len}"),
+                    ValidationIssue::InvalidCharacters { keyword, .. } =>
+                        keyword.name().to_string(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>(),
+            vec!["path 33", "component 26", "gname"]
+        );
+        assert!(issues.iter().all(|issue| match issue {
+            ValidationIssue::PathTooLong { path: p, .. }
+            | ValidationIssue::ComponentTooLong { path: p, .. }
+            | ValidationIssue::InvalidCharacters { path: p, .. } => *p == path,
+            _ => false,
+        }));
+        assert!(spec.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_saturated_ids() {
+        let input = "/set uid=0 gid=4294967295\nfoo uid=99999999999\n";
+        assert!(Spec::parse(input).is_err());
+
+        let options = ParseOptions {
+            saturate_ids: true,
+            ..ParseOptions::default()
+        };
+        let spec = Spec::parse_with(input, &options).unwrap();
+        assert_eq!(
+            spec.find(Path::new("foo")).unwrap().keywords,
+            [Keyword::Uid(u32::MAX)]
+        );
+
+        assert_eq!(
+            spec.validate()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 2: uid of ./foo is out of range",
+                "line 2: gid of ./foo is out of range"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_times() {
+        let input = "foo time=99999999999999999999.0
+bar time=18446744073.0
+baz time=1769640177.0
+";
+        assert!(Spec::parse(input).is_err());
+
+        let options = ParseOptions {
+            saturate_times: true,
+            ..ParseOptions::default()
+        };
+        let spec = Spec::parse_with(input, &options).unwrap();
+        assert_eq!(
+            spec.find(Path::new("foo")).unwrap().keywords,
+            [Keyword::Time(Timestamp::MAX)]
+        );
+        assert_eq!(spec.validate().len(), 1);
+
+        let options = ValidationOptions {
+            latest_time: Some(Timestamp::new(4102444800, 0)),
+            ..ValidationOptions::default()
+        };
+        assert_eq!(
+            spec.validate_with(&options)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 1: time 9223372036854775807.999999999 of ./foo is out of range",
+                "line 2: time 18446744073.000000000 of ./bar is out of range"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_conflicts() {
+        let spec = Spec::parse(
+            "/set type=file size=1\n\
+             foo size=2 size=3 size=3 uid=0\n\
+             baz type=char device=native,4,1 md5=d41d8cd98f00b204e9800998ecf8427e\n\
+             bar type=dir link=foo xattr.a=aGk= xattr.b=aGk=\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.validate()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 2: ./foo has both size=2 and size=3",
+                "line 3: ./baz is of type char, which can't have md5digest",
+                "line 4: ./bar is of type dir, which can't have link",
+            ]
+        );
+        assert!(matches!(
+            &spec.validate()[0],
+            ValidationIssue::ConflictingValues {
+                first: Keyword::Size(2),
+                second: Keyword::Size(3),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_validate_duplicate_paths() {
+        let spec = Spec::parse(
+            "/set type=file\n\
+             . type=dir\n\
+             etc type=dir\n\
+             \x20   passwd\n\
+             ..\n\
+             etc/passwd size=1\n\
+             etc/hosts\n\
+             ./etc/passwd\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.validate()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 6: ./etc/passwd is already described on line 4",
+                "line 8: ./etc/passwd is already described on line 6",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_clean() {
+        let spec = Spec::parse(". type=dir\netc type=dir\npasswd type=file\n").unwrap();
+
+        assert_eq!(spec.validate(), vec![]);
+    }
+}
@@ -0,0 +1,1858 @@
+//! Checking a spec against a filesystem tree.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::cksum::cksum;
+use crate::create::{DeviceReader, dev_ino, file_type, metadata_keywords, modified, spec_child};
+#[cfg(target_os = "linux")]
+use crate::dirfd::Dir;
+use crate::matcher::{PathMatcher, Selection};
+use crate::progress::{ProgressReader, ProgressSink};
+use crate::sanitize;
+use crate::xattr;
+use crate::{
+    Device, ExcludeList, Keyword, KeywordKind, KeywordSet, OnlyList, Progress, ResolvedEntry,
+    RootPath, Spec, Timestamp, Type,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VerifyFinding {
+    /// The entry is in the spec but doesn't exist on disk.
+    Missing { path: PathBuf },
+    /// A keyword's value on disk differs from the spec.
+    Mismatch {
+        path: PathBuf,
+        expected: Keyword,
+        actual: Keyword,
+    },
+    /// The entry couldn't be checked, e.g. because it couldn't be read.
+    Error { path: PathBuf, message: String },
+    /// The file exists on disk but isn't in the spec.
+    Extra { path: PathBuf },
+}
+
+impl VerifyFinding {
+    pub fn path(&self) -> &Path {
+        match self {
+            VerifyFinding::Missing { path }
+            | VerifyFinding::Mismatch { path, .. }
+            | VerifyFinding::Error { path, .. }
+            | VerifyFinding::Extra { path } => path,
+        }
+    }
+
+    /// The keyword a mismatch is about, if any.
+    pub fn keyword(&self) -> Option<KeywordKind> {
+        match self {
+            VerifyFinding::Mismatch { expected, .. } => Some(expected.kind()),
+            _ => None,
+        }
+    }
+
+    /// Whether `other` reports the same problem, ignoring the actual values
+    /// found: a file whose size keeps drifting is still the same finding.
+    pub fn same_issue(&self, other: &VerifyFinding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && self.path() == other.path()
+            && self.keyword() == other.keyword()
+    }
+}
+
+impl fmt::Display for VerifyFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFinding::Missing { path } => write!(f, "{}: missing", sanitize::path(path)),
+            VerifyFinding::Mismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: {} expected {}, found {}",
+                sanitize::path(path),
+                expected.kind().name(),
+                sanitize::text(&expected.to_string()),
+                sanitize::text(&actual.to_string())
+            ),
+            VerifyFinding::Error { path, message } => {
+                write!(f, "{}: {message}", sanitize::path(path))
+            }
+            VerifyFinding::Extra { path } => write!(f, "{}: extra", sanitize::path(path)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerifyReport {
+    pub findings: Vec<VerifyFinding>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// The findings grouped by path, in the order each path was first
+    /// reported.
+    pub fn by_path(&self) -> Vec<(&Path, Vec<&VerifyFinding>)> {
+        let mut groups: Vec<(&Path, Vec<&VerifyFinding>)> = Vec::new();
+        let mut index = HashMap::new();
+
+        for finding in &self.findings {
+            let idx = *index.entry(finding.path()).or_insert_with(|| {
+                groups.push((finding.path(), Vec::new()));
+                groups.len() - 1
+            });
+            groups[idx].1.push(finding);
+        }
+
+        groups
+    }
+
+    pub fn summary(&self) -> VerifySummary {
+        let mut summary = VerifySummary::default();
+        let mut changed = HashSet::new();
+
+        for finding in &self.findings {
+            match finding {
+                VerifyFinding::Missing { .. } => summary.missing += 1,
+                VerifyFinding::Mismatch { path, .. } => {
+                    summary.mismatches += 1;
+                    changed.insert(path);
+                }
+                VerifyFinding::Error { .. } => summary.errors += 1,
+                VerifyFinding::Extra { .. } => summary.extra += 1,
+            }
+        }
+
+        summary.changed = changed.len();
+        summary
+    }
+
+    /// Compares this report against one from a previous run.
+    pub fn since(&self, previous: &VerifyReport) -> ReportDelta {
+        let known = |finding: &VerifyFinding, report: &VerifyReport| {
+            report
+                .findings
+                .iter()
+                .any(|other| finding.same_issue(other))
+        };
+
+        let mut delta = ReportDelta::default();
+
+        for finding in &self.findings {
+            if known(finding, previous) {
+                delta.persisting.push(finding.clone());
+            } else {
+                delta.new.push(finding.clone());
+            }
+        }
+
+        delta.resolved = previous
+            .findings
+            .iter()
+            .filter(|finding| !known(finding, self))
+            .cloned()
+            .collect();
+
+        delta
+    }
+}
+
+/// Writes the report the way mtree(8) does when verifying:
+///
+/// ```text
+/// ./etc/passwd changed
+///         size expected 10 found 15
+/// ./etc/hosts missing
+/// extra: ./etc/extra
+/// ```
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |keyword: &Keyword| {
+            let text = keyword.to_string();
+            match text.split_once('=') {
+                Some((_, value)) => value.to_string(),
+                None => text,
+            }
+        };
+
+        for (path, findings) in self.by_path() {
+            let path = sanitize::path(path);
+            let mut changed = false;
+
+            for finding in findings {
+                match finding {
+                    VerifyFinding::Missing { .. } => writeln!(f, "{path} missing")?,
+                    VerifyFinding::Mismatch {
+                        expected, actual, ..
+                    } => {
+                        if !changed {
+                            writeln!(f, "{path} changed")?;
+                            changed = true;
+                        }
+                        writeln!(
+                            f,
+                            "\t{} expected {} found {}",
+                            expected.kind().name(),
+                            sanitize::text(&value(expected)),
+                            sanitize::text(&value(actual))
+                        )?;
+                    }
+                    VerifyFinding::Error { message, .. } => writeln!(f, "{path}: {message}")?,
+                    VerifyFinding::Extra { .. } => writeln!(f, "extra: {path}")?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts of what a verification run found, for monitoring.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerifySummary {
+    /// Paths with at least one mismatched keyword.
+    pub changed: usize,
+    /// Mismatched keywords, across all paths.
+    pub mismatches: usize,
+    pub missing: usize,
+    pub extra: usize,
+    pub errors: usize,
+}
+
+impl VerifySummary {
+    /// The number of problems found, counting each changed path once.
+    pub fn total(&self) -> usize {
+        self.changed + self.missing + self.extra + self.errors
+    }
+}
+
+/// Verification results for the entries tagged with one package, in the
+/// spirit of `pacman -Qk` summary lines.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageSummary {
+    pub package: String,
+    pub clean: usize,
+    pub modified: usize,
+    pub missing: usize,
+}
+
+impl PackageSummary {
+    pub fn total(&self) -> usize {
+        self.clean + self.modified + self.missing
+    }
+
+    fn count(&mut self, missing: Option<&bool>) {
+        match missing {
+            None => self.clean += 1,
+            Some(false) => self.modified += 1,
+            Some(true) => self.missing += 1,
+        }
+    }
+}
+
+impl fmt::Display for PackageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} total files, {} altered files, {} missing files",
+            sanitize::text(&self.package),
+            self.total(),
+            self.modified,
+            self.missing
+        )
+    }
+}
+
+impl VerifyReport {
+    /// Groups the results by the `tags` of the entries in `spec`, the spec
+    /// the report was produced from. An entry with several tags counts
+    /// towards each of them; untagged and ignored entries aren't counted.
+    pub fn package_summaries(&self, spec: &Spec) -> Vec<PackageSummary> {
+        let missing = self.missing_by_path();
+        let mut summaries: BTreeMap<&str, PackageSummary> = BTreeMap::new();
+        let resolved = spec.resolve();
+
+        for entry in resolved.iter().filter(|entry| !entry.is_ignored()) {
+            for tag in entry.tags() {
+                let summary = summaries
+                    .entry(tag.as_str())
+                    .or_insert_with(|| PackageSummary {
+                        package: tag.clone(),
+                        ..Default::default()
+                    });
+                summary.count(missing.get(entry.path.as_path()));
+            }
+        }
+
+        summaries.into_values().collect()
+    }
+
+    /// Counts every entry in `spec`, the spec of a single package, towards
+    /// one summary for `package`, whatever their tags.
+    pub fn package_summary(&self, package: impl Into<String>, spec: &Spec) -> PackageSummary {
+        let missing = self.missing_by_path();
+        let mut summary = PackageSummary {
+            package: package.into(),
+            ..Default::default()
+        };
+
+        for entry in spec.resolve().iter().filter(|entry| !entry.is_ignored()) {
+            summary.count(missing.get(entry.path.as_path()));
+        }
+
+        summary
+    }
+
+    /// Whether each path with findings is missing, rather than modified.
+    fn missing_by_path(&self) -> HashMap<&Path, bool> {
+        let mut missing = HashMap::new();
+        for finding in &self.findings {
+            let is_missing = matches!(finding, VerifyFinding::Missing { .. });
+            *missing.entry(finding.path()).or_insert(false) |= is_missing;
+        }
+
+        missing
+    }
+}
+
+/// The difference between two verification runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReportDelta {
+    /// Findings that weren't present in the previous report.
+    pub new: Vec<VerifyFinding>,
+    /// Findings from the previous report that no longer occur.
+    pub resolved: Vec<VerifyFinding>,
+    /// Findings present in both reports.
+    pub persisting: Vec<VerifyFinding>,
+}
+
+/// Checks a spec against the files under `root`.
+pub fn verify(spec: &Spec, root: &Path) -> VerifyReport {
+    Verifier::new(RootPath::new(root)).verify(spec)
+}
+
+/// Compares spec entries with the files under a root directory.
+///
+/// On Linux, files are looked up through directory file descriptors (see
+/// [`Dir`]), so a directory on the way to a file that has been replaced by a
+/// symlink is reported as an error instead of being followed. With
+/// [`RootPath::jail`] enabled, or on other platforms, lookups go through
+/// [`RootPath::resolve`] instead.
+///
+/// `uname`, `gname`, `flags` and `resdevice` aren't checked, and digests are
+/// only checked with the `hash` feature enabled. Outside Unix, `uid`, `gid`,
+/// `nlink` and `inode` aren't checked either, and `mode` only as far as
+/// whether the file is writable.
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    root: RootPath,
+    extras: bool,
+    exclude: ExcludeList,
+    only: Option<OnlyList>,
+    select: Option<Selection>,
+    keywords: Option<KeywordSet>,
+    device_limit: Option<u64>,
+    progress: ProgressSink,
+    cache: Option<Arc<ContentCache>>,
+}
+
+impl Verifier {
+    pub fn new(root: RootPath) -> Self {
+        Verifier {
+            root,
+            extras: false,
+            exclude: ExcludeList::new(),
+            only: None,
+            select: None,
+            keywords: None,
+            device_limit: None,
+            progress: ProgressSink::default(),
+            cache: None,
+        }
+    }
+
+    /// Also walks the root and reports files that aren't in the spec as
+    /// [`VerifyFinding::Extra`]. Extra directories aren't descended into.
+    /// Off by default, like passing `-e` to mtree.
+    pub fn extras(mut self, extras: bool) -> Self {
+        self.extras = extras;
+        self
+    }
+
+    /// Skips entries matching `exclude`, and everything below excluded
+    /// directories, as if they weren't in the spec or on disk.
+    pub fn exclude(mut self, exclude: ExcludeList) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Only checks the files in `only` and the directories leading up to
+    /// them. Other entries, and other files on disk, are skipped.
+    pub fn only(mut self, only: OnlyList) -> Self {
+        self.only = Some(only);
+        self
+    }
+
+    /// Only checks entries, and files on disk, whose paths match `matcher`.
+    /// Unlike [`Verifier::only`], directories aren't kept just because
+    /// something below them matches.
+    pub fn select(mut self, matcher: impl PathMatcher + 'static) -> Self {
+        self.select = Some(Selection(Arc::new(matcher)));
+        self
+    }
+
+    /// Only checks the keywords in `keywords`, such as
+    /// [`KeywordSet::BACKUP`], ignoring the rest of the spec's. Keywords
+    /// that can't be selected, like `optional`, still apply.
+    pub fn keywords(mut self, keywords: KeywordSet) -> Self {
+        self.keywords = Some(keywords);
+        self
+    }
+
+    /// Also checks the `cksum` and digests of block and character devices,
+    /// as recorded with
+    /// [`SpecBuilder::device_contents`](crate::SpecBuilder::device_contents).
+    /// A device with more than `limit` bytes is reported as an error.
+    pub fn device_contents(mut self, limit: u64) -> Self {
+        self.device_limit = Some(limit);
+        self
+    }
+
+    /// Reports the entries checked and bytes read while verifying to
+    /// `progress`. Walking the root for [`Verifier::extras`] isn't counted.
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = ProgressSink(Arc::new(progress));
+        self
+    }
+
+    pub fn verify(&self, spec: &Spec) -> VerifyReport {
+        self.verify_entries(&spec.resolve())
+    }
+
+    /// Checks each spec against its root, with this verifier's settings
+    /// other than its root, returning one report per pair in the same
+    /// order. With the `rayon` feature the pairs are checked in parallel on
+    /// rayon's thread pool.
+    ///
+    /// The `cksum` and digests computed are shared between the pairs, so a
+    /// file reached more than once, such as a base layer checked against
+    /// several specs, is only read again if it has changed in between.
+    pub fn verify_many(&self, targets: &[(Spec, RootPath)]) -> Vec<VerifyReport> {
+        let cache = Arc::new(ContentCache::default());
+        let verify = |(spec, root): &(Spec, RootPath)| {
+            Verifier {
+                root: root.clone(),
+                cache: Some(cache.clone()),
+                ..self.clone()
+            }
+            .verify(spec)
+        };
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            targets.par_iter().map(verify).collect()
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        targets.iter().map(verify).collect()
+    }
+
+    /// Checks `entries` in the order given. Entries marked `ignore`, and
+    /// everything beneath them, are skipped.
+    pub fn verify_entries(&self, entries: &[ResolvedEntry]) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        self.verify_each(entries, |finding| report.findings.push(finding));
+        report
+    }
+
+    /// Checks again, with every keyword in the spec rather than only those
+    /// given to [`Verifier::keywords`], the entries `report` found a problem
+    /// with. Meant to follow a quick pass with [`KeywordSet::QUICK`], so that
+    /// only files whose metadata changed have their contents read.
+    pub fn verify_flagged(&self, spec: &Spec, report: &VerifyReport) -> VerifyReport {
+        let flagged: HashSet<&Path> = report
+            .findings
+            .iter()
+            .filter(|finding| !matches!(finding, VerifyFinding::Extra { .. }))
+            .map(VerifyFinding::path)
+            .collect();
+        let entries: Vec<_> = spec
+            .resolve()
+            .into_iter()
+            .filter(|entry| flagged.contains(entry.path.as_path()))
+            .collect();
+
+        let full = Verifier {
+            keywords: None,
+            extras: false,
+            ..self.clone()
+        };
+        full.verify_entries(&entries)
+    }
+
+    /// Like [`Verifier::verify_entries`], but passes each finding to
+    /// `on_finding` as soon as it is found.
+    pub fn verify_each(
+        &self,
+        entries: &[ResolvedEntry],
+        mut on_finding: impl FnMut(VerifyFinding),
+    ) {
+        let files = match Files::open(&self.root) {
+            Ok(files) => files,
+            Err(err) => {
+                on_finding(VerifyFinding::Error {
+                    path: PathBuf::from("."),
+                    message: err.to_string(),
+                });
+                return;
+            }
+        };
+
+        let checked: Vec<_> = self.checked(entries).collect();
+        let options = self.check_options();
+        self.progress.0.start(checked.len() as u64);
+
+        for entry in checked {
+            self.progress.0.file(&entry.path);
+            files
+                .check(&entry, &options, &*self.progress.0)
+                .into_iter()
+                .for_each(&mut on_finding);
+        }
+
+        if self.extras {
+            files.extras(entries, |path| self.skips(path), &mut on_finding);
+        }
+    }
+
+    /// Checks the metadata of every entry in `spec` right away, leaving the
+    /// `cksum` and digests to be checked with
+    /// [`DeferredCheck::verify_content`], so that an interactive tool can
+    /// show the state of a large tree quickly and read files only when
+    /// asked to. Extra files aren't reported.
+    pub fn verify_deferred(&self, spec: &Spec) -> io::Result<Vec<DeferredCheck<'_>>> {
+        let files = Arc::new(Files::open(&self.root)?);
+        let entries = spec.resolve();
+
+        let checks = self
+            .checked(&entries)
+            .map(|entry| {
+                // Keywords like `optional` apply to both halves.
+                let content = entry
+                    .keywords
+                    .iter()
+                    .filter(|keyword| {
+                        is_content_keyword(keyword)
+                            || KeywordSet::from_kind(keyword.kind()).is_empty()
+                    })
+                    .cloned()
+                    .collect();
+                let metadata = entry
+                    .keywords
+                    .iter()
+                    .filter(|keyword| !is_content_keyword(keyword))
+                    .cloned()
+                    .collect();
+
+                let findings = files.check(
+                    &ResolvedEntry {
+                        keywords: metadata,
+                        ..entry.clone()
+                    },
+                    &self.check_options(),
+                    &(),
+                );
+
+                DeferredCheck {
+                    files: files.clone(),
+                    options: self.check_options(),
+                    progress: self.progress.clone(),
+                    entry: ResolvedEntry {
+                        keywords: content,
+                        ..entry
+                    },
+                    findings,
+                }
+            })
+            .collect();
+
+        Ok(checks)
+    }
+
+    /// The entries to check, with [`Verifier::keywords`] applied.
+    fn checked<'e>(
+        &'e self,
+        entries: &'e [ResolvedEntry],
+    ) -> impl Iterator<Item = ResolvedEntry> + 'e {
+        let ignored: Vec<&Path> = entries
+            .iter()
+            .filter(|entry| entry.is_ignored())
+            .map(|entry| entry.path.as_path())
+            .collect();
+
+        entries
+            .iter()
+            .filter(move |entry| {
+                !ignored.iter().any(|path| entry.path.starts_with(path)) && !self.skips(&entry.path)
+            })
+            .map(|entry| match self.keywords {
+                Some(keywords) => restrict(entry, keywords),
+                None => entry.clone(),
+            })
+    }
+
+    fn check_options(&self) -> CheckOptions {
+        CheckOptions {
+            device_limit: self.device_limit,
+            xattrs: match self.keywords {
+                Some(keywords) => keywords & (KeywordSet::XATTR | KeywordSet::SECURITY),
+                None => KeywordSet::XATTR,
+            },
+            cache: self.cache.clone(),
+        }
+    }
+
+    /// Whether `path` is left out by the exclude or only lists or the
+    /// selection.
+    fn skips(&self, path: &Path) -> bool {
+        self.exclude.matches(path)
+            || self.only.as_ref().is_some_and(|only| !only.matches(path))
+            || self
+                .select
+                .as_ref()
+                .is_some_and(|select| !select.0.matches(path))
+    }
+}
+
+/// An entry whose metadata has been checked, holding on to what's needed to
+/// check its contents later.
+pub struct DeferredCheck<'a> {
+    files: Arc<Files<'a>>,
+    options: CheckOptions,
+    progress: ProgressSink,
+    /// The entry with only its `cksum`, digests and keywords such as
+    /// `optional` left.
+    entry: ResolvedEntry,
+    /// What checking the metadata found.
+    pub findings: Vec<VerifyFinding>,
+}
+
+impl DeferredCheck<'_> {
+    pub fn path(&self) -> &Path {
+        &self.entry.path
+    }
+
+    /// Whether the entry has a `cksum` or digest to check.
+    pub fn has_content(&self) -> bool {
+        self.entry.keywords.iter().any(is_content_keyword)
+    }
+
+    /// Reads the file and checks its `cksum` and digests, reporting to the
+    /// verifier's [`Progress`]. Digests are only checked with the `hash`
+    /// feature enabled.
+    pub fn verify_content(&self) -> Vec<VerifyFinding> {
+        if !self.has_content() {
+            return Vec::new();
+        }
+
+        self.progress.0.file(self.path());
+        self.files
+            .check(&self.entry, &self.options, &*self.progress.0)
+    }
+}
+
+impl fmt::Debug for DeferredCheck<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredCheck")
+            .field("entry", &self.entry)
+            .field("findings", &self.findings)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The spec path of `relative`, a path relative to the root.
+fn spec_path(relative: &Path) -> PathBuf {
+    relative
+        .iter()
+        .fold(PathBuf::from("."), |path, name| spec_child(&path, name))
+}
+
+/// Whether the value on disk matches the spec. Outside Unix, `mode` can only
+/// tell whether the file is writable.
+fn same_value(expected: &Keyword, actual: &Keyword) -> bool {
+    match (expected, actual) {
+        (Keyword::Mode(expected), Keyword::Mode(actual)) if cfg!(not(unix)) => {
+            (expected.bits() & 0o222 == 0) == (actual.bits() & 0o222 == 0)
+        }
+        _ => expected == actual,
+    }
+}
+
+fn is_content_keyword(keyword: &Keyword) -> bool {
+    matches!(keyword, Keyword::Cksum(_) | Keyword::Digest(_))
+}
+
+fn restrict(entry: &ResolvedEntry, keywords: KeywordSet) -> ResolvedEntry {
+    let mut entry = entry.clone();
+    entry.keywords.retain(|keyword| {
+        let kind = keyword.kind();
+        keywords.contains_kind(kind)
+            || KeywordSet::from_kind(kind).is_empty()
+            || matches!(keyword, Keyword::Xattr { name, .. }
+                if keywords.contains(KeywordSet::SECURITY) && xattr::is_security(name))
+    });
+    entry
+}
+
+/// The verifier's settings that checking a file depends on.
+#[derive(Clone, Debug)]
+struct CheckOptions {
+    device_limit: Option<u64>,
+    /// [`KeywordSet::XATTR`] to compare all extended attributes, or
+    /// [`KeywordSet::SECURITY`] for only the `security.*` ones.
+    xattrs: KeywordSet,
+    cache: Option<Arc<ContentCache>>,
+}
+
+/// The `cksum` and digests computed for regular files during
+/// [`Verifier::verify_many`], by device and inode number.
+#[derive(Debug, Default)]
+struct ContentCache(Mutex<HashMap<(u64, u64), CachedContents>>);
+
+#[derive(Debug)]
+struct CachedContents {
+    /// The size and modification time the keywords were computed for.
+    stamp: (u64, Option<Timestamp>),
+    keywords: Vec<Keyword>,
+}
+
+impl ContentCache {
+    /// The content keywords among `expected` for the file with `metadata`,
+    /// computed with `compute` unless they already have been.
+    fn get_or_compute(
+        &self,
+        metadata: &Metadata,
+        expected: &[Keyword],
+        compute: impl FnOnce() -> io::Result<Vec<Keyword>>,
+    ) -> io::Result<Vec<Keyword>> {
+        let Some(id) = dev_ino(metadata) else {
+            return compute();
+        };
+        let stamp = (metadata.len(), modified(metadata));
+        let wanted: Vec<KeywordKind> = expected
+            .iter()
+            .filter(|keyword| is_content_keyword(keyword))
+            .map(Keyword::kind)
+            .collect();
+
+        {
+            let cache = self.0.lock().unwrap_or_else(|err| err.into_inner());
+            if let Some(cached) = cache.get(&id).filter(|cached| cached.stamp == stamp)
+                && wanted.iter().all(|kind| {
+                    cached
+                        .keywords
+                        .iter()
+                        .any(|keyword| keyword.kind() == *kind)
+                })
+            {
+                return Ok(cached.keywords.clone());
+            }
+        }
+
+        // Read without holding the lock, so other files aren't held up.
+        let keywords = compute()?;
+
+        let mut cache = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        match cache.get_mut(&id) {
+            Some(cached) if cached.stamp == stamp => {
+                for keyword in &keywords {
+                    if !cached
+                        .keywords
+                        .iter()
+                        .any(|other| other.kind() == keyword.kind())
+                    {
+                        cached.keywords.push(keyword.clone());
+                    }
+                }
+            }
+            _ => {
+                cache.insert(
+                    id,
+                    CachedContents {
+                        stamp,
+                        keywords: keywords.clone(),
+                    },
+                );
+            }
+        }
+
+        Ok(keywords)
+    }
+}
+
+/// How files under the root are looked up.
+enum Files<'a> {
+    Path(&'a RootPath),
+    #[cfg(target_os = "linux")]
+    Dir(&'a RootPath, Dir),
+}
+
+/// A file found under the root, which may not exist.
+enum Located {
+    Path(PathBuf),
+    #[cfg(target_os = "linux")]
+    At(Dir, OsString),
+}
+
+impl<'a> Files<'a> {
+    fn open(root: &'a RootPath) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        if !root.is_jail() {
+            return Ok(Files::Dir(root, Dir::open(root.path())?));
+        }
+
+        Ok(Files::Path(root))
+    }
+
+    fn root(&self) -> &RootPath {
+        match self {
+            Files::Path(root) => root,
+            #[cfg(target_os = "linux")]
+            Files::Dir(root, _) => root,
+        }
+    }
+
+    /// Lists the directory at `relative`, sorted by name, with whether each
+    /// child is a directory. Symlinks are not followed.
+    fn read_dir(&self, relative: &Path) -> io::Result<Vec<(OsString, bool)>> {
+        let mut children = match self {
+            Files::Path(root) => fs::read_dir(root.path().join(relative))?
+                .map(|child| {
+                    let child = child?;
+                    Ok((child.file_name(), child.file_type()?.is_dir()))
+                })
+                .collect::<io::Result<Vec<_>>>()?,
+            #[cfg(target_os = "linux")]
+            Files::Dir(_, dir) => {
+                let (parent, name) = dir.open_parent(relative)?;
+                let dir = parent.open_dir(&name)?;
+
+                dir.entries()?
+                    .into_iter()
+                    .map(|name| {
+                        let is_dir = dir.metadata(&name)?.is_dir();
+                        Ok((name, is_dir))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?
+            }
+        };
+
+        children.sort();
+        Ok(children)
+    }
+
+    /// Reports the files under the root that aren't among `entries`.
+    fn extras(
+        &self,
+        entries: &[ResolvedEntry],
+        skip: impl Fn(&Path) -> bool,
+        on_finding: &mut impl FnMut(VerifyFinding),
+    ) {
+        let mut listed = HashSet::new();
+        let mut ignored = HashSet::new();
+
+        for entry in entries {
+            let Ok(path) = self.root().relative(&entry.path) else {
+                continue;
+            };
+
+            if entry.is_ignored() {
+                ignored.insert(path.clone());
+            }
+            listed.insert(path);
+        }
+
+        if !ignored.contains(Path::new("")) {
+            self.visit_extras(Path::new(""), &listed, &ignored, &skip, on_finding);
+        }
+    }
+
+    fn visit_extras(
+        &self,
+        dir: &Path,
+        listed: &HashSet<PathBuf>,
+        ignored: &HashSet<PathBuf>,
+        skip: &impl Fn(&Path) -> bool,
+        on_finding: &mut impl FnMut(VerifyFinding),
+    ) {
+        let children = match self.read_dir(dir) {
+            Ok(children) => children,
+            Err(err) => {
+                on_finding(VerifyFinding::Error {
+                    path: spec_path(dir),
+                    message: err.to_string(),
+                });
+                return;
+            }
+        };
+
+        for (name, is_dir) in children {
+            let path = dir.join(&name);
+
+            if skip(&path) {
+                continue;
+            }
+
+            if !listed.contains(&path) {
+                on_finding(VerifyFinding::Extra {
+                    path: spec_path(&path),
+                });
+            } else if is_dir && !ignored.contains(&path) {
+                self.visit_extras(&path, listed, ignored, skip, on_finding);
+            }
+        }
+    }
+
+    fn locate(&self, path: &Path) -> io::Result<Located> {
+        match self {
+            Files::Path(root) => Ok(Located::Path(root.resolve(path)?)),
+            #[cfg(target_os = "linux")]
+            Files::Dir(root, dir) => {
+                let relative = root.relative(path)?;
+                let (parent, name) = dir.open_parent(&relative)?;
+                Ok(Located::At(parent, name))
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        entry: &ResolvedEntry,
+        options: &CheckOptions,
+        progress: &dyn Progress,
+    ) -> Vec<VerifyFinding> {
+        let path = &entry.path;
+        let error = |err: io::Error| {
+            vec![VerifyFinding::Error {
+                path: path.clone(),
+                message: err.to_string(),
+            }]
+        };
+
+        let located = self.locate(path);
+        let (located, metadata) = match located.and_then(|file| Ok((file.metadata()?, file))) {
+            Ok((metadata, located)) => (located, metadata),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                if entry.is_optional() {
+                    return Vec::new();
+                }
+
+                return vec![VerifyFinding::Missing { path: path.clone() }];
+            }
+            Err(err) => return error(err),
+        };
+
+        if entry.get(KeywordKind::NoChange).is_some() {
+            return Vec::new();
+        }
+
+        let actual = match metadata_keywords(&metadata, || located.read_link()) {
+            Ok(actual) => actual,
+            Err(err) => return error(err),
+        };
+
+        let is_device = matches!(file_type(&metadata), Some(Type::Block | Type::Char));
+        let contents = if metadata.is_file() {
+            let compute = || {
+                content_keywords(
+                    || Ok(ProgressReader::new(located.open()?, progress)),
+                    &entry.keywords,
+                )
+            };
+
+            match &options.cache {
+                Some(cache) => cache.get_or_compute(&metadata, &entry.keywords, compute),
+                None => compute(),
+            }
+        } else if let Some(limit) = options.device_limit.filter(|_| is_device) {
+            content_keywords(
+                || {
+                    let device = DeviceReader::new(located.open()?, limit);
+                    Ok(ProgressReader::new(device, progress))
+                },
+                &entry.keywords,
+            )
+        } else {
+            Ok(Vec::new())
+        };
+        let contents = match contents {
+            Ok(contents) => contents,
+            Err(err) => return error(err),
+        };
+
+        let mut findings: Vec<VerifyFinding> = entry
+            .keywords
+            .iter()
+            .filter(|expected| {
+                !matches!(
+                    expected,
+                    Keyword::Device(Device::Parts { .. }) | Keyword::Xattr { .. }
+                )
+            })
+            .filter_map(|expected| {
+                let actual = actual
+                    .iter()
+                    .chain(&contents)
+                    .find(|actual| actual.kind() == expected.kind())?;
+
+                (!same_value(expected, actual)).then(|| VerifyFinding::Mismatch {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                })
+            })
+            .collect();
+
+        let selinux = entry.get(KeywordKind::Selinux);
+        if entry.get(KeywordKind::Xattr).is_some() || selinux.is_some() {
+            let xattrs = match located.xattrs() {
+                Ok(xattrs) => xattrs,
+                Err(err) => return error(err),
+            };
+
+            if let Some(expected) = selinux {
+                let actual = xattr::select(xattrs.clone(), KeywordSet::SELINUX)
+                    .pop()
+                    .unwrap_or(Keyword::Selinux(String::new()));
+
+                if &actual != expected {
+                    findings.push(VerifyFinding::Mismatch {
+                        path: path.clone(),
+                        expected: expected.clone(),
+                        actual,
+                    });
+                }
+            }
+
+            if entry.get(KeywordKind::Xattr).is_some() {
+                let mut scope = options.xattrs;
+                if selinux.is_some() {
+                    scope |= KeywordSet::SELINUX;
+                }
+
+                // The context was compared on its own above.
+                let actual: Vec<_> = xattr::select(xattrs, scope)
+                    .into_iter()
+                    .filter(|keyword| keyword.kind() == KeywordKind::Xattr)
+                    .collect();
+                findings.extend(xattr_findings(path, &entry.keywords, &actual));
+            }
+        }
+
+        if let Some(Keyword::HardLink(first)) = entry.get(KeywordKind::HardLink) {
+            // A missing first file is reported on its own entry.
+            let linked = self
+                .locate(first)
+                .and_then(|first| first.metadata())
+                .map(|first| dev_ino(&first) == dev_ino(&metadata));
+
+            if let Ok(false) = linked {
+                findings.push(VerifyFinding::Mismatch {
+                    path: path.clone(),
+                    expected: Keyword::HardLink(first.clone()),
+                    actual: Keyword::HardLink(path.clone()),
+                });
+            }
+        }
+
+        findings
+    }
+}
+
+impl Located {
+    fn metadata(&self) -> io::Result<Metadata> {
+        match self {
+            Located::Path(path) => fs::symlink_metadata(path),
+            #[cfg(target_os = "linux")]
+            Located::At(dir, name) => dir.metadata(name),
+        }
+    }
+
+    fn read_link(&self) -> io::Result<PathBuf> {
+        match self {
+            Located::Path(path) => fs::read_link(path),
+            #[cfg(target_os = "linux")]
+            Located::At(dir, name) => dir.read_link(name),
+        }
+    }
+
+    fn xattrs(&self) -> io::Result<Vec<Keyword>> {
+        match self {
+            Located::Path(path) => xattr::read(path),
+            #[cfg(target_os = "linux")]
+            Located::At(dir, name) => dir.xattrs(name),
+        }
+    }
+
+    fn open(&self) -> io::Result<File> {
+        match self {
+            Located::Path(path) => File::open(path),
+            #[cfg(target_os = "linux")]
+            Located::At(dir, name) => dir.open_file(name),
+        }
+    }
+}
+
+/// Compares the attributes on disk with those of an entry that lists any,
+/// taking its list to be complete. An attribute missing on one side is
+/// reported as having an empty value there.
+fn xattr_findings(path: &Path, expected: &[Keyword], actual: &[Keyword]) -> Vec<VerifyFinding> {
+    let by_name = |keywords: &'_ [Keyword]| -> BTreeMap<String, Keyword> {
+        keywords
+            .iter()
+            .filter_map(|keyword| match keyword {
+                Keyword::Xattr { name, .. } => Some((name.clone(), keyword.clone())),
+                _ => None,
+            })
+            .collect()
+    };
+    let (mut expected, mut actual) = (by_name(expected), by_name(actual));
+    let names: BTreeSet<String> = expected.keys().chain(actual.keys()).cloned().collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let missing = || Keyword::Xattr {
+                name: name.clone(),
+                value: Vec::new(),
+            };
+            let expected = expected.remove(&name).unwrap_or_else(missing);
+            let actual = actual.remove(&name).unwrap_or_else(missing);
+
+            (expected != actual).then(|| VerifyFinding::Mismatch {
+                path: path.to_path_buf(),
+                expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// Computes the `cksum` and digest keywords among `expected` from the file's
+/// contents, opening it with `open` for each pass.
+fn content_keywords<R: Read>(
+    open: impl Fn() -> io::Result<R>,
+    expected: &[Keyword],
+) -> io::Result<Vec<Keyword>> {
+    let mut keywords = Vec::new();
+
+    if expected
+        .iter()
+        .any(|keyword| keyword.kind() == KeywordKind::Cksum)
+    {
+        keywords.push(Keyword::Cksum(cksum(open()?)?));
+    }
+
+    #[cfg(feature = "hash")]
+    {
+        let kinds: Vec<_> = expected
+            .iter()
+            .filter_map(|keyword| match keyword {
+                Keyword::Digest(digest) => Some(digest.kind()),
+                _ => None,
+            })
+            .collect();
+
+        if !kinds.is_empty() {
+            let digests = crate::hash::hash_reader(open()?, kinds)?;
+            keywords.extend(digests.into_iter().map(Keyword::Digest));
+        }
+    }
+
+    Ok(keywords)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use crate::{DigestKind, KeywordSet, SpecBuilder, Type};
+
+    fn size_mismatch(path: &str, actual: u64) -> VerifyFinding {
+        VerifyFinding::Mismatch {
+            path: PathBuf::from(path),
+            expected: Keyword::Size(1),
+            actual: Keyword::Size(actual),
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = TempDir::new();
+        dir.file("etc/passwd", "root:x:0:0");
+        dir.file("etc/hosts", "127.0.0.1");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::MODE | KeywordSet::CKSUM)
+            .build()
+            .unwrap();
+        assert!(verify(&spec, dir.path()).is_clean());
+
+        dir.file("etc/passwd", "root:x:0:0:root");
+        fs::remove_file(dir.path().join("etc/hosts")).unwrap();
+        fs::create_dir(dir.path().join("etc/extra")).unwrap();
+
+        let findings = verify(&spec, dir.path()).findings;
+        let path = PathBuf::from("./etc/passwd");
+
+        assert_eq!(findings.len(), 3, "{findings:?}");
+        assert!(findings.contains(&VerifyFinding::Missing {
+            path: PathBuf::from("./etc/hosts")
+        }));
+        assert!(findings.contains(&VerifyFinding::Mismatch {
+            path: path.clone(),
+            expected: Keyword::Size(10),
+            actual: Keyword::Size(15),
+        }));
+        assert!(
+            findings
+                .iter()
+                .any(|finding| finding.path() == path
+                    && finding.keyword() == Some(KeywordKind::Cksum))
+        );
+    }
+
+    #[test]
+    fn test_verify_many() {
+        let base = TempDir::new();
+        base.file("lib/libc.so", "libc");
+        let app = TempDir::new();
+        app.file("bin/app", "app");
+
+        let keywords = KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::CKSUM;
+        let base_spec = SpecBuilder::from_dir(base.path())
+            .keywords(keywords)
+            .build()
+            .unwrap();
+        let app_spec = SpecBuilder::from_dir(app.path())
+            .keywords(keywords)
+            .build()
+            .unwrap();
+        base.file("lib/libc.so", "LIBC");
+
+        let reports = Verifier::new(RootPath::new("unused")).verify_many(&[
+            (base_spec.clone(), RootPath::new(base.path())),
+            (app_spec, RootPath::new(app.path())),
+            (base_spec, RootPath::new(base.path())),
+        ]);
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports[1].is_clean());
+        assert_eq!(reports[0], reports[2]);
+        assert_eq!(
+            reports[0]
+                .findings
+                .iter()
+                .map(VerifyFinding::keyword)
+                .collect::<Vec<_>>(),
+            [Some(KeywordKind::Cksum)]
+        );
+    }
+
+    #[test]
+    fn test_verify_progress() {
+        use crate::ProgressEvent;
+        use std::sync::mpsc;
+
+        let dir = TempDir::new();
+        dir.file("file", "hello");
+
+        let spec = Spec::parse("./file type=file cksum=1\n").unwrap();
+        let (sender, receiver) = mpsc::channel();
+        Verifier::new(RootPath::new(dir.path()))
+            .progress(sender)
+            .verify(&spec);
+
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                ProgressEvent::Start(1),
+                ProgressEvent::File(PathBuf::from("./file")),
+                ProgressEvent::Bytes(5),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verify_hard_links() {
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE)
+            .hard_links(true)
+            .build()
+            .unwrap();
+        assert!(verify(&spec, dir.path()).is_clean());
+
+        fs::remove_file(dir.path().join("b")).unwrap();
+        dir.file("b", "hello");
+
+        assert_eq!(
+            verify(&spec, dir.path()).findings,
+            vec![VerifyFinding::Mismatch {
+                path: PathBuf::from("./b"),
+                expected: Keyword::HardLink(PathBuf::from("./a")),
+                actual: Keyword::HardLink(PathBuf::from("./b")),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verify_keywords() {
+        let dir = TempDir::new();
+        dir.file("file", "hello");
+        dir.file("skipped/file", "");
+
+        let spec = Spec::parse(
+            "/set type=file\n\
+             . type=dir\n\
+             file size=5 nochange\n\
+             dir type=dir\n\
+             \x20   missing optional\n\
+             ..\n\
+             skipped type=dir ignore\n\
+             \x20   file size=100\n\
+             \x20   gone\n\
+             ..\n\
+             other type=dir optional\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify(&spec, dir.path()).findings,
+            vec![VerifyFinding::Missing {
+                path: PathBuf::from("./dir")
+            }]
+        );
+
+        fs::write(dir.path().join("other"), "").unwrap();
+        assert_eq!(
+            verify(&spec, dir.path()).findings,
+            vec![
+                VerifyFinding::Missing {
+                    path: PathBuf::from("./dir")
+                },
+                VerifyFinding::Mismatch {
+                    path: PathBuf::from("./other"),
+                    expected: Keyword::Type(Type::Dir),
+                    actual: Keyword::Type(Type::File),
+                }
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "blake3", feature = "xxh3"))]
+    #[test]
+    fn test_verify_extended_digests() {
+        let dir = TempDir::new();
+        dir.file("file", "abc");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::BLAKE3 | KeywordSet::XXH3)
+            .build()
+            .unwrap();
+        let text = spec.to_string();
+        assert!(text.contains(
+            "blake3digest=6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        ));
+        assert!(text.contains("xxh3digest=78af5f94892f3950"));
+
+        let spec: Spec = text.parse().unwrap();
+        let verifier = Verifier::new(RootPath::new(dir.path()));
+        assert!(verifier.verify(&spec).is_clean());
+
+        dir.file("file", "abd");
+        assert_eq!(
+            verifier
+                .verify(&spec)
+                .findings
+                .iter()
+                .map(|finding| finding.keyword())
+                .collect::<Vec<_>>(),
+            [
+                Some(KeywordKind::Digest(DigestKind::Blake3)),
+                Some(KeywordKind::Digest(DigestKind::Xxh3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_flagged() {
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        dir.file("b", "world");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::TIME | KeywordSet::CKSUM)
+            .build()
+            .unwrap();
+
+        dir.file("a", "HELLO");
+        File::options()
+            .write(true)
+            .open(dir.path().join("a"))
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH)
+            .unwrap();
+
+        let verifier = Verifier::new(RootPath::new(dir.path())).keywords(KeywordSet::QUICK);
+        let quick = verifier.verify(&spec);
+        assert_eq!(
+            quick
+                .findings
+                .iter()
+                .map(|finding| (finding.path(), finding.keyword()))
+                .collect::<Vec<_>>(),
+            [(Path::new("./a"), Some(KeywordKind::Time))]
+        );
+
+        let full = verifier.verify_flagged(&spec, &quick);
+        assert_eq!(
+            full.findings
+                .iter()
+                .map(|finding| (finding.path(), finding.keyword()))
+                .collect::<Vec<_>>(),
+            [
+                (Path::new("./a"), Some(KeywordKind::Time)),
+                (Path::new("./a"), Some(KeywordKind::Cksum)),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verify_symlinked_directory() {
+        let dir = TempDir::new();
+        dir.file("real/file", "hello");
+        std::os::unix::fs::symlink("real", dir.path().join("link")).unwrap();
+
+        let spec = Spec::parse("./link/file size=5\n").unwrap();
+        let findings = Verifier::new(RootPath::new(dir.path()).jail(true))
+            .verify(&spec)
+            .findings;
+        assert!(findings.is_empty(), "{findings:?}");
+
+        #[cfg(target_os = "linux")]
+        assert!(matches!(
+            verify(&spec, dir.path()).findings.as_slice(),
+            [VerifyFinding::Error { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_verify_extras() {
+        let dir = TempDir::new();
+        dir.file("etc/passwd", "");
+        dir.file("etc/extra/file", "");
+        dir.file("var/cache/file", "");
+        dir.file("stray", "");
+
+        let spec = Spec::parse(
+            ". type=dir
+             etc type=dir
+                 passwd type=file
+             ..
+             var type=dir
+                 cache type=dir ignore
+             ..
+",
+        )
+        .unwrap();
+
+        assert!(verify(&spec, dir.path()).is_clean());
+
+        let verifier = Verifier::new(RootPath::new(dir.path())).extras(true);
+        assert_eq!(
+            verifier.verify(&spec).findings,
+            vec![
+                VerifyFinding::Extra {
+                    path: PathBuf::from("./etc/extra")
+                },
+                VerifyFinding::Extra {
+                    path: PathBuf::from("./stray")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_backup() {
+        let dir = TempDir::new();
+        dir.file("data/file", "hello");
+
+        let mut spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::BACKUP)
+            .build()
+            .unwrap();
+        let id = spec.find_id(Path::new("./data/file")).unwrap();
+        let entry = spec.entry_mut(id).unwrap();
+        assert!(entry.keywords.iter().all(|keyword| !matches!(
+            keyword.kind(),
+            KeywordKind::Uid | KeywordKind::Gid | KeywordKind::Mode | KeywordKind::Nlink
+        )));
+
+        entry
+            .keywords
+            .extend([Keyword::Uid(12345), Keyword::Nlink(7), Keyword::Inode(1)]);
+        let verifier = Verifier::new(RootPath::new(dir.path())).keywords(KeywordSet::BACKUP);
+        assert!(verifier.verify(&spec).is_clean());
+
+        dir.file("data/file", "hello, world");
+        let changed: Vec<_> = verifier
+            .verify(&spec)
+            .findings
+            .iter()
+            .filter_map(VerifyFinding::keyword)
+            .collect();
+        assert!(changed.contains(&KeywordKind::Size));
+        assert!(changed.iter().all(|kind| matches!(
+            kind,
+            KeywordKind::Size | KeywordKind::Time | KeywordKind::Digest(DigestKind::Sha256)
+        )));
+    }
+
+    #[test]
+    fn test_verify_deferred() {
+        let dir = TempDir::new();
+        dir.file("a", "hello");
+        dir.file("b", "world");
+
+        let spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SIZE | KeywordSet::CKSUM)
+            .build()
+            .unwrap();
+        dir.file("a", "jello");
+
+        let verifier = Verifier::new(RootPath::new(dir.path()));
+        let checks = verifier.verify_deferred(&spec).unwrap();
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().all(|check| check.findings.is_empty()));
+        assert!(!checks[0].has_content());
+
+        let a = &checks[1];
+        assert_eq!(a.path(), Path::new("./a"));
+        assert!(a.has_content());
+        assert!(matches!(
+            a.verify_content()[..],
+            [VerifyFinding::Mismatch {
+                expected: Keyword::Cksum(_),
+                ..
+            }]
+        ));
+        assert!(checks[2].verify_content().is_empty());
+    }
+
+    #[test]
+    fn test_verify_xattrs() {
+        let dir = TempDir::new();
+        dir.file("bin/ping", "");
+
+        let mut spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::XATTR)
+            .build()
+            .unwrap();
+        let verifier = Verifier::new(RootPath::new(dir.path()));
+        assert!(verifier.verify(&spec).is_clean());
+
+        let capability = Keyword::Xattr {
+            name: "security.capability".to_string(),
+            value: vec![1, 0, 0, 2, 0, 32, 0, 0],
+        };
+        let id = spec.find_id(Path::new("./bin/ping")).unwrap();
+        spec.entry_mut(id)
+            .unwrap()
+            .keywords
+            .push(capability.clone());
+
+        assert_eq!(
+            verifier.verify(&spec).findings,
+            [VerifyFinding::Mismatch {
+                path: PathBuf::from("./bin/ping"),
+                expected: capability,
+                actual: Keyword::Xattr {
+                    name: "security.capability".to_string(),
+                    value: Vec::new(),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verify_selinux() {
+        let dir = TempDir::new();
+        dir.file("bin/ping", "");
+
+        let mut spec = SpecBuilder::from_dir(dir.path())
+            .keywords(KeywordSet::TYPE | KeywordSet::SELINUX)
+            .build()
+            .unwrap();
+        let verifier = Verifier::new(RootPath::new(dir.path()));
+        assert!(verifier.verify(&spec).is_clean());
+
+        let id = spec.find_id(Path::new("./bin/ping")).unwrap();
+        let entry = spec.entry_mut(id).unwrap();
+        let actual = entry
+            .keywords
+            .iter()
+            .find(|keyword| keyword.kind() == KeywordKind::Selinux)
+            .cloned()
+            .unwrap_or(Keyword::Selinux(String::new()));
+        let label = Keyword::Selinux("system_u:object_r:ping_exec_t:s0".to_string());
+        entry
+            .keywords
+            .retain(|keyword| keyword.kind() != KeywordKind::Selinux);
+        entry.keywords.push(label.clone());
+
+        assert_eq!(
+            verifier.verify(&spec).findings,
+            [VerifyFinding::Mismatch {
+                path: PathBuf::from("./bin/ping"),
+                expected: label,
+                actual,
+            }]
+        );
+        assert!(
+            verifier
+                .keywords(KeywordSet::TYPE | KeywordSet::SECURITY)
+                .verify(&spec)
+                .is_clean()
+        );
+    }
+
+    #[test]
+    fn test_verify_exclude() {
+        let dir = TempDir::new();
+        dir.file("etc/passwd", "");
+        dir.file("etc/passwd.bak", "");
+        dir.file("tmp/scratch", "");
+
+        let spec = Spec::parse(
+            ". type=dir
+             etc type=dir
+                 passwd type=file
+                 shadow type=file
+             ..
+",
+        )
+        .unwrap();
+
+        let verifier = Verifier::new(RootPath::new(dir.path()))
+            .extras(true)
+            .exclude(
+                ExcludeList::new()
+                    .pattern("*.bak")
+                    .pattern("./tmp")
+                    .pattern("./etc/shadow"),
+            );
+        assert!(verifier.verify(&spec).is_clean());
+    }
+
+    #[test]
+    fn test_verify_only() {
+        let dir = TempDir::new();
+        dir.file("etc/passwd", "");
+        dir.file("etc/hosts", "");
+        dir.file("stray", "");
+
+        let spec = Spec::parse(
+            ". type=dir
+             etc type=dir
+                 passwd type=file
+                 shadow type=file
+             ..
+",
+        )
+        .unwrap();
+
+        let verifier = Verifier::new(RootPath::new(dir.path()))
+            .extras(true)
+            .only(["etc/passwd"].into_iter().collect());
+        assert!(verifier.verify(&spec).is_clean());
+
+        let verifier = Verifier::new(RootPath::new(dir.path()))
+            .extras(true)
+            .only(["etc/shadow", "etc/hosts"].into_iter().collect());
+        assert_eq!(
+            verifier.verify(&spec).findings,
+            vec![
+                VerifyFinding::Missing {
+                    path: PathBuf::from("./etc/shadow")
+                },
+                VerifyFinding::Extra {
+                    path: PathBuf::from("./etc/hosts")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_display() {
+        let report = VerifyReport {
+            findings: vec![
+                VerifyFinding::Mismatch {
+                    path: PathBuf::from("./etc/passwd"),
+                    expected: Keyword::Size(10),
+                    actual: Keyword::Size(15),
+                },
+                VerifyFinding::Missing {
+                    path: PathBuf::from("./etc/hosts"),
+                },
+                VerifyFinding::Mismatch {
+                    path: PathBuf::from("./etc/passwd"),
+                    expected: Keyword::Uid(0),
+                    actual: Keyword::Uid(1000),
+                },
+                VerifyFinding::Extra {
+                    path: PathBuf::from("./etc/extra"),
+                },
+            ],
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "./etc/passwd changed\n\
+             \tsize expected 10 found 15\n\
+             \tuid expected 0 found 1000\n\
+             ./etc/hosts missing\n\
+             extra: ./etc/extra\n"
+        );
+        assert_eq!(
+            report.summary(),
+            VerifySummary {
+                changed: 1,
+                mismatches: 2,
+                missing: 1,
+                extra: 1,
+                errors: 0,
+            }
+        );
+        assert_eq!(report.summary().total(), 3);
+    }
+
+    #[test]
+    fn test_report_since() {
+        let previous = VerifyReport {
+            findings: vec![
+                size_mismatch("a", 2),
+                VerifyFinding::Missing {
+                    path: PathBuf::from("b"),
+                },
+            ],
+        };
+
+        let current = VerifyReport {
+            findings: vec![
+                size_mismatch("a", 3),
+                VerifyFinding::Missing {
+                    path: PathBuf::from("c"),
+                },
+            ],
+        };
+
+        let delta = current.since(&previous);
+
+        assert_eq!(delta.persisting, vec![size_mismatch("a", 3)]);
+        assert_eq!(
+            delta.new,
+            vec![VerifyFinding::Missing {
+                path: PathBuf::from("c")
+            }]
+        );
+        assert_eq!(
+            delta.resolved,
+            vec![VerifyFinding::Missing {
+                path: PathBuf::from("b")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_package_summaries() {
+        let spec = Spec::parse(
+            "/set tags=coreutils\n\
+             ls\n\
+             cat\n\
+             cp\n\
+             /set tags=bash\n\
+             bash\n\
+             /unset tags\n\
+             untracked\n",
+        )
+        .unwrap();
+
+        let report = VerifyReport {
+            findings: vec![
+                size_mismatch("cat", 2),
+                VerifyFinding::Missing {
+                    path: PathBuf::from("cp"),
+                },
+                VerifyFinding::Missing {
+                    path: PathBuf::from("untracked"),
+                },
+            ],
+        };
+
+        let summaries = report.package_summaries(&spec);
+
+        assert_eq!(
+            summaries,
+            vec![
+                PackageSummary {
+                    package: "bash".to_string(),
+                    clean: 1,
+                    modified: 0,
+                    missing: 0,
+                },
+                PackageSummary {
+                    package: "coreutils".to_string(),
+                    clean: 1,
+                    modified: 1,
+                    missing: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            summaries[1].to_string(),
+            "coreutils: 3 total files, 1 altered files, 1 missing files"
+        );
+    }
+
+    #[test]
+    fn test_report_since_empty() {
+        let current = VerifyReport {
+            findings: vec![size_mismatch("a", 2)],
+        };
+
+        assert_eq!(current.since(&VerifyReport::default()).new.len(), 1);
+        assert_eq!(VerifyReport::default().since(&current).resolved.len(), 1);
+    }
+}
@@ -0,0 +1,716 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter};
+use std::path::{Component, Path, PathBuf};
+
+use crate::resolve::SetState;
+use crate::{
+    Command, Entry, Keyword, KeywordKind, KeywordSet, Line, Spec, Timestamp, UnsetTarget, escape,
+};
+
+/// How full paths (paths containing a `/`) are written. Bare names in
+/// relative-form specs are always written as-is, since prefixing them would
+/// change their meaning.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Write paths exactly as they appear in the spec.
+    #[default]
+    AsIs,
+    /// `./usr/bin/ls`, as written by BSD mtree and pacman.
+    DotSlash,
+    /// `usr/bin/ls`.
+    Bare,
+    /// `/usr/bin/ls`. Lines starting with `/` are commands in mtree(5), so
+    /// this style is only suitable for consumers that don't parse the output
+    /// as a spec again.
+    Absolute,
+}
+
+impl PathStyle {
+    pub fn apply(&self, path: &Path) -> PathBuf {
+        let is_full = path.components().count() > 1 || path.has_root();
+        if *self == PathStyle::AsIs || !is_full {
+            return path.to_path_buf();
+        }
+
+        let rest: PathBuf = path
+            .components()
+            .skip_while(|component| matches!(component, Component::RootDir | Component::CurDir))
+            .collect();
+
+        match self {
+            PathStyle::AsIs | PathStyle::Bare => rest,
+            PathStyle::DotSlash => Path::new(".").join(rest),
+            PathStyle::Absolute => Path::new("/").join(rest),
+        }
+    }
+}
+
+/// How the nanoseconds after the `.` in `time` and `tar_time` are written.
+/// They are a count of nanoseconds rather than a decimal fraction, so
+/// `1.5` is 5 nanoseconds past the second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimePrecision {
+    /// Zero-padded to nine digits, `1630456800.000000000`, as BSD mtree
+    /// writes them.
+    #[default]
+    Nanos,
+    /// Without padding, `1630456800.0`, as libarchive and so pacman write
+    /// them.
+    Unpadded,
+    /// Left out, `1630456800`, dropping anything below a second.
+    Seconds,
+}
+
+impl TimePrecision {
+    pub fn format(&self, time: Timestamp) -> String {
+        match self {
+            TimePrecision::Nanos => time.to_string(),
+            TimePrecision::Unpadded => format!("{}.{}", time.secs, time.nanos),
+            TimePrecision::Seconds => time.secs.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub path_style: PathStyle,
+    pub time_precision: TimePrecision,
+    /// Write the same text for the same tree, however it was described:
+    /// every entry gets its full path and all of its keywords, including
+    /// those from `/set`, and entries are sorted by path, so each directory
+    /// is followed by its contents. Keywords are sorted by kind. Comments
+    /// before the first entry or command are kept and the rest are dropped.
+    pub canonical: bool,
+    /// Factor keywords shared by runs of consecutive entries out into `/set`
+    /// lines, as BSD mtree does, and leave them off the entries. Only
+    /// [`KeywordSet::FACTORED`] are factored; the spec's own `/set` and `/unset` lines are replaced,
+    /// and keywords they gave other than these are written on each entry.
+    /// With `canonical`, entries are sorted first.
+    pub factor_sets: bool,
+}
+
+/// How many consecutive entries have to share a value before it's worth a
+/// `/set` line.
+const MIN_RUN: usize = 2;
+
+/// Writes `spec` as mtree(5) text, one line per [`Line`].
+pub fn write_spec(spec: &Spec, out: impl io::Write) -> io::Result<()> {
+    write_spec_with(spec, out, &WriteOptions::default())
+}
+
+pub fn write_spec_with(
+    spec: &Spec,
+    mut out: impl io::Write,
+    options: &WriteOptions,
+) -> io::Result<()> {
+    if options.canonical {
+        return write_canonical(spec, out, options);
+    }
+    if options.factor_sets {
+        return write_factored(spec, out, options);
+    }
+
+    for line in &spec.lines {
+        write_line(&mut out, line, options)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a spec one line at a time as it's produced, without holding it
+/// in memory, the counterpart of [`SpecReader`](crate::SpecReader).
+///
+/// Paths and times are written as [`WriteOptions::path_style`] and
+/// [`WriteOptions::time_precision`] say. [`WriteOptions::canonical`] and
+/// [`WriteOptions::factor_sets`] need the whole spec, so they're ignored.
+/// Output is written as it comes, so wrap an unbuffered sink such as a
+/// [`File`] in a [`BufWriter`].
+#[derive(Debug)]
+pub struct SpecWriter<W> {
+    out: W,
+    options: WriteOptions,
+}
+
+impl<W: io::Write> SpecWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self::with_options(out, WriteOptions::default())
+    }
+
+    pub fn with_options(out: W, options: WriteOptions) -> Self {
+        SpecWriter { out, options }
+    }
+
+    pub fn write_line(&mut self, line: &Line) -> io::Result<()> {
+        write_line(&mut self.out, line, &self.options)
+    }
+
+    pub fn write_entry(&mut self, entry: &Entry) -> io::Result<()> {
+        write_entry(&mut self.out, entry, &self.options)
+    }
+
+    pub fn write_command(&mut self, command: &Command) -> io::Result<()> {
+        write_command(&mut self.out, command, &self.options)
+    }
+
+    /// Writes `#` followed by `text`.
+    pub fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        writeln!(self.out, "#{text}")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn write_line(out: &mut impl io::Write, line: &Line, options: &WriteOptions) -> io::Result<()> {
+    match line {
+        Line::Entry { entry, .. } => write_entry(out, entry, options),
+        Line::Command { command, .. } => write_command(out, command, options),
+        _ => writeln!(out, "{line}"),
+    }
+}
+
+fn write_command(
+    out: &mut impl io::Write,
+    command: &Command,
+    options: &WriteOptions,
+) -> io::Result<()> {
+    match command {
+        Command::Set(keywords) => {
+            out.write_all(b"/set")?;
+            write_keywords(out, keywords, options)?;
+            writeln!(out)
+        }
+        Command::Unset(_) => writeln!(out, "{command}"),
+    }
+}
+
+fn write_entry(out: &mut impl io::Write, entry: &Entry, options: &WriteOptions) -> io::Result<()> {
+    let path = options.path_style.apply(&entry.path);
+    out.write_all(escape::encode_path(&path).as_bytes())?;
+    write_keywords(out, &entry.keywords, options)?;
+    writeln!(out)
+}
+
+/// Writes each keyword preceded by a space.
+fn write_keywords(
+    out: &mut impl io::Write,
+    keywords: &[Keyword],
+    options: &WriteOptions,
+) -> io::Result<()> {
+    let precision = options.time_precision;
+
+    for keyword in keywords {
+        match keyword {
+            Keyword::Time(time) => write!(out, " time={}", precision.format(*time))?,
+            Keyword::TarTime(time) => write!(out, " tar_time={}", precision.format(*time))?,
+            _ => write!(out, " {keyword}")?,
+        }
+    }
+
+    Ok(())
+}
+
+fn write_canonical(spec: &Spec, mut out: impl io::Write, options: &WriteOptions) -> io::Result<()> {
+    let mut spec = spec.clone();
+    spec.sort();
+
+    if options.factor_sets {
+        return write_factored(&spec, out, options);
+    }
+
+    for line in &spec.lines {
+        match line {
+            Line::Entry { entry, .. } => write_entry(&mut out, entry, options)?,
+            _ => writeln!(out, "{line}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `spec` with [`KeywordSet::FACTORED`] keywords moved into `/set` lines. A
+/// value goes into `/set` when it starts a run of at least [`MIN_RUN`]
+/// entries that share it, and stays there until an entry needs another
+/// value or none at all.
+fn write_factored(spec: &Spec, mut out: impl io::Write, options: &WriteOptions) -> io::Result<()> {
+    // Each entry with every keyword it ends up with, so the spec's own
+    // commands can be dropped.
+    let mut state = SetState::new();
+    let mut materialized = Vec::new();
+    for line in &spec.lines {
+        match line {
+            Line::Command { command, .. } => state.apply(command),
+            Line::Entry { entry, .. } if entry.path.as_os_str() != ".." => {
+                materialized.push(state.materialize(&entry.keywords));
+            }
+            _ => {}
+        }
+    }
+
+    let kinds: Vec<KeywordKind> = materialized
+        .iter()
+        .flatten()
+        .map(Keyword::kind)
+        .filter(|kind| KeywordSet::FACTORED.contains(KeywordSet::from_kind(*kind)))
+        .fold(Vec::new(), |mut kinds, kind| {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+            kinds
+        });
+
+    // runs[idx][k]: how many entries from `idx` on share its value of
+    // `kinds[k]`.
+    let mut runs = vec![vec![1; kinds.len()]; materialized.len()];
+    for idx in (0..materialized.len().saturating_sub(1)).rev() {
+        for (k, &kind) in kinds.iter().enumerate() {
+            let value = find_kind(&materialized[idx], kind);
+            if value.is_some() && value == find_kind(&materialized[idx + 1], kind) {
+                runs[idx][k] = runs[idx + 1][k] + 1;
+            }
+        }
+    }
+
+    let mut set: Vec<Keyword> = Vec::new();
+    let mut entries = materialized.into_iter().zip(runs);
+
+    for line in &spec.lines {
+        let entry = match line {
+            Line::Command { .. } => continue,
+            Line::Entry { entry, .. } if entry.path.as_os_str() != ".." => entry,
+            Line::Entry { entry, .. } => {
+                write_entry(&mut out, entry, options)?;
+                continue;
+            }
+            _ => {
+                writeln!(out, "{line}")?;
+                continue;
+            }
+        };
+        let (keywords, runs) = entries
+            .next()
+            .expect("one materialized entry per entry line");
+
+        let mut unset = Vec::new();
+        let mut changed = Vec::new();
+        for (&kind, &run) in kinds.iter().zip(&runs) {
+            let current = find_kind(&set, kind);
+            match find_kind(&keywords, kind) {
+                value if value == current => {}
+                Some(value) if run >= MIN_RUN => changed.push(value.clone()),
+                None => unset.push(kind),
+                Some(_) => {}
+            }
+        }
+
+        if !unset.is_empty() {
+            set.retain(|keyword| !unset.contains(&keyword.kind()));
+            let names = unset.iter().map(|kind| kind.name().to_owned()).collect();
+            write_command(
+                &mut out,
+                &Command::Unset(UnsetTarget::Keywords(names)),
+                options,
+            )?;
+        }
+        if !changed.is_empty() {
+            crate::resolve::overlay(&mut set, &changed);
+            write_command(&mut out, &Command::Set(changed), options)?;
+        }
+
+        let entry = Entry {
+            path: entry.path.clone(),
+            keywords: keywords
+                .into_iter()
+                .filter(|keyword| !set.contains(keyword))
+                .collect(),
+        };
+        write_entry(&mut out, &entry, options)?;
+    }
+
+    Ok(())
+}
+
+fn find_kind(keywords: &[Keyword], kind: KeywordKind) -> Option<&Keyword> {
+    keywords.iter().find(|keyword| keyword.kind() == kind)
+}
+
+/// Writes `spec` to the file at `path` so that it never holds a partial
+/// spec: the text goes to a temporary file in the same directory, which is
+/// synced to disk and then renamed over `path`. If writing fails, the
+/// temporary file is removed and `path` is left as it was.
+pub fn write_spec_atomic(spec: &Spec, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path doesn't name a file"))?;
+
+    let (temp, file) = create_temp(dir, &name.to_string_lossy())?;
+
+    let written = (|| {
+        let mut out = BufWriter::new(file);
+        write_spec(spec, &mut out)?;
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+/// Creates a new file named after `name` in `dir`, never one that exists.
+fn create_temp(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let mut attempt = 0;
+
+    loop {
+        let temp = dir.join(format!(".{name}.{}.{attempt}.tmp", std::process::id()));
+
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+
+    fn strip_spans(spec: &Spec) -> Vec<Line> {
+        spec.lines
+            .iter()
+            .cloned()
+            .map(|line| match line {
+                Line::Comment { text, .. } => Line::Comment {
+                    text,
+                    span: Span::default(),
+                },
+                Line::Blank { .. } => Line::Blank {
+                    span: Span::default(),
+                },
+                Line::Command { command, .. } => Line::Command {
+                    command,
+                    span: Span::default(),
+                },
+                Line::Entry { id, entry, .. } => Line::Entry {
+                    id,
+                    entry,
+                    span: Span::default(),
+                },
+            })
+            .collect()
+    }
+
+    fn assert_roundtrip(input: &str) {
+        let spec = Spec::parse(input).unwrap();
+
+        let mut out = Vec::new();
+        write_spec(&spec, &mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+
+        let reparsed = Spec::parse(&written).unwrap();
+        assert_eq!(strip_spans(&reparsed), strip_spans(&spec));
+        assert_eq!(reparsed.to_string(), written);
+    }
+
+    #[test]
+    fn test_write_spec() {
+        let spec = Spec::parse(
+            "#mtree\n\
+             \n\
+             /set type=file uid=0\n\
+             foo\\040bar size=1 time=1769203027.5\n\
+             /unset uid\n\
+             /unset all\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.to_string(),
+            "#mtree\n\
+             \n\
+             /set type=file uid=0\n\
+             foo\\040bar size=1 time=1769203027.000000005\n\
+             /unset uid\n\
+             /unset all\n"
+        );
+    }
+
+    #[test]
+    fn test_path_style() {
+        let spec = Spec::parse(
+            ". type=dir\n\
+             ./usr/bin type=dir\n\
+             etc/passwd size=1\n\
+             var/log type=dir\n\
+             README size=2\n",
+        )
+        .unwrap();
+
+        let write = |path_style| {
+            let mut out = Vec::new();
+            write_spec_with(
+                &spec,
+                &mut out,
+                &WriteOptions {
+                    path_style,
+                    ..WriteOptions::default()
+                },
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(write(PathStyle::AsIs), spec.to_string());
+        assert_eq!(
+            write(PathStyle::DotSlash),
+            ". type=dir\n\
+             ./usr/bin type=dir\n\
+             ./etc/passwd size=1\n\
+             ./var/log type=dir\n\
+             README size=2\n"
+        );
+        assert_eq!(
+            write(PathStyle::Bare),
+            ". type=dir\n\
+             usr/bin type=dir\n\
+             etc/passwd size=1\n\
+             var/log type=dir\n\
+             README size=2\n"
+        );
+        assert_eq!(
+            write(PathStyle::Absolute),
+            ". type=dir\n\
+             /usr/bin type=dir\n\
+             /etc/passwd size=1\n\
+             /var/log type=dir\n\
+             README size=2\n"
+        );
+    }
+
+    #[test]
+    fn test_time_precision() {
+        let spec = Spec::parse(
+            "/set time=1630456800.000000000\n\
+             ./a time=1630456800.000000250 tar_time=1630456800.000000000\n",
+        )
+        .unwrap();
+
+        let write = |time_precision| {
+            let mut out = Vec::new();
+            write_spec_with(
+                &spec,
+                &mut out,
+                &WriteOptions {
+                    time_precision,
+                    ..WriteOptions::default()
+                },
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(write(TimePrecision::Nanos), spec.to_string());
+        assert_eq!(
+            write(TimePrecision::Unpadded),
+            "/set time=1630456800.0\n\
+             ./a time=1630456800.250 tar_time=1630456800.0\n"
+        );
+        assert_eq!(
+            Spec::parse(&write(TimePrecision::Unpadded))
+                .unwrap()
+                .resolve()[0]
+                .keywords,
+            spec.resolve()[0].keywords
+        );
+        assert_eq!(
+            write(TimePrecision::Seconds),
+            "/set time=1630456800\n\
+             ./a time=1630456800 tar_time=1630456800\n"
+        );
+    }
+
+    #[test]
+    fn test_canonical() {
+        let relative = Spec::parse(
+            "#mtree v2.0\n\
+             /set type=file uid=0\n\
+             . type=dir\n\
+             usr type=dir\n\
+             \x20   lib-x xattr.b=aGk= size=2 xattr.a=aGk=\n\
+             \x20   lib type=dir\n\
+             \x20   ..\n\
+             ..\n\
+             # trailing\n\
+             etc type=dir\n",
+        )
+        .unwrap();
+        let full = Spec::parse(
+            "#mtree v2.0\n\
+             ./etc type=dir uid=0\n\
+             ./usr/lib uid=0 type=dir\n\
+             ./usr/lib-x type=file uid=0 xattr.a=aGk= size=2 xattr.b=aGk=\n\
+             ./usr type=dir uid=0\n\
+             . uid=0 type=dir\n",
+        )
+        .unwrap();
+
+        let options = WriteOptions {
+            canonical: true,
+            ..WriteOptions::default()
+        };
+        let write = |spec: &Spec| {
+            let mut out = Vec::new();
+            write_spec_with(spec, &mut out, &options).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            write(&relative),
+            "#mtree v2.0\n\
+             . type=dir uid=0\n\
+             ./etc type=dir uid=0\n\
+             ./usr type=dir uid=0\n\
+             ./usr/lib type=dir uid=0\n\
+             ./usr/lib-x type=file uid=0 size=2 xattr.a=aGk= xattr.b=aGk=\n"
+        );
+        assert_eq!(write(&relative), write(&full));
+    }
+
+    #[test]
+    fn test_factor_sets() {
+        let spec = Spec::parse(
+            "#mtree\n\
+             /set uid=0 time=1.0\n\
+             ./bin type=dir gid=0 mode=0755\n\
+             ./bin/ls type=file gid=0 mode=0755 size=1\n\
+             ./bin/cat type=file gid=0 mode=0755 size=2\n\
+             ./etc type=dir gid=0 mode=0755\n\
+             ./etc/passwd type=file gid=0 mode=0644\n\
+             ./etc/shadow type=file gid=42 mode=0600\n\
+             /unset uid\n\
+             ./home type=dir gid=0 mode=0755\n",
+        )
+        .unwrap();
+
+        let options = WriteOptions {
+            factor_sets: true,
+            ..WriteOptions::default()
+        };
+        let mut out = Vec::new();
+        write_spec_with(&spec, &mut out, &options).unwrap();
+        let written = String::from_utf8(out).unwrap();
+
+        assert_eq!(
+            written,
+            "#mtree\n\
+             /set uid=0 gid=0 mode=0755\n\
+             ./bin time=1.000000000 type=dir\n\
+             /set type=file\n\
+             ./bin/ls time=1.000000000 size=1\n\
+             ./bin/cat time=1.000000000 size=2\n\
+             ./etc time=1.000000000 type=dir\n\
+             ./etc/passwd time=1.000000000 mode=0644\n\
+             ./etc/shadow time=1.000000000 gid=42 mode=0600\n\
+             /unset uid\n\
+             ./home time=1.000000000 type=dir\n"
+        );
+        let sorted = |spec: &Spec| {
+            let mut spec = spec.clone();
+            spec.sort();
+            spec.to_string()
+        };
+        assert_eq!(sorted(&Spec::parse(&written).unwrap()), sorted(&spec));
+    }
+
+    #[test]
+    fn test_spec_writer() {
+        let options = WriteOptions {
+            path_style: PathStyle::DotSlash,
+            time_precision: TimePrecision::Seconds,
+            ..WriteOptions::default()
+        };
+        let mut writer = SpecWriter::with_options(Vec::new(), options);
+
+        writer.write_comment("mtree v2.0").unwrap();
+        writer
+            .write_command(&Command::Set(vec![Keyword::Time(Timestamp::new(5, 1))]))
+            .unwrap();
+        writer
+            .write_entry(&Entry {
+                path: PathBuf::from("usr/bin"),
+                keywords: vec![Keyword::Type(crate::Type::Dir)],
+            })
+            .unwrap();
+        writer
+            .write_line(&Line::Command {
+                command: Command::Unset(UnsetTarget::All),
+                span: Span::default(),
+            })
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "#mtree v2.0\n\
+             /set time=5\n\
+             ./usr/bin type=dir\n\
+             /unset all\n"
+        );
+    }
+
+    #[test]
+    fn test_write_spec_atomic() {
+        let dir = crate::testutil::TempDir::new();
+        let path = dir.path().join("manifest.mtree");
+        fs::write(&path, "./old\n").unwrap();
+
+        let spec = Spec::parse("./new size=1\n").unwrap();
+        write_spec_atomic(&spec, &path).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "./new size=1\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let missing = dir.path().join("missing/manifest.mtree");
+        assert!(write_spec_atomic(&spec, &missing).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        assert_roundtrip("foo size=1\n");
+        assert_roundtrip(
+            "# ./.git\n\
+             .git type=dir size=384 time=1769640373.412526597\n\
+             \x20   COMMIT_EDITMSG \\\n\
+             \x20               size=365 time=1769640373.411006676\n\
+             \x20   HEAD size=21 time=1769626398.993045847\n\
+             \x20   hooks type=link link=../hooks\n\
+             ..\n\
+             /set type=file uid=0\n\
+             /unset type uid\n\
+             LICENSE sha256digest=014bb31e83d5c2e76aea1cc6e82217346ab41362f32cb355ad0f5c10aa0aeaff\n",
+        );
+    }
+}
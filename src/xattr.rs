@@ -0,0 +1,148 @@
+//! Reading extended attributes for the `xattr` keyword, such as the
+//! `security.capability` attribute that holds a file's capabilities.
+//!
+//! Only Linux is supported; elsewhere files appear to have none.
+
+use std::io;
+use std::path::Path;
+
+use crate::{Keyword, KeywordSet};
+
+/// The attribute holding a file's SELinux context.
+pub const SELINUX: &str = "security.selinux";
+
+/// Whether `name` is in the `security` namespace, which holds attributes
+/// such as SELinux contexts, capabilities and IMA signatures.
+pub fn is_security(name: &str) -> bool {
+    name.starts_with("security.")
+}
+
+/// Picks the keywords `keywords` asks for out of the extended attributes
+/// `xattrs`, as returned by [`read`]: the SELinux context as a `selinux`
+/// keyword, and all the attributes or only the `security.*` ones as `xattr`
+/// keywords. With `selinux` selected, `security.selinux` isn't also given as
+/// an `xattr`.
+pub fn select(xattrs: Vec<Keyword>, keywords: KeywordSet) -> Vec<Keyword> {
+    let mut selected = Vec::new();
+
+    for keyword in xattrs {
+        let Keyword::Xattr { name, value } = &keyword else {
+            continue;
+        };
+
+        if name == SELINUX && keywords.contains(KeywordSet::SELINUX) {
+            let label = value.strip_suffix(&[0]).unwrap_or(value);
+            selected.push(Keyword::Selinux(
+                String::from_utf8_lossy(label).into_owned(),
+            ));
+        } else if keywords.contains(KeywordSet::XATTR)
+            || keywords.contains(KeywordSet::SECURITY) && is_security(name)
+        {
+            selected.push(keyword);
+        }
+    }
+
+    selected
+}
+
+/// The extended attributes of the file at `path`, without following it if
+/// it's a symlink, sorted by name. A filesystem without support for them
+/// gives an empty list.
+#[cfg(target_os = "linux")]
+pub fn read(path: &Path) -> io::Result<Vec<Keyword>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+
+    let names =
+        match read_buffer(|buf, len| unsafe { libc::llistxattr(path.as_ptr(), buf.cast(), len) }) {
+            Ok(names) => names,
+            Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+    let mut names: Vec<&[u8]> = names
+        .split(|&c| c == 0)
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+
+    let mut keywords = Vec::new();
+
+    for name in names {
+        let c_name = CString::new(name)?;
+        let value = match read_buffer(|buf, len| unsafe {
+            libc::lgetxattr(path.as_ptr(), c_name.as_ptr(), buf.cast(), len)
+        }) {
+            Ok(value) => value,
+            // Removed since it was listed.
+            Err(err) if err.raw_os_error() == Some(libc::ENODATA) => continue,
+            Err(err) => return Err(err),
+        };
+
+        keywords.push(Keyword::Xattr {
+            name: String::from_utf8_lossy(name).into_owned(),
+            value,
+        });
+    }
+
+    Ok(keywords)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_path: &Path) -> io::Result<Vec<Keyword>> {
+    Ok(Vec::new())
+}
+
+/// Calls `fill` first to learn the size, then to fill a buffer of it,
+/// retrying if the value grew in between.
+#[cfg(target_os = "linux")]
+fn read_buffer(fill: impl Fn(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let len = fill(std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; len as usize];
+        let len = fill(buf.as_mut_ptr(), buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let xattr = |name: &str, value: &[u8]| Keyword::Xattr {
+            name: name.to_string(),
+            value: value.to_vec(),
+        };
+        let xattrs = vec![
+            xattr("security.capability", &[1, 0, 0, 2]),
+            xattr("security.selinux", b"system_u:object_r:bin_t:s0\0"),
+            xattr("user.comment", b"hello"),
+        ];
+        let label = Keyword::Selinux("system_u:object_r:bin_t:s0".to_string());
+
+        assert_eq!(select(xattrs.clone(), KeywordSet::SELINUX), [label.clone()]);
+        assert_eq!(
+            select(xattrs.clone(), KeywordSet::SECURITY | KeywordSet::SELINUX),
+            [xattrs[0].clone(), label.clone()]
+        );
+        assert_eq!(select(xattrs.clone(), KeywordSet::XATTR), xattrs);
+
+        assert_eq!(label.to_string().parse::<Keyword>(), Ok(label));
+    }
+}